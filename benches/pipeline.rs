@@ -0,0 +1,159 @@
+//! Benchmarks of the audio pipeline
+//!
+//! Run with `cargo bench`. Results are written to `target/criterion`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use songrec::audio::AudioProcessor;
+use songrec::{Config, DecodedSignature, PcmBuffer, PcmFormat, RecognitionResult, SignatureGenerator};
+
+/// Deterministic, peak-rich test signal: a few tones plus pseudo-random noise
+fn test_signal(seconds: usize, sample_rate: usize) -> Vec<f32> {
+    let mut state: u32 = 12345;
+    (0..seconds * sample_rate)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let noise = (state >> 16) as f32 / 32768.0 - 1.0;
+            0.3 * (t * 440.0 * std::f32::consts::TAU).sin()
+                + 0.2 * (t * 1250.0 * std::f32::consts::TAU).sin()
+                + 0.1 * (t * 3100.0 * std::f32::consts::TAU).sin()
+                + 0.1 * noise
+        })
+        .collect()
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples.iter().map(|&s| (s * 32767.0) as i16).collect()
+}
+
+fn signature_generation(c: &mut Criterion) {
+    let samples = to_i16(&test_signal(12, 16000));
+
+    let mut group = c.benchmark_group("fingerprint");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+
+    group.bench_function("make_signature_12s", |b| {
+        b.iter(|| SignatureGenerator::make_signature_from_buffer(black_box(&samples)))
+    });
+
+    // Silence never passes the peak threshold: the difference with the benchmark above
+    // is the cost of peak recognition, the rest is spent in the FFT and peak spreading
+    let silence = vec![0i16; samples.len()];
+    group.bench_function("make_signature_12s_silence", |b| {
+        b.iter(|| SignatureGenerator::make_signature_from_buffer(black_box(&silence)))
+    });
+
+    // Continuous mode feeds the generator batch by batch and reuses it between signatures
+    group.bench_function("feed_12s_in_4096_sample_batches", |b| {
+        let mut generator = SignatureGenerator::new();
+        b.iter(|| {
+            for batch in samples.chunks(4096) {
+                generator.feed(black_box(batch));
+            }
+            generator.finalize()
+        })
+    });
+
+    group.finish();
+}
+
+/// CPU spent on a minute of live audio, as a Raspberry Pi Zero-class device doing continuous
+/// monitoring would; the low resource mode leaves most of the audio unfingerprinted
+fn continuous_monitoring(c: &mut Criterion) {
+    let samples = to_i16(&test_signal(60, 16000));
+
+    let mut group = c.benchmark_group("continuous");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    for (name, config) in [("default_60s", Config::default()), ("low_resource_60s", Config::default().with_low_resource_mode())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut processor = AudioProcessor::with_config(config.clone());
+                samples.chunks(4096).filter_map(|batch| processor.process_samples(black_box(batch)).unwrap()).count()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn resampling(c: &mut Criterion) {
+    let bytes: Vec<u8> = test_signal(12, 48000)
+        .iter()
+        .flat_map(|&s| {
+            let sample = s.to_le_bytes();
+            [sample, sample]
+        })
+        .flatten()
+        .collect();
+    let pcm = PcmBuffer::new(&bytes, 48000, 2, PcmFormat::F32Le);
+
+    let mut group = c.benchmark_group("resample");
+    group.throughput(Throughput::Elements(12 * 48000));
+    group.bench_function("stereo_f32_48khz_to_mono_16khz_12s", |b| {
+        b.iter(|| black_box(&pcm).to_mono_16khz().unwrap())
+    });
+    group.finish();
+}
+
+fn signature_codec(c: &mut Criterion) {
+    let signature = SignatureGenerator::make_signature_from_buffer(&to_i16(&test_signal(12, 16000)));
+    let uri = signature.encode_to_uri().unwrap();
+
+    let mut group = c.benchmark_group("codec");
+    group.bench_function("encode_to_uri", |b| b.iter(|| black_box(&signature).encode_to_uri().unwrap()));
+    group.bench_function("decode_from_uri", |b| {
+        b.iter(|| DecodedSignature::decode_from_uri(black_box(&uri)).unwrap())
+    });
+    group.finish();
+}
+
+/// Typical response shape: a match, plus a track with metadata sections and hub actions
+fn sample_response() -> serde_json::Value {
+    serde_json::json!({
+        "matches": [{ "id": "11111", "offset": 61.2, "timeskew": 0.0001, "frequencyskew": 0.0 }],
+        "timestamp": 1700000000000u64,
+        "track": {
+            "key": "11111",
+            "title": "Marble Machine",
+            "subtitle": "Wintergatan",
+            "isrc": "SE1234567890",
+            "genres": { "primary": "Electronic" },
+            "artists": [{ "adamid": "1234", "id": "42" }],
+            "sections": [{
+                "type": "SONG",
+                "metadata": [
+                    { "title": "Album", "text": "Marble Machine (Single)" },
+                    { "title": "Label", "text": "Wintergatan" },
+                    { "title": "Released", "text": "2016" }
+                ]
+            }],
+            "hub": {
+                "actions": [
+                    { "name": "apple", "type": "applemusicplay", "id": "1" },
+                    { "name": "apple", "type": "uri", "uri": "https://audio-ssl.itunes.apple.com/preview.m4a" }
+                ]
+            }
+        }
+    })
+}
+
+fn response_parsing(c: &mut Criterion) {
+    let response = sample_response();
+    let text = response.to_string();
+
+    let mut group = c.benchmark_group("response");
+    group.bench_function("from_raw_response", |b| {
+        b.iter(|| RecognitionResult::from_raw_response(black_box(&response).clone()).unwrap())
+    });
+
+    // Includes deserializing the JSON body, as done for every API response
+    group.bench_function("from_json_text", |b| {
+        b.iter(|| {
+            let value: serde_json::Value = serde_json::from_str(black_box(&text)).unwrap();
+            RecognitionResult::try_from(value).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, signature_generation, continuous_monitoring, resampling, signature_codec, response_parsing);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! Fingerprinting core of songrec-lib, compiling with `no_std` and `alloc`
+//!
+//! Peak detection and signature encoding, so microcontroller firmware can fingerprint
+//! audio itself and send the signature to a gateway running songrec-lib for the lookup.
+//! The FFT is the `Fft` trait: `RadixTwoFft` needs nothing but `alloc`, songrec-lib
+//! plugs in chfft.
+//!
+//! ```
+//! use songrec_core::{RadixTwoFft, SignatureGenerator};
+//!
+//! let mut generator = SignatureGenerator::new(RadixTwoFft::new());
+//! # let microphone_samples = vec![0i16; 16000 * 4];
+//! generator.feed(&microphone_samples); // Mono 16 KHz samples, in slices of any length
+//! if generator.ready() {
+//!     let uri = generator.finalize().encode_to_uri().unwrap();
+//! }
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod hanning;
+pub mod signature;
+pub mod peaks;
+mod math;
+
+pub use signature::{EncodeError, FrequencyBand, FrequencyPeak, Signature};
+pub use peaks::{Fft, RadixTwoFft, SignatureGenerator};
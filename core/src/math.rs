@@ -0,0 +1,22 @@
+//! Floating point functions that `core` lacks, from std when available so that signatures
+//! are bit for bit those songrec-lib always computed, from libm otherwise
+
+#[cfg(feature = "std")]
+pub(crate) fn ln(x: f32) -> f32 {
+    x.ln()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn ln(x: f32) -> f32 {
+    libm::logf(x)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn sin_cos(x: f64) -> (f64, f64) {
+    x.sin_cos()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn sin_cos(x: f64) -> (f64, f64) {
+    (libm::sin(x), libm::cos(x))
+}
@@ -0,0 +1,401 @@
+//! Peak detection of the Shazam algorithm, streaming over mono 16 KHz audio
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
+use crate::math;
+use crate::signature::{FrequencyBand, FrequencyPeak, Signature};
+
+/// Samples of each FFT
+pub const FFT_SIZE: usize = 2048;
+
+/// Bins of each FFT, from 0 Hz to half the sample rate
+pub const FFT_BINS: usize = FFT_SIZE / 2 + 1;
+
+/// New samples between two FFTs
+const STEP: usize = 128;
+
+/// Number of 128-sample steps needed to fill the FFT history before peaks can be detected
+const WARM_UP_STEPS: u32 = 46;
+
+/// Minimum amount of audio for a signature worth sending (3 seconds at 16 KHz)
+const MIN_READY_SAMPLES: u32 = 3 * 16000;
+
+/// FFT outputs kept, peak recognition looks 46 steps back; a power of two for index masking
+const FFT_HISTORY: usize = 64;
+
+/// Spread FFT outputs kept, peak recognition looks up to 91 steps back
+const SPREAD_FFT_HISTORY: usize = 128;
+
+/// Forward FFT of real samples
+pub trait Fft {
+    /// Complex spectrum of `FFT_SIZE` samples into `FFT_BINS` (real, imaginary) pairs, unnormalized
+    fn forward(&mut self, input: &[f32], output: &mut [(f32, f32)]);
+}
+
+/// Iterative radix-2 FFT needing nothing but `alloc`, about 24 KB with its buffers
+///
+/// Its rounding differs from the FFT of songrec-lib, so a few peak magnitudes may differ
+/// by one unit, which does not change what the signature matches.
+pub struct RadixTwoFft {
+    /// e^(-2πik/N) for k below N/2
+    twiddles: Vec<(f32, f32)>,
+    buffer: Vec<(f32, f32)>,
+}
+
+impl RadixTwoFft {
+    pub fn new() -> Self {
+        let twiddles = (0..FFT_SIZE / 2)
+            .map(|k| {
+                let (sin, cos) = math::sin_cos(-2.0 * core::f64::consts::PI * k as f64 / FFT_SIZE as f64);
+                (cos as f32, sin as f32)
+            })
+            .collect();
+        Self { twiddles, buffer: vec![(0.0, 0.0); FFT_SIZE] }
+    }
+}
+
+impl Default for RadixTwoFft {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fft for RadixTwoFft {
+    fn forward(&mut self, input: &[f32], output: &mut [(f32, f32)]) {
+        let bits = FFT_SIZE.trailing_zeros();
+        for (index, &sample) in input.iter().enumerate() {
+            self.buffer[index.reverse_bits() >> (usize::BITS - bits)] = (sample, 0.0);
+        }
+
+        let mut length = 2;
+        while length <= FFT_SIZE {
+            let half = length / 2;
+            let stride = FFT_SIZE / length;
+            for start in (0..FFT_SIZE).step_by(length) {
+                for k in 0..half {
+                    let (w_re, w_im) = self.twiddles[k * stride];
+                    let (a_re, a_im) = self.buffer[start + k];
+                    let (b_re, b_im) = self.buffer[start + k + half];
+                    let (t_re, t_im) = (b_re * w_re - b_im * w_im, b_re * w_im + b_im * w_re);
+                    self.buffer[start + k] = (a_re + t_re, a_im + t_im);
+                    self.buffer[start + k + half] = (a_re - t_re, a_im - t_im);
+                }
+            }
+            length *= 2;
+        }
+
+        output.copy_from_slice(&self.buffer[..FFT_BINS]);
+    }
+}
+
+/// Generates Shazam signatures from mono 16 KHz audio, with the FFT of `F`
+///
+/// Stream audio through `feed()` and call `finalize()` once `ready()` returns true. Audio
+/// can be fed in slices of any length; splitting it into the 128-sample steps of the
+/// algorithm and the warm-up of the first steps are handled internally.
+pub struct SignatureGenerator<F: Fft> {
+    /// Ring buffer
+    ring_buffer_of_samples: Vec<i16>,
+    ring_buffer_of_samples_index: usize,
+
+    /// Reordered, temporary version of the ring buffer above, with floats for precision because we applied Hanning window
+    reordered_ring_buffer_of_samples: Vec<f32>,
+
+    /// Complex output of the last FFT
+    spectrum: Vec<(f32, f32)>,
+
+    /// Ring buffer of power spectra, computed from the ring buffer every new 128 samples
+    fft_outputs: Vec<Vec<f32>>,
+    fft_outputs_index: usize,
+
+    fft: F,
+
+    /// Ring buffer
+    spread_fft_outputs: Vec<Vec<f32>>,
+    spread_fft_outputs_index: usize,
+
+    num_spread_ffts_done: u32,
+
+    /// Samples received through feed that do not fill a complete 128-sample step yet
+    pending_samples: Vec<i16>,
+
+    /// Power gain of each FFT bin, from `with_bin_gains`
+    bin_gains: Option<Vec<f32>>,
+
+    number_samples: u32,
+    frequency_band_to_sound_peaks: BTreeMap<FrequencyBand, Vec<FrequencyPeak>>,
+}
+
+impl<F: Fft> SignatureGenerator<F> {
+    pub fn new(fft: F) -> Self {
+        Self {
+            ring_buffer_of_samples: vec![0i16; FFT_SIZE],
+            ring_buffer_of_samples_index: 0,
+            reordered_ring_buffer_of_samples: vec![0.0f32; FFT_SIZE],
+            spectrum: vec![(0.0, 0.0); FFT_BINS],
+            fft_outputs: vec![vec![0.0f32; FFT_BINS]; FFT_HISTORY],
+            fft_outputs_index: 0,
+            fft,
+            spread_fft_outputs: vec![vec![0.0f32; FFT_BINS]; SPREAD_FFT_HISTORY],
+            spread_fft_outputs_index: 0,
+            num_spread_ffts_done: 0,
+            pending_samples: Vec::with_capacity(STEP),
+            bin_gains: None,
+            number_samples: 0,
+            frequency_band_to_sound_peaks: BTreeMap::new(),
+        }
+    }
+
+    /// Multiply the power of each of the `FFT_BINS` bins before peak detection, None for no weighting
+    pub fn with_bin_gains(mut self, gains: Option<Vec<f32>>) -> Self {
+        self.bin_gains = gains;
+        self
+    }
+
+    /// Feed mono 16 KHz samples
+    ///
+    /// Slices of any length are accepted: samples are buffered internally and processed
+    /// in the 128-sample steps the algorithm works with.
+    pub fn feed(&mut self, s16_mono_16khz_buffer: &[i16]) {
+        self.number_samples += s16_mono_16khz_buffer.len() as u32;
+
+        let mut samples = s16_mono_16khz_buffer;
+
+        // Complete the step left over from the previous call first
+        if !self.pending_samples.is_empty() {
+            let missing = (STEP - self.pending_samples.len()).min(samples.len());
+            self.pending_samples.extend_from_slice(&samples[..missing]);
+            samples = &samples[missing..];
+
+            if self.pending_samples.len() < STEP {
+                return;
+            }
+
+            let step = core::mem::take(&mut self.pending_samples);
+            self.process_step(&step);
+            self.pending_samples = step;
+            self.pending_samples.clear();
+        }
+
+        let mut chunks = samples.chunks_exact(STEP);
+        for chunk in &mut chunks {
+            self.process_step(chunk);
+        }
+        self.pending_samples.extend_from_slice(chunks.remainder());
+    }
+
+    /// Whether enough audio was fed for a signature worth sending
+    pub fn ready(&self) -> bool {
+        self.number_samples >= MIN_READY_SAMPLES
+    }
+
+    /// Signature of all audio fed so far
+    pub fn signature(&self) -> Signature {
+        Signature {
+            sample_rate_hz: 16000,
+            number_samples: self.number_samples,
+            frequency_band_to_sound_peaks: self.frequency_band_to_sound_peaks.clone(),
+        }
+    }
+
+    /// Return the signature of all audio fed so far and start over with an empty one
+    pub fn finalize(&mut self) -> Signature {
+        let signature = Signature {
+            sample_rate_hz: 16000,
+            number_samples: self.number_samples,
+            frequency_band_to_sound_peaks: core::mem::take(&mut self.frequency_band_to_sound_peaks),
+        };
+        self.reset();
+        signature
+    }
+
+    /// Forget all audio fed so far, keeping the allocated buffers and the FFT
+    pub fn reset(&mut self) {
+        self.ring_buffer_of_samples.iter_mut().for_each(|sample| *sample = 0);
+        self.ring_buffer_of_samples_index = 0;
+        self.reordered_ring_buffer_of_samples.iter_mut().for_each(|sample| *sample = 0.0);
+        self.fft_outputs.iter_mut().flatten().for_each(|value| *value = 0.0);
+        self.fft_outputs_index = 0;
+        self.spread_fft_outputs.iter_mut().flatten().for_each(|value| *value = 0.0);
+        self.spread_fft_outputs_index = 0;
+        self.num_spread_ffts_done = 0;
+        self.pending_samples.clear();
+        self.number_samples = 0;
+        self.frequency_band_to_sound_peaks.clear();
+    }
+
+    /// Run the algorithm on exactly 128 new samples
+    fn process_step(&mut self, chunk: &[i16]) {
+        self.do_fft(chunk);
+
+        self.do_peak_spreading();
+        self.num_spread_ffts_done += 1;
+
+        if self.num_spread_ffts_done >= WARM_UP_STEPS {
+            self.do_peak_recognition();
+        }
+    }
+
+    fn do_fft(&mut self, s16_mono_16khz_buffer: &[i16]) {
+
+        // Copy the 128 input s16le samples to the local ring buffer
+
+        self.ring_buffer_of_samples[self.ring_buffer_of_samples_index..self.ring_buffer_of_samples_index + STEP].copy_from_slice(s16_mono_16khz_buffer);
+
+        self.ring_buffer_of_samples_index += STEP;
+        self.ring_buffer_of_samples_index &= FFT_SIZE - 1;
+
+        // Reorder the items (put the latest data at end) and apply Hanning window
+
+        for (index, multiplier) in HANNING_WINDOW_2048_MULTIPLIERS.iter().enumerate() {
+            self.reordered_ring_buffer_of_samples[index] =
+                self.ring_buffer_of_samples[(index + self.ring_buffer_of_samples_index) & (FFT_SIZE - 1)] as f32 *
+                    multiplier;
+        }
+
+        // Perform Fast Fourier transform
+
+        self.fft.forward(&self.reordered_ring_buffer_of_samples, &mut self.spectrum);
+
+        // Turn complex into reals, and put the results into a local array
+
+        let real_fft_results = &mut self.fft_outputs[self.fft_outputs_index];
+
+        for (value, &(re, im)) in real_fft_results.iter_mut().zip(&self.spectrum) {
+            *value = ((re * re + im * im) / ((1 << 17) as f32)).max(0.0000000001);
+        }
+
+        if let Some(bin_gains) = &self.bin_gains {
+            for (value, gain) in real_fft_results.iter_mut().zip(bin_gains) {
+                *value = (*value * gain).max(0.0000000001);
+            }
+        }
+
+        self.fft_outputs_index += 1;
+        self.fft_outputs_index &= FFT_HISTORY - 1;
+    }
+
+    fn do_peak_spreading(&mut self) {
+        let real_fft_results = &self.fft_outputs[(self.fft_outputs_index as i32 - 1) as usize & (FFT_HISTORY - 1)];
+
+        let spread_fft_results = &mut self.spread_fft_outputs[self.spread_fft_outputs_index];
+
+        // Perform frequency-domain spreading of peak values
+
+        spread_fft_results.copy_from_slice(real_fft_results);
+
+        for position in 0..=1022 {
+            spread_fft_results[position] = spread_fft_results[position]
+                .max(spread_fft_results[position + 1])
+                .max(spread_fft_results[position + 2]);
+        }
+
+        // Perform time-domain spreading of peak values
+
+        let spread_fft_results_copy = spread_fft_results.clone(); // Avoid mutable+mutable borrow of self.spread_fft_outputs
+
+        for position in 0..=1024 {
+            for former_fft_number in &[1, 3, 6] {
+                let former_fft_output = &mut self.spread_fft_outputs[(self.spread_fft_outputs_index as i32 - *former_fft_number) as usize & (SPREAD_FFT_HISTORY - 1)];
+
+                former_fft_output[position] = former_fft_output[position]
+                    .max(spread_fft_results_copy[position]);
+            }
+        }
+
+        self.spread_fft_outputs_index += 1;
+        self.spread_fft_outputs_index &= SPREAD_FFT_HISTORY - 1;
+    }
+
+    fn do_peak_recognition(&mut self) {
+
+        // Note: when substracting an array index, casting to signed is needed
+        // to avoid underflow panics at runtime.
+
+        let fft_minus_46 = &self.fft_outputs[(self.fft_outputs_index as i32 - 46) as usize & (FFT_HISTORY - 1)];
+        let fft_minus_49 = &self.spread_fft_outputs[(self.spread_fft_outputs_index as i32 - 49) as usize & (SPREAD_FFT_HISTORY - 1)];
+
+        for bin_position in 10..=1014 {
+
+            // Ensure that the bin is large enough to be a peak
+
+            if fft_minus_46[bin_position] >= 1.0 / 64.0 &&
+                fft_minus_46[bin_position] >= fft_minus_49[bin_position - 1] {
+
+                // Ensure that it is frequency-domain local minimum
+
+                let mut max_neighbor_in_fft_minus_49: f32 = 0.0;
+
+                for neighbor_offset in &[-10, -7, -4, -3, 1, 2, 5, 8] {
+                    max_neighbor_in_fft_minus_49 = max_neighbor_in_fft_minus_49
+                        .max(fft_minus_49[(bin_position as i32 + *neighbor_offset) as usize]);
+                }
+
+                if fft_minus_46[bin_position] > max_neighbor_in_fft_minus_49 {
+
+                    // Ensure that it is a time-domain local minimum
+
+                    let mut max_neighbor_in_other_adjacent_ffts = max_neighbor_in_fft_minus_49;
+
+                    // The reference implementation indexes a 256-entry ring and writes the
+                    // older FFTs as forward offsets: 165 to 249 are 91 to 7 steps back
+                    for other_offset in &[-53, -45,
+                        -91, -84, -77, -70, -63, -56,
+                        -42, -35, -28, -21, -14, -7] {
+                        let other_fft = &self.spread_fft_outputs[(self.spread_fft_outputs_index as i32 + other_offset) as usize & (SPREAD_FFT_HISTORY - 1)];
+
+                        max_neighbor_in_other_adjacent_ffts = max_neighbor_in_other_adjacent_ffts
+                            .max(other_fft[bin_position - 1]);
+                    }
+
+                    if fft_minus_46[bin_position] > max_neighbor_in_other_adjacent_ffts {
+
+                        // This is a peak, store the peak
+
+                        let fft_pass_number = self.num_spread_ffts_done - WARM_UP_STEPS;
+
+                        let peak_magnitude: f32 = math::ln(fft_minus_46[bin_position]).max(1.0 / 64.0) * 1477.3 + 6144.0;
+                        let peak_magnitude_before: f32 = math::ln(fft_minus_46[bin_position - 1]).max(1.0 / 64.0) * 1477.3 + 6144.0;
+                        let peak_magnitude_after: f32 = math::ln(fft_minus_46[bin_position + 1]).max(1.0 / 64.0) * 1477.3 + 6144.0;
+
+                        let peak_variation_1: f32 = peak_magnitude * 2.0 - peak_magnitude_before - peak_magnitude_after;
+                        let peak_variation_2: f32 = (peak_magnitude_after - peak_magnitude_before) * 32.0 / peak_variation_1;
+
+                        let corrected_peak_frequency_bin: u16 = ((bin_position as i32 * 64) + (peak_variation_2 as i32)) as u16;
+
+                        assert!(peak_variation_1 >= 0.0);
+
+                        // Convert back a FFT bin to a frequency, given a 16 KHz sample
+                        // rate, 1024 useful bins and the multiplication by 64 made before
+                        // storing the information
+
+                        let frequency_hz: f32 = corrected_peak_frequency_bin as f32 * (16000.0 / 2.0 / 1024.0 / 64.0);
+
+                        // Ignore peaks outside the 250 Hz-5.5 KHz range, store them into
+                        // a lookup table that will be used to generate the binary fingerprint
+                        // otherwise
+
+                        let frequency_band = match frequency_hz as i32 {
+                            250..=519 => FrequencyBand::_250_520,
+                            520..=1449 => FrequencyBand::_520_1450,
+                            1450..=3499 => FrequencyBand::_1450_3500,
+                            3500..=5500 => FrequencyBand::_3500_5500,
+                            _ => { continue; }
+                        };
+
+                        self.frequency_band_to_sound_peaks.entry(frequency_band).or_default().push(
+                            FrequencyPeak {
+                                fft_pass_number,
+                                peak_magnitude: peak_magnitude as u16,
+                                corrected_peak_frequency_bin
+                            }
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
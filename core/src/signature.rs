@@ -0,0 +1,185 @@
+//! Shazam signatures: frequency peaks by band, and their binary encoding
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+use crc32fast::Hasher;
+
+/// Prefix of signatures encoded as data URIs, as sent to the API
+pub const DATA_URI_PREFIX: &str = "data:audio/vnd.shazam.sig;base64,";
+
+/// Size of the fixed signature header in bytes
+pub const HEADER_SIZE: usize = 48;
+
+/// Largest binary signature accepted when decoding, real ones are a few kilobytes
+pub const MAX_SIGNATURE_SIZE: usize = 512 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct FrequencyPeak {
+    pub fft_pass_number: u32,
+    pub peak_magnitude: u16,
+    pub corrected_peak_frequency_bin: u16
+}
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+pub enum FrequencyBand {
+    _250_520 = 0,
+    _520_1450 = 1,
+    _1450_3500 = 2,
+    _3500_5500 = 3
+}
+
+impl Ord for FrequencyBand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (*self as i32).cmp(&(*other as i32))
+    }
+}
+
+impl PartialOrd for FrequencyBand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Why a signature cannot be encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// Sample rate without an identifier in the format, in Hz
+    InvalidSampleRate(u32),
+    TooManySamples,
+    /// Peaks of a band must be sorted by FFT pass number
+    UnsortedPeaks,
+    /// Encoded size over `MAX_SIGNATURE_SIZE`, in bytes
+    TooLarge(usize),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::InvalidSampleRate(hz) => write!(f, "Invalid sample rate passed when encoding Shazam packet: {} Hz", hz),
+            EncodeError::TooManySamples => write!(f, "Too many samples to encode in a Shazam packet"),
+            EncodeError::UnsortedPeaks => write!(f, "Frequency peaks must be sorted by FFT pass number"),
+            EncodeError::TooLarge(size) => write!(f, "Signature too large: {} bytes", size),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodeError {}
+
+/// Signature made by `SignatureGenerator`
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub sample_rate_hz: u32,
+    pub number_samples: u32,
+    pub frequency_band_to_sound_peaks: BTreeMap<FrequencyBand, Vec<FrequencyPeak>>,
+}
+
+impl Signature {
+    pub fn encode_to_binary(&self) -> Result<Vec<u8>, EncodeError> {
+        encode_binary(
+            self.sample_rate_hz,
+            self.number_samples,
+            self.frequency_band_to_sound_peaks.iter().map(|(band, peaks)| (*band, peaks.as_slice())),
+        )
+    }
+
+    pub fn encode_to_uri(&self) -> Result<String, EncodeError> {
+        Ok(format!("{}{}", DATA_URI_PREFIX, base64::encode(self.encode_to_binary()?)))
+    }
+
+    /// Number of peaks in all frequency bands
+    pub fn peak_count(&self) -> usize {
+        self.frequency_band_to_sound_peaks.values().map(Vec::len).sum()
+    }
+}
+
+/// Encode the peaks of each band in the binary format of the API, bands in any order
+pub fn encode_binary<'a, I>(sample_rate_hz: u32, number_samples: u32, bands: I) -> Result<Vec<u8>, EncodeError>
+where
+    I: IntoIterator<Item = (FrequencyBand, &'a [FrequencyPeak])>,
+{
+    let mut buffer: Vec<u8> = Vec::new();
+
+    // Header, see the decoder of songrec-lib for the meaning of each field
+    write_u32(&mut buffer, 0xcafe2580); // magic1
+    write_u32(&mut buffer, 0); // crc32 - Will write later
+    write_u32(&mut buffer, 0); // size_minus_header - Will write later
+    write_u32(&mut buffer, 0x94119c00); // magic2
+    write_u32(&mut buffer, 0); // void1
+    write_u32(&mut buffer, 0);
+    write_u32(&mut buffer, 0);
+    let sample_rate_id = match sample_rate_hz {
+        8000 => 1,
+        11025 => 2,
+        16000 => 3,
+        32000 => 4,
+        44100 => 5,
+        48000 => 6,
+        _ => return Err(EncodeError::InvalidSampleRate(sample_rate_hz)),
+    };
+    write_u32(&mut buffer, sample_rate_id << 27); // shifted_sample_rate_id
+    write_u32(&mut buffer, 0); // void2
+    write_u32(&mut buffer, 0);
+    let number_samples_plus_divided_sample_rate = number_samples
+        .checked_add((sample_rate_hz as f32 * 0.24) as u32)
+        .ok_or(EncodeError::TooManySamples)?;
+    write_u32(&mut buffer, number_samples_plus_divided_sample_rate);
+    write_u32(&mut buffer, (15 << 19) + 0x40000); // fixed_value
+
+    write_u32(&mut buffer, 0x40000000);
+    write_u32(&mut buffer, 0); // size_minus_header - Will write later
+
+    let mut bands: Vec<_> = bands.into_iter().collect();
+    bands.sort_by_key(|band| band.0);
+
+    for (frequency_band, frequency_peaks) in bands {
+        let mut peaks_buffer: Vec<u8> = Vec::new();
+        let mut fft_pass_number = 0;
+
+        for frequency_peak in frequency_peaks {
+            if frequency_peak.fft_pass_number < fft_pass_number {
+                return Err(EncodeError::UnsortedPeaks);
+            }
+
+            if frequency_peak.fft_pass_number - fft_pass_number >= 255 {
+                peaks_buffer.push(0xff);
+                write_u32(&mut peaks_buffer, frequency_peak.fft_pass_number);
+                fft_pass_number = frequency_peak.fft_pass_number;
+            }
+
+            peaks_buffer.push((frequency_peak.fft_pass_number - fft_pass_number) as u8);
+            peaks_buffer.extend_from_slice(&frequency_peak.peak_magnitude.to_le_bytes());
+            peaks_buffer.extend_from_slice(&frequency_peak.corrected_peak_frequency_bin.to_le_bytes());
+
+            fft_pass_number = frequency_peak.fft_pass_number;
+        }
+
+        write_u32(&mut buffer, 0x60030040 + frequency_band as u32);
+        write_u32(&mut buffer, peaks_buffer.len() as u32);
+        buffer.extend_from_slice(&peaks_buffer);
+        buffer.resize(buffer.len() + (4 - peaks_buffer.len() % 4) % 4, 0);
+    }
+
+    if buffer.len() > MAX_SIGNATURE_SIZE {
+        return Err(EncodeError::TooLarge(buffer.len()));
+    }
+
+    let size_minus_header = ((buffer.len() - HEADER_SIZE) as u32).to_le_bytes();
+    buffer[8..12].copy_from_slice(&size_minus_header);
+    buffer[HEADER_SIZE + 4..HEADER_SIZE + 8].copy_from_slice(&size_minus_header);
+
+    let mut hasher = Hasher::new();
+    hasher.update(&buffer[8..]);
+    buffer[4..8].copy_from_slice(&hasher.finalize().to_le_bytes());
+
+    Ok(buffer)
+}
+
+fn write_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
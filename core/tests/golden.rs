@@ -0,0 +1,100 @@
+//! Checks of the core on its own, where the `std` feature songrec-lib enables is off
+//!
+//! Within the workspace the feature is unified with songrec-lib's, so run these with
+//! `cargo test -p songrec-core` to cover the libm math of `no_std` builds.
+
+use std::path::Path;
+use std::process::Command;
+
+use serde_json::Value;
+use songrec_core::{RadixTwoFft, SignatureGenerator};
+
+/// Share of golden peaks that must be found again, and how far each may move, as in the
+/// golden tests of songrec-lib
+const MIN_MATCHED_PEAKS: f64 = 0.97;
+const BIN_TOLERANCE: i64 = 1;
+const MAGNITUDE_TOLERANCE: i64 = 16;
+
+/// Bare-metal target the `no_std` build is checked for
+const NO_STD_TARGET: &str = "thumbv7em-none-eabihf";
+
+fn workspace_path(path: &str) -> String {
+    format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), path)
+}
+
+/// The peaks of the white noise fixture are those of its golden file, with the math of
+/// libm when the `std` feature is off
+#[test]
+fn white_noise_matches_golden() {
+    // 16-bit mono 16 kHz PCM after the 44-byte WAV header
+    let wav = std::fs::read(workspace_path("tests/golden/white_noise.wav")).unwrap();
+    let samples: Vec<i16> = wav[44..].chunks_exact(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+    let golden: Value = serde_json::from_str(&std::fs::read_to_string(workspace_path("tests/golden/white_noise.json")).unwrap()).unwrap();
+
+    let mut generator = SignatureGenerator::new(RadixTwoFft::new());
+    generator.feed(&samples);
+    let signature = generator.finalize();
+    assert_eq!(Value::from(signature.sample_rate_hz), golden["sample_rate_hz"]);
+    assert_eq!(Value::from(signature.number_samples), golden["number_samples"]);
+
+    let mut golden_total = 0;
+    let mut matched = 0;
+    for (band, expected) in golden["bands"].as_object().unwrap() {
+        let expected = expected.as_array().unwrap();
+        let found = signature
+            .frequency_band_to_sound_peaks
+            .iter()
+            .find(|(found_band, _)| (**found_band as i32).to_string() == *band)
+            .map(|(_, peaks)| peaks.as_slice())
+            .unwrap_or_default();
+
+        golden_total += expected.len();
+        matched += expected
+            .iter()
+            .filter(|peak| {
+                let (pass, magnitude, bin) = (peak[0].as_i64().unwrap(), peak[1].as_i64().unwrap(), peak[2].as_i64().unwrap());
+                found.iter().any(|found| {
+                    found.fft_pass_number as i64 == pass
+                        && (found.corrected_peak_frequency_bin as i64 - bin).abs() <= BIN_TOLERANCE
+                        && (found.peak_magnitude as i64 - magnitude).abs() <= MAGNITUDE_TOLERANCE
+                })
+            })
+            .count();
+    }
+
+    assert!(
+        matched as f64 >= MIN_MATCHED_PEAKS * golden_total as f64,
+        "only {}/{} golden peaks found again",
+        matched,
+        golden_total
+    );
+    let changed = (signature.peak_count() as f64 - golden_total as f64).abs();
+    assert!(
+        changed <= (1.0 - MIN_MATCHED_PEAKS) * golden_total as f64,
+        "peak count changed from {} to {}",
+        golden_total,
+        signature.peak_count()
+    );
+}
+
+/// The core builds without `std` for a microcontroller, skipped if its target is not installed
+#[test]
+fn builds_without_std() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--no-default-features", "--target", NO_STD_TARGET])
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("Cargo.toml"))
+        // A directory of its own, not to wait on the lock of the build running the tests
+        .arg("--target-dir")
+        .arg(manifest_dir.join("../target/no_std"))
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("can't find crate for `core`") {
+        println!("Skipping no_std build - add the target with: rustup target add {}", NO_STD_TARGET);
+        return;
+    }
+    assert!(output.status.success(), "{}", stderr);
+}
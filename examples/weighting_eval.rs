@@ -0,0 +1,154 @@
+use songrec::{ChannelMode, Config, SongRec, SpectralWeighting, SongRecError};
+use std::path::{Path, PathBuf};
+
+/// Weightings compared, the first one is the reference
+const WEIGHTINGS: [(&str, SpectralWeighting); 3] = [
+    ("none", SpectralWeighting::None),
+    ("pre-emphasis", SpectralWeighting::PreEmphasis),
+    ("a-weighting", SpectralWeighting::AWeighting),
+];
+
+/// Compare match rates of the spectral weightings on a corpus of noisy recordings
+///
+/// Usage: `cargo run --example weighting_eval -- <dir> [--snr DB]`
+///
+/// Without `--snr`, the files are expected to be degraded already (e.g. phone recordings
+/// made in a bar) and only match rates are reported. With `--snr`, clean files are turned
+/// into phone-speaker-in-a-bar recordings first: band-limited to 300-3400 Hz, mixed with
+/// rumble and hiss at the given signal-to-noise ratio, then clipped. A degraded match is
+/// only counted as correct if it finds the track recognized on the clean audio.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let dir = arguments.first().ok_or("Usage: weighting_eval <dir> [--snr DB]")?;
+    let snr = match arguments.iter().position(|argument| argument == "--snr") {
+        Some(index) => Some(arguments.get(index + 1).ok_or("--snr needs a value in dB")?.parse::<f32>()?),
+        None => None,
+    };
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    // Requests are spaced out to stay clear of rate limiting
+    let config = Config::default().with_quiet_mode(true).with_lookup_delay(1.0);
+    let recognizers: Vec<(&str, SongRec)> = WEIGHTINGS
+        .iter()
+        .map(|(name, weighting)| (*name, SongRec::new(config.clone().with_spectral_weighting(*weighting))))
+        .collect();
+
+    let mut matches = [0usize; WEIGHTINGS.len()];
+    let mut correct = [0usize; WEIGHTINGS.len()];
+    let mut evaluated = 0;
+
+    for path in &files {
+        let samples = match decode(path) {
+            Ok(samples) if samples.len() >= 3 * 16000 => samples,
+            _ => continue, // Not audio, or too short
+        };
+        let window = middle_window(&samples);
+
+        let (reference, clip) = match snr {
+            Some(snr) => {
+                let reference = match recognizers[0].1.recognize_from_samples(window, 16000) {
+                    Ok(result) => result.track_key,
+                    Err(SongRecError::NoMatch(_)) => {
+                        println!("{}: not recognized even without noise, skipped", path.display());
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                (Some(reference), degrade(window, snr))
+            }
+            None => (None, window.to_vec()),
+        };
+        evaluated += 1;
+
+        let mut line = format!("{}:", path.display());
+        for (index, (name, songrec)) in recognizers.iter().enumerate() {
+            let outcome = match songrec.recognize_from_samples(&clip, 16000) {
+                Ok(result) => {
+                    matches[index] += 1;
+                    if reference.is_none() || reference.as_ref() == Some(&result.track_key) {
+                        correct[index] += 1;
+                        format!("{} - {}", result.artist_name, result.song_name)
+                    } else {
+                        format!("wrong match ({} - {})", result.artist_name, result.song_name)
+                    }
+                }
+                Err(SongRecError::NoMatch(_)) => "no match".to_string(),
+                Err(e) => return Err(e.into()),
+            };
+            line.push_str(&format!("  [{}] {}", name, outcome));
+        }
+        println!("{}", line);
+    }
+
+    println!("\n{} recordings evaluated", evaluated);
+    for (index, (name, _)) in WEIGHTINGS.iter().enumerate() {
+        let rate = |count: usize| count as f32 * 100.0 / evaluated.max(1) as f32;
+        match snr {
+            Some(_) => println!("{:>13}: {:5.1}% correct, {:5.1}% matched", name, rate(correct[index]), rate(matches[index])),
+            None => println!("{:>13}: {:5.1}% matched", name, rate(matches[index])),
+        }
+    }
+    Ok(())
+}
+
+/// Decode an audio file to mono 16 KHz samples
+fn decode(path: &Path) -> Result<Vec<i16>, Box<dyn std::error::Error>> {
+    Ok(songrec::audio::decode_file(&path.to_string_lossy(), ChannelMode::Mid)?)
+}
+
+/// The 12 seconds from the middle, as fingerprinted for files
+fn middle_window(samples: &[i16]) -> &[i16] {
+    if samples.len() <= 12 * 16000 {
+        return samples;
+    }
+    let middle = samples.len() / 2;
+    &samples[middle - 6 * 16000..middle + 6 * 16000]
+}
+
+/// Simulate a phone speaker heard in a noisy room, `snr` in dB
+fn degrade(samples: &[i16], snr: f32) -> Vec<i16> {
+    // Telephone band: one-pole high-pass at 300 Hz, then low-pass at 3400 Hz
+    let coefficient = |cutoff: f32| (-std::f32::consts::TAU * cutoff / 16000.0).exp();
+    let (high_pass, low_pass) = (coefficient(300.0), coefficient(3400.0));
+    let (mut previous_input, mut high, mut low) = (0.0f32, 0.0f32, 0.0f32);
+    let band: Vec<f32> = samples
+        .iter()
+        .map(|&sample| {
+            let input = sample as f32;
+            high = high_pass * (high + input - previous_input);
+            previous_input = input;
+            low = (1.0 - low_pass) * high + low_pass * low;
+            low
+        })
+        .collect();
+
+    // Crowd noise: low rumble plus hiss, scaled to the requested ratio
+    let mut seed: u32 = 0x2545_f491;
+    let mut white = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let mut rumble = 0.0f32;
+    let noise: Vec<f32> = (0..band.len())
+        .map(|_| {
+            rumble = 0.98 * rumble + 0.02 * white();
+            rumble * 8.0 + white() * 0.3
+        })
+        .collect();
+
+    let power = |signal: &[f32]| signal.iter().map(|value| value * value).sum::<f32>() / signal.len().max(1) as f32;
+    let gain = (power(&band) / power(&noise).max(f32::MIN_POSITIVE) / 10f32.powf(snr / 10.0)).sqrt();
+
+    // Cheap speakers clip well below full scale
+    band.iter()
+        .zip(&noise)
+        .map(|(signal, noise)| (signal * 2.0 + noise * gain * 2.0).clamp(-16000.0, 16000.0) as i16)
+        .collect()
+}
@@ -0,0 +1,76 @@
+use super::{spectrogram, KeyMode, MusicalKey};
+
+/// Krumhansl-Kessler key profiles, from the tonic
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Frequency range folded into pitch classes, in Hz
+const MIN_FREQUENCY: f32 = 100.0;
+const MAX_FREQUENCY: f32 = 2000.0;
+
+/// Frequency of middle C
+const C4_FREQUENCY: f32 = 261.63;
+
+/// Estimate the key by matching the pitch class profile of the audio with the key profiles
+pub(super) fn estimate_key(samples: &[i16], sample_rate: u32) -> Option<MusicalKey> {
+    const FRAME_SIZE: usize = 4096;
+    let spectra = spectrogram(samples, FRAME_SIZE, FRAME_SIZE / 2);
+
+    // Pitch class of each bin, only for bins less than a third of a semitone from a note
+    let bin_frequency = sample_rate as f32 / FRAME_SIZE as f32;
+    let pitch_classes: Vec<Option<usize>> = (0..=FRAME_SIZE / 2)
+        .map(|bin| {
+            let frequency = bin as f32 * bin_frequency;
+            if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+                return None;
+            }
+            let semitones = 12.0 * (frequency / C4_FREQUENCY).log2();
+            ((semitones - semitones.round()).abs() < 0.33).then(|| semitones.round().rem_euclid(12.0) as usize)
+        })
+        .collect();
+
+    let mut chroma = [0.0f32; 12];
+    for spectrum in &spectra {
+        for (magnitude, pitch_class) in spectrum.iter().zip(&pitch_classes) {
+            if let Some(pitch_class) = pitch_class {
+                chroma[*pitch_class] += magnitude;
+            }
+        }
+    }
+
+    // Pitch classes do not cover the same number of bins, average them so noise stays flat
+    for (pitch_class, value) in chroma.iter_mut().enumerate() {
+        let bins = pitch_classes.iter().filter(|bin| **bin == Some(pitch_class)).count();
+        *value /= bins.max(1) as f32;
+    }
+
+    // Noise and unpitched sounds spread evenly over pitch classes
+    let mean = chroma.iter().sum::<f32>() / 12.0;
+    let spread = chroma.iter().fold(0.0f32, |max, value| max.max(*value)) - chroma.iter().fold(f32::MAX, |min, value| min.min(*value));
+    if mean <= f32::EPSILON || spread < 0.25 * mean {
+        return None;
+    }
+
+    (0..12u8)
+        .flat_map(|tonic| [(tonic, KeyMode::Major, &MAJOR_PROFILE), (tonic, KeyMode::Minor, &MINOR_PROFILE)])
+        .map(|(tonic, mode, profile)| {
+            let rotated: Vec<f32> = (0..12).map(|pitch_class| chroma[(pitch_class + tonic as usize) % 12]).collect();
+            (MusicalKey { tonic, mode }, correlation(&rotated, profile))
+        })
+        .max_by(|first, second| first.1.total_cmp(&second.1))
+        .filter(|(_, correlation)| *correlation > 0.0)
+        .map(|(key, _)| key)
+}
+
+/// Pearson correlation of two vectors
+fn correlation(first: &[f32], second: &[f32]) -> f32 {
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let (first_mean, second_mean) = (mean(first), mean(second));
+    let (mut covariance, mut first_variance, mut second_variance) = (0.0, 0.0, 0.0);
+    for (a, b) in first.iter().zip(second) {
+        covariance += (a - first_mean) * (b - second_mean);
+        first_variance += (a - first_mean) * (a - first_mean);
+        second_variance += (b - second_mean) * (b - second_mean);
+    }
+    covariance / (first_variance * second_variance).sqrt().max(f32::MIN_POSITIVE)
+}
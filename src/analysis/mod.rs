@@ -0,0 +1,99 @@
+//! Local tempo and key estimation of recognized songs
+//!
+//! The types are always available so results can be stored and read back, the
+//! estimation itself requires the analysis feature.
+
+#[cfg(feature = "analysis")]
+mod key;
+#[cfg(feature = "analysis")]
+mod tempo;
+
+use serde::{Deserialize, Serialize};
+
+/// Names of the pitch classes, from C
+const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/// Tempo and key estimated from the audio of a match, see `Config::with_track_analysis`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackAnalysis {
+    /// Beats per minute, between 60 and 200, None without a clear pulse
+    pub bpm: Option<f32>,
+
+    /// None for audio without a clear pitch content, e.g. speech or drums only
+    pub key: Option<MusicalKey>,
+}
+
+/// Major or minor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyMode {
+    Major,
+    Minor,
+}
+
+/// Musical key, e.g. A minor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MusicalKey {
+    /// Pitch class of the tonic, 0 for C to 11 for B
+    pub tonic: u8,
+    pub mode: KeyMode,
+}
+
+impl MusicalKey {
+    /// Key name, e.g. "F# minor"
+    pub fn name(&self) -> String {
+        let mode = match self.mode {
+            KeyMode::Major => "major",
+            KeyMode::Minor => "minor",
+        };
+        format!("{} {}", PITCH_NAMES[self.tonic as usize % 12], mode)
+    }
+
+    /// Camelot wheel notation used by DJ software, e.g. "8A" for A minor or "8B" for C major
+    pub fn camelot(&self) -> String {
+        // Neighbours on the wheel are a fifth apart
+        let (offset, letter) = match self.mode {
+            KeyMode::Major => (7, 'B'),
+            KeyMode::Minor => (4, 'A'),
+        };
+        format!("{}{}", (self.tonic as usize * 7 + offset) % 12 + 1, letter)
+    }
+}
+
+impl std::fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Estimate the tempo and key of mono audio (requires the analysis feature)
+///
+/// A few seconds are enough for the tempo, the key is more reliable over longer excerpts.
+#[cfg(feature = "analysis")]
+pub fn analyze(samples: &[i16], sample_rate: u32) -> TrackAnalysis {
+    TrackAnalysis {
+        bpm: tempo::estimate_bpm(samples, sample_rate),
+        key: key::estimate_key(samples, sample_rate),
+    }
+}
+
+/// Magnitude spectra of Hann-windowed frames of `frame_size` samples taken every `hop` samples
+#[cfg(feature = "analysis")]
+fn spectrogram(samples: &[i16], frame_size: usize, hop: usize) -> Vec<Vec<f32>> {
+    let window: Vec<f32> = (0..frame_size)
+        .map(|index| 0.5 - 0.5 * (std::f32::consts::TAU * index as f32 / frame_size as f32).cos())
+        .collect();
+    let mut fft = chfft::RFft1D::<f32>::new(frame_size);
+    let frames = if samples.len() < frame_size { 0 } else { (samples.len() - frame_size) / hop + 1 };
+
+    (0..frames)
+        .map(|frame| {
+            let input: Vec<f32> = samples[frame * hop..frame * hop + frame_size]
+                .iter()
+                .zip(&window)
+                .map(|(&sample, weight)| sample as f32 / 32768.0 * weight)
+                .collect();
+            fft.forward(&input).iter().map(|bin| bin.norm()).collect()
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+use super::spectrogram;
+
+/// Tempo range searched, in beats per minute
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+
+/// Tempo favoured between candidates an octave apart, as listeners tap along
+const PREFERRED_BPM: f32 = 120.0;
+
+/// Onset strength frames per second
+const FRAME_RATE: u32 = 100;
+
+/// Estimate the tempo from the periodicity of onsets, None for audio without a steady pulse
+pub(super) fn estimate_bpm(samples: &[i16], sample_rate: u32) -> Option<f32> {
+    let hop = (sample_rate / FRAME_RATE).max(1) as usize;
+    let frame_rate = sample_rate as f32 / hop as f32;
+    let spectra = spectrogram(samples, 1024, hop);
+
+    // Onset strength: spectral flux of the log-compressed magnitudes
+    let compressed: Vec<Vec<f32>> = spectra
+        .iter()
+        .map(|spectrum| spectrum.iter().map(|magnitude| (1.0 + 100.0 * magnitude).ln()).collect())
+        .collect();
+    let flux: Vec<f32> = compressed
+        .windows(2)
+        .map(|pair| pair[1].iter().zip(&pair[0]).map(|(current, previous)| (current - previous).max(0.0)).sum())
+        .collect();
+
+    // Less than 4 seconds cannot show a pulse reliably
+    if (flux.len() as f32) < 4.0 * frame_rate {
+        return None;
+    }
+
+    // Keep the peaks above the local average, then center for the autocorrelation
+    let half_width = (frame_rate / 10.0) as usize;
+    let mut onsets: Vec<f32> = (0..flux.len())
+        .map(|index| {
+            let around = &flux[index.saturating_sub(half_width)..(index + half_width + 1).min(flux.len())];
+            (flux[index] - around.iter().sum::<f32>() / around.len() as f32).max(0.0)
+        })
+        .collect();
+    let mean = onsets.iter().sum::<f32>() / onsets.len() as f32;
+    onsets.iter_mut().for_each(|onset| *onset -= mean);
+    let energy: f32 = onsets.iter().map(|onset| onset * onset).sum::<f32>() / onsets.len() as f32;
+    if energy <= f32::EPSILON {
+        return None;
+    }
+
+    let autocorrelation = |lag: usize| -> f32 {
+        let products: f32 = onsets.iter().zip(&onsets[lag..]).map(|(first, second)| first * second).sum();
+        products / (onsets.len() - lag) as f32 / energy
+    };
+
+    let min_lag = (60.0 * frame_rate / MAX_BPM).floor() as usize;
+    let max_lag = ((60.0 * frame_rate / MIN_BPM).ceil() as usize).min(onsets.len() / 2);
+    let scores: Vec<(usize, f32)> = (min_lag.saturating_sub(1)..=max_lag + 1).map(|lag| (lag, autocorrelation(lag))).collect();
+
+    let weight = |lag: f32| {
+        let octaves = (60.0 * frame_rate / lag / PREFERRED_BPM).log2();
+        (-0.5 * octaves * octaves).exp()
+    };
+    let best = (1..scores.len() - 1)
+        .filter(|&index| scores[index].1 >= scores[index - 1].1 && scores[index].1 >= scores[index + 1].1)
+        .max_by(|&first, &second| {
+            let score = |index: usize| scores[index].1 * weight(scores[index].0 as f32);
+            score(first).total_cmp(&score(second))
+        })?;
+
+    // The pulse must stand out from the chance correlation of unrelated onsets
+    let (lag, score) = scores[best];
+    if score < 0.2 {
+        return None;
+    }
+
+    // Parabolic interpolation between the neighbouring lags
+    let (previous, next) = (scores[best - 1].1, scores[best + 1].1);
+    let curvature = previous - 2.0 * score + next;
+    let shift = if curvature < 0.0 { (0.5 * (previous - next) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+    let bpm = 60.0 * frame_rate / (lag as f32 + shift);
+
+    (MIN_BPM..=MAX_BPM).contains(&bpm).then(|| (bpm * 10.0).round() / 10.0)
+}
@@ -0,0 +1,125 @@
+use chfft::RFft1D;
+use serde::{Deserialize, Serialize};
+
+/// Windows quieter than this are silence, in dBFS
+const SILENCE_THRESHOLD_DBFS: f32 = -50.0;
+
+/// Share of frames much quieter than their surroundings above which the level is uneven like speech
+const SPEECH_LOW_ENERGY_RATIO: f32 = 0.3;
+
+/// Share of frames with many more zero crossings than their surroundings above which a window
+/// alternates voiced and unvoiced sounds like speech
+const SPEECH_HIGH_ZCR_RATIO: f32 = 0.15;
+
+/// Spectral flatness above which a window is noisy like speech, music is mostly tonal
+const SPEECH_FLATNESS: f32 = 0.15;
+
+/// What a window of audio sounds like, see `Config::with_music_only`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioClass {
+    Silence,
+    Speech,
+    Music,
+}
+
+/// Features a window of audio is classified with
+///
+/// Speech alternates syllables and short pauses, and voiced and unvoiced sounds, which
+/// makes its spectrum noisier than the one of music.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioFeatures {
+    /// Unweighted RMS level, in dBFS
+    pub rms_dbfs: f32,
+
+    /// Share of 20 ms frames below half the average energy of the second around them
+    pub low_energy_ratio: f32,
+
+    /// Share of 20 ms frames with over 1.5 times the average zero-crossing rate of the second around them
+    pub high_zcr_ratio: f32,
+
+    /// Average spectral flatness between 100 Hz and 4 kHz, 0 for pure tones to 1 for white noise
+    pub spectral_flatness: f32,
+}
+
+impl AudioFeatures {
+    /// Measure mono 16-bit samples
+    pub fn measure(samples: &[i16], sample_rate: u32) -> Self {
+        let frame_length = (sample_rate as usize / 50).max(1);
+        let frames: Vec<(f32, f32)> = samples
+            .chunks_exact(frame_length)
+            .map(|frame| {
+                let energy = frame.iter().map(|&sample| (sample as f32 / 32768.0).powi(2)).sum::<f32>() / frame_length as f32;
+                let crossings = frame.windows(2).filter(|pair| (pair[0] < 0) != (pair[1] < 0)).count();
+                (energy, crossings as f32 / frame_length as f32)
+            })
+            .collect();
+
+        let (mut low_energy, mut high_zcr) = (0, 0);
+        for second in frames.chunks(50) {
+            let average_energy = second.iter().map(|frame| frame.0).sum::<f32>() / second.len() as f32;
+            let average_zcr = second.iter().map(|frame| frame.1).sum::<f32>() / second.len() as f32;
+            low_energy += second.iter().filter(|frame| frame.0 < 0.5 * average_energy).count();
+            high_zcr += second.iter().filter(|frame| frame.1 > 1.5 * average_zcr).count();
+        }
+        let ratio = |count: usize| count as f32 / frames.len().max(1) as f32;
+
+        let mean_square = frames.iter().map(|frame| frame.0).sum::<f32>() / frames.len().max(1) as f32;
+        let rms_dbfs = if mean_square > 0.0 { (10.0 * mean_square.log10()).max(-120.0) } else { -120.0 };
+
+        Self {
+            rms_dbfs,
+            low_energy_ratio: ratio(low_energy),
+            high_zcr_ratio: ratio(high_zcr),
+            spectral_flatness: spectral_flatness(samples, sample_rate),
+        }
+    }
+
+    /// Silence below -50 dBFS, speech for a noisy spectrum with an uneven level or zero-crossing rate
+    ///
+    /// Percussive music has an uneven level too, the flatness of its spectrum tells it apart.
+    pub fn class(&self) -> AudioClass {
+        if self.rms_dbfs < SILENCE_THRESHOLD_DBFS {
+            AudioClass::Silence
+        } else if self.spectral_flatness > SPEECH_FLATNESS
+            && (self.low_energy_ratio > SPEECH_LOW_ENERGY_RATIO || self.high_zcr_ratio > SPEECH_HIGH_ZCR_RATIO)
+        {
+            AudioClass::Speech
+        } else {
+            AudioClass::Music
+        }
+    }
+}
+
+/// Classify mono 16-bit samples, a few seconds are needed for reliable results
+pub fn classify(samples: &[i16], sample_rate: u32) -> AudioClass {
+    AudioFeatures::measure(samples, sample_rate).class()
+}
+
+/// Average flatness of the power spectrum of 512-sample frames, quiet frames left out
+fn spectral_flatness(samples: &[i16], sample_rate: u32) -> f32 {
+    const FRAME_SIZE: usize = 512;
+    let mut fft = RFft1D::<f32>::new(FRAME_SIZE);
+    let bin_frequency = sample_rate as f32 / FRAME_SIZE as f32;
+    let first_bin = (100.0 / bin_frequency).ceil() as usize;
+    let last_bin = ((4000.0 / bin_frequency) as usize).min(FRAME_SIZE / 2);
+    if first_bin >= last_bin {
+        return 0.0;
+    }
+
+    let flatness: Vec<f32> = samples
+        .chunks_exact(FRAME_SIZE)
+        .filter_map(|frame| {
+            let input: Vec<f32> = frame.iter().map(|&sample| sample as f32 / 32768.0).collect();
+            let power: Vec<f32> = fft.forward(&input)[first_bin..=last_bin].iter().map(|bin| bin.norm_sqr()).collect();
+            let arithmetic = power.iter().sum::<f32>() / power.len() as f32;
+            if arithmetic < 1e-6 {
+                return None;
+            }
+            let geometric = (power.iter().map(|value| (value + 1e-12).ln()).sum::<f32>() / power.len() as f32).exp();
+            Some(geometric / arithmetic)
+        })
+        .collect();
+
+    if flatness.is_empty() { 0.0 } else { flatness.iter().sum::<f32>() / flatness.len() as f32 }
+}
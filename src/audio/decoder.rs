@@ -0,0 +1,137 @@
+//! Decoding of audio files to the mono 16 kHz samples fingerprinting expects, through
+//! symphonia, which needs no audio device
+
+use std::io::{Read, Seek, SeekFrom};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::config::ChannelMode;
+use crate::fingerprinting::algorithm::{decode_error, SignatureGenerator};
+use crate::Result;
+
+const TARGET_SAMPLE_RATE: u64 = 16000;
+
+/// Decode an audio file to the mono 16 kHz samples it is fingerprinted from, keeping the
+/// channel selected by `channel_mode`
+pub fn decode_file(path: &str, channel_mode: ChannelMode) -> Result<Vec<i16>> {
+    SignatureGenerator::decode_file(path, channel_mode).map_err(decode_error)
+}
+
+/// Decode a WAV, MP3, Ogg Vorbis, FLAC or M4A file, or the audio of an MP4, MOV, WebM or
+/// MKV video, calling `push` with each mono 16 kHz sample
+///
+/// Frames that cannot be decoded are skipped, as players do.
+pub(crate) fn decode_to_mono_16khz<R>(reader: R, channel_mode: ChannelMode, push: &mut dyn FnMut(i16)) -> std::result::Result<(), String>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let source = MediaSourceStream::new(Box::new(SeekableSource(reader)), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&Hint::new(), source, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| e.to_string())?;
+    let mut format = probed.format;
+
+    // Videos also have tracks without a sample rate
+    let track = format.tracks().iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL && track.codec_params.sample_rate.is_some())
+        .ok_or("No audio track found")?;
+    let track_id = track.id;
+    let mut resampler = Resampler::new(track.codec_params.sample_rate.unwrap_or(TARGET_SAMPLE_RATE as u32));
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut buffer: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        // Packets are usually the same length, the buffer only grows for a longer one
+        if buffer.as_ref().is_none_or(|buffer| buffer.capacity() < decoded.capacity() * channels) {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buffer = buffer.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, spec));
+
+        buffer.copy_interleaved_ref(decoded);
+        for frame in buffer.samples().chunks_exact(channels) {
+            resampler.push(channel_mode.mix(frame, f32::from), &mut |sample| push(sample as i16));
+        }
+    }
+    Ok(())
+}
+
+/// Linear interpolation of mono audio to 16 kHz, fed one sample at a time
+pub(crate) struct Resampler {
+    sample_rate: u64,
+    /// Index of the next input sample
+    input: u64,
+    /// Index of the next output sample
+    output: u64,
+    previous: f32,
+}
+
+impl Resampler {
+    /// Resampler of audio at `sample_rate`
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        Self { sample_rate: sample_rate.max(1) as u64, input: 0, output: 0, previous: 0.0 }
+    }
+
+    /// Add the next input sample, calling `output` with each output sample it completes
+    pub(crate) fn push(&mut self, sample: f32, output: &mut dyn FnMut(f32)) {
+        // Output sample n lies at n * sample_rate / 16000 input samples, between the
+        // previous input sample and this one
+        while self.output * self.sample_rate <= self.input * TARGET_SAMPLE_RATE {
+            let fraction = (self.output * self.sample_rate + TARGET_SAMPLE_RATE - self.input * TARGET_SAMPLE_RATE) as f32
+                / TARGET_SAMPLE_RATE as f32;
+            output(self.previous + (sample - self.previous) * fraction);
+            self.output += 1;
+        }
+        self.previous = sample;
+        self.input += 1;
+    }
+}
+
+/// Any seekable reader, which symphonia only takes for files and in-memory data
+struct SeekableSource<R>(R);
+
+impl<R: Read> Read for SeekableSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for SeekableSource<R> {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(position)
+    }
+}
+
+impl<R: Read + Seek + Send + Sync> MediaSource for SeekableSource<R> {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,77 @@
+//! Detection of DRM-protected files, whose audio cannot be decoded
+
+/// Bytes read from the start of a file to look for DRM, enough for the header of MP4 files
+pub(crate) const DRM_PROBE_BYTES: u64 = 256 * 1024;
+
+/// Boxes leading from the top of an MP4 file to the sample descriptions of a track
+const SAMPLE_DESCRIPTION_PATH: [&[u8; 4]; 6] = [b"moov", b"trak", b"mdia", b"minf", b"stbl", b"stsd"];
+
+/// Name of the DRM protecting an MP4 file from its first bytes, None if it is not protected
+///
+/// Covers iTunes purchases (M4P), Audible audiobooks (AAX) and encrypted MP4 audio,
+/// recognized by the brand of the file or the type of the sample entries of its tracks.
+/// Files whose `moov` box comes after the probed bytes are taken as unprotected.
+pub(crate) fn drm_protection(start: &[u8]) -> Option<&'static str> {
+    if start.get(4..8) != Some(b"ftyp".as_slice()) {
+        return None;
+    }
+
+    match start.get(8..12) {
+        Some(b"M4P ") => return Some("Apple FairPlay"),
+        Some(b"aax ") | Some(b"aaxc") => return Some("Audible"),
+        _ => {}
+    }
+
+    let mut protection = None;
+    visit_sample_entries(start, &SAMPLE_DESCRIPTION_PATH, &mut |kind| {
+        protection = protection.or(match kind {
+            b"drms" => Some("Apple FairPlay"),
+            b"aavd" => Some("Audible"),
+            b"enca" => Some("Common Encryption"),
+            _ => None,
+        });
+    });
+    protection
+}
+
+/// Call `visit` with the type of every sample entry found by following `path` from the boxes of `data`
+fn visit_sample_entries(data: &[u8], path: &[&[u8; 4]], visit: &mut dyn FnMut(&[u8; 4])) {
+    let Some((&kind, rest)) = path.split_first() else {
+        return;
+    };
+    for (box_kind, body) in boxes(data) {
+        if box_kind != *kind {
+            continue;
+        }
+        if rest.is_empty() {
+            // stsd is a full box: version and flags, then the number of entries
+            for (entry_kind, _) in boxes(body.get(8..).unwrap_or_default()) {
+                visit(&entry_kind);
+            }
+        } else {
+            visit_sample_entries(body, rest, visit);
+        }
+    }
+}
+
+/// Type and body of the boxes following each other in `data`, up to the first one cut off
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as u64;
+        let kind: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            // The box extends to the end of the file
+            0 => (8, data.len() as u64),
+            // The size follows the type, as 64 bits
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+            size => (8, size),
+        };
+        if size < header || size > data.len() as u64 {
+            return None;
+        }
+
+        let body = &data[header as usize..size as usize];
+        data = &data[size as usize..];
+        Some((kind, body))
+    })
+}
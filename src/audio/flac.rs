@@ -0,0 +1,178 @@
+//! Minimal FLAC encoder for mono 16-bit audio
+//!
+//! Only what is needed to store short recordings losslessly: fixed blocks, fixed
+//! linear predictors and a single Rice partition per block. Files are about half the
+//! size of the raw PCM and open in any FLAC decoder.
+
+const BLOCK_SIZE: usize = 4096;
+
+/// Encode mono 16-bit samples as a FLAC file
+pub fn encode_mono_16bit(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let mut out = b"fLaC".to_vec();
+
+    // STREAMINFO, the only metadata block. The last block may be shorter than the others.
+    let block_size = samples.len().clamp(16, BLOCK_SIZE) as u64;
+    let mut info = BitWriter::default();
+    info.write(block_size, 16);
+    info.write(block_size, 16);
+    info.write(0, 24); // Minimum frame size, unknown
+    info.write(0, 24); // Maximum frame size, unknown
+    info.write(u64::from(sample_rate), 20);
+    info.write(0, 3); // One channel
+    info.write(15, 5); // 16 bits per sample
+    info.write(samples.len() as u64, 36);
+    info.write(0, 64); // No MD5 signature
+    info.write(0, 64);
+    let info = info.finish();
+
+    out.push(0x80); // Last metadata block, STREAMINFO
+    out.extend_from_slice(&(info.len() as u32).to_be_bytes()[1..]);
+    out.extend_from_slice(&info);
+
+    for (index, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+        encode_frame(&mut out, index as u64, block);
+    }
+    out
+}
+
+fn encode_frame(out: &mut Vec<u8>, index: u64, block: &[i16]) {
+    let mut frame = BitWriter::default();
+    frame.write(0b11_1111_1111_1110, 14); // Sync code
+    frame.write(0, 1);
+    frame.write(0, 1); // Fixed block size
+    let full = block.len() == BLOCK_SIZE;
+    frame.write(if full { 0b1100 } else { 0b0111 }, 4); // 4096, or 16-bit size at the end of the header
+    frame.write(0, 4); // Sample rate from STREAMINFO
+    frame.write(0, 4); // Mono
+    frame.write(0b100, 3); // 16 bits per sample
+    frame.write(0, 1);
+    frame.write_utf8(index);
+    if !full {
+        frame.write(block.len() as u64 - 1, 16);
+    }
+    let crc = crc8(&frame.bytes_so_far());
+    frame.write(u64::from(crc), 8);
+
+    write_subframe(&mut frame, block);
+
+    let mut frame = frame.finish();
+    let crc = crc16(&frame);
+    frame.extend_from_slice(&crc.to_be_bytes());
+    out.extend_from_slice(&frame);
+}
+
+/// Fixed predictor subframe using the order with the smallest residuals
+fn write_subframe(frame: &mut BitWriter, block: &[i16]) {
+    let max_order = 4.min(block.len().saturating_sub(1));
+    let (order, residuals) = (0..=max_order)
+        .map(|order| (order, fixed_residuals(block, order)))
+        .min_by_key(|(_, residuals)| residuals.iter().map(|residual| residual.unsigned_abs() as u64).sum::<u64>())
+        .unwrap();
+
+    frame.write(0, 1);
+    frame.write(0b001000 | order as u64, 6); // FIXED subframe
+    frame.write(0, 1); // No wasted bits
+    for &sample in &block[..order] {
+        frame.write(u64::from(sample as u16), 16);
+    }
+
+    let folded: Vec<u32> = residuals.iter().map(|&residual| ((residual << 1) ^ (residual >> 31)) as u32).collect();
+    let parameter = (0..15u32)
+        .min_by_key(|&parameter| {
+            folded.iter().map(|&value| u64::from(value >> parameter)).sum::<u64>() + folded.len() as u64 * u64::from(parameter)
+        })
+        .unwrap();
+
+    frame.write(0, 2); // Rice coding with 4-bit parameters
+    frame.write(0, 4); // A single partition
+    frame.write(u64::from(parameter), 4);
+    for value in folded {
+        frame.write_unary(value >> parameter);
+        frame.write(u64::from(value) & ((1 << parameter) - 1), parameter);
+    }
+}
+
+fn fixed_residuals(block: &[i16], order: usize) -> Vec<i32> {
+    let x = |i: usize| i32::from(block[i]);
+    (order..block.len())
+        .map(|i| match order {
+            0 => x(i),
+            1 => x(i) - x(i - 1),
+            2 => x(i) - 2 * x(i - 1) + x(i - 2),
+            3 => x(i) - 3 * x(i - 1) + 3 * x(i - 2) - x(i - 3),
+            _ => x(i) - 4 * x(i - 1) + 6 * x(i - 2) - 4 * x(i - 3) + x(i - 4),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u64,
+    pending_bits: u32,
+}
+
+impl BitWriter {
+    /// Append the `bits` low bits of `value`, most significant first
+    fn write(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            self.pending = (self.pending << 1) | ((value >> shift) & 1);
+            self.pending_bits += 1;
+            if self.pending_bits == 8 {
+                self.bytes.push(self.pending as u8);
+                self.pending = 0;
+                self.pending_bits = 0;
+            }
+        }
+    }
+
+    /// `value` zeros followed by a one
+    fn write_unary(&mut self, value: u32) {
+        for _ in 0..value {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    /// Frame number in the UTF-8-like variable length coding of FLAC headers
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+            return;
+        }
+        let continuation_bytes = (1..=6).find(|&count| value < 1 << (6 + 5 * count)).unwrap_or(6);
+        let first_bits = 6 - continuation_bytes as u32;
+        let marker = (0xFF00u64 >> (continuation_bytes + 1)) & 0xFF;
+        self.write(marker | (value >> (6 * continuation_bytes)), 8);
+        debug_assert!(value >> (6 * continuation_bytes) < 1 << first_bits);
+        for index in (0..continuation_bytes).rev() {
+            self.write(0x80 | ((value >> (6 * index)) & 0x3F), 8);
+        }
+    }
+
+    /// Complete bytes written so far, the header must end on a byte boundary
+    fn bytes_so_far(&self) -> Vec<u8> {
+        debug_assert_eq!(self.pending_bits, 0);
+        self.bytes.clone()
+    }
+
+    /// Written bytes, padding the last one with zeros
+    fn finish(mut self) -> Vec<u8> {
+        if self.pending_bits > 0 {
+            self.write(0, 8 - self.pending_bits);
+        }
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 })
+    })
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+/// Level reported for digital silence, in dBFS
+pub const SILENCE_DBFS: f32 = -120.0;
+
+/// Gating blocks last 400 ms and start every 100 ms, as in EBU R128
+const STEPS_PER_BLOCK: usize = 4;
+
+/// Blocks quieter than this do not count toward the integrated loudness, in LUFS
+const ABSOLUTE_GATE: f32 = -70.0;
+
+/// Blocks this much quieter than the ungated loudness do not count either, in LU
+const RELATIVE_GATE: f32 = -10.0;
+
+/// Loudness of the audio a match was made from, see `RecognitionResult::audio_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioStats {
+    /// Gated integrated loudness with K-weighting (EBU R128 / ITU-R BS.1770), None for
+    /// silence or audio shorter than 400 ms
+    pub integrated_lufs: Option<f32>,
+
+    /// Unweighted RMS level, in dBFS
+    pub rms_dbfs: f32,
+
+    /// Highest sample, in dBFS (sample peak, not true peak)
+    pub peak_dbfs: f32,
+}
+
+impl AudioStats {
+    /// Measure mono 16-bit samples
+    pub fn measure(samples: &[i16], sample_rate: u32) -> Self {
+        let mut meter = LoudnessMeter::new(sample_rate);
+        meter.feed(samples);
+        meter.finish()
+    }
+}
+
+/// Second order IIR filter, transposed direct form II
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// Measures `AudioStats` of audio fed in pieces
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    high_pass: Biquad,
+    step_length: usize,
+    /// Sum of the squared K-weighted samples of the current 100 ms step
+    step_energy: f64,
+    step_samples: usize,
+    /// Mean square of every complete step
+    step_powers: Vec<f64>,
+    squares: f64,
+    samples: usize,
+    peak: u16,
+}
+
+impl LoudnessMeter {
+    /// Meter for mono audio at `sample_rate`
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(1) as f64;
+
+        // K-weighting, BS.1770 filters derived for any sample rate: a high shelf
+        // modelling the head, then a high-pass removing rumble
+        let k = (std::f64::consts::PI * 1681.974450955533 / sample_rate).tan();
+        let (q, vh) = (0.7071752369554196, 10f64.powf(3.999843853973347 / 20.0));
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        let k = (std::f64::consts::PI * 38.13547087602444 / sample_rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        Self {
+            shelf,
+            high_pass,
+            step_length: ((sample_rate / 10.0).round() as usize).max(1),
+            step_energy: 0.0,
+            step_samples: 0,
+            step_powers: Vec::new(),
+            squares: 0.0,
+            samples: 0,
+            peak: 0,
+        }
+    }
+
+    /// Add mono 16-bit samples following the ones already fed
+    pub fn feed(&mut self, samples: &[i16]) {
+        for &sample in samples {
+            let value = sample as f64 / 32768.0;
+            self.squares += value * value;
+            self.peak = self.peak.max(sample.unsigned_abs());
+
+            let weighted = self.high_pass.process(self.shelf.process(value));
+            self.step_energy += weighted * weighted;
+            self.step_samples += 1;
+            if self.step_samples == self.step_length {
+                self.step_powers.push(self.step_energy / self.step_length as f64);
+                self.step_energy = 0.0;
+                self.step_samples = 0;
+            }
+        }
+        self.samples += samples.len();
+    }
+
+    /// Stats of everything fed since the last call, the meter starts over
+    pub fn finish(&mut self) -> AudioStats {
+        let blocks: Vec<f64> = self
+            .step_powers
+            .windows(STEPS_PER_BLOCK)
+            .map(|steps| steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+            .collect();
+        let loudness = |power: f64| (-0.691 + 10.0 * power.log10()) as f32;
+        let mean_loudness = |blocks: &[f64]| (!blocks.is_empty()).then(|| loudness(blocks.iter().sum::<f64>() / blocks.len() as f64));
+
+        let audible: Vec<f64> = blocks.iter().copied().filter(|&power| power > 0.0 && loudness(power) > ABSOLUTE_GATE).collect();
+        let integrated_lufs = mean_loudness(&audible).and_then(|ungated| {
+            let gated: Vec<f64> = audible.iter().copied().filter(|&power| loudness(power) > ungated + RELATIVE_GATE).collect();
+            mean_loudness(&gated)
+        });
+
+        let decibels = |amplitude: f64| if amplitude > 0.0 { (20.0 * amplitude.log10()).max(SILENCE_DBFS as f64) as f32 } else { SILENCE_DBFS };
+        let stats = AudioStats {
+            integrated_lufs,
+            rms_dbfs: decibels((self.squares / self.samples.max(1) as f64).sqrt()),
+            peak_dbfs: decibels(self.peak as f64 / 32768.0),
+        };
+
+        self.reset();
+        stats
+    }
+
+    /// Forget the audio fed so far
+    pub fn reset(&mut self) {
+        self.shelf.state = [0.0; 2];
+        self.high_pass.state = [0.0; 2];
+        self.step_energy = 0.0;
+        self.step_samples = 0;
+        self.step_powers.clear();
+        self.squares = 0.0;
+        self.samples = 0;
+        self.peak = 0;
+    }
+}
@@ -0,0 +1,195 @@
+//! Format, duration and channel layout of audio files, read from their headers without decoding
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::audio::drm::{drm_protection, DRM_PROBE_BYTES};
+use crate::{Result, SongRecError};
+
+/// Bytes read from the end of an Ogg file to find its last page, which holds the length of the stream
+const OGG_TAIL_BYTES: u64 = 64 * 1024;
+
+/// What the headers of an audio file tell about its audio, see `probe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaInfo {
+    /// None if the headers do not hold it, e.g. for some streams; the file must then be decoded to know
+    pub duration: Option<Duration>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Short name of the codec, e.g. "mp3", "aac", "flac", "vorbis", "opus" or "pcm_s16le"
+    pub codec: String,
+}
+
+/// Read the format of an audio file or of the first audio track of a video, without decoding it
+///
+/// Only the headers are read (and the last page of Ogg files), so this is cheap even for long
+/// recordings. MP3 files without a length header have an estimated duration.
+pub fn probe<P: AsRef<Path>>(path: P) -> Result<MediaInfo> {
+    let path = path.as_ref();
+    let mut file = File::open(path)
+        .map_err(|e| SongRecError::InvalidInput(format!("Failed to open file '{}': {}", path.display(), e)))?;
+
+    let mut start = Vec::new();
+    (&mut file).take(DRM_PROBE_BYTES).read_to_end(&mut start)
+        .map_err(|e| SongRecError::AudioError(format!("Failed to read file '{}': {}", path.display(), e)))?;
+    if let Some(drm) = drm_protection(&start) {
+        return Err(SongRecError::UnsupportedMedia(format!("'{}' is protected by {} DRM", path.display(), drm)));
+    }
+    let file_size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+    let info = if start.starts_with(b"RIFF") && start.get(8..12) == Some(b"WAVE".as_slice()) {
+        probe_wav(&start, file_size)
+    } else if start.starts_with(b"fLaC") {
+        probe_flac(&start)
+    } else if start.starts_with(b"OggS") {
+        probe_ogg(&start, &mut file, file_size)
+    } else {
+        probe_container(file, path)
+    };
+
+    info.ok_or_else(|| SongRecError::AudioError(format!("Could not read the audio format of '{}'", path.display())))
+}
+
+/// WAV files: the fmt chunk and the size of the data chunk
+fn probe_wav(start: &[u8], file_size: u64) -> Option<MediaInfo> {
+    let mut format = None;
+    let mut offset = 12;
+
+    while let Some(header) = start.get(offset..offset + 8) {
+        let size = u32::from_le_bytes(header[4..8].try_into().ok()?) as u64;
+        let body = offset + 8;
+
+        match &header[0..4] {
+            b"fmt " => {
+                let fmt = start.get(body..body + 16)?;
+                let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().ok()?);
+                let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+                let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                format = Some((tag, channels, sample_rate, block_align, bits));
+            }
+            b"data" => {
+                let (tag, channels, sample_rate, block_align, bits) = format?;
+                // Streamed files may leave the size of the data chunk unset
+                let size = size.min(file_size.saturating_sub(body as u64));
+                let duration = (sample_rate > 0 && block_align > 0)
+                    .then(|| Duration::from_secs_f64((size / block_align as u64) as f64 / sample_rate as f64));
+                let codec = match (tag, bits) {
+                    (1, 8) | (0xFFFE, 8) => "pcm_u8".to_string(),
+                    (1, _) | (0xFFFE, _) => format!("pcm_s{}le", bits),
+                    (3, _) => format!("pcm_f{}le", bits),
+                    (6, _) => "pcm_alaw".to_string(),
+                    (7, _) => "pcm_mulaw".to_string(),
+                    _ => format!("wav_0x{:04x}", tag),
+                };
+                return Some(MediaInfo { duration, sample_rate, channels, codec });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        offset = body + size as usize + (size as usize & 1);
+    }
+
+    None
+}
+
+/// FLAC files: the STREAMINFO block, always the first one
+fn probe_flac(start: &[u8]) -> Option<MediaInfo> {
+    if start.get(4)? & 0x7f != 0 {
+        return None;
+    }
+    let info = start.get(8..8 + 34)?;
+
+    let sample_rate = (info[10] as u32) << 12 | (info[11] as u32) << 4 | (info[12] as u32) >> 4;
+    let channels = ((info[12] >> 1) & 0x07) as u16 + 1;
+    let total_samples = ((info[13] & 0x0f) as u64) << 32 | u32::from_be_bytes(info[14..18].try_into().ok()?) as u64;
+
+    // A total of 0 means the encoder did not know the length
+    let duration = (total_samples > 0 && sample_rate > 0)
+        .then(|| Duration::from_secs_f64(total_samples as f64 / sample_rate as f64));
+    Some(MediaInfo { duration, sample_rate, channels, codec: "flac".to_string() })
+}
+
+/// Ogg files: the identification header of the first page and the granule position of the last one
+fn probe_ogg(start: &[u8], file: &mut File, file_size: u64) -> Option<MediaInfo> {
+    let packet = start.get(27 + *start.get(26)? as usize..)?;
+    let le_u32 = |bytes: &[u8], at: usize| bytes.get(at..at + 4).and_then(|b| b.try_into().ok()).map(u32::from_le_bytes);
+
+    // Sample rate and channels, the rate of granule positions and the samples to skip at the start
+    let (codec, sample_rate, channels, granule_rate, pre_skip) = if packet.starts_with(b"OpusHead") {
+        // Opus is always decoded at 48 kHz, whatever the rate of the original input
+        let pre_skip = u16::from_le_bytes([*packet.get(10)?, *packet.get(11)?]) as u64;
+        ("opus", 48000, *packet.get(9)? as u16, 48000, pre_skip)
+    } else if packet.starts_with(b"\x01vorbis") {
+        let sample_rate = le_u32(packet, 12)?;
+        ("vorbis", sample_rate, *packet.get(11)? as u16, sample_rate, 0)
+    } else if packet.starts_with(b"Speex   ") {
+        let sample_rate = le_u32(packet, 36)?;
+        ("speex", sample_rate, le_u32(packet, 48)? as u16, sample_rate, 0)
+    } else {
+        return None;
+    };
+
+    let tail_start = file_size.saturating_sub(OGG_TAIL_BYTES);
+    let mut tail = Vec::new();
+    let last_granule = file.seek(SeekFrom::Start(tail_start))
+        .and_then(|_| file.read_to_end(&mut tail))
+        .ok()
+        .and_then(|_| {
+            let page = tail.windows(4).rposition(|window| window == b"OggS")?;
+            tail.get(page + 6..page + 14)?.try_into().ok().map(u64::from_le_bytes)
+        })
+        // -1 marks pages where no packet ends
+        .filter(|&granule| granule != u64::MAX);
+
+    let duration = last_granule
+        .filter(|_| granule_rate > 0)
+        .map(|granule| Duration::from_secs_f64(granule.saturating_sub(pre_skip) as f64 / granule_rate as f64));
+    Some(MediaInfo { duration, sample_rate, channels, codec: codec.to_string() })
+}
+
+/// MP3 files and MP4, M4A, MOV, WebM and MKV containers, through symphonia
+fn probe_container(file: File, path: &Path) -> Option<MediaInfo> {
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+
+    // Videos also have tracks without a sample rate
+    let track = probed.format.tracks().iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL && track.codec_params.sample_rate.is_some())?;
+    let params = &track.codec_params;
+    let sample_rate = params.sample_rate?;
+    let channels = params.channels
+        .or_else(|| params.channel_layout.map(|layout| layout.into_channels()))?
+        .count() as u16;
+
+    // Lengths are counted in the time base of the track, e.g. the timescale of MP4 files
+    let duration = params.n_frames.map(|frames| match params.time_base {
+        Some(time_base) => {
+            let time = time_base.calc_time(frames);
+            Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+        }
+        None => Duration::from_secs_f64(frames as f64 / sample_rate as f64),
+    });
+    let codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map_or("unknown", |descriptor| descriptor.short_name);
+
+    Some(MediaInfo { duration, sample_rate, channels, codec: codec.to_string() })
+}
@@ -1,5 +1,24 @@
+#[cfg(feature = "audio-devices")]
 pub mod recorder;
 pub mod processor;
+pub mod pcm;
+pub mod snippet;
+pub mod loudness;
+pub mod classifier;
+#[cfg(feature = "audio-devices")]
+pub mod monitor;
+pub mod media;
+mod flac;
+pub mod decoder;
+pub(crate) mod drm;
+pub(crate) mod opus;
 
-pub use recorder::AudioRecorder;
+#[cfg(feature = "audio-devices")]
+pub use recorder::{AudioRecorder, DeviceReport};
 pub use processor::AudioProcessor;
+pub use pcm::{PcmBuffer, PcmFormat};
+pub use snippet::AudioSnippet;
+pub use loudness::{AudioStats, LoudnessMeter};
+pub use classifier::{AudioClass, AudioFeatures};
+pub use media::{probe, MediaInfo};
+pub use decoder::decode_file;
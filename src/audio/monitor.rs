@@ -0,0 +1,66 @@
+//! Capture of what the computer plays, through the monitor source of PulseAudio or PipeWire
+
+use crate::audio::recorder::AudioError;
+
+/// Suffix of the sources mirroring an output of PulseAudio or PipeWire
+const MONITOR_SUFFIX: &str = ".monitor";
+
+/// Environment variable naming the source the ALSA plugin of PulseAudio records
+pub const PULSE_SOURCE: &str = "PULSE_SOURCE";
+
+/// Name of the monitor source of the default output, e.g. "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"
+///
+/// Asks `pactl`, which PipeWire also provides through pipewire-pulse. The name can be
+/// passed as a device to any recording function once `PULSE_SOURCE` is set to it. Set it
+/// at the start of `main`, before any thread is spawned: changing the environment while
+/// other threads read it is undefined behavior on Linux, so the library never does.
+#[cfg(target_os = "linux")]
+pub fn default_monitor_source() -> Result<String, AudioError> {
+    // get-default-sink only exists since PulseAudio 15, older versions print it in their info
+    let sink = pactl(&["get-default-sink"]).or_else(|error| {
+        pactl(&["info"])?
+            .lines()
+            .find_map(|line| line.strip_prefix("Default Sink:"))
+            .map(|sink| sink.trim().to_string())
+            .ok_or(error)
+    })?;
+
+    if sink.is_empty() {
+        return Err(AudioError::DeviceError("PulseAudio has no default output".to_string()));
+    }
+    Ok(format!("{}{}", sink, MONITOR_SUFFIX))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn default_monitor_source() -> Result<String, AudioError> {
+    Err(AudioError::DeviceError(
+        "System audio is only found automatically with PulseAudio or PipeWire, pass a loopback device instead".to_string(),
+    ))
+}
+
+/// Whether `name` is a PulseAudio or PipeWire monitor source rather than a device of the audio host
+pub fn is_monitor_source(name: &str) -> bool {
+    name.len() > MONITOR_SUFFIX.len() && name.ends_with(MONITOR_SUFFIX)
+}
+
+/// Whether the ALSA plugin of PulseAudio records `source`, see `PULSE_SOURCE`
+#[cfg(target_os = "linux")]
+pub(crate) fn is_selected_source(source: &str) -> bool {
+    std::env::var_os(PULSE_SOURCE).is_some_and(|selected| selected == source)
+}
+
+#[cfg(target_os = "linux")]
+fn pactl(arguments: &[&str]) -> Result<String, AudioError> {
+    let output = std::process::Command::new("pactl")
+        .args(arguments)
+        .output()
+        .map_err(|e| AudioError::DeviceError(format!("Could not run pactl, is PulseAudio or PipeWire running? {}", e)))?;
+    if !output.status.success() {
+        return Err(AudioError::DeviceError(format!(
+            "pactl {} failed: {}",
+            arguments.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,101 @@
+//! Opus and Speex audio in Ogg files, e.g. voice notes, which symphonia cannot decode
+
+use std::io::{Read, Seek};
+
+use crate::config::ChannelMode;
+
+/// Codec of an Ogg file that symphonia cannot decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OggCodec {
+    Opus,
+    Speex,
+}
+
+impl OggCodec {
+    /// Codec of an Ogg file from its first bytes, None for other files including Ogg Vorbis
+    pub(crate) fn detect(start: &[u8]) -> Option<Self> {
+        // The first page holds the identification header, after the 27-byte page header and its segment table
+        if !start.starts_with(b"OggS") || start.len() < 27 {
+            return None;
+        }
+        let packet = start.get(27 + start[26] as usize..)?;
+        if packet.starts_with(b"OpusHead") {
+            Some(OggCodec::Opus)
+        } else if packet.starts_with(b"Speex   ") {
+            Some(OggCodec::Speex)
+        } else {
+            None
+        }
+    }
+}
+
+/// Largest Opus frame, 120 ms at 16 kHz
+#[cfg(feature = "opus")]
+const MAX_FRAME_SAMPLES: usize = 1920;
+
+/// Decode an Ogg Opus file to mono 16 kHz samples passed to `push` (requires the opus feature)
+#[cfg(feature = "opus")]
+pub(crate) fn decode<R: Read + Seek>(reader: R, codec: OggCodec, channel_mode: ChannelMode, push: &mut dyn FnMut(i16)) -> Result<(), String> {
+    use audiopus::coder::Decoder;
+    use audiopus::packet::Packet;
+    use audiopus::{Channels, MutSignals, SampleRate};
+
+    if codec == OggCodec::Speex {
+        return Err(speex_unsupported());
+    }
+
+    let mut packets = ogg::PacketReader::new(reader);
+    let head = packets.read_packet().map_err(|e| e.to_string())?.ok_or("Empty Ogg file")?;
+    // OpusHead: magic, version, channel count, pre-skip, input rate, gain and channel mapping
+    if head.data.len() < 19 {
+        return Err("Invalid Opus header".to_string());
+    }
+    let channels = head.data[9] as usize;
+    let decoder_channels = match channels {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        _ => return Err(format!("Opus audio with {} channels is not supported, only mono and stereo", channels)),
+    };
+    let serial = head.stream_serial();
+
+    // Decoded straight at 16 kHz, while the samples to drop at the start are counted at 48 kHz
+    let mut decoder = Decoder::new(SampleRate::Hz16000, decoder_channels).map_err(|e| e.to_string())?;
+    let mut skip = u16::from_le_bytes([head.data[10], head.data[11]]) as usize / 3;
+    let mut output = vec![0i16; MAX_FRAME_SAMPLES * channels];
+    let mut tags_skipped = false;
+
+    while let Some(packet) = packets.read_packet().map_err(|e| e.to_string())? {
+        if packet.stream_serial() != serial || packet.data.is_empty() {
+            continue;
+        }
+        if !tags_skipped {
+            // OpusTags, the comment header
+            tags_skipped = true;
+            continue;
+        }
+
+        let input = Packet::try_from(packet.data.as_slice()).map_err(|e| e.to_string())?;
+        let signals = MutSignals::try_from(&mut output[..]).map_err(|e| e.to_string())?;
+        let frames = decoder.decode(Some(input), signals, false).map_err(|e| e.to_string())?;
+        for frame in output[..frames * channels].chunks_exact(channels) {
+            if skip > 0 {
+                skip -= 1;
+            } else {
+                push(channel_mode.mix(frame, f32::from) as i16);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "opus"))]
+pub(crate) fn decode<R: Read + Seek>(_reader: R, codec: OggCodec, _channel_mode: ChannelMode, _push: &mut dyn FnMut(i16)) -> Result<(), String> {
+    match codec {
+        OggCodec::Opus => Err("Opus audio is not supported: rebuild with --features opus".to_string()),
+        OggCodec::Speex => Err(speex_unsupported()),
+    }
+}
+
+fn speex_unsupported() -> String {
+    "Speex audio is not supported, convert the file to Opus, e.g. with ffmpeg".to_string()
+}
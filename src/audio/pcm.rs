@@ -0,0 +1,121 @@
+use crate::{Result, SongRecError};
+use crate::audio::decoder::Resampler;
+use crate::config::ChannelMode;
+
+/// Highest sample rate accepted for raw PCM input
+const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Encoding of the samples in a `PcmBuffer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    /// Unsigned 8-bit
+    U8,
+    /// Signed 16-bit, little endian
+    S16Le,
+    /// Signed 16-bit, big endian
+    S16Be,
+    /// Signed 24-bit packed in 3 bytes, little endian
+    S24Le,
+    /// Signed 32-bit, little endian
+    S32Le,
+    /// 32-bit float in the -1.0..1.0 range, little endian
+    F32Le,
+}
+
+impl PcmFormat {
+    /// Size of one sample of one channel in bytes
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            PcmFormat::U8 => 1,
+            PcmFormat::S16Le | PcmFormat::S16Be => 2,
+            PcmFormat::S24Le => 3,
+            PcmFormat::S32Le | PcmFormat::F32Le => 4,
+        }
+    }
+
+    /// Decode one sample to a float in the -1.0..1.0 range
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            PcmFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            PcmFormat::S16Le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            PcmFormat::S16Be => i16::from_be_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            PcmFormat::S24Le => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8_388_608.0,
+            PcmFormat::S32Le => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2_147_483_648.0,
+            PcmFormat::F32Le => {
+                let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if sample.is_finite() { sample.clamp(-1.0, 1.0) } else { 0.0 }
+            }
+        }
+    }
+}
+
+/// Raw interleaved PCM audio along with the description needed to interpret it
+#[derive(Debug, Clone, Copy)]
+pub struct PcmBuffer<'a> {
+    /// Interleaved sample bytes
+    pub data: &'a [u8],
+
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+
+    /// Number of interleaved channels
+    pub channels: u16,
+
+    /// Encoding of each sample
+    pub format: PcmFormat,
+}
+
+impl<'a> PcmBuffer<'a> {
+    /// Describe a buffer of raw PCM bytes
+    pub fn new(data: &'a [u8], sample_rate: u32, channels: u16, format: PcmFormat) -> Self {
+        Self { data, sample_rate, channels, format }
+    }
+
+    /// Duration of the buffer in seconds
+    pub fn duration(&self) -> f32 {
+        let frame_size = self.format.bytes_per_sample() * self.channels.max(1) as usize;
+        (self.data.len() / frame_size) as f32 / self.sample_rate.max(1) as f32
+    }
+
+    /// Check that the description is possible and matches the data
+    pub fn validate(&self) -> Result<()> {
+        if self.sample_rate == 0 || self.sample_rate > MAX_SAMPLE_RATE {
+            return Err(SongRecError::InvalidInput(format!("Unsupported sample rate: {} Hz", self.sample_rate)));
+        }
+
+        if self.channels == 0 {
+            return Err(SongRecError::InvalidInput("PCM buffer must have at least one channel".to_string()));
+        }
+
+        let frame_size = self.format.bytes_per_sample() * self.channels as usize;
+        if (self.data.len() / frame_size) * frame_size != self.data.len() {
+            return Err(SongRecError::InvalidInput(format!(
+                "PCM buffer length {} is not a multiple of the {} byte frame size",
+                self.data.len(),
+                frame_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Downmix to mono by averaging the channels and resample to 16 kHz, as expected by fingerprinting
+    pub fn to_mono_16khz(&self) -> Result<Vec<i16>> {
+        self.to_mono_16khz_with(ChannelMode::Mid)
+    }
+
+    /// Same as `to_mono_16khz`, keeping the channel selected by `channel_mode`
+    pub fn to_mono_16khz_with(&self, channel_mode: ChannelMode) -> Result<Vec<i16>> {
+        self.validate()?;
+
+        let bytes_per_sample = self.format.bytes_per_sample();
+        let mut resampler = Resampler::new(self.sample_rate);
+        let mut samples = Vec::new();
+        for frame in self.data.chunks_exact(bytes_per_sample * self.channels as usize) {
+            let channels: Vec<&[u8]> = frame.chunks_exact(bytes_per_sample).collect();
+            let mono = channel_mode.mix(&channels, |sample| self.format.decode(sample));
+            resampler.push(mono, &mut |sample| samples.push((sample * 32768.0) as i16));
+        }
+        Ok(samples)
+    }
+}
@@ -1,6 +1,12 @@
 use crate::fingerprinting::algorithm::SignatureGenerator;
 use crate::fingerprinting::signature_format::DecodedSignature;
 use crate::config::Config;
+use crate::logging::{log, LogLevel};
+use crate::audio::snippet::AudioSnippet;
+use crate::audio::loudness::{AudioStats, LoudnessMeter};
+
+/// Shortest window fingerprinted, shorter signatures are too sparse to be looked up
+const MIN_WINDOW_SECS: f32 = 3.0;
 
 /// Audio processor for generating fingerprints from audio samples
 pub struct AudioProcessor {
@@ -9,6 +15,13 @@ pub struct AudioProcessor {
     samples_processed: usize,
     target_sample_rate: u32,
     config: Config,
+    /// Samples fed since the last signature, only kept when needed after fingerprinting
+    window: Vec<i16>,
+    snippet: Option<AudioSnippet>,
+    meter: LoudnessMeter,
+    audio_stats: Option<AudioStats>,
+    /// Samples still dropped before the next window, see `Config::recognition_interval`
+    samples_to_skip: usize,
 }
 
 impl AudioProcessor {
@@ -20,17 +33,27 @@ impl AudioProcessor {
             samples_processed: 0,
             target_sample_rate: 16000, // Standard sample rate for fingerprinting
             config: Config::default(),
+            window: Vec::new(),
+            snippet: None,
+            meter: LoudnessMeter::new(16000),
+            audio_stats: None,
+            samples_to_skip: 0,
         }
     }
 
     /// Create a new audio processor with config
     pub fn with_config(config: Config) -> Self {
         Self {
-            signature_generator: SignatureGenerator::new(),
+            signature_generator: SignatureGenerator::new().with_weighting(config.spectral_weighting),
             sample_buffer: Vec::new(),
             samples_processed: 0,
             target_sample_rate: 16000, // Standard sample rate for fingerprinting
             config,
+            window: Vec::new(),
+            snippet: None,
+            meter: LoudnessMeter::new(16000),
+            audio_stats: None,
+            samples_to_skip: 0,
         }
     }
 
@@ -40,40 +63,48 @@ impl AudioProcessor {
         // Add samples to our buffer
         self.sample_buffer.extend_from_slice(samples);
         
+        // Audio between two windows is dropped without being fingerprinted
+        let skipped = self.samples_to_skip.min(self.sample_buffer.len());
+        self.sample_buffer.drain(..skipped);
+        self.samples_to_skip -= skipped;
+
         // Process samples in chunks of 128 (as per original algorithm)
         while self.sample_buffer.len() >= 128 {
             let chunk: Vec<i16> = self.sample_buffer.drain(0..128).collect();
             
             // Process the chunk
-            self.signature_generator.do_fft(&chunk, self.target_sample_rate);
+            self.signature_generator.feed(&chunk);
             self.samples_processed += 128;
+            self.meter.feed(&chunk);
+            if self.keeps_window() {
+                self.window.extend_from_slice(&chunk);
+            }
             
             // Check if we have enough samples for a signature
-            // Use 12 seconds for better recognition accuracy (Shazam's optimal window)
-            let min_samples = (12.0 * self.target_sample_rate as f32) as usize;
-            
-            if self.samples_processed >= min_samples {
-                if !self.config.quiet_mode {
-                    eprintln!("Attempting recognition with {} samples", self.samples_processed);
-                }
+            if self.samples_processed >= self.window_samples() {
+                log(&self.config, LogLevel::Debug, format_args!("Attempting recognition with {} samples", self.samples_processed));
                 // Get the signature
-                let signature = self.signature_generator.get_signature();
+                let signature = self.signature_generator.finalize();
                 
                 // Debug: Check if we have any frequency peaks
                 let total_peaks: usize = signature.frequency_band_to_sound_peaks.values().map(|v| v.len()).sum();
-                if !self.config.quiet_mode {
-                    eprintln!("Generated signature with {} total frequency peaks across {} bands", 
-                        total_peaks, signature.frequency_band_to_sound_peaks.len());
-                
-                    if total_peaks == 0 {
-                        eprintln!("WARNING: No frequency peaks detected in audio - may be too quiet or not musical content");
-                    }
+                log(&self.config, LogLevel::Debug, format_args!("Generated signature with {} total frequency peaks across {} bands",
+                    total_peaks, signature.frequency_band_to_sound_peaks.len()));
+                if total_peaks == 0 {
+                    log(&self.config, LogLevel::Warn, format_args!("No frequency peaks detected in audio - may be too quiet or not musical content"));
                 }
                 
                 // Removed delay to test rate-limiting impact
                 
-                // Reset for next recognition
+                self.audio_stats = Some(self.meter.finish());
+                if self.keeps_window() {
+                    self.snippet = Some(AudioSnippet::new(&self.window, self.target_sample_rate));
+                }
+
+                // Reset for next recognition, skipping the audio until the next one is due
                 self.reset();
+                let gap = (self.config.recognition_interval - self.config.window_duration).max(0.0);
+                self.samples_to_skip = (gap * self.target_sample_rate as f32) as usize;
                 
                 return Ok(Some(signature));
             }
@@ -82,17 +113,50 @@ impl AudioProcessor {
         Ok(None)
     }
 
+    /// Apply a new configuration, e.g. a reloaded `Config::recognition_interval`
+    ///
+    /// The window in progress goes on, unless the spectral weighting changed.
+    pub fn set_config(&mut self, config: Config) {
+        if config.spectral_weighting != self.config.spectral_weighting {
+            self.signature_generator = SignatureGenerator::new().with_weighting(config.spectral_weighting);
+            self.reset();
+        }
+        self.config = config;
+    }
+
     /// Reset the processor for a new recognition session
     pub fn reset(&mut self) {
-        self.signature_generator = SignatureGenerator::new();
+        self.signature_generator.reset();
         self.sample_buffer.clear();
         self.samples_processed = 0;
+        self.window.clear();
+        self.meter.reset();
+        self.samples_to_skip = 0;
+    }
+
+    /// Audio of the last signature returned by `process_samples`, for snippets, track analysis or the music gate
+    pub fn take_snippet(&mut self) -> Option<AudioSnippet> {
+        self.snippet.take()
+    }
+
+    /// Whether the audio of signatures is needed: for snippets, track analysis or the music gate
+    fn keeps_window(&self) -> bool {
+        self.config.keep_audio_snippets || self.config.analyze_tracks || self.config.music_only
+    }
+
+    /// Loudness of the audio of the last signature returned by `process_samples`
+    pub fn take_audio_stats(&mut self) -> Option<AudioStats> {
+        self.audio_stats.take()
     }
 
     /// Get the current progress (0.0 to 1.0)
     pub fn get_progress(&self) -> f32 {
-        let min_samples = (12.0 * self.target_sample_rate as f32) as usize;
-        (self.samples_processed as f32 / min_samples as f32).min(1.0)
+        (self.samples_processed as f32 / self.window_samples() as f32).min(1.0)
+    }
+
+    /// Samples fingerprinted into each signature, see `Config::window_duration`
+    fn window_samples(&self) -> usize {
+        (self.config.window_duration.max(MIN_WINDOW_SECS) * self.target_sample_rate as f32) as usize
     }
 }
 
@@ -1,13 +1,64 @@
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Stream, StreamConfig};
+use cpal::{Device, SampleFormat, SampleRate, Stream, StreamConfig, SupportedStreamConfig, SupportedStreamConfigRange};
 
+use serde::{Deserialize, Serialize};
+
+use crate::audio::loudness::AudioStats;
+#[cfg(target_os = "linux")]
+use crate::audio::monitor;
 use crate::config::Config;
+use crate::logging::{log, LogLevel};
+
+/// Rate the captured audio is converted to, the one fingerprinting expects
+const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// Cutoff of the low-pass filter applied before decimation, above the 5.5 kHz fingerprints go
+/// up to and far enough below 8 kHz for what folds back under 5.5 kHz to be attenuated
+const ANTI_ALIAS_CUTOFF: f64 = 6000.0;
+
+/// Capture rates tried first, supported by most hardware
+const PREFERRED_SAMPLE_RATES: [u32; 2] = [48000, 44100];
+
+/// Time a device is recorded by `AudioRecorder::probe`
+const PROBE_DURATION: Duration = Duration::from_secs(2);
+
+/// Peak level under which a probed device only captured silence, e.g. a muted microphone
+const SILENT_PEAK_DBFS: f32 = -80.0;
+
+/// What an input device supports and captured, see `AudioRecorder::probe`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceReport {
+    pub name: String,
+
+    /// Configurations the device supports, e.g. "2 channel(s) at 8000-192000 Hz (F32)"
+    pub supported_configs: Vec<String>,
+
+    /// Configuration recording uses, e.g. "2 channel(s) at 48000 Hz (F32)"
+    pub recommended_config: String,
+
+    /// Length of the audio captured, short if the device stalled
+    pub captured_ms: u64,
+
+    /// RMS level of the quietest 100 ms captured, in dBFS
+    pub noise_floor_dbfs: Option<f32>,
+
+    /// Highest level captured, in dBFS
+    pub peak_dbfs: f32,
+
+    /// Whether the device captured more than silence
+    pub produces_audio: bool,
+}
 
 /// Cross-platform audio recorder using CPAL
 pub struct AudioRecorder {
     config: Config,
+    dropped_batches: Arc<AtomicU64>,
+    stream_errors: Option<mpsc::Sender<AudioError>>,
 }
 
 /// Audio recording error
@@ -33,38 +84,48 @@ impl std::error::Error for AudioError {}
 impl AudioRecorder {
     /// Create a new audio recorder with the given configuration
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            dropped_batches: Arc::new(AtomicU64::new(0)),
+            stream_errors: None,
+        }
+    }
+
+    /// Count dropped sample batches in the given counter instead of a private one
+    pub fn with_overrun_counter(mut self, counter: Arc<AtomicU64>) -> Self {
+        self.dropped_batches = counter;
+        self
+    }
+
+    /// Forward errors of the running stream, a disconnected device is reported as `AudioError::DeviceError`
+    pub fn with_error_sender(mut self, errors: mpsc::Sender<AudioError>) -> Self {
+        self.stream_errors = Some(errors);
+        self
+    }
+
+    /// Number of sample batches dropped because the consumer did not keep up
+    pub fn dropped_batches(&self) -> u64 {
+        self.dropped_batches.load(Ordering::Relaxed)
     }
 
     /// Start recording audio and return a receiver for audio samples
+    ///
+    /// Samples arrive as mono ~16 kHz batches of `Config::buffer_size` samples. At most
+    /// `Config::sample_queue_size` batches are buffered. The audio callback never blocks
+    /// or allocates: when the queue is full the newest batch is dropped and counted as an
+    /// overrun. Drop received batches promptly, their buffers are reused by the callback.
     pub fn start_recording(
         &mut self,
         device_name: Option<String>,
         _control_rx: mpsc::Receiver<()>,
-    ) -> Result<mpsc::Receiver<Vec<i16>>, AudioError> {
+    ) -> Result<mpsc::Receiver<Arc<[i16]>>, AudioError> {
         let host = cpal::default_host();
+        let device = self.device(&host, device_name.as_deref())?;
+        let config = Self::recommended_config(&device)?;
+        log(&self.config, LogLevel::Debug, format_args!("Capturing {}", describe_config(&config)));
 
-        // Get the audio device
-        let device = if let Some(name) = device_name {
-            self.find_device_by_name(&host, &name)?
-        } else {
-            host.default_input_device().ok_or_else(|| {
-                AudioError::DeviceError("No default input device found".to_string())
-            })?
-        };
-
-        // Get the default input config
-        let config = device.default_input_config().or_else(|input_err| {
-            // Try output config as fallback
-            device.default_output_config().map_err(|output_err| {
-                AudioError::ConfigError(format!(
-                    "Failed to get audio config: input error: {}, output error: {}",
-                    input_err, output_err
-                ))
-            })
-        })?;
         // Create a channel for sending audio samples
-        let (sample_tx, sample_rx) = mpsc::channel();
+        let (sample_tx, sample_rx) = mpsc::sync_channel(self.config.sample_queue_size.max(1));
 
         // Start the audio stream
         let stream = self.create_input_stream(&device, config, sample_tx)?;
@@ -81,6 +142,83 @@ impl AudioRecorder {
         Ok(sample_rx)
     }
 
+    /// Record a device, or the default one, for two seconds and report what it captured
+    ///
+    /// Helps finding a working device before listening, e.g. one that is not muted.
+    pub fn probe(&self, device_name: Option<&str>) -> Result<DeviceReport, AudioError> {
+        let host = cpal::default_host();
+        let device = self.device(&host, device_name)?;
+        let config = Self::recommended_config(&device)?;
+
+        let (sample_tx, sample_rx) = mpsc::sync_channel(self.config.sample_queue_size.max(1));
+        let stream = self.create_input_stream(&device, config.clone(), sample_tx)?;
+        stream
+            .play()
+            .map_err(|e| AudioError::StreamError(format!("Failed to start stream: {}", e)))?;
+
+        let deadline = Instant::now() + PROBE_DURATION;
+        let mut samples = Vec::new();
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match sample_rx.recv_timeout(remaining) {
+                Ok(batch) => samples.extend_from_slice(&batch),
+                Err(_) => break,
+            }
+        }
+        drop(stream);
+
+        let block_length = TARGET_SAMPLE_RATE as usize / 10;
+        let noise_floor_dbfs = samples
+            .chunks_exact(block_length)
+            .map(|block| AudioStats::measure(block, TARGET_SAMPLE_RATE).rms_dbfs)
+            .min_by(f32::total_cmp);
+        let peak_dbfs = AudioStats::measure(&samples, TARGET_SAMPLE_RATE).peak_dbfs;
+
+        Ok(DeviceReport {
+            name: device.name().unwrap_or_else(|_| device_name.unwrap_or("default").to_string()),
+            supported_configs: Self::supported_configs(&device)?.1.iter().map(describe_config_range).collect(),
+            recommended_config: describe_config(&config),
+            captured_ms: samples.len() as u64 * 1000 / TARGET_SAMPLE_RATE as u64,
+            noise_floor_dbfs,
+            peak_dbfs,
+            produces_audio: peak_dbfs > SILENT_PEAK_DBFS,
+        })
+    }
+
+    /// Capture settings recording on `device` uses, see `choose_input_config`
+    pub fn recommended_config(device: &Device) -> Result<SupportedStreamConfig, AudioError> {
+        let (default, supported) = Self::supported_configs(device)?;
+        choose_input_config(Some(default), &supported)
+    }
+
+    /// Default and supported input configs, or the output ones of a monitored output device
+    fn supported_configs(device: &Device) -> Result<(SupportedStreamConfig, Vec<SupportedStreamConfigRange>), AudioError> {
+        match device.default_input_config() {
+            Ok(default) => Ok((default, device.supported_input_configs().map(Iterator::collect).unwrap_or_default())),
+            Err(input_err) => {
+                let default = device.default_output_config().map_err(|output_err| {
+                    AudioError::ConfigError(format!(
+                        "Failed to get audio config: input error: {}, output error: {}",
+                        input_err, output_err
+                    ))
+                })?;
+                Ok((default, device.supported_output_configs().map(Iterator::collect).unwrap_or_default()))
+            }
+        }
+    }
+
+    /// The device named `name`, or the default input device, unless recording from JACK
+    fn device(&self, host: &cpal::Host, name: Option<&str>) -> Result<Device, AudioError> {
+        if let Some(client) = &self.config.jack_client {
+            return jack_device(client);
+        }
+        match name {
+            Some(name) => self.find_device_by_name(host, name),
+            None => host
+                .default_input_device()
+                .ok_or_else(|| AudioError::DeviceError("No default input device found".to_string())),
+        }
+    }
+
     /// Find a device by name
     fn find_device_by_name(&self, host: &cpal::Host, name: &str) -> Result<Device, AudioError> {
         let devices = host.input_devices().map_err(|e| {
@@ -107,6 +245,27 @@ impl AudioRecorder {
             }
         }
 
+        // PulseAudio and PipeWire sources are not ALSA devices, they are recorded through the plugin
+        #[cfg(target_os = "linux")]
+        if monitor::is_monitor_source(name) {
+            if !monitor::is_selected_source(name) {
+                return Err(AudioError::DeviceError(format!(
+                    "Recording '{}' needs {}={} in the environment, set when the program starts",
+                    name,
+                    monitor::PULSE_SOURCE,
+                    name
+                )));
+            }
+            for plugin in ["pulse", "default"] {
+                let mut devices = host.input_devices().map_err(|e| {
+                    AudioError::DeviceError(format!("Failed to enumerate input devices: {}", e))
+                })?;
+                if let Some(device) = devices.find(|device| device.name().is_ok_and(|device_name| device_name == plugin)) {
+                    return Ok(device);
+                }
+            }
+        }
+
         Err(AudioError::DeviceError(format!(
             "Device '{}' not found",
             name
@@ -118,107 +277,64 @@ impl AudioRecorder {
         &self,
         device: &Device,
         config: cpal::SupportedStreamConfig,
-        sample_tx: mpsc::Sender<Vec<i16>>,
+        sample_tx: SyncSender<Arc<[i16]>>,
     ) -> Result<Stream, AudioError> {
-        // Create a buffer for collecting samples
-        let buffer_size = self.config.buffer_size;
-        let mut sample_buffer = Vec::with_capacity(buffer_size);
-
         let stream_config = StreamConfig {
             channels: config.channels(),
             sample_rate: config.sample_rate(),
             buffer_size: cpal::BufferSize::Default,
         };
 
+        let mut writer = BatchWriter::new(
+            self.config.buffer_size.max(1),
+            self.config.sample_queue_size.max(1) + 2,
+            config.channels() as usize,
+            config.sample_rate().0,
+            sample_tx,
+            Arc::clone(&self.dropped_batches),
+        );
+
         // Capture config values for use in closures
-        let quiet_mode = self.config.quiet_mode;
+        let channel_mode = self.config.channel_mode;
+        let log_config = self.config.clone();
+        let stream_errors = self.stream_errors.clone();
+        let error_callback = move |err: cpal::StreamError| {
+            log(&log_config, LogLevel::Error, format_args!("An error occurred on the input audio stream: {}", err));
+            if let Some(stream_errors) = &stream_errors {
+                let _ = stream_errors.send(match err {
+                    cpal::StreamError::DeviceNotAvailable => AudioError::DeviceError(err.to_string()),
+                    _ => AudioError::StreamError(err.to_string()),
+                });
+            }
+        };
 
         let stream: Result<Stream, cpal::BuildStreamError> = match config.sample_format() {
-            cpal::SampleFormat::F32 => {
-                let channels = config.channels() as usize;
-                let sample_rate = config.sample_rate().0;
-
-                device.build_input_stream(
-                    &stream_config,
-                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                        // Process audio properly for fingerprinting
-                        let processed_samples =
-                            Self::process_audio_data_f32(data, channels, sample_rate);
-
-                        for sample in processed_samples {
-                            sample_buffer.push(sample);
-
-                            if sample_buffer.len() >= buffer_size {
-                                if sample_tx.send(sample_buffer.clone()).is_err() {
-                                    return; // Receiver dropped, stop recording
-                                }
-                                sample_buffer.clear();
-                            }
-                        }
-                    },
-                    move |err| {
-                        if !quiet_mode {
-                            eprintln!("An error occurred on the input audio stream: {}", err);
-                        }
-                    },
-                    None,
-                )
-            }
-            cpal::SampleFormat::I16 => {
-                let channels = config.channels() as usize;
-                let sample_rate = config.sample_rate().0;
-
-                device.build_input_stream(
-                    &stream_config,
-                    move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                        // Process audio properly for fingerprinting
-                        let processed_samples =
-                            Self::process_audio_data_i16(data, channels, sample_rate);
-
-                        for sample in processed_samples {
-                            sample_buffer.push(sample);
-
-                            if sample_buffer.len() >= buffer_size {
-                                if sample_tx.send(sample_buffer.clone()).is_err() {
-                                    return; // Receiver dropped, stop recording
-                                }
-                                sample_buffer.clear();
-                            }
-                        }
-                    },
-                    move |err| {
-                        if !quiet_mode {
-                            eprintln!("An error occurred on the input audio stream: {}", err);
-                        }
-                    },
-                    None,
-                )
-            }
-            cpal::SampleFormat::U16 => {
-                device.build_input_stream(
-                    &stream_config,
-                    move |data: &[u16], _: &cpal::InputCallbackInfo| {
-                        // Convert u16 samples to i16
-                        for &sample in data.iter() {
-                            let sample_i16 = (sample as i32 - 32768) as i16;
-                            sample_buffer.push(sample_i16);
-
-                            if sample_buffer.len() >= buffer_size {
-                                if sample_tx.send(sample_buffer.clone()).is_err() {
-                                    return; // Receiver dropped, stop recording
-                                }
-                                sample_buffer.clear();
-                            }
-                        }
-                    },
-                    move |err| {
-                        if !quiet_mode {
-                            eprintln!("An error occurred on the input audio stream: {}", err);
-                        }
-                    },
-                    None,
-                )
-            }
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &stream_config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    writer.write_frames(data, |frame| {
+                        (channel_mode.mix(frame, |sample| sample) * 32767.0).clamp(-32768.0, 32767.0) as i16
+                    });
+                },
+                error_callback,
+                None,
+            ),
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &stream_config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    writer.write_frames(data, |frame| channel_mode.mix(frame, f32::from) as i16);
+                },
+                error_callback,
+                None,
+            ),
+            cpal::SampleFormat::U16 => device.build_input_stream(
+                &stream_config,
+                move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                    writer.write_frames(data, |frame| channel_mode.mix(frame, |sample| sample as f32 - 32768.0) as i16);
+                },
+                error_callback,
+                None,
+            ),
             _ => {
                 return Err(AudioError::ConfigError(format!(
                     "Unsupported sample format: {:?}",
@@ -254,66 +370,231 @@ impl AudioRecorder {
 
         Ok(device_names)
     }
+}
 
-    /// Process F32 audio data - convert to mono, resample if needed, and convert to i16
-    fn process_audio_data_f32(data: &[f32], channels: usize, sample_rate: u32) -> Vec<i16> {
-        // Convert to mono if stereo
-        let mono_data: Vec<f32> = if channels == 2 {
-            // Convert stereo to mono by averaging left and right channels
-            data.chunks_exact(2)
-                .map(|stereo_pair| (stereo_pair[0] + stereo_pair[1]) / 2.0)
-                .collect()
-        } else {
-            // Already mono or handle other channel configurations
-            data.iter().step_by(channels).cloned().collect()
-        };
+/// Pick capture settings among the ones a device supports
+///
+/// 48 or 44.1 kHz with at most two channels comes first, then the device default if
+/// usable, then the lowest supported rate of at least 16 kHz. Lower rates and sample
+/// formats other than F32, I16 and U16 cannot be converted, a device offering nothing
+/// else is an error.
+pub fn choose_input_config(
+    default: Option<SupportedStreamConfig>,
+    supported: &[SupportedStreamConfigRange],
+) -> Result<SupportedStreamConfig, AudioError> {
+    let convertible = |format: SampleFormat| matches!(format, SampleFormat::F32 | SampleFormat::I16 | SampleFormat::U16);
+    let usable = || supported.iter().filter(|range| convertible(range.sample_format()) && range.channels() > 0);
+
+    for rate in PREFERRED_SAMPLE_RATES {
+        let preferred = usable()
+            .filter(|range| range.channels() <= 2)
+            .find_map(|range| (*range).try_with_sample_rate(SampleRate(rate)));
+        if let Some(config) = preferred {
+            return Ok(config);
+        }
+    }
 
-        // Simple downsampling if needed (note: this is basic, could be improved with proper filtering)
-        let target_sample_rate = 16000u32;
-        let downsampled_data: Vec<f32> = if sample_rate > target_sample_rate {
-            let downsample_factor = sample_rate / target_sample_rate;
-            mono_data
-                .iter()
-                .step_by(downsample_factor as usize)
-                .cloned()
-                .collect()
-        } else {
-            mono_data
-        };
+    if let Some(default) = default.filter(|config| convertible(config.sample_format()) && config.sample_rate().0 >= TARGET_SAMPLE_RATE) {
+        return Ok(default);
+    }
+
+    let lowest_rate = |range: &SupportedStreamConfigRange| range.min_sample_rate().0.max(TARGET_SAMPLE_RATE);
+    usable()
+        .filter(|range| range.max_sample_rate().0 >= TARGET_SAMPLE_RATE)
+        .min_by_key(|range| (range.channels() > 2, lowest_rate(range)))
+        .map(|range| (*range).with_sample_rate(SampleRate(lowest_rate(range))))
+        .ok_or_else(|| {
+            let offered: Vec<String> = supported.iter().map(describe_config_range).collect();
+            AudioError::ConfigError(format!(
+                "No usable audio config, at least {} Hz with F32, I16 or U16 samples is needed but the device offers: {}",
+                TARGET_SAMPLE_RATE,
+                if offered.is_empty() { "nothing".to_string() } else { offered.join(", ") }
+            ))
+        })
+}
+
+/// Input device of a new JACK client, whose ports are not connected to anything
+#[cfg(all(feature = "jack", target_os = "linux"))]
+fn jack_device(client: &str) -> Result<Device, AudioError> {
+    let mut host = cpal::platform::JackHost::new()
+        .map_err(|e| AudioError::DeviceError(format!("JACK is unavailable: {}", e)))?;
+    host.set_connect_automatically(false);
+    host.input_device_with_name(client)
+        .map(Device::from)
+        .ok_or_else(|| AudioError::DeviceError(format!("Could not create JACK client '{}', is a JACK or PipeWire server running?", client)))
+}
 
-        // Convert to i16
-        downsampled_data
-            .iter()
-            .map(|&sample| (sample * 32767.0).clamp(-32768.0, 32767.0) as i16)
-            .collect()
+#[cfg(not(all(feature = "jack", target_os = "linux")))]
+fn jack_device(_client: &str) -> Result<Device, AudioError> {
+    Err(AudioError::ConfigError("Recording from JACK requires the jack feature, on Linux".to_string()))
+}
+
+fn describe_config(config: &SupportedStreamConfig) -> String {
+    format!("{} channel(s) at {} Hz ({:?})", config.channels(), config.sample_rate().0, config.sample_format())
+}
+
+fn describe_config_range(range: &SupportedStreamConfigRange) -> String {
+    format!(
+        "{} channel(s) at {}-{} Hz ({:?})",
+        range.channels(),
+        range.min_sample_rate().0,
+        range.max_sample_rate().0,
+        range.sample_format()
+    )
+}
+
+/// Real-time side of the recorder, owned by the cpal callback
+///
+/// The callback runs on the audio driver's thread, so it must not block or allocate.
+/// Captured frames are downmixed, low-pass filtered and decimated to 16 kHz, and written straight into
+/// a ring of batch buffers allocated up front. A full batch is handed to the consumer
+/// by cloning its `Arc` (a reference count increment), and a buffer is reused once the
+/// consumer has dropped its reference. Sending never waits: when the consumer's queue
+/// is full the batch is dropped and counted as an overrun.
+///
+/// The only allocation left happens if the consumer keeps more batches alive than the
+/// ring holds, in which case the busy buffer is replaced by a new one.
+struct BatchWriter {
+    ring: Vec<Arc<[i16]>>,
+    slot: usize,
+    filled: usize,
+    channels: usize,
+    decimator: Decimator,
+    sample_tx: SyncSender<Arc<[i16]>>,
+    dropped_batches: Arc<AtomicU64>,
+    disconnected: bool,
+}
+
+impl BatchWriter {
+    fn new(
+        batch_size: usize,
+        ring_size: usize,
+        channels: usize,
+        sample_rate: u32,
+        sample_tx: SyncSender<Arc<[i16]>>,
+        dropped_batches: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            ring: (0..ring_size).map(|_| vec![0i16; batch_size].into()).collect(),
+            slot: 0,
+            filled: 0,
+            channels: channels.max(1),
+            decimator: Decimator::new(sample_rate),
+            sample_tx,
+            dropped_batches,
+            disconnected: false,
+        }
     }
 
-    /// Process I16 audio data - convert to mono, resample if needed
-    fn process_audio_data_i16(data: &[i16], channels: usize, sample_rate: u32) -> Vec<i16> {
-        // Convert to mono if stereo
-        let mono_data: Vec<i16> = if channels == 2 {
-            // Convert stereo to mono by averaging left and right channels
-            data.chunks_exact(2)
-                .map(|stereo_pair| ((stereo_pair[0] as i32 + stereo_pair[1] as i32) / 2) as i16)
-                .collect()
-        } else {
-            // Already mono or handle other channel configurations
-            data.iter().step_by(channels).cloned().collect()
-        };
+    /// Convert interleaved frames to mono samples and append them to the current batch
+    fn write_frames<T: Copy>(&mut self, data: &[T], to_mono: impl Fn(&[T]) -> i16) {
+        if self.disconnected {
+            return; // Receiver dropped, stop recording
+        }
+
+        for frame in data.chunks_exact(self.channels) {
+            if let Some(sample) = self.decimator.push(to_mono(frame)) {
+                self.push(sample);
+            }
+        }
+    }
+
+    fn push(&mut self, sample: i16) {
+        let batch_size = self.ring[self.slot].len();
+
+        if self.filled == 0 && Arc::get_mut(&mut self.ring[self.slot]).is_none() {
+            // The consumer still holds this buffer, fall back to a fresh one
+            self.ring[self.slot] = vec![0i16; batch_size].into();
+        }
+
+        if let Some(buffer) = Arc::get_mut(&mut self.ring[self.slot]) {
+            buffer[self.filled] = sample;
+            self.filled += 1;
+        }
 
-        // Simple downsampling if needed
-        let target_sample_rate = 16000u32;
-        let downsampled_data: Vec<i16> = if sample_rate > target_sample_rate {
-            let downsample_factor = sample_rate / target_sample_rate;
-            mono_data
-                .iter()
-                .step_by(downsample_factor as usize)
-                .cloned()
-                .collect()
-        } else {
-            mono_data
+        if self.filled == batch_size {
+            self.filled = 0;
+            match self.sample_tx.try_send(Arc::clone(&self.ring[self.slot])) {
+                Ok(()) => self.slot = (self.slot + 1) % self.ring.len(),
+                Err(TrySendError::Full(_)) => {
+                    // Overwrite the same buffer with the next batch
+                    self.dropped_batches.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Disconnected(_)) => self.disconnected = true,
+            }
+        }
+    }
+}
+
+/// Conversion of mono audio captured at any rate of at least 16 kHz to 16 kHz, fed one
+/// sample at a time without allocating
+///
+/// An 8th order Butterworth low-pass filter removes what would fold back into the band
+/// fingerprints use, then a sample is kept each time the phase reaches the capture rate,
+/// which also handles rates that are not a multiple of 16 kHz such as 44.1 kHz.
+pub struct Decimator {
+    sample_rate: u32,
+    /// Position between two kept samples, kept across callbacks
+    phase: u32,
+    /// None at 16 kHz, which keeps every sample
+    filter: Option<[Biquad; 4]>,
+}
+
+impl Decimator {
+    /// Decimator of audio at `sample_rate`, lower rates keep every sample
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(TARGET_SAMPLE_RATE);
+        let filter = (sample_rate > TARGET_SAMPLE_RATE).then(|| {
+            // Quality factors of the poles of a Butterworth filter, by pair
+            std::array::from_fn(|pair| {
+                let quality = 1.0 / (2.0 * (std::f64::consts::PI * (2 * pair + 1) as f64 / 16.0).sin());
+                Biquad::low_pass(ANTI_ALIAS_CUTOFF / sample_rate as f64, quality)
+            })
+        });
+        Self { sample_rate, phase: 0, filter }
+    }
+
+    /// Add the next captured sample, returns the 16 kHz sample it completes if any
+    pub fn push(&mut self, sample: i16) -> Option<i16> {
+        let sample = match &mut self.filter {
+            Some(filter) => filter.iter_mut().fold(sample as f64, |sample, biquad| biquad.process(sample)).clamp(-32768.0, 32767.0) as i16,
+            None => sample,
         };
 
-        downsampled_data
+        self.phase += TARGET_SAMPLE_RATE;
+        if self.phase < self.sample_rate {
+            return None;
+        }
+        self.phase -= self.sample_rate;
+        Some(sample)
+    }
+}
+
+/// Second order section of a filter, in transposed direct form II
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    /// Low-pass section with a cutoff of `frequency` times the sample rate
+    fn low_pass(frequency: f64, quality: f64) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * frequency;
+        let alpha = omega.sin() / (2.0 * quality);
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - omega.cos()) / a0;
+        Self {
+            b: [b1 / 2.0, b1, b1 / 2.0],
+            a: [-2.0 * omega.cos() / a0, (1.0 - alpha) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
     }
 }
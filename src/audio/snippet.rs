@@ -0,0 +1,31 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::flac;
+
+/// Exact mono 16-bit audio a signature was made from, see `Config::with_audio_snippets`
+///
+/// Kept so a match can be listened to again when it is disputed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioSnippet {
+    pub samples: Arc<[i16]>,
+    pub sample_rate: u32,
+}
+
+impl AudioSnippet {
+    pub fn new(samples: &[i16], sample_rate: u32) -> Self {
+        Self {
+            samples: samples.into(),
+            sample_rate,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    /// Losslessly compressed FLAC file, about half the size of the raw samples
+    pub fn to_flac(&self) -> Vec<u8> {
+        flac::encode_mono_16bit(&self.samples, self.sample_rate)
+    }
+}
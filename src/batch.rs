@@ -0,0 +1,587 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::media::{self, MediaInfo};
+use crate::cache::ResultCache;
+use crate::config::{Config, LongFileAction};
+use crate::ledger::ProcessedLedger;
+use crate::songrec::{RecognitionProgress, RecognitionResult, SongRec};
+use crate::tracklist::{Tracklist, DEFAULT_TRACKLIST_INTERVAL};
+use crate::{Result, SongRecError};
+
+/// Final status of one file of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileStatus {
+    Recognized,
+    NoMatch,
+    Error,
+    /// Left out by the duration limits of the configuration, see `Config::with_file_duration_limits`
+    Skipped,
+}
+
+impl FileStatus {
+    fn of(outcome: &Result<RecognitionResult>) -> Self {
+        match outcome {
+            Ok(_) => FileStatus::Recognized,
+            Err(SongRecError::NoMatch(_)) => FileStatus::NoMatch,
+            Err(_) => FileStatus::Error,
+        }
+    }
+}
+
+/// Status of one file, stored as a JSON line in the state file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    pub status: FileStatus,
+
+    /// Recognized song, as "Artist - Title"
+    pub song: Option<String>,
+    pub track_key: Option<String>,
+
+    /// Songs of a long file recognized as a tracklist, as "Artist - Title", see `LongFileAction::Tracklist`
+    #[serde(default)]
+    pub tracks: Vec<String>,
+    pub error: Option<String>,
+
+    /// Category of the error, see `SongRecError::kind`
+    #[serde(default)]
+    pub error_kind: Option<String>,
+    pub processed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Per-file status of a batch job, persisted so an interrupted run can resume
+///
+/// Files that were recognized or had no match are skipped when the job runs again,
+/// files that failed with an error are retried.
+pub struct BatchState {
+    path: Option<PathBuf>,
+    records: HashMap<PathBuf, FileRecord>,
+}
+
+impl BatchState {
+    /// Load the state file at `path`, a missing file starts a new job
+    ///
+    /// The file is appended to after each file, the last line of a path wins on reload.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let mut records = HashMap::new();
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                for record in content.lines().filter_map(|line| serde_json::from_str::<FileRecord>(line).ok()) {
+                    records.insert(record.path.clone(), record);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SongRecError::ConfigError(format!("Could not read job state '{}': {}", path.display(), e))),
+        }
+
+        Ok(Self { path: Some(path), records })
+    }
+
+    /// State of a job that is not persisted
+    pub fn in_memory() -> Self {
+        Self { path: None, records: HashMap::new() }
+    }
+
+    /// Last record of a file
+    pub fn get(&self, file: &Path) -> Option<&FileRecord> {
+        self.records.get(file)
+    }
+
+    /// Whether a file reached a final status in a previous run
+    ///
+    /// Skipped files are checked again, as the duration limits may have changed.
+    pub fn is_done(&self, file: &Path) -> bool {
+        self.records.get(file).is_some_and(|record| matches!(record.status, FileStatus::Recognized | FileStatus::NoMatch))
+    }
+
+    /// Record the outcome of a file and append it to the state file
+    pub fn record(&mut self, file: &Path, outcome: &Result<RecognitionResult>) -> Result<()> {
+        self.append(FileRecord {
+            path: file.to_path_buf(),
+            status: FileStatus::of(outcome),
+            song: outcome.as_ref().ok().map(|result| format!("{} - {}", result.artist_name, result.song_name)),
+            track_key: outcome.as_ref().ok().map(|result| result.track_key.clone()),
+            tracks: Vec::new(),
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+            error_kind: outcome.as_ref().err().map(|e| e.kind().to_string()),
+            processed_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Record the tracklist of a long file, recognized if at least one track was
+    pub fn record_tracklist(&mut self, file: &Path, outcome: &Result<Tracklist>) -> Result<()> {
+        let tracks: Vec<String> = outcome
+            .iter()
+            .flat_map(|tracklist| &tracklist.entries)
+            .map(|entry| format!("{} - {}", entry.result.artist_name, entry.result.song_name))
+            .collect();
+        let status = match outcome {
+            Ok(_) if tracks.is_empty() => FileStatus::NoMatch,
+            Ok(_) => FileStatus::Recognized,
+            Err(_) => FileStatus::Error,
+        };
+
+        self.append(FileRecord {
+            path: file.to_path_buf(),
+            status,
+            song: None,
+            track_key: None,
+            tracks,
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+            error_kind: outcome.as_ref().err().map(|e| e.kind().to_string()),
+            processed_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Record a file left out by the duration limits, `duration` being its length
+    pub fn record_skipped(&mut self, file: &Path, duration: Duration) -> Result<()> {
+        self.append(FileRecord {
+            path: file.to_path_buf(),
+            status: FileStatus::Skipped,
+            song: None,
+            track_key: None,
+            tracks: Vec::new(),
+            error: Some(skip_reason(duration)),
+            error_kind: None,
+            processed_at: chrono::Utc::now(),
+        })
+    }
+
+    fn append(&mut self, record: FileRecord) -> Result<()> {
+        if let Some(path) = &self.path {
+            let line = serde_json::to_string(&record)
+                .map_err(|e| SongRecError::ConfigError(format!("Could not serialize job state: {}", e)))?;
+
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut state| writeln!(state, "{}", line))
+                .map_err(|e| SongRecError::ConfigError(format!("Could not write job state '{}': {}", path.display(), e)))?;
+        }
+
+        self.records.insert(record.path.clone(), record);
+        Ok(())
+    }
+
+    /// Summary of the given files, including the ones processed by previous runs
+    pub fn summary(&self, files: &[PathBuf]) -> BatchSummary {
+        let mut summary = BatchSummary {
+            total: files.len(),
+            ..Default::default()
+        };
+
+        for file in files {
+            match self.records.get(file) {
+                Some(record) => summary.count(record),
+                None => summary.pending += 1,
+            }
+        }
+
+        summary
+    }
+}
+
+/// File that was not recognized
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchFailure {
+    pub path: PathBuf,
+    pub status: FileStatus,
+    pub error: Option<String>,
+}
+
+/// Counts and failures of a batch job, serializable as the final JSON report
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub recognized: usize,
+    pub no_match: usize,
+    pub errors: usize,
+
+    /// Files skipped because the ledger knows them from previous runs
+    #[serde(default)]
+    pub known: usize,
+
+    /// Files not processed yet, e.g. after an interruption
+    pub pending: usize,
+
+    /// Files whose result came from the result cache, without a lookup
+    #[serde(default)]
+    pub cached: usize,
+
+    /// Files left out by the duration limits of the configuration
+    #[serde(default)]
+    pub skipped: usize,
+
+    /// Number of errors of each category, see `SongRecError::kind`
+    #[serde(default)]
+    pub errors_by_kind: BTreeMap<String, usize>,
+
+    /// Files processed by this run, the others were done by previous runs, known or cached
+    #[serde(default)]
+    pub processed: usize,
+
+    /// API lookups sent by this run
+    #[serde(default)]
+    pub api_calls: u64,
+
+    /// Duration of this run in milliseconds
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    pub failures: Vec<BatchFailure>,
+}
+
+impl BatchSummary {
+    /// Count a file processed outside of a `BatchJob`, e.g. by a `DirectoryWatcher`, `error` is None if it was recognized
+    pub fn add(&mut self, path: &Path, error: Option<&SongRecError>) {
+        let record = FileRecord {
+            path: path.to_path_buf(),
+            status: match error {
+                None => FileStatus::Recognized,
+                Some(SongRecError::NoMatch(_)) => FileStatus::NoMatch,
+                Some(_) => FileStatus::Error,
+            },
+            song: None,
+            track_key: None,
+            tracks: Vec::new(),
+            error: error.map(|e| e.to_string()),
+            error_kind: error.map(|e| e.kind().to_string()),
+            processed_at: chrono::Utc::now(),
+        };
+        self.total += 1;
+        self.processed += 1;
+        self.count(&record);
+    }
+
+    /// Count a file left out by the duration limits outside of a `BatchJob`
+    pub fn add_skipped(&mut self) {
+        self.total += 1;
+        self.skipped += 1;
+    }
+
+    fn count(&mut self, record: &FileRecord) {
+        match record.status {
+            FileStatus::Recognized => self.recognized += 1,
+            FileStatus::NoMatch => self.no_match += 1,
+            FileStatus::Skipped => {
+                self.skipped += 1;
+                return;
+            }
+            FileStatus::Error => {
+                self.errors += 1;
+                let kind = record.error_kind.as_deref().unwrap_or("unknown");
+                *self.errors_by_kind.entry(kind.to_string()).or_default() += 1;
+            }
+        }
+        if record.status != FileStatus::Recognized {
+            self.failures.push(BatchFailure {
+                path: record.path.clone(),
+                status: record.status,
+                error: record.error.clone(),
+            });
+        }
+    }
+}
+
+/// One line report, e.g. "120 files: 97 matched, 20 no match, 3 errors (network: 3); 120 processed in 4m 05s, 164 API calls"
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files: {} matched, {} no match, {} errors", self.total, self.recognized, self.no_match, self.errors)?;
+        if !self.errors_by_kind.is_empty() {
+            let kinds: Vec<String> = self.errors_by_kind.iter().map(|(kind, count)| format!("{}: {}", kind, count)).collect();
+            write!(f, " ({})", kinds.join(", "))?;
+        }
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        if self.known > 0 {
+            write!(f, ", {} known", self.known)?;
+        }
+        if self.cached > 0 {
+            write!(f, ", {} cached", self.cached)?;
+        }
+        if self.pending > 0 {
+            write!(f, ", {} pending", self.pending)?;
+        }
+        write!(f, "; {} processed in {}, {} API calls", self.processed, format_elapsed(self.elapsed_ms), self.api_calls)
+    }
+}
+
+/// Duration of a run, e.g. "850 ms", "42s", "4m 05s" or "2h 03m"
+pub(crate) fn format_elapsed(ms: u64) -> String {
+    let seconds = ms / 1000;
+    match seconds {
+        0 => format!("{} ms", ms),
+        1..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds / 60 % 60),
+    }
+}
+
+/// Shortest audio that can be fingerprinted
+const MIN_FILE_DURATION: Duration = Duration::from_secs(3);
+
+/// How batch and watch modes handle a file, from its duration and the limits of the configuration
+pub(crate) enum FileRoute {
+    Recognize,
+    /// Longer than `Config::max_file_duration`, recognized as a tracklist
+    Tracklist,
+    /// Left out by the duration limits, with its duration
+    Skip(Duration),
+}
+
+impl FileRoute {
+    /// Route of a file from its headers, before decoding it
+    ///
+    /// Files too short to be fingerprinted are rejected. Files whose headers do not hold
+    /// their duration, or cannot be probed, are left to the decoder.
+    pub(crate) fn of(config: &Config, path: &Path) -> Result<Self> {
+        let duration = match media::probe(path) {
+            Ok(MediaInfo { duration: Some(duration), .. }) => duration,
+            _ => return Ok(FileRoute::Recognize),
+        };
+        let seconds = duration.as_secs_f32();
+
+        if config.min_file_duration.is_some_and(|min| seconds < min) {
+            Ok(FileRoute::Skip(duration))
+        } else if duration < MIN_FILE_DURATION {
+            Err(SongRecError::FingerprintingError(format!(
+                "Audio file '{}' is too short for fingerprinting. Need at least 3 seconds of audio, but only got {:.2} seconds.",
+                path.display(),
+                seconds
+            )))
+        } else if config.max_file_duration.is_some_and(|max| seconds > max) {
+            match config.long_files {
+                LongFileAction::Skip => Ok(FileRoute::Skip(duration)),
+                LongFileAction::Tracklist => Ok(FileRoute::Tracklist),
+            }
+        } else {
+            Ok(FileRoute::Recognize)
+        }
+    }
+}
+
+/// Why a file was skipped, e.g. "2.5 s, outside the duration limits"
+pub(crate) fn skip_reason(duration: Duration) -> String {
+    format!("{:.1} s, outside the duration limits", duration.as_secs_f32())
+}
+
+/// Notification sent while a batch job runs
+#[derive(Debug)]
+pub enum BatchEvent<'a> {
+    /// A file was skipped, it reached a final status in a previous run of the job
+    /// or the ledger knows it
+    Skipped { index: usize, path: &'a Path },
+    /// Progress of the file being recognized
+    Progress { index: usize, path: &'a Path, progress: RecognitionProgress },
+    /// A file was processed
+    Finished { index: usize, path: &'a Path, outcome: &'a Result<RecognitionResult> },
+    /// A file longer than `Config::max_file_duration` was recognized as a tracklist
+    Tracklist { index: usize, path: &'a Path, outcome: &'a Result<Tracklist> },
+    /// A file was left out by the duration limits of the configuration, `duration` being its length
+    OutOfLimits { index: usize, path: &'a Path, duration: Duration },
+    /// The outcome of a file could not be kept in the ledger or the result cache, the job
+    /// goes on without it
+    RecordFailed { index: usize, path: &'a Path, error: &'a SongRecError },
+}
+
+/// Sent by batch workers to the thread running the job
+enum WorkerMessage {
+    Progress(usize, RecognitionProgress),
+    Finished(usize, Box<Result<RecognitionResult>>),
+    Tracklist(usize, Box<Result<Tracklist>>),
+    OutOfLimits(usize, Duration),
+}
+
+/// Recognizes a list of files, recording each outcome in a `BatchState`
+///
+/// Up to `Config::max_concurrent_lookups` files are recognized at once, one if it is unset.
+pub struct BatchJob<'a> {
+    songrec: &'a SongRec,
+    state: BatchState,
+    ledger: Option<ProcessedLedger>,
+    cache: Option<ResultCache>,
+    force: bool,
+}
+
+impl<'a> BatchJob<'a> {
+    pub fn new(songrec: &'a SongRec, state: BatchState) -> Self {
+        Self {
+            songrec,
+            state,
+            ledger: None,
+            cache: None,
+            force: false,
+        }
+    }
+
+    /// Skip files processed by previous runs, whatever the job, and record the ones processed
+    pub fn with_ledger(mut self, ledger: ProcessedLedger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Take the result of unchanged files from the cache instead of recognizing them,
+    /// and store the matches of the files recognized
+    ///
+    /// Results cached with other settings than those of the `SongRec` are not used.
+    pub fn with_cache(mut self, cache: ResultCache) -> Self {
+        self.cache = Some(cache.with_config(self.songrec.config()));
+        self
+    }
+
+    /// Process files even if the ledger knows them, they are still recorded
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn state(&self) -> &BatchState {
+        &self.state
+    }
+
+    /// Process the files not done yet, `on_event` is called for every file
+    ///
+    /// Files outside the duration limits of the configuration are skipped or recognized
+    /// as tracklists. Fails only if the state file cannot be written, recognition errors
+    /// are recorded and ledger or cache errors reported as `BatchEvent::RecordFailed`.
+    pub fn run<F>(&mut self, files: &[PathBuf], mut on_event: F) -> Result<BatchSummary>
+    where
+        F: FnMut(BatchEvent<'_>),
+    {
+        let started = Instant::now();
+        let lookups_before = self.songrec.lookup_count();
+        let mut known = 0;
+        let mut cached = 0;
+        let mut todo = Vec::new();
+
+        for (index, path) in files.iter().enumerate() {
+            if self.state.is_done(path) {
+                on_event(BatchEvent::Skipped { index, path });
+                continue;
+            }
+
+            if !self.force && self.ledger.as_ref().is_some_and(|ledger| ledger.contains(path)) {
+                if self.state.get(path).is_none() {
+                    known += 1;
+                }
+                on_event(BatchEvent::Skipped { index, path });
+                continue;
+            }
+
+            if let Some(result) = self.cache.as_ref().and_then(|cache| cache.get(path)) {
+                let outcome = Ok(result.clone());
+                self.state.record(path, &outcome)?;
+                if let Some(Err(error)) = self.ledger.as_mut().map(|ledger| ledger.record(path, outcome.as_ref().ok())) {
+                    on_event(BatchEvent::RecordFailed { index, path, error: &error });
+                }
+                cached += 1;
+                on_event(BatchEvent::Finished { index, path, outcome: &outcome });
+                continue;
+            }
+
+            todo.push(index);
+        }
+
+        // Files are recognized by worker threads, while outcomes are recorded and
+        // reported on this thread in the order they complete
+        let workers = self.songrec.config().max_concurrent_lookups.unwrap_or(1).clamp(1, todo.len().max(1));
+        let next = AtomicUsize::new(0);
+        let songrec = self.songrec;
+        let (message_tx, message_rx) = mpsc::channel();
+
+        thread::scope(|scope| -> Result<()> {
+            for _ in 0..workers {
+                let message_tx = message_tx.clone();
+                let (next, todo) = (&next, &todo);
+
+                scope.spawn(move || {
+                    while let Some(&index) = todo.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let path = files[index].to_string_lossy();
+                        let on_progress = |progress| {
+                            let _ = message_tx.send(WorkerMessage::Progress(index, progress));
+                        };
+                        let message = match FileRoute::of(songrec.config(), &files[index]) {
+                            Ok(FileRoute::Recognize) => {
+                                WorkerMessage::Finished(index, Box::new(songrec.recognize_from_file_with_progress(&path, on_progress)))
+                            }
+                            Ok(FileRoute::Tracklist) => WorkerMessage::Tracklist(
+                                index,
+                                Box::new(songrec.recognize_tracklist_with_progress(&path, DEFAULT_TRACKLIST_INTERVAL, on_progress)),
+                            ),
+                            Ok(FileRoute::Skip(duration)) => WorkerMessage::OutOfLimits(index, duration),
+                            Err(e) => WorkerMessage::Finished(index, Box::new(Err(e))),
+                        };
+                        if message_tx.send(message).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(message_tx);
+
+            for message in message_rx {
+                match message {
+                    WorkerMessage::Progress(index, progress) => {
+                        on_event(BatchEvent::Progress { index, path: &files[index], progress });
+                    }
+                    WorkerMessage::Finished(index, outcome) => {
+                        let outcome = *outcome;
+                        let path = &files[index];
+                        self.state.record(path, &outcome)?;
+                        if let Some(ledger) = self.ledger.as_mut().filter(|_| !matches!(outcome, Err(SongRecError::NetworkError(_)))) {
+                            if let Err(error) = ledger.record(path, outcome.as_ref().ok()) {
+                                on_event(BatchEvent::RecordFailed { index, path, error: &error });
+                            }
+                        }
+                        if let (Some(cache), Ok(result)) = (self.cache.as_mut(), &outcome) {
+                            if let Err(error) = cache.store(path, result) {
+                                on_event(BatchEvent::RecordFailed { index, path, error: &error });
+                            }
+                        }
+                        on_event(BatchEvent::Finished { index, path, outcome: &outcome });
+                    }
+                    WorkerMessage::Tracklist(index, outcome) => {
+                        let outcome = *outcome;
+                        let path = &files[index];
+                        self.state.record_tracklist(path, &outcome)?;
+                        if let Some(ledger) = self.ledger.as_mut().filter(|_| !matches!(outcome, Err(SongRecError::NetworkError(_)))) {
+                            if let Err(error) = ledger.record(path, None) {
+                                on_event(BatchEvent::RecordFailed { index, path, error: &error });
+                            }
+                        }
+                        on_event(BatchEvent::Tracklist { index, path, outcome: &outcome });
+                    }
+                    WorkerMessage::OutOfLimits(index, duration) => {
+                        let path = &files[index];
+                        self.state.record_skipped(path, duration)?;
+                        on_event(BatchEvent::OutOfLimits { index, path, duration });
+                    }
+                }
+            }
+
+            Ok(())
+        })?;
+
+        let mut summary = self.state.summary(files);
+        summary.known = known;
+        summary.pending -= known;
+        summary.cached = cached;
+        summary.processed = todo.len();
+        summary.api_calls = self.songrec.lookup_count() - lookups_before;
+        summary.elapsed_ms = started.elapsed().as_millis() as u64;
+        Ok(summary)
+    }
+}
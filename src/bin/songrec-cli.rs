@@ -1,18 +1,34 @@
-use clap::{App, Arg, SubCommand};
-use songrec::{SongRec, Config, OutputFormat, RecognitionOutput};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use songrec::{SongRec, ChannelMode, Config, LongFileAction, OutputFormat, PipelineEvent, RecognitionOutput, RecognitionPhase, RecognitionStream, SecretLocation, SecretStore, Sink, SpectralWeighting, StdoutSink, FileSink};
+use songrec::batch::{BatchEvent, BatchJob, BatchState, BatchSummary};
+use songrec::cache::ResultCache;
+use songrec::ledger::{FileIdentity, ProcessedLedger};
+use songrec::service::ServiceDefinition;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 fn main() {
     let matches = App::new("SongRec CLI")
         .version("0.4.3")
         .about("An open-source Shazam client library and CLI")
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help("Recognize as usual but only log the files that would be renamed, tagged or split and the webhooks and playlists that would be updated")
+        )
         .subcommand(
-            SubCommand::with_name("recognize")
-                .about("Recognize a song from an audio file")
+            duration_limit_args(SubCommand::with_name("recognize"))
+                .about("Recognize songs from audio files")
                 .arg(
                     Arg::with_name("input")
                         .required(true)
-                        .help("Input audio file path")
+                        .multiple(true)
+                        .help("Input audio file paths, several files are recognized one after the other")
                         .index(1)
                 )
                 .arg(
@@ -20,7 +36,7 @@ fn main() {
                         .short("f")
                         .long("format")
                         .value_name("FORMAT")
-                        .help("Output format: simple, json, csv")
+                        .help("Output format: simple, json (one object per line, also jsonl), csv")
                         .takes_value(true)
                         .default_value("simple")
                 )
@@ -36,32 +52,281 @@ fn main() {
                         .long("verbose")
                         .help("Enable verbose debug output")
                 )
+                .arg(
+                    Arg::with_name("dump-api")
+                        .long("dump-api")
+                        .value_name("DIR")
+                        .help("Write raw API requests and responses to DIR for troubleshooting")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("language")
+                        .long("language")
+                        .visible_alias("lang")
+                        .value_name("TAG")
+                        .help("Language of the song metadata and of the album and release year picked from it, e.g. de or de-DE")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("normalize-genres")
+                        .long("normalize-genres")
+                        .help("Add the genre mapped onto a small vocabulary, e.g. Hip-Hop for Hip-Hop/Rap, to JSON output as genre_normalized")
+                )
+                .arg(
+                    Arg::with_name("weighting")
+                        .long("weighting")
+                        .value_name("CURVE")
+                        .help("Experimental spectral weighting for noisy recordings: none, pre-emphasis, a-weighting")
+                        .takes_value(true)
+                        .possible_values(&["none", "pre-emphasis", "a-weighting"])
+                )
+                .arg(
+                    Arg::with_name("channel")
+                        .long("channel")
+                        .value_name("MODE")
+                        .help("Channel of stereo audio to fingerprint: mid (both), left, right or side (without the center)")
+                        .takes_value(true)
+                        .possible_values(&["mid", "left", "right", "side"])
+                )
+                .arg(
+                    Arg::with_name("analyze")
+                        .long("analyze")
+                        .help("Estimate the tempo and key of recognized songs (requires the analysis feature)")
+                )
+                .arg(
+                    Arg::with_name("retry")
+                        .long("retry")
+                        .value_name("SECONDS")
+                        .help("Longer samples looked up in turn when a file does not match, comma-separated, or none [default: 18,24]")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("pitch-shifts")
+                        .long("pitch-shifts")
+                        .value_name("PERCENTS")
+                        .help("Also match audio sped up or slowed down by these percentages, comma-separated, e.g. 4,8 for pitched vinyl or nightcore")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("open")
+                        .long("open")
+                        .help("Open the page of the recognized song in the default browser")
+                )
+                .arg(
+                    Arg::with_name("copy")
+                        .long("copy")
+                        .help("Copy the formatted results to the clipboard (requires the clipboard feature)")
+                )
+                .arg(
+                    Arg::with_name("play-preview")
+                        .long("play-preview")
+                        .help("Play the preview clip of the recognized song (requires the playback feature)")
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .value_name("FILE")
+                        .help("Record the status of each file in FILE, running again with the same FILE skips the files already done")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("summary")
+                        .long("summary")
+                        .value_name("FILE")
+                        .help("Write a JSON report with the counts, errors by category, API calls and the files not recognized to FILE")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .short("j")
+                        .long("jobs")
+                        .value_name("N")
+                        .help("Number of files recognized at once [default: 1]")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("delay")
+                        .long("delay")
+                        .value_name("SECONDS")
+                        .help("Minimum delay between two requests to the API")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("skip-known")
+                        .long("skip-known")
+                        .help("Skip files processed by previous runs, remembered in a ledger in the state directory")
+                )
+                .arg(
+                    Arg::with_name("ledger")
+                        .long("ledger")
+                        .value_name("FILE")
+                        .help("Same as --skip-known with the ledger stored in FILE")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("by-content")
+                        .long("by-content")
+                        .help("Match files against the ledger by content hash, so renamed, moved and duplicate files are skipped too")
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Process files even if the ledger already knows them")
+                )
+                .arg(
+                    Arg::with_name("no-cache")
+                        .long("no-cache")
+                        .help("Recognize every file again instead of reusing the results of unchanged files from the state directory")
+                )
+        )
+        .subcommand(
+            continuous_args(
+                SubCommand::with_name("listen")
+                    .about("Listen continuously for songs")
+                    .arg(
+                        Arg::with_name("device")
+                            .short("d")
+                            .long("device")
+                            .value_name("DEVICE")
+                            .help("Audio input device name")
+                            .takes_value(true)
+                    )
+                    .arg(
+                        Arg::with_name("system-audio")
+                            .long("system-audio")
+                            .help("Recognize what this computer plays, through the monitor of the default PulseAudio or PipeWire output")
+                            .conflicts_with("device")
+                    )
+                    .arg(
+                        Arg::with_name("jack")
+                            .long("jack")
+                            .value_name("CLIENT")
+                            .help("Record from the input ports of a new JACK client, to connect with a patchbay (requires the jack feature)")
+                            .takes_value(true)
+                            .conflicts_with_all(&["device", "system-audio"])
+                    )
+                    .arg(
+                        Arg::with_name("once")
+                            .long("once")
+                            .help("Record a single 12-second window, print its match and exit, failing without a match")
+                            .conflicts_with_all(&["events", "jack"])
+                    )
+                    .arg(
+                        Arg::with_name("attempts")
+                            .long("attempts")
+                            .value_name("N")
+                            .help("Windows --once records at most when they are silent or hold almost no sound [default: 3]")
+                            .takes_value(true)
+                            .requires("once")
+                    )
+            )
+            .arg(
+                Arg::with_name("config")
+                    .short("c")
+                    .long("config")
+                    .value_name("FILE")
+                    .help("TOML configuration file, re-read on SIGHUP without restarting the audio stream")
+                    .takes_value(true)
+            )
+        )
+        .subcommand(
+            continuous_args(
+                SubCommand::with_name("monitor")
+                    .about("Monitor a radio station defined in the configuration file or the environment, e.g. monitor -c songrec.toml --station bbc6")
+                    .arg(
+                        Arg::with_name("station")
+                            .short("s")
+                            .long("station")
+                            .value_name("NAME")
+                            .help("Station profile, a [stations.NAME] table of the configuration file")
+                            .takes_value(true)
+                            .required(true)
+                    )
+            )
+            .arg(
+                Arg::with_name("config")
+                    .short("c")
+                    .long("config")
+                    .value_name("FILE")
+                    .help("TOML configuration file defining the station, re-read on SIGHUP; without it, SONGREC_STATIONS__NAME__STREAM_URL and the other SONGREC_* variables define it")
+                    .takes_value(true)
+            )
         )
         .subcommand(
-            SubCommand::with_name("listen")
-                .about("Listen continuously for songs")
+            duration_limit_args(SubCommand::with_name("watch"))
+                .about("Recognize audio files as they appear in a directory (requires the watch feature)")
                 .arg(
-                    Arg::with_name("device")
-                        .short("d")
-                        .long("device")
-                        .value_name("DEVICE")
-                        .help("Audio input device name")
-                        .takes_value(true)
+                    Arg::with_name("directory")
+                        .required(true)
+                        .help("Directory to watch, including subdirectories")
+                        .index(1)
                 )
                 .arg(
                     Arg::with_name("format")
                         .short("f")
                         .long("format")
                         .value_name("FORMAT")
-                        .help("Output format: simple, json, csv")
+                        .help("Output format: simple, json (one object per line, also jsonl), csv")
                         .takes_value(true)
                         .default_value("simple")
                 )
                 .arg(
-                    Arg::with_name("quiet")
-                        .short("q")
-                        .long("quiet")
-                        .help("Suppress verbose debug output (default)")
+                    Arg::with_name("ledger")
+                        .long("ledger")
+                        .value_name("FILE")
+                        .help("File recording the processed files [default: .songrec-processed.jsonl in the watched directory]")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("organize")
+                        .long("organize")
+                        .value_name("MODE")
+                        .help("What to do with recognized files: rename, move or copy")
+                        .takes_value(true)
+                        .possible_values(&["rename", "move", "copy"])
+                )
+                .arg(
+                    Arg::with_name("library")
+                        .long("library")
+                        .value_name("DIR")
+                        .help("Library root for --organize move and copy, files are sorted into Artist/Album/")
+                        .takes_value(true)
+                        .required_ifs(&[("organize", "move"), ("organize", "copy")])
+                )
+                .arg(
+                    Arg::with_name("by-content")
+                        .long("by-content")
+                        .help("Match files against the ledger by content hash, so renamed, moved and duplicate files are skipped too")
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Process files even if the ledger already knows them")
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .long("tag")
+                        .requires("organize")
+                        .help("Write title, artist, album, year, genre, track and disc number tags to organized MP3 and WAV files (requires the tagging feature)")
+                )
+                .arg(
+                    Arg::with_name("track-numbers")
+                        .long("track-numbers")
+                        .requires("library")
+                        .help("Start moved and copied file names with their track number when known, e.g. 03 - Artist - Title")
+                )
+                .arg(
+                    Arg::with_name("genre-folders")
+                        .long("genre-folders")
+                        .requires("library")
+                        .help("Sort the library into Genre/Artist/Album/ by normalized genre, implies --normalize-genres")
+                )
+                .arg(
+                    Arg::with_name("summary")
+                        .long("summary")
+                        .value_name("FILE")
+                        .help("Also write the report printed when watching stops (Ctrl-C) as JSON to FILE")
+                        .takes_value(true)
                 )
                 .arg(
                     Arg::with_name("verbose")
@@ -70,108 +335,2369 @@ fn main() {
                         .help("Enable verbose debug output")
                 )
                 .arg(
-                    Arg::with_name("no-dedupe")
-                        .long("no-dedupe")
-                        .help("Disable request deduplication")
+                    Arg::with_name("dump-api")
+                        .long("dump-api")
+                        .value_name("DIR")
+                        .help("Write raw API requests and responses to DIR for troubleshooting")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("language")
+                        .long("language")
+                        .visible_alias("lang")
+                        .value_name("TAG")
+                        .help("Language of the song metadata and of the album and release year picked from it, e.g. de or de-DE")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("normalize-genres")
+                        .long("normalize-genres")
+                        .help("Add the genre mapped onto a small vocabulary, e.g. Hip-Hop for Hip-Hop/Rap, to JSON output as genre_normalized")
                 )
         )
         .subcommand(
             SubCommand::with_name("devices")
                 .about("List available audio input devices")
+                .arg(
+                    Arg::with_name("probe")
+                        .long("probe")
+                        .help("Record each device for two seconds and report its supported configurations and noise floor")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("service")
+                .about("Run listen mode in the background: systemd user unit, launchd agent or Windows service")
+                .subcommand(
+                    SubCommand::with_name("install")
+                        .about("Register and start the service, e.g. service install -- --device \"USB Audio\" -f json")
+                        .arg(
+                            Arg::with_name("print")
+                                .long("print")
+                                .help("Print the systemd unit (launchd plist on macOS) instead of installing it")
+                        )
+                        .arg(
+                            Arg::with_name("args")
+                                .multiple(true)
+                                .last(true)
+                                .help("Arguments of the listen subcommand, after --")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("uninstall")
+                        .about("Stop and remove the service")
+                )
+                .subcommand(
+                    SubCommand::with_name("run")
+                        .about("Run listen mode as the service does, used as the Windows service entry point")
+                        .arg(
+                            Arg::with_name("args")
+                                .multiple(true)
+                                .last(true)
+                                .help("Arguments of the listen subcommand, after --")
+                        )
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("eval")
+                .about("Measure recognition accuracy and latency on a labeled corpus")
+                .arg(
+                    Arg::with_name("manifest")
+                        .required(true)
+                        .help("CSV file of file,expected lines: expected is a track key or \"Artist - Title\", empty if the file should not match")
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("Configuration to evaluate, repeat to compare several [default: built-in settings]")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                )
+                .arg(
+                    Arg::with_name("weighting")
+                        .long("weighting")
+                        .value_name("CURVE")
+                        .help("Spectral weighting to evaluate each configuration with, repeat to compare several")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .possible_values(&["none", "pre-emphasis", "a-weighting"])
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format: simple, json (one report per configuration and line)")
+                        .takes_value(true)
+                        .default_value("simple")
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .help("Show the outcome of every file")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("dedupe")
+                .about("Find audio files holding the same recording, e.g. different encodes of a track")
+                .arg(
+                    Arg::with_name("directory")
+                        .required(true)
+                        .help("Directory searched recursively for audio files")
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .short("t")
+                        .long("threshold")
+                        .value_name("SCORE")
+                        .help("Fingerprint similarity from 0 to 1 above which files are duplicates [default: 0.5]")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format: simple, json (one group per line)")
+                        .takes_value(true)
+                        .default_value("simple")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("tracklist")
+                .about("Recognize every track of a long recording, such as a DJ mix or an aircheck")
+                .arg(
+                    Arg::with_name("input")
+                        .required(true)
+                        .help("Input audio file path")
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .short("i")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .help("Time between the fingerprinted windows")
+                        .takes_value(true)
+                        .default_value("30")
+                )
+                .arg(
+                    Arg::with_name("no-refine")
+                        .long("no-refine")
+                        .help("Place track changes at the first window of each track instead of looking up extra windows around them")
+                )
+                .arg(
+                    Arg::with_name("start")
+                        .long("start")
+                        .value_name("TIME")
+                        .help("Wall-clock time the recording started, RFC 3339 or local \"YYYY-MM-DD HH:MM:SS\", to show when each track played")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format: simple, json, cue, mkv-chapters (Matroska chapter XML), ffmetadata")
+                        .takes_value(true)
+                        .default_value("simple")
+                )
+                .arg(
+                    Arg::with_name("embed")
+                        .long("embed")
+                        .value_name("OUTPUT")
+                        .help("Also copy the recording to OUTPUT (.m4a, .mp4, .mka...) with a chapter per track, requires ffmpeg")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("split")
+                        .long("split")
+                        .value_name("DIRECTORY")
+                        .help("Also cut the recording into one file per track in DIRECTORY, without re-encoding, requires ffmpeg")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .value_name("TEMPLATE")
+                        .help("Name of the files cut by --split, with {number}, {artist}, {song}, {album}, {year} or {genre}")
+                        .takes_value(true)
+                        .default_value(songrec::tracklist::DEFAULT_SPLIT_TEMPLATE)
+                )
+                .arg(
+                    Arg::with_name("summary")
+                        .long("summary")
+                        .value_name("FILE")
+                        .help("Also write the report printed at the end, with errors by category and API calls, as JSON to FILE")
+                        .takes_value(true)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Query the songs recorded by listen --history (requires the history feature)")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List plays of recorded songs, oldest first")
+                        .arg(
+                            Arg::with_name("history-db")
+                                .long("history-db")
+                                .value_name("FILE|URL")
+                                .help("History database or postgres:// URL, instead of the one in the state directory")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("since")
+                                .long("since")
+                                .value_name("WHEN")
+                                .help("Only songs recognized since a date (2024-05-01), a time (RFC 3339) or a duration ago (30m, 12h, 7d, 2w)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("artist")
+                                .long("artist")
+                                .value_name("TEXT")
+                                .help("Only artists whose name contains TEXT")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("device")
+                                .long("device")
+                                .value_name("TEXT")
+                                .help("Only songs heard on input devices whose name contains TEXT (\"default\" for the default device)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("agent")
+                                .long("agent")
+                                .value_name("NAME")
+                                .help("Only songs reported to the hub by this agent")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("limit")
+                                .short("n")
+                                .long("limit")
+                                .value_name("COUNT")
+                                .help("Only the COUNT most recent plays")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("snippets")
+                                .long("snippets")
+                                .help("Show the audio snippets saved for each play")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("stats")
+                        .about("Most played artists and tracks of each day")
+                        .arg(
+                            Arg::with_name("history-db")
+                                .long("history-db")
+                                .value_name("FILE|URL")
+                                .help("History database or postgres:// URL, instead of the one in the state directory")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("since")
+                                .long("since")
+                                .value_name("WHEN")
+                                .help("Only songs recognized since a date (2024-05-01), a time (RFC 3339) or a duration ago (30m, 12h, 7d, 2w)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("artist")
+                                .long("artist")
+                                .value_name("TEXT")
+                                .help("Only artists whose name contains TEXT")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("device")
+                                .long("device")
+                                .value_name("TEXT")
+                                .help("Only songs heard on input devices whose name contains TEXT (\"default\" for the default device)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("agent")
+                                .long("agent")
+                                .value_name("NAME")
+                                .help("Only songs reported to the hub by this agent")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("top")
+                                .long("top")
+                                .value_name("COUNT")
+                                .help("Number of artists and tracks shown per day")
+                                .takes_value(true)
+                                .default_value("5")
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .short("f")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("Output format: simple, json (one object per day and line)")
+                                .takes_value(true)
+                                .default_value("simple")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Write plays of recorded songs to stdout, oldest first")
+                        .arg(
+                            Arg::with_name("history-db")
+                                .long("history-db")
+                                .value_name("FILE|URL")
+                                .help("History database or postgres:// URL, instead of the one in the state directory")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("since")
+                                .long("since")
+                                .value_name("WHEN")
+                                .help("Only songs recognized since a date (2024-05-01), a time (RFC 3339) or a duration ago (30m, 12h, 7d, 2w)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("artist")
+                                .long("artist")
+                                .value_name("TEXT")
+                                .help("Only artists whose name contains TEXT")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("device")
+                                .long("device")
+                                .value_name("TEXT")
+                                .help("Only songs heard on input devices whose name contains TEXT (\"default\" for the default device)")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("agent")
+                                .long("agent")
+                                .value_name("NAME")
+                                .help("Only songs reported to the hub by this agent")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .short("f")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("Output format: csv, json (one object per line, also jsonl)")
+                                .takes_value(true)
+                                .default_value("csv")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Check that no signed play was changed or deleted since it was recorded with --sign")
+                        .arg(
+                            Arg::with_name("history-db")
+                                .long("history-db")
+                                .value_name("FILE|URL")
+                                .help("History database or postgres:// URL, instead of the one in the state directory")
+                                .takes_value(true)
+                        )
+                        .arg(
+                            Arg::with_name("public-key")
+                                .long("public-key")
+                                .value_name("KEY")
+                                .help("Public key the plays were signed with, as printed by this command [default: the key of the state directory]")
+                                .takes_value(true)
+                        )
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("hub")
+                .about("Record the songs reported by listen --report-to agents into the history (requires the history feature)")
+                .arg(
+                    Arg::with_name("bind")
+                        .long("bind")
+                        .value_name("ADDRESS")
+                        .help("Address to listen on for agents, e.g. 0.0.0.0:8470 for agents on other hosts along with --token")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:8470")
+                )
+                .arg(
+                    Arg::with_name("history-db")
+                        .long("history-db")
+                        .value_name("FILE|URL")
+                        .help("History database or postgres:// URL to record into, instead of the one in the state directory")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .help("Only accept agents sending this token with --hub-token [env: SONGREC_HUB_TOKEN]")
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("sign")
+                        .long("sign")
+                        .help("Sign each recorded play with the key of the state directory, to check later with history verify")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("auth")
+                .about("Store the secrets of an integration in the keyring, so configuration files can leave them empty")
+                .arg(
+                    Arg::with_name("service")
+                        .required(true)
+                        .possible_values(&["spotify", "discord", "listenbrainz"])
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("remove")
+                        .long("remove")
+                        .help("Forget the stored secrets instead")
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .short("c")
+                        .long("config")
+                        .value_name("FILE")
+                        .help("TOML configuration file listen reads the secrets for, whose state_dir holds the secrets file")
+                        .takes_value(true)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("inspect-response")
+                .about("Describe every field of a raw API response saved with --dump-api")
+                .arg(
+                    Arg::with_name("input")
+                        .required(true)
+                        .help("JSON response file")
+                        .index(1)
+                )
         )
         .get_matches();
 
     match matches.subcommand() {
         ("recognize", Some(sub_matches)) => {
-            let input_file = sub_matches.value_of("input").unwrap();
+            let input_files: Vec<&str> = sub_matches.values_of("input").unwrap().collect();
             let format_str = sub_matches.value_of("format").unwrap();
             let verbose = sub_matches.is_present("verbose");
+            let play_preview = sub_matches.is_present("play-preview");
+            let open_page = sub_matches.is_present("open");
+            let copy = sub_matches.is_present("copy");
             
-            let format = match format_str {
-                "json" => OutputFormat::Json,
-                "csv" => OutputFormat::Csv,
-                _ => OutputFormat::Simple,
-            };
+            let format = output_format(format_str);
 
-            let config = Config::default()
+            let mut config = env_config()
                 .with_quiet_mode(!verbose); // Invert: verbose mode disables quiet
-            let songrec = SongRec::new(config);
-
-            match songrec.recognize_from_file(input_file) {
-                Ok(result) => {
-                    let output = RecognitionOutput::format_result(&result, format);
-                    println!("{}", output);
+            if let Some(dir) = sub_matches.value_of("dump-api") {
+                config = config.with_api_dump_dir(dir);
+            }
+            if let Some(language) = sub_matches.value_of("language") {
+                config = config.with_metadata_language(language);
+            }
+            if sub_matches.is_present("normalize-genres") {
+                config = config.with_genre_normalization(true);
+            }
+            if let Some(weighting) = sub_matches.value_of("weighting") {
+                config = config.with_spectral_weighting(spectral_weighting(weighting));
+            }
+            if let Some(mode) = sub_matches.value_of("channel") {
+                config = config.with_channel_mode(channel_mode(mode));
+            }
+            if sub_matches.is_present("analyze") {
+                config = track_analysis(config);
+            }
+            if let Some(durations) = sub_matches.value_of("retry") {
+                match retry_durations(durations) {
+                    Ok(durations) => config = config.with_retry_durations(durations),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    process::exit(1);
+            }
+            if let Some(shifts) = sub_matches.value_of("pitch-shifts") {
+                match pitch_shifts(shifts) {
+                    Ok(shifts) => config = config.with_pitch_shifts(shifts),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
                 }
             }
-        }
-        ("listen", Some(sub_matches)) => {
-            let device = sub_matches.value_of("device").map(|s| s.to_string());
-            let format_str = sub_matches.value_of("format").unwrap();
-            let verbose = sub_matches.is_present("verbose");
-            let no_dedupe = sub_matches.is_present("no-dedupe");
-            
-            let format = match format_str {
-                "json" => OutputFormat::Json,
-                "csv" => OutputFormat::Csv,
-                _ => OutputFormat::Simple,
+            if let Some(jobs) = sub_matches.value_of("jobs") {
+                match jobs.parse() {
+                    Ok(jobs) => config = config.with_max_concurrent_lookups(jobs),
+                    Err(_) => {
+                        eprintln!("Error: --jobs must be a positive number");
+                        process::exit(1);
+                    }
+                }
+            }
+            if let Some(delay) = sub_matches.value_of("delay") {
+                match delay.parse() {
+                    Ok(delay) => config = config.with_lookup_delay(delay),
+                    Err(_) => {
+                        eprintln!("Error: --delay must be a number of seconds");
+                        process::exit(1);
+                    }
+                }
+            }
+            let config = duration_limits(config, sub_matches).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+            let songrec = SongRec::new(config);
+
+            // Verbose output would be garbled by the progress bar
+            let progress_bar = if verbose {
+                ProgressBar::hidden()
+            } else {
+                ProgressBar::new(input_files.len() as u64)
             };
+            progress_bar.set_style(
+                ProgressStyle::with_template("[{pos}/{len}] {bar:30} {wide_msg}")
+                    .unwrap_or_else(|_| ProgressStyle::default_bar())
+            );
 
-            let config = Config::default()
-                .with_quiet_mode(!verbose) // Invert: verbose mode disables quiet
-                .with_deduplication(!no_dedupe);
-            let songrec = SongRec::new(config);
+            // Results are placed on the clipboard at the end, as printed to stdout
+            let mut copied = Vec::new();
 
-            if verbose {
-                println!("Starting continuous recognition...");
-            }
-            if format == OutputFormat::Csv {
+            if input_files.len() > 1 && format == OutputFormat::Csv {
                 println!("{}", RecognitionOutput::csv_header());
+                copied.push(RecognitionOutput::csv_header().to_string());
             }
+            let header_lines = copied.len();
 
-            match songrec.start_continuous_recognition_with_device(device) {
-                Ok(stream) => {
-                    for result in stream {
-                        match result {
-                            Ok(recognition) => {
-                                let output = RecognitionOutput::format_result(&recognition, format);
-                                println!("{}", output);
-                            }
-                            Err(e) => {
-                                if verbose {
-                                    eprintln!("Recognition error: {}", e);
+            let state = match sub_matches.value_of("state") {
+                Some(path) => BatchState::open(path),
+                None => Ok(BatchState::in_memory()),
+            };
+            let state = state.unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+
+            let dry_run = sub_matches.is_present("dry-run");
+            let ledger = match sub_matches.value_of("ledger") {
+                Some(path) => Some(ProcessedLedger::open(path)),
+                None if sub_matches.is_present("skip-known") => Some(ProcessedLedger::open_default()),
+                None => None,
+            };
+
+            let files: Vec<PathBuf> = input_files.iter().map(PathBuf::from).collect();
+            let mut job = BatchJob::new(&songrec, state).with_force(sub_matches.is_present("force"));
+            if let Some(ledger) = ledger {
+                match ledger {
+                    Ok(ledger) => job = job.with_ledger(ledger.with_identity(file_identity(sub_matches)).with_dry_run(dry_run)),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                }
+            }
+            // Without a state directory files are simply not cached, nor in a dry run
+            if !sub_matches.is_present("no-cache") && !dry_run {
+                if let Ok(cache) = ResultCache::open_default() {
+                    job = job.with_cache(cache);
+                }
+            }
+            let summary = job.run(&files, |event| match event {
+                BatchEvent::Skipped { .. } => progress_bar.inc(1),
+                BatchEvent::OutOfLimits { path, duration, .. } => {
+                    progress_bar.suspend(|| eprintln!("Skipped: {}: {:.1} s, outside the duration limits", path.display(), duration.as_secs_f32()));
+                    progress_bar.inc(1);
+                }
+                BatchEvent::Tracklist { path, outcome, .. } => {
+                    match outcome {
+                        Ok(tracklist) => {
+                            let output = format_tracklist(tracklist, format);
+                            progress_bar.suspend(|| {
+                                eprintln!("{}: {} tracks", path.display(), tracklist.entries.len());
+                                if !output.is_empty() {
+                                    println!("{}", output);
                                 }
+                            });
+                            if copy && !output.is_empty() {
+                                copied.push(output);
                             }
                         }
+                        Err(e) => progress_bar.suspend(|| eprintln!("Error: {}: {}", path.display(), e)),
                     }
+                    progress_bar.inc(1);
                 }
-                Err(e) => {
-                    if verbose {
-                        eprintln!("Error starting recognition: {}", e);
-                    }
-                    process::exit(1);
+                BatchEvent::RecordFailed { path, error, .. } => {
+                    progress_bar.suspend(|| eprintln!("Warning: {}: {}", path.display(), error));
                 }
-            }
-        }
-        ("devices", Some(_)) => {
-            match songrec::audio::AudioRecorder::list_input_devices() {
-                Ok(devices) => {
-                    println!("Available audio input devices:");
-                    for (i, device) in devices.iter().enumerate() {
-                        println!("  {}: {}", i, device);
+                BatchEvent::Progress { path, progress, .. } => {
+                    progress_bar.set_message(format!("{}: {} {:.0}%", path.display(), phase_name(progress.phase), progress.percent));
+                }
+                BatchEvent::Finished { path, outcome, .. } => {
+                    match outcome {
+                        Ok(result) => {
+                            let output = RecognitionOutput::format_result(result, format);
+                            progress_bar.suspend(|| println!("{}", output));
+                            if copy {
+                                copied.push(output.to_string());
+                            }
+
+                            if open_page {
+                                progress_bar.suspend(|| open_track_page(result));
+                            }
+
+                            if play_preview {
+                                progress_bar.suspend(|| play_preview_clip(&songrec, result));
+                            }
+                        }
+                        Err(e) if files.len() > 1 => progress_bar.suspend(|| eprintln!("Error: {}: {}", path.display(), e)),
+                        Err(e) => progress_bar.suspend(|| eprintln!("Error: {}", e)),
                     }
+                    progress_bar.inc(1);
                 }
+            });
+            progress_bar.finish_and_clear();
+
+            let summary = summary.unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+
+            if files.len() > 1 {
+                eprintln!("{}", summary);
+            }
+            if let Some(path) = sub_matches.value_of("summary") {
+                write_summary(path, &summary);
+            }
+            if copy && copied.len() > header_lines {
+                copy_to_clipboard(&copied.join("\n"));
+            }
+
+            if !summary.failures.is_empty() {
+                process::exit(1);
+            }
+        }
+        ("listen", Some(sub_matches)) | ("monitor", Some(sub_matches)) => run_listen(sub_matches),
+        ("watch", Some(sub_matches)) => {
+            let format = output_format(sub_matches.value_of("format").unwrap());
+
+            let mut config = env_config()
+                .with_quiet_mode(!sub_matches.is_present("verbose"))
+                .with_dry_run(sub_matches.is_present("dry-run"));
+            if let Some(dir) = sub_matches.value_of("dump-api") {
+                config = config.with_api_dump_dir(dir);
+            }
+            if let Some(language) = sub_matches.value_of("language") {
+                config = config.with_metadata_language(language);
+            }
+            if sub_matches.is_present("normalize-genres") || sub_matches.is_present("genre-folders") {
+                config = config.with_genre_normalization(true);
+            }
+            let config = duration_limits(config, sub_matches).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+
+            if let Err(e) = run_watch(sub_matches, SongRec::new(config), format) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("devices", Some(sub_matches)) => {
+            if let Err(e) = run_devices(sub_matches) {
+                eprintln!("Error listing devices: {}", e);
+                process::exit(1);
+            }
+        }
+        ("inspect-response", Some(sub_matches)) => {
+            let input_file = sub_matches.value_of("input").unwrap();
+
+            let response = std::fs::read_to_string(input_file)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()));
+
+            match response {
+                Ok(response) => print!("{}", songrec::debug::describe_response(&response)),
                 Err(e) => {
-                    eprintln!("Error listing devices: {}", e);
+                    eprintln!("Error reading response file '{}': {}", input_file, e);
                     process::exit(1);
                 }
             }
         }
+        ("service", Some(sub_matches)) => {
+            if let Err(e) = run_service(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("hub", Some(sub_matches)) => {
+            if let Err(e) = run_hub(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("eval", Some(sub_matches)) => {
+            if let Err(e) = run_eval(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("dedupe", Some(sub_matches)) => {
+            if let Err(e) = run_dedupe(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("tracklist", Some(sub_matches)) => {
+            if let Err(e) = run_tracklist(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("history", Some(sub_matches)) => {
+            if let Err(e) = run_history(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        ("auth", Some(sub_matches)) => {
+            if let Err(e) = run_auth(sub_matches) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
         _ => {
             // No output in quiet mode for unknown subcommands
         }
     }
 }
+
+#[cfg(feature = "watch")]
+fn run_watch(matches: &clap::ArgMatches, songrec: SongRec, format: OutputFormat) -> songrec::Result<()> {
+    use songrec::library::{LibraryOrganizer, OrganizeMode};
+    use songrec::watch::{DirectoryWatcher, WatchEvent};
+    use std::path::Path;
+
+    let directory = matches.value_of("directory").unwrap();
+    let ledger_path = matches
+        .value_of("ledger")
+        .map(Path::new)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Path::new(directory).join(".songrec-processed.jsonl"));
+
+    let dry_run = songrec.config().dry_run;
+    let ledger = ProcessedLedger::open(ledger_path)?
+        .with_identity(file_identity(matches))
+        .with_dry_run(dry_run);
+    let mut watcher = DirectoryWatcher::new(directory, songrec)
+        .with_ledger(ledger)
+        .with_force(matches.is_present("force"));
+
+    if let Some(mode) = matches.value_of("organize") {
+        let mode = match mode {
+            "move" => OrganizeMode::Move,
+            "copy" => OrganizeMode::Copy,
+            _ => OrganizeMode::Rename,
+        };
+        let organizer = LibraryOrganizer::new(matches.value_of("library").unwrap_or(directory), mode)
+            .with_tags(matches.is_present("tag"))
+            .with_genre_folders(matches.is_present("genre-folders"))
+            .with_track_numbers(matches.is_present("track-numbers"))
+            .with_dry_run(dry_run);
+        watcher = watcher.with_organizer(organizer);
+    }
+
+    let summary = Arc::new(std::sync::Mutex::new(BatchSummary::default()));
+    watcher = watcher.with_summary(Arc::clone(&summary));
+    let started = std::time::Instant::now();
+    report_on_interrupt(Arc::clone(&summary), matches.value_of("summary").map(String::from), started);
+
+    if format == OutputFormat::Csv {
+        println!("{}", RecognitionOutput::csv_header());
+    }
+
+    let outcome = watcher.run(|event| match event {
+        WatchEvent::Recognized { path, result, destination } => {
+            println!("{}", RecognitionOutput::format_result(&result, format));
+            if let Some(destination) = destination.filter(|destination| *destination != path) {
+                let prefix = if dry_run { "Dry run: would move " } else { "" };
+                eprintln!("{}{} -> {}", prefix, path.display(), destination.display());
+            }
+        }
+        WatchEvent::Tracklist { path, tracklist } => {
+            eprintln!("{}: {} tracks", path.display(), tracklist.entries.len());
+            let output = format_tracklist(&tracklist, format);
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+        }
+        WatchEvent::OutOfLimits { path, duration } => {
+            eprintln!("Skipped: {}: {:.1} s, outside the duration limits", path.display(), duration.as_secs_f32());
+        }
+        WatchEvent::Failed { path, error } => eprintln!("Error: {}: {}", path.display(), error),
+    });
+    report_watch(&summary, matches.value_of("summary"), started);
+    outcome
+}
+
+/// Print the report of a watch and write it to `path` as JSON
+#[cfg(feature = "watch")]
+fn report_watch(summary: &std::sync::Mutex<BatchSummary>, path: Option<&str>, started: std::time::Instant) {
+    let mut summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    eprintln!("{}", summary);
+    if let Some(path) = path {
+        write_summary(path, &summary);
+    }
+}
+
+/// Report the watch when interrupted with Ctrl-C or stopped, then exit
+#[cfg(all(feature = "watch", unix))]
+fn report_on_interrupt(summary: Arc<std::sync::Mutex<BatchSummary>>, path: Option<String>, started: std::time::Instant) {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+
+    let mut signals = match signal_hook::iterator::Signals::new([SIGINT, SIGTERM]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            eprintln!("Could not register the interrupt handler: {}", e);
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            report_watch(&summary, path.as_deref(), started);
+            process::exit(130);
+        }
+    });
+}
+
+/// Without signals the report is only printed when watching fails
+#[cfg(all(feature = "watch", not(unix)))]
+fn report_on_interrupt(_summary: Arc<std::sync::Mutex<BatchSummary>>, _path: Option<String>, _started: std::time::Instant) {}
+
+/// Write the report of a run as JSON
+fn write_summary(path: &str, summary: &BatchSummary) {
+    let written = serde_json::to_string_pretty(summary)
+        .map_err(|e| e.to_string())
+        .and_then(|report| std::fs::write(path, report + "\n").map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Error writing summary '{}': {}", path, e);
+    }
+}
+
+#[cfg(not(feature = "watch"))]
+fn run_watch(_matches: &clap::ArgMatches, _songrec: SongRec, _format: OutputFormat) -> songrec::Result<()> {
+    Err(songrec::SongRecError::ConfigError("Watch mode is not available: rebuild with --features watch".to_string()))
+}
+
+#[cfg(feature = "audio-devices")]
+fn run_devices(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    let devices = songrec::audio::AudioRecorder::list_input_devices()
+        .map_err(|e| songrec::SongRecError::AudioError(e.to_string()))?;
+    println!("Available audio input devices:");
+    let recorder = songrec::audio::AudioRecorder::new(Config::default().with_quiet_mode(true));
+    for (i, device) in devices.iter().enumerate() {
+        println!("  {}: {}", i, device);
+        if matches.is_present("probe") {
+            print_probe(&recorder, device);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "audio-devices"))]
+fn run_devices(_matches: &clap::ArgMatches) -> songrec::Result<()> {
+    Err(devices_unavailable())
+}
+
+#[cfg(not(feature = "audio-devices"))]
+fn devices_unavailable() -> songrec::SongRecError {
+    songrec::SongRecError::ConfigError("Audio devices are not available: rebuild with --features audio-devices".to_string())
+}
+
+/// Print what `AudioRecorder::probe` found out about a device
+#[cfg(feature = "audio-devices")]
+fn print_probe(recorder: &songrec::audio::AudioRecorder, device: &str) {
+    let report = match recorder.probe(Some(device)) {
+        Ok(report) => report,
+        Err(e) => {
+            println!("     Unusable: {}", e);
+            return;
+        }
+    };
+
+    println!("     Supports: {}", report.supported_configs.join(", "));
+    println!("     Records: {}", report.recommended_config);
+    match report.noise_floor_dbfs {
+        Some(noise_floor) => println!("     Noise floor: {:.1} dBFS, peak {:.1} dBFS", noise_floor, report.peak_dbfs),
+        None => println!("     No audio captured in {} ms, the device may be in use or stalled", report.captured_ms),
+    }
+    if report.noise_floor_dbfs.is_some() && !report.produces_audio {
+        println!("     Only silence captured, check that the device is not muted");
+    }
+}
+
+fn run_eval(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    let cases = songrec::eval::read_manifest(matches.value_of("manifest").unwrap())?;
+    let json = output_format(matches.value_of("format").unwrap()) == OutputFormat::Json;
+    let verbose = matches.is_present("verbose");
+
+    let mut configs = Vec::new();
+    match matches.values_of("config") {
+        Some(paths) => {
+            for path in paths {
+                let config = Config::from_file(path)
+                    .map_err(|e| songrec::SongRecError::ConfigError(format!("Could not read configuration '{}': {}", path, e)))?;
+                configs.push((path.to_string(), config));
+            }
+        }
+        None => configs.push(("default".to_string(), Config::default())),
+    }
+    if let Some(weightings) = matches.values_of("weighting") {
+        let weightings: Vec<&str> = weightings.collect();
+        configs = configs
+            .into_iter()
+            .flat_map(|(label, config)| {
+                weightings.iter().map(move |weighting| {
+                    (format!("{}+{}", label, weighting), config.clone().with_spectral_weighting(spectral_weighting(weighting)))
+                })
+            })
+            .collect();
+    }
+
+    let mut reports = Vec::new();
+    for (label, config) in configs {
+        let songrec = SongRec::new(config.with_quiet_mode(true));
+        let report = songrec::eval::evaluate(&songrec, &label, &cases, |outcome| {
+            if !verbose {
+                return;
+            }
+            let verdict = match (&outcome.error, &outcome.recognized) {
+                (Some(error), _) => format!("error: {}", error),
+                (None, Some(song)) if outcome.correct => format!("ok: {}", song),
+                (None, Some(song)) => format!("WRONG: {}", song),
+                (None, None) if outcome.case.expected.is_some() => "MISSED".to_string(),
+                (None, None) => "ok: no match".to_string(),
+            };
+            eprintln!("[{}] {}: {}", label, outcome.case.path.display(), verdict);
+        });
+
+        if json {
+            println!("{}", serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string()));
+        }
+        reports.push(report);
+    }
+
+    if !json {
+        println!("{:<30} {:>6} {:>9} {:>7} {:>7} {:>7} {:>7} {:>7}", "Configuration", "Files", "Precision", "Recall", "p50 ms", "p90 ms", "p99 ms", "Errors");
+        for report in &reports {
+            println!(
+                "{:<30} {:>6} {:>8.1}% {:>6.1}% {:>7} {:>7} {:>7} {:>7}",
+                report.label,
+                report.cases,
+                report.precision * 100.0,
+                report.recall * 100.0,
+                report.latency_p50_ms,
+                report.latency_p90_ms,
+                report.latency_p99_ms,
+                report.errors
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_dedupe(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    use songrec::dedupe::{audio_files, find_duplicates, FileFingerprint, DEFAULT_DUPLICATE_THRESHOLD};
+
+    let threshold: f32 = match matches.value_of("threshold") {
+        Some(threshold) => threshold
+            .parse()
+            .map_err(|_| songrec::SongRecError::InvalidInput("The threshold must be a number from 0 to 1".to_string()))?,
+        None => DEFAULT_DUPLICATE_THRESHOLD,
+    };
+    let json = output_format(matches.value_of("format").unwrap()) == OutputFormat::Json;
+
+    let files = audio_files(matches.value_of("directory").unwrap());
+    let progress_bar = ProgressBar::new(files.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::with_template("[{pos}/{len}] {bar:30} {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+    );
+
+    let mut fingerprints = Vec::new();
+    for path in &files {
+        progress_bar.set_message(path.display().to_string());
+        match FileFingerprint::from_file(path) {
+            Ok(fingerprint) => fingerprints.push(fingerprint),
+            Err(e) => progress_bar.println(format!("Skipped {}: {}", path.display(), e)),
+        }
+        progress_bar.inc(1);
+    }
+    progress_bar.finish_and_clear();
+
+    let groups = find_duplicates(&fingerprints, threshold);
+    for group in &groups {
+        if json {
+            println!("{}", serde_json::to_string(group).unwrap_or_else(|_| "{}".to_string()));
+            continue;
+        }
+        println!("keep    {}", group.keeper.display());
+        for file in group.redundant() {
+            println!("  dup   {} ({:.0}%, {:.1} MB)", file.path.display(), file.similarity * 100.0, file.size as f32 / 1_000_000.0);
+        }
+    }
+    if !json {
+        let redundant: usize = groups.iter().map(|group| group.files.len() - 1).sum();
+        eprintln!("{} files scanned, {} duplicate groups, {} redundant files", files.len(), groups.len(), redundant);
+    }
+    Ok(())
+}
+
+fn run_tracklist(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    use songrec::ChapterFormat;
+
+    let input_file = matches.value_of("input").unwrap();
+    let interval: f32 = matches
+        .value_of("interval")
+        .unwrap()
+        .parse()
+        .ok()
+        .filter(|&interval: &f32| interval > 0.0)
+        .ok_or_else(|| songrec::SongRecError::InvalidInput("--interval must be a positive number of seconds".to_string()))?;
+    let started_at = matches.value_of("start").map(parse_start_time).transpose()?;
+
+    let config = Config::default()
+        .with_quiet_mode(true)
+        .with_boundary_refinement(!matches.is_present("no-refine"));
+    let songrec = SongRec::new(config);
+    let started = std::time::Instant::now();
+    let progress_bar = ProgressBar::new(100);
+    progress_bar.set_style(
+        ProgressStyle::with_template("{bar:30} {percent:>3}% {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+    );
+    let tracklist = songrec.recognize_tracklist_with_progress(input_file, std::time::Duration::from_secs_f32(interval), |progress| {
+        progress_bar.set_message(phase_name(progress.phase));
+        progress_bar.set_position(progress.percent as u64);
+    });
+    progress_bar.finish_and_clear();
+
+    // Reported like a batch of one file, a recording without any track being no match
+    let mut summary = BatchSummary::default();
+    match &tracklist {
+        Ok(tracklist) if tracklist.entries.is_empty() => summary.add(Path::new(input_file), Some(&songrec::SongRecError::NoMatch(None))),
+        Ok(tracklist) => {
+            eprintln!("{} tracks in {}", tracklist.entries.len(), songrec::tracklist::format_position(tracklist.duration_ms));
+            summary.add(Path::new(input_file), None);
+        }
+        Err(e) => summary.add(Path::new(input_file), Some(e)),
+    }
+    summary.api_calls = songrec.lookup_count();
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    eprintln!("{}", summary);
+    if let Some(path) = matches.value_of("summary") {
+        write_summary(path, &summary);
+    }
+    let mut tracklist = tracklist?;
+    if let Some(started_at) = started_at {
+        tracklist = tracklist.with_started_at(started_at);
+    }
+
+    match matches.value_of("format").unwrap() {
+        "cue" => print!("{}", tracklist.export(ChapterFormat::Cue)),
+        "mkv-chapters" => print!("{}", tracklist.export(ChapterFormat::MatroskaXml)),
+        "ffmetadata" => print!("{}", tracklist.export(ChapterFormat::FfMetadata)),
+        "json" | "jsonl" => println!("{}", serde_json::to_string(&tracklist).unwrap_or_else(|_| "{}".to_string())),
+        _ => print!("{}", tracklist.to_text()),
+    }
+    let dry_run = matches.is_present("dry-run");
+    if let Some(output) = matches.value_of("embed") {
+        if dry_run {
+            eprintln!("Dry run: would write {} with {} chapters", output, tracklist.entries.len());
+        } else {
+            tracklist.embed_chapters(output)?;
+            eprintln!("{} chapters written to {}", tracklist.entries.len(), output);
+        }
+    }
+    if let Some(directory) = matches.value_of("split") {
+        let template = matches.value_of("name").unwrap();
+        if dry_run {
+            for (index, entry) in tracklist.entries.iter().enumerate() {
+                let piece = Path::new(directory).join(tracklist.piece_name(index, template));
+                let start_ms = if index == 0 { 0 } else { entry.start_ms };
+                eprintln!("Dry run: would write {} from {}", piece.display(), songrec::tracklist::format_position(start_ms));
+            }
+        } else {
+            let pieces = tracklist.split(directory, template)?;
+            eprintln!("{} tracks written to {}", pieces.len(), directory);
+        }
+    }
+    Ok(())
+}
+
+/// Start of a recording: an RFC 3339 time or a local "YYYY-MM-DD HH:MM:SS"
+fn parse_start_time(value: &str) -> songrec::Result<chrono::DateTime<chrono::Utc>> {
+    use chrono::TimeZone;
+
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|time| chrono::Local.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .ok_or_else(|| songrec::SongRecError::InvalidInput(format!("Invalid --start value '{}'", value)))
+}
+
+/// Prompt for the secrets of an integration and store them, or remove them
+fn run_auth(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    use songrec::secrets::{DISCORD_WEBHOOK, LISTENBRAINZ_TOKEN, SPOTIFY_CLIENT_SECRET, SPOTIFY_REFRESH_TOKEN};
+
+    let secrets: &[(&str, &str)] = match matches.value_of("service").unwrap() {
+        "spotify" => &[(SPOTIFY_CLIENT_SECRET, "Spotify client secret"), (SPOTIFY_REFRESH_TOKEN, "Spotify refresh token")],
+        "discord" => &[(DISCORD_WEBHOOK, "Discord webhook URL")],
+        _ => &[(LISTENBRAINZ_TOKEN, "ListenBrainz user token")],
+    };
+    // The state directory of the configuration listen runs with, as set there or by SONGREC_STATE_DIR
+    let config = match matches.value_of("config") {
+        Some(path) => Config::from_file(path)
+            .map_err(|e| songrec::SongRecError::ConfigError(format!("Could not read configuration '{}': {}", path, e)))?,
+        None => Config::default(),
+    };
+    let config = config
+        .with_env_overrides()
+        .map_err(|e| songrec::SongRecError::ConfigError(format!("Invalid SONGREC_* environment variable: {}", e)))?;
+    let store = SecretStore::for_config(&config);
+
+    if matches.is_present("remove") {
+        for (name, _) in secrets {
+            store.remove(name)?;
+        }
+        println!("Secrets removed");
+        return Ok(());
+    }
+
+    for (name, description) in secrets {
+        let secret = rpassword::prompt_password(format!("{}: ", description))
+            .map_err(|e| songrec::SongRecError::InvalidInput(format!("Could not read the secret: {}", e)))?;
+        let secret = secret.trim();
+        if secret.is_empty() {
+            return Err(songrec::SongRecError::InvalidInput(format!("No {} given", description)));
+        }
+        match store.set(name, secret)? {
+            SecretLocation::Keyring => println!("{} stored in the keyring", description),
+            SecretLocation::File(path) => println!("{} stored in {} (no keyring available)", description, path.display()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "history")]
+fn run_history(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    use songrec::history::HistoryEntry;
+
+    let (command, sub_matches) = match matches.subcommand() {
+        (command, Some(sub_matches)) => (command, sub_matches),
+        _ => return Err(songrec::SongRecError::InvalidInput("Expected list, stats, export or verify".to_string())),
+    };
+
+    let history = open_history(sub_matches)?;
+    let mut filter = songrec::history::HistoryFilter::default();
+    if let Some(since) = sub_matches.value_of("since") {
+        filter = filter.with_since(parse_since(since)?);
+    }
+    if let Some(artist) = sub_matches.value_of("artist") {
+        filter = filter.with_artist(artist);
+    }
+    if let Some(device) = sub_matches.value_of("device") {
+        filter = filter.with_device(device);
+    }
+    if let Some(agent) = sub_matches.value_of("agent") {
+        filter = filter.with_agent(agent);
+    }
+    if let Some(limit) = sub_matches.value_of("limit") {
+        filter = filter.with_limit(parse_count(limit)?);
+    }
+
+    match command {
+        "list" => {
+            for entry in history.list(&filter)? {
+                let agent = entry.agent.as_ref().map(|agent| format!(" from {}", agent)).unwrap_or_default();
+                println!(
+                    "{}-{}  {} - {}  ({}x, {}{})",
+                    entry.first_seen.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    entry.last_seen.with_timezone(&chrono::Local).format("%H:%M"),
+                    entry.artist_name,
+                    entry.song_name,
+                    entry.detections,
+                    entry.source,
+                    agent
+                );
+                if sub_matches.is_present("snippets") {
+                    for snippet in history.snippets(entry.id)? {
+                        println!("    {}", snippet.path.display());
+                    }
+                }
+            }
+        }
+        "stats" => {
+            let top = parse_count(sub_matches.value_of("top").unwrap())?;
+            let json = output_format(sub_matches.value_of("format").unwrap()) == OutputFormat::Json;
+            for day in history.daily_stats(&filter, top)? {
+                if json {
+                    println!("{}", serde_json::to_string(&day).unwrap_or_else(|_| "{}".to_string()));
+                    continue;
+                }
+
+                let counts = |counts: &[songrec::history::PlayCount]| -> String {
+                    counts.iter().map(|count| format!("{} ({})", count.name, count.plays)).collect::<Vec<_>>().join(", ")
+                };
+                println!("{}  {} plays", day.day, day.plays);
+                println!("  Top artists: {}", counts(&day.top_artists));
+                println!("  Top tracks:  {}", counts(&day.top_tracks));
+            }
+        }
+        "export" => {
+            let csv = match output_format(sub_matches.value_of("format").unwrap()) {
+                OutputFormat::Csv => true,
+                OutputFormat::Json => false,
+                _ => return Err(songrec::SongRecError::InvalidInput("Export formats are csv and json".to_string())),
+            };
+            if csv {
+                println!("{}", HistoryEntry::csv_header());
+            }
+            for entry in history.list(&filter)? {
+                if csv {
+                    println!("{}", entry.to_csv());
+                } else {
+                    println!("{}", serde_json::to_string(&entry).unwrap_or_else(|_| "{}".to_string()));
+                }
+            }
+        }
+        "verify" => {
+            let key = match sub_matches.value_of("public-key") {
+                Some(key) => songrec::history::VerifyingKey::from_base64(key)?,
+                None => songrec::history::SigningKey::load_default()?.verifying_key(),
+            };
+            let verification = history.verify(&key)?;
+            let ids = |ids: &[i64]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+
+            println!("Public key: {}", key);
+            println!("{} plays verified", verification.verified);
+            if !verification.unsigned.is_empty() {
+                println!("Unsigned: {}", ids(&verification.unsigned));
+            }
+            if !verification.invalid.is_empty() {
+                println!("Changed or unsigned since signing began: {}", ids(&verification.invalid));
+            }
+            if !verification.missing.is_empty() {
+                println!("Deleted: {}", ids(&verification.missing));
+            }
+            if !verification.is_intact() {
+                return Err(songrec::SongRecError::InvalidInput("The history does not match its signatures".to_string()));
+            }
+        }
+        _ => return Err(songrec::SongRecError::InvalidInput("Expected list, stats, export or verify".to_string())),
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "history"))]
+fn run_history(_matches: &clap::ArgMatches) -> songrec::Result<()> {
+    Err(songrec::SongRecError::ConfigError("History is not available: rebuild with --features history".to_string()))
+}
+
+/// Record the results of agents until the listener fails
+#[cfg(feature = "history")]
+fn run_hub(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    let address = matches.value_of("bind").unwrap();
+    let mut history = open_history(matches)?;
+    if matches.is_present("sign") {
+        history = history.with_signing_key(songrec::history::SigningKey::load_or_create_default()?);
+    }
+    let mut hub = songrec::hub::HubServer::bind(address, history)?;
+    match matches.value_of("token").map(String::from).or_else(|| std::env::var("SONGREC_HUB_TOKEN").ok()) {
+        Some(token) => hub = hub.with_token(&token),
+        None => eprintln!("Warning: no --token, any client reaching {} can record songs", address),
+    }
+
+    eprintln!("Waiting for agents on {}", hub.local_addr()?);
+    hub.serve()
+}
+
+#[cfg(not(feature = "history"))]
+fn run_hub(_matches: &clap::ArgMatches) -> songrec::Result<()> {
+    Err(songrec::SongRecError::ConfigError("The hub records into the history: rebuild with --features history".to_string()))
+}
+
+/// The `--history-db` database, or the default one
+#[cfg(feature = "history")]
+fn open_history(matches: &clap::ArgMatches) -> songrec::Result<songrec::history::History> {
+    match matches.value_of("history-db") {
+        Some(location) => songrec::history::History::open_location(location),
+        None => songrec::history::History::open_default(),
+    }
+}
+
+/// Outputs of listen and monitor, fed by the pipeline: the results or events on stdout, then
+/// the `--output` file, the history, the `--report-to` hub and the message brokers
+fn listen_sinks(matches: &clap::ArgMatches, station_db: Option<&Path>, format: OutputFormat) -> songrec::Result<Vec<Box<dyn Sink>>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if matches.is_present("events") {
+        sinks.push(Box::new(EventLineSink));
+    } else {
+        sinks.push(Box::new(StdoutSink::new(format)));
+    }
+    if let Some(path) = matches.value_of("output") {
+        let sink = FileSink::open(path, format)
+            .map_err(|e| songrec::SongRecError::ConfigError(format!("Could not open '{}': {}", path, e)))?;
+        sinks.push(Box::new(sink));
+    }
+    if let Some(history) = open_listen_history(matches, station_db)? {
+        sinks.push(history);
+    }
+    if let Some(reporter) = open_reporter(matches)? {
+        sinks.push(Box::new(reporter.into_sink()));
+    }
+    sinks.extend(publisher_sinks(matches)?);
+    Ok(sinks)
+}
+
+/// Prints every pipeline event as a JSON line, for `--events`
+struct EventLineSink;
+
+impl Sink for EventLineSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn std::error::Error>> {
+        println!("{}", serde_json::to_string(event)?);
+        Ok(())
+    }
+
+    fn is_dry_run_safe(&self) -> bool {
+        true
+    }
+}
+
+/// History of listen and monitor, none unless `--history`, `--history-db`, `--snippets`,
+/// `--sign` or the station's `history_db` is given
+#[cfg(feature = "history")]
+fn open_listen_history(matches: &clap::ArgMatches, station_db: Option<&Path>) -> songrec::Result<Option<Box<dyn Sink>>> {
+    let enabled = matches.is_present("history") || matches.is_present("history-db") || matches.is_present("snippets") || matches.is_present("sign");
+    let history = match (matches.value_of("history-db"), station_db) {
+        (None, Some(path)) => songrec::history::History::open_location(&path.to_string_lossy())?,
+        _ if enabled => open_history(matches)?,
+        _ => return Ok(None),
+    };
+
+    let history = if matches.is_present("sign") {
+        history.with_signing_key(songrec::history::SigningKey::load_or_create_default()?)
+    } else {
+        history
+    };
+    let history = match matches.value_of("snippets") {
+        Some(dir) => {
+            let mut retention = songrec::history::SnippetRetention::default();
+            if let Some(days) = matches.value_of("snippet-days") {
+                retention = retention.with_max_age(Duration::from_secs(parse_count(days)? as u64 * 24 * 60 * 60));
+            }
+            if let Some(count) = matches.value_of("max-snippets") {
+                retention = retention.with_max_count(parse_count(count)?);
+            }
+            history.with_snippet_dir(dir).with_snippet_retention(retention)
+        }
+        None => history,
+    };
+    Ok(Some(Box::new(history)))
+}
+
+#[cfg(not(feature = "history"))]
+fn open_listen_history(matches: &clap::ArgMatches, station_db: Option<&Path>) -> songrec::Result<Option<Box<dyn Sink>>> {
+    if matches.is_present("history") || matches.is_present("history-db") || matches.is_present("snippets") || matches.is_present("sign") || station_db.is_some() {
+        return Err(songrec::SongRecError::ConfigError("History is not available: rebuild with --features history".to_string()));
+    }
+    Ok(None)
+}
+
+/// The `--report-to` hub, with an outbox in the state directory for the songs it misses
+fn open_reporter(matches: &clap::ArgMatches) -> songrec::Result<Option<songrec::hub::HubReporter>> {
+    let Some(url) = matches.value_of("report-to") else {
+        return Ok(None);
+    };
+    let agent = match matches.value_of("agent") {
+        Some(agent) => agent.to_string(),
+        None => host_name().ok_or_else(|| songrec::SongRecError::ConfigError("No host name found, name this agent with --agent".to_string()))?,
+    };
+
+    let mut reporter = songrec::hub::HubReporter::new(url, &agent)?;
+    if let Some(token) = matches.value_of("hub-token").map(String::from).or_else(|| std::env::var("SONGREC_HUB_TOKEN").ok()) {
+        reporter = reporter.with_token(&token);
+    }
+    let state_dir = songrec::state::StateDir::platform_default()
+        .ok_or_else(|| songrec::SongRecError::ConfigError("No state directory available for the hub outbox".to_string()))?;
+    Ok(Some(reporter.with_outbox_in(&state_dir)?))
+}
+
+/// The `--kafka` and `--nats` publishers
+fn publisher_sinks(matches: &clap::ArgMatches) -> songrec::Result<Vec<Box<dyn Sink>>> {
+    let mut sinks = Vec::new();
+    if let Some(brokers) = matches.value_of("kafka") {
+        sinks.push(kafka_sink(matches, brokers)?);
+    }
+    if let Some(url) = matches.value_of("nats") {
+        sinks.push(nats_sink(matches, url)?);
+    }
+    Ok(sinks)
+}
+
+#[cfg(feature = "kafka")]
+fn kafka_sink(matches: &clap::ArgMatches, brokers: &str) -> songrec::Result<Box<dyn Sink>> {
+    let topic = matches.value_of("kafka-topic").unwrap();
+    let sink = songrec::publish::KafkaSink::connect(brokers, topic)
+        .map_err(|e| songrec::SongRecError::NetworkError(format!("Could not connect to Kafka {}: {}", brokers, e)))?;
+    Ok(Box::new(sink.with_encoding(publish_encoding(matches))))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka_sink(_matches: &clap::ArgMatches, _brokers: &str) -> songrec::Result<Box<dyn Sink>> {
+    Err(songrec::SongRecError::ConfigError("Kafka publishing is not available: rebuild with --features kafka".to_string()))
+}
+
+#[cfg(feature = "nats")]
+fn nats_sink(matches: &clap::ArgMatches, url: &str) -> songrec::Result<Box<dyn Sink>> {
+    let subject = matches.value_of("nats-subject").unwrap();
+    let sink = songrec::publish::NatsSink::connect(url, subject)
+        .map_err(|e| songrec::SongRecError::NetworkError(format!("Could not connect to NATS {}: {}", url, e)))?;
+    Ok(Box::new(sink.with_encoding(publish_encoding(matches))))
+}
+
+#[cfg(not(feature = "nats"))]
+fn nats_sink(_matches: &clap::ArgMatches, _url: &str) -> songrec::Result<Box<dyn Sink>> {
+    Err(songrec::SongRecError::ConfigError("NATS publishing is not available: rebuild with --features nats".to_string()))
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+fn publish_encoding(matches: &clap::ArgMatches) -> songrec::codec::EventEncoding {
+    match matches.value_of("publish-encoding") {
+        Some("msgpack") => songrec::codec::EventEncoding::MessagePack,
+        _ => songrec::codec::EventEncoding::Json,
+    }
+}
+
+/// Name of this machine, the default agent name
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = process::Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Start of a `--since` range: a date, an RFC 3339 time or a duration ago such as 12h or 7d
+#[cfg(feature = "history")]
+fn parse_since(value: &str) -> songrec::Result<chrono::DateTime<chrono::Utc>> {
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        return Ok(chrono::Local.from_local_datetime(&midnight).earliest().map_or_else(
+            || Utc.from_utc_datetime(&midnight),
+            |time| time.with_timezone(&Utc),
+        ));
+    }
+
+    let invalid = || songrec::SongRecError::InvalidInput(format!("Invalid --since value '{}'", value));
+    let split = value.len().checked_sub(1).filter(|&split| value.is_char_boundary(split)).ok_or_else(invalid)?;
+    let amount: i64 = value[..split].parse().map_err(|_| invalid())?;
+    let ago = match &value[split..] {
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        "w" => Duration::weeks(amount),
+        _ => return Err(invalid()),
+    };
+    Ok(Utc::now() - ago)
+}
+
+#[cfg(feature = "history")]
+fn parse_count(value: &str) -> songrec::Result<usize> {
+    value
+        .parse()
+        .map_err(|_| songrec::SongRecError::InvalidInput(format!("Invalid count '{}'", value)))
+}
+
+fn run_service(matches: &clap::ArgMatches) -> songrec::Result<()> {
+    let listen_arguments = |matches: &clap::ArgMatches| -> Vec<String> {
+        matches.values_of("args").map(|values| values.map(String::from).collect()).unwrap_or_default()
+    };
+
+    match matches.subcommand() {
+        ("install", Some(sub_matches)) => {
+            let executable = std::env::current_exe()
+                .map_err(|e| songrec::SongRecError::ConfigError(format!("Cannot locate the executable: {}", e)))?;
+            let service = ServiceDefinition::new(executable, listen_arguments(sub_matches));
+
+            if sub_matches.is_present("print") {
+                if cfg!(target_os = "macos") {
+                    print!("{}", service.launchd_plist(&songrec::service::launchd_log_path()?));
+                } else {
+                    print!("{}", service.systemd_unit());
+                }
+                return Ok(());
+            }
+
+            match service.install()? {
+                Some(path) => eprintln!("Installed and started {}", path.display()),
+                None => eprintln!("Installed and started the {} service", songrec::service::SERVICE_NAME),
+            }
+            Ok(())
+        }
+        ("uninstall", Some(_)) => songrec::service::uninstall(),
+        ("run", Some(sub_matches)) => run_listen_service(listen_arguments(sub_matches)),
+        _ => Err(songrec::SongRecError::InvalidInput("Expected install, uninstall or run".to_string())),
+    }
+}
+
+/// Command running listen mode in a child process, so a service can stop it at any time
+fn listen_command(arguments: &[String]) -> std::io::Result<process::Command> {
+    let mut command = process::Command::new(std::env::current_exe()?);
+    command.arg("listen").args(arguments);
+    Ok(command)
+}
+
+#[cfg(not(windows))]
+fn run_listen_service(arguments: Vec<String>) -> songrec::Result<()> {
+    let status = listen_command(&arguments)
+        .and_then(|mut command| command.spawn())
+        .and_then(|mut child| child.wait())
+        .map_err(|e| songrec::SongRecError::ConfigError(format!("Could not run listen mode: {}", e)))?;
+    process::exit(status.code().unwrap_or(1));
+}
+
+#[cfg(windows)]
+fn run_listen_service(arguments: Vec<String>) -> songrec::Result<()> {
+    windows_service_main::run(arguments)
+}
+
+/// Entry point of the Windows service, reporting the state of listen mode to the service manager
+#[cfg(windows)]
+mod windows_service_main {
+    use std::ffi::OsString;
+    use std::fs::OpenOptions;
+    use std::process::Child;
+    use std::sync::{mpsc, OnceLock};
+    use std::time::Duration;
+
+    use songrec::service::{windows_log_path, SERVICE_NAME};
+    use songrec::SongRecError;
+    use windows_service::service::{ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType};
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+
+    static LISTEN_ARGUMENTS: OnceLock<Vec<String>> = OnceLock::new();
+
+    windows_service::define_windows_service!(ffi_service_main, service_main);
+
+    pub fn run(arguments: Vec<String>) -> songrec::Result<()> {
+        let _ = LISTEN_ARGUMENTS.set(arguments);
+        windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not start the service dispatcher: {}", e)))
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let handler = move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                let _ = stop_tx.send(());
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+            Ok(status_handle) => status_handle,
+            Err(_) => return,
+        };
+        let set_state = |current_state, controls_accepted, exit_code| {
+            let _ = status_handle.set_service_status(ServiceStatus {
+                service_type: ServiceType::OWN_PROCESS,
+                current_state,
+                controls_accepted,
+                exit_code: ServiceExitCode::Win32(exit_code),
+                checkpoint: 0,
+                wait_hint: Duration::default(),
+                process_id: None,
+            });
+        };
+
+        let mut child = match spawn_listen(LISTEN_ARGUMENTS.get().map(Vec::as_slice).unwrap_or_default()) {
+            Ok(child) => child,
+            Err(_) => return set_state(ServiceState::Stopped, ServiceControlAccept::empty(), 1),
+        };
+        set_state(ServiceState::Running, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN, 0);
+
+        // Stop when asked to, or when listen mode exits by itself
+        let exit_code = loop {
+            if stop_rx.recv_timeout(Duration::from_millis(500)).is_ok() {
+                let _ = child.kill();
+                let _ = child.wait();
+                break 0;
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                break if status.success() { 0 } else { 1 };
+            }
+        };
+        set_state(ServiceState::Stopped, ServiceControlAccept::empty(), exit_code);
+    }
+
+    /// Start listen mode with its output appended to the log of the service, services have no console
+    fn spawn_listen(arguments: &[String]) -> std::io::Result<Child> {
+        let log_path = windows_log_path();
+        if let Some(parent) = log_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let mut command = super::listen_command(arguments)?;
+        command.stdout(log.try_clone()?).stderr(log);
+        command.spawn()
+    }
+}
+
+fn file_identity(matches: &clap::ArgMatches) -> FileIdentity {
+    if matches.is_present("by-content") {
+        FileIdentity::Content
+    } else {
+        FileIdentity::Path
+    }
+}
+
+fn spectral_weighting(name: &str) -> SpectralWeighting {
+    match name {
+        "pre-emphasis" => SpectralWeighting::PreEmphasis,
+        "a-weighting" => SpectralWeighting::AWeighting,
+        _ => SpectralWeighting::None,
+    }
+}
+
+fn channel_mode(name: &str) -> ChannelMode {
+    match name {
+        "left" => ChannelMode::Left,
+        "right" => ChannelMode::Right,
+        "side" => ChannelMode::Side,
+        _ => ChannelMode::Mid,
+    }
+}
+
+/// Default configuration overridden by the `SONGREC_*` environment variables
+fn env_config() -> Config {
+    Config::from_env().unwrap_or_else(|e| {
+        eprintln!("Error: Invalid SONGREC_* environment variable: {}", e);
+        process::exit(1);
+    })
+}
+
+/// Machine-readable formats write nothing but results to stdout, messages go to stderr
+fn output_format(name: &str) -> OutputFormat {
+    match name {
+        "json" | "jsonl" => OutputFormat::Json,
+        "csv" => OutputFormat::Csv,
+        _ => OutputFormat::Simple,
+    }
+}
+
+fn phase_name(phase: RecognitionPhase) -> &'static str {
+    match phase {
+        RecognitionPhase::Decode => "decoding",
+        RecognitionPhase::Fingerprint => "fingerprinting",
+        RecognitionPhase::Lookup => "looking up",
+    }
+}
+
+/// Tracks of a long file recognized as a tracklist in batch and watch modes, in the output format of results
+fn format_tracklist(tracklist: &songrec::Tracklist, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string(tracklist).unwrap_or_else(|_| "{}".to_string()),
+        OutputFormat::Simple => tracklist.to_text().trim_end().to_string(),
+        _ => tracklist
+            .entries
+            .iter()
+            .map(|entry| RecognitionOutput::format_result(&entry.result, format).to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Options of the file duration limits, shared by the recognize and watch subcommands
+fn duration_limit_args<'a, 'b>(command: App<'a, 'b>) -> App<'a, 'b> {
+    command
+        .arg(
+            Arg::with_name("min-duration")
+                .long("min-duration")
+                .value_name("SECONDS")
+                .help("Skip files shorter than SECONDS, e.g. ringtones and notification sounds")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("max-duration")
+                .long("max-duration")
+                .value_name("SECONDS")
+                .help("Skip files longer than SECONDS, or recognize them as tracklists with --long-files tracklist")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("long-files")
+                .long("long-files")
+                .value_name("ACTION")
+                .help("What to do with files longer than --max-duration: skip them or recognize every track of them")
+                .takes_value(true)
+                .possible_values(&["skip", "tracklist"])
+                .requires("max-duration")
+        )
+}
+
+/// Apply `--min-duration`, `--max-duration` and `--long-files`
+fn duration_limits(config: Config, sub_matches: &ArgMatches) -> Result<Config, String> {
+    let seconds = |name: &str| {
+        sub_matches
+            .value_of(name)
+            .map(|value| match value.parse::<f32>() {
+                Ok(seconds) if seconds > 0.0 => Ok(seconds),
+                _ => Err(format!("--{} must be a positive number of seconds", name)),
+            })
+            .transpose()
+    };
+    let long_files = match sub_matches.value_of("long-files") {
+        Some("tracklist") => LongFileAction::Tracklist,
+        _ => LongFileAction::Skip,
+    };
+    Ok(config
+        .with_file_duration_limits(seconds("min-duration")?, seconds("max-duration")?)
+        .with_long_files(long_files))
+}
+
+/// Options shared by the continuous recognition subcommands, listen and monitor
+fn continuous_args<'a, 'b>(command: App<'a, 'b>) -> App<'a, 'b> {
+    command
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Settings of a [profile.NAME] table of the configuration file, merged over the others")
+                .takes_value(true)
+                .requires("config")
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format: simple, json (one object per line, also jsonl), csv")
+                .takes_value(true)
+                .default_value("simple")
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Suppress verbose debug output (default)")
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Enable verbose debug output")
+        )
+        .arg(
+            Arg::with_name("dump-api")
+                .long("dump-api")
+                .value_name("DIR")
+                .help("Write raw API requests and responses to DIR for troubleshooting")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("language")
+                .long("language")
+                .visible_alias("lang")
+                .value_name("TAG")
+                .help("Language of the song metadata and of the album and release year picked from it, e.g. de or de-DE")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("normalize-genres")
+                .long("normalize-genres")
+                .help("Add the genre mapped onto a small vocabulary, e.g. Hip-Hop for Hip-Hop/Rap, to JSON output as genre_normalized")
+        )
+        .arg(
+            Arg::with_name("weighting")
+                .long("weighting")
+                .value_name("CURVE")
+                .help("Experimental spectral weighting for noisy recordings: none, pre-emphasis, a-weighting")
+                .takes_value(true)
+                .possible_values(&["none", "pre-emphasis", "a-weighting"])
+        )
+        .arg(
+            Arg::with_name("channel")
+                .long("channel")
+                .value_name("MODE")
+                .help("Channel of stereo audio to fingerprint: mid (both), left, right or side (without the center)")
+                .takes_value(true)
+                .possible_values(&["mid", "left", "right", "side"])
+        )
+        .arg(
+            Arg::with_name("analyze")
+                .long("analyze")
+                .help("Estimate the tempo and key of recognized songs (requires the analysis feature)")
+        )
+        .arg(
+            Arg::with_name("music-only")
+                .long("music-only")
+                .help("Only submit audio that sounds like music, skipping speech and silence, e.g. on talk radio")
+        )
+        .arg(
+            Arg::with_name("low-resource")
+                .long("low-resource")
+                .help("Use little CPU and memory, e.g. on a Raspberry Pi Zero: one window every 30 seconds, one lookup at a time, no retries")
+        )
+        .arg(
+            Arg::with_name("window")
+                .long("window")
+                .value_name("SECONDS")
+                .help("Length of the windows fingerprinted, 12 by default; shorter ones use less memory but match less often")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .value_name("SECONDS")
+                .help("Lengths of the recent audio looked up in turn when a window does not match, comma-separated, or none [default: 18,24]")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("pitch-shifts")
+                .long("pitch-shifts")
+                .value_name("PERCENTS")
+                .help("Also match audio sped up or slowed down by these percentages, comma-separated, e.g. 4,8 for pitched vinyl or nightcore")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("no-dedupe")
+                .long("no-dedupe")
+                .help("Disable request deduplication")
+        )
+        .arg(
+            Arg::with_name("open")
+                .long("open")
+                .help("Open the page of each new song in the default browser")
+        )
+        .arg(
+            Arg::with_name("events")
+                .long("events")
+                .help("Write every state change as a JSON line (listening, buffering, request-sent, match, no-match, error, device-lost) instead of results only")
+        )
+        .arg(
+            Arg::with_name("history")
+                .long("history")
+                .help("Record recognized songs in the history database (requires the history feature)")
+        )
+        .arg(
+            Arg::with_name("history-db")
+                .long("history-db")
+                .value_name("FILE|URL")
+                .help("History database or postgres:// URL to record into, instead of the one in the state directory")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("snippets")
+                .long("snippets")
+                .value_name("DIR")
+                .help("Save the audio behind each recorded match to DIR as FLAC, implies --history")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("snippet-days")
+                .long("snippet-days")
+                .value_name("DAYS")
+                .help("Delete snippets older than DAYS")
+                .takes_value(true)
+                .requires("snippets")
+        )
+        .arg(
+            Arg::with_name("max-snippets")
+                .long("max-snippets")
+                .value_name("COUNT")
+                .help("Keep only the COUNT most recent snippets")
+                .takes_value(true)
+                .requires("snippets")
+        )
+        .arg(
+            Arg::with_name("sign")
+                .long("sign")
+                .help("Sign each recorded play with the key of the state directory, to check later with history verify; implies --history")
+        )
+        .arg(
+            Arg::with_name("report-to")
+                .long("report-to")
+                .value_name("URL")
+                .help("Also send recognized songs to the hub at URL (see the hub subcommand), keeping them in the state directory while it is down")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("agent")
+                .long("agent")
+                .value_name("NAME")
+                .help("Name the hub records the songs of this agent under [default: host name]")
+                .takes_value(true)
+                .requires("report-to")
+        )
+        .arg(
+            Arg::with_name("hub-token")
+                .long("hub-token")
+                .value_name("TOKEN")
+                .help("Token the hub was started with [env: SONGREC_HUB_TOKEN]")
+                .takes_value(true)
+                .requires("report-to")
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Also append recognized songs to FILE, in the --format")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("kafka")
+                .long("kafka")
+                .value_name("BROKERS")
+                .help("Publish every event to the Kafka brokers, e.g. kafka1:9092,kafka2:9092 (requires the kafka feature)")
+                .takes_value(true)
+                .requires("kafka-topic")
+        )
+        .arg(
+            Arg::with_name("kafka-topic")
+                .long("kafka-topic")
+                .value_name("TOPIC")
+                .help("Kafka topic to publish to, which must exist")
+                .takes_value(true)
+                .requires("kafka")
+        )
+        .arg(
+            Arg::with_name("nats")
+                .long("nats")
+                .value_name("URL")
+                .help("Publish every event to the NATS server at URL, e.g. nats://token@nats.local:4222 (requires the nats feature)")
+                .takes_value(true)
+                .requires("nats-subject")
+        )
+        .arg(
+            Arg::with_name("nats-subject")
+                .long("nats-subject")
+                .value_name("SUBJECT")
+                .help("NATS subject to publish to")
+                .takes_value(true)
+                .requires("nats")
+        )
+        .arg(
+            Arg::with_name("publish-encoding")
+                .long("publish-encoding")
+                .value_name("ENCODING")
+                .help("Encoding of the events published to Kafka and NATS [default: json]")
+                .takes_value(true)
+                .possible_values(&["json", "msgpack"])
+        )
+}
+
+/// Where listen and monitor take their audio from
+enum AudioInput {
+    Device(Option<String>),
+    Stream(String),
+}
+
+impl AudioInput {
+    fn start(&self, songrec: &SongRec, sinks: Vec<Box<dyn Sink>>) -> songrec::Result<RecognitionStream> {
+        match self {
+            #[cfg(feature = "audio-devices")]
+            AudioInput::Device(device) => songrec.start_continuous_recognition_with_sinks(device.clone(), sinks),
+            #[cfg(not(feature = "audio-devices"))]
+            AudioInput::Device(_) => Err(devices_unavailable()),
+            AudioInput::Stream(url) => songrec.start_stream_recognition_with_sinks(url, sinks),
+        }
+    }
+}
+
+#[cfg(feature = "audio-devices")]
+fn system_audio_source() -> songrec::Result<String> {
+    songrec::audio::monitor::default_monitor_source().map_err(|e| songrec::SongRecError::AudioError(e.to_string()))
+}
+
+#[cfg(not(feature = "audio-devices"))]
+fn system_audio_source() -> songrec::Result<String> {
+    Err(devices_unavailable())
+}
+
+#[cfg(feature = "audio-devices")]
+fn recognize_device(songrec: &SongRec, device: Option<String>) -> songrec::Result<songrec::RecognitionResult> {
+    songrec.recognize_from_device(device)
+}
+
+#[cfg(not(feature = "audio-devices"))]
+fn recognize_device(_songrec: &SongRec, _device: Option<String>) -> songrec::Result<songrec::RecognitionResult> {
+    Err(devices_unavailable())
+}
+
+/// Continuous recognition of listen and monitor, until capture stops
+fn run_listen(sub_matches: &ArgMatches) {
+    let verbose = sub_matches.is_present("verbose");
+    let open_page = sub_matches.is_present("open");
+    let format = output_format(sub_matches.value_of("format").unwrap());
+
+    let mut config = match listen_config(sub_matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(attempts) = sub_matches.value_of("attempts") {
+        match attempts.parse() {
+            Ok(attempts) if attempts > 0 => config = config.with_record_attempts(attempts),
+            _ => {
+                eprintln!("Error: --attempts must be a positive number");
+                process::exit(1);
+            }
+        }
+    }
+
+    let station = sub_matches.value_of("station").and_then(|name| config.stations.get(name));
+    let input = match station {
+        Some(station) => match &station.stream_url {
+            Some(url) => AudioInput::Stream(url.clone()),
+            None => AudioInput::Device(station.device.clone()),
+        },
+        None if sub_matches.is_present("system-audio") => match system_audio_source() {
+            Ok(source) => {
+                if verbose {
+                    eprintln!("Recording system audio from {}", source);
+                }
+                AudioInput::Device(Some(source))
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        None => AudioInput::Device(sub_matches.value_of("device").map(String::from).or_else(|| config.device.clone())),
+    };
+    #[cfg(not(feature = "audio-devices"))]
+    if let AudioInput::Device(_) = &input {
+        eprintln!("Error: {}", devices_unavailable());
+        process::exit(1);
+    }
+    // The ALSA plugin of PulseAudio records the source named in the environment, which is
+    // only safe to change while this is the only thread: before the sinks and SongRec start theirs
+    #[cfg(all(target_os = "linux", feature = "audio-devices"))]
+    if let AudioInput::Device(Some(device)) = &input {
+        if songrec::audio::monitor::is_monitor_source(device) {
+            std::env::set_var(songrec::audio::monitor::PULSE_SOURCE, device);
+        }
+    }
+    let station_history = station.and_then(|station| station.history_db.as_deref());
+
+    let sinks = match listen_sinks(sub_matches, station_history, format) {
+        Ok(sinks) => sinks,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let songrec = SongRec::new(config);
+    let reload = ReloadSignal::new(sub_matches.is_present("config"));
+
+    if format == OutputFormat::Csv && !sub_matches.is_present("events") {
+        println!("{}", RecognitionOutput::csv_header());
+    }
+    if sub_matches.is_present("once") {
+        run_once(&songrec, input, sinks, open_page);
+        return;
+    }
+    if verbose {
+        eprintln!("Starting continuous recognition...");
+    }
+
+    let stream = match input.start(&songrec, sinks) {
+        Ok(stream) => stream,
+        Err(e) => {
+            if verbose {
+                eprintln!("Error starting recognition: {}", e);
+            }
+            process::exit(1);
+        }
+    };
+
+    // Pages are only opened when the song changes, not for every match of the same song
+    let mut last_opened: Option<String> = None;
+
+    // The sinks print and record the results, they are only taken here to open their pages
+    loop {
+        if reload.take() {
+            reload_config(&stream, sub_matches);
+        }
+
+        let result = match stream.next_timeout(RELOAD_CHECK) {
+            Some(result) => result,
+            None if stream.is_alive() || stream.pending_len() > 0 => continue,
+            None => break,
+        };
+        match result {
+            Ok(recognition) => {
+                if open_page && last_opened.as_deref() != Some(recognition.track_key.as_str()) {
+                    open_track_page(&recognition);
+                    last_opened = Some(recognition.track_key.clone());
+                }
+            }
+            Err(e) => {
+                if verbose {
+                    eprintln!("Recognition error: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Recognize a single window of a device, exiting with an error without a match
+fn run_once(songrec: &SongRec, input: AudioInput, mut sinks: Vec<Box<dyn Sink>>, open_page: bool) {
+    let device = match input {
+        AudioInput::Device(device) => device,
+        AudioInput::Stream(_) => {
+            eprintln!("Error: --once records from a device, not from a stream");
+            process::exit(1);
+        }
+    };
+
+    match recognize_device(songrec, device) {
+        Ok(recognition) => {
+            // Handed to the sinks like the pipeline does, skipping those with side effects in a dry run
+            let dry_run = songrec.config().dry_run;
+            let event = PipelineEvent::Match { result: Box::new(recognition.clone()) };
+            for sink in sinks.iter_mut().filter(|sink| !dry_run || sink.is_dry_run_safe()) {
+                if let Err(e) = sink.emit(&event) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            if open_page {
+                open_track_page(&recognition);
+            }
+        }
+        Err(e) => {
+            match &e {
+                songrec::SongRecError::NoMatch(Some(diagnostics)) if diagnostics.attempts > 1 => {
+                    eprintln!("Error: {} ({} windows recorded)", e, diagnostics.attempts);
+                }
+                _ => eprintln!("Error: {}", e),
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Configuration of listen and monitor: the `--config` file if any, overridden by the
+/// `SONGREC_*` environment variables, with the settings of the `--profile` then the
+/// `--station` applied, overridden by the command line flags
+fn listen_config(sub_matches: &ArgMatches) -> Result<Config, String> {
+    let mut config = match sub_matches.value_of("config") {
+        Some(path) => Config::from_file(path).map_err(|e| format!("Could not read configuration '{}': {}", path, e))?,
+        None => Config::default(),
+    };
+    config = config.with_env_overrides().map_err(|e| format!("Invalid SONGREC_* environment variable: {}", e))?;
+    if let Some(name) = sub_matches.value_of("profile") {
+        config = config.profile_config(name).map_err(|e| e.to_string())?;
+    }
+    if let Some(name) = sub_matches.value_of("station") {
+        config = config
+            .station_config(name)
+            .ok_or_else(|| format!("No station '{}' in the configuration, expected a [stations.{}] table", name, name))?;
+    }
+    config = SecretStore::for_config(&config).apply(config).map_err(|e| e.to_string())?;
+    if sub_matches.is_present("dry-run") {
+        config = config.with_dry_run(true);
+    }
+
+    if sub_matches.is_present("verbose") {
+        config = config.with_quiet_mode(false);
+    } else if sub_matches.is_present("quiet") {
+        config = config.with_quiet_mode(true);
+    }
+    if sub_matches.is_present("no-dedupe") {
+        config = config.with_deduplication(false);
+    }
+    if let Some(dir) = sub_matches.value_of("dump-api") {
+        config = config.with_api_dump_dir(dir);
+    }
+    if let Some(language) = sub_matches.value_of("language") {
+        config = config.with_metadata_language(language);
+    }
+    if sub_matches.is_present("normalize-genres") {
+        config = config.with_genre_normalization(true);
+    }
+    if let Some(weighting) = sub_matches.value_of("weighting") {
+        config = config.with_spectral_weighting(spectral_weighting(weighting));
+    }
+    if let Some(mode) = sub_matches.value_of("channel") {
+        config = config.with_channel_mode(channel_mode(mode));
+    }
+    if sub_matches.is_present("snippets") {
+        config = config.with_audio_snippets(true);
+    }
+    if sub_matches.is_present("analyze") {
+        config = track_analysis(config);
+    }
+    if sub_matches.is_present("music-only") {
+        config = config.with_music_only(true);
+    }
+    if sub_matches.is_present("low-resource") {
+        config = config.with_low_resource_mode();
+    }
+    if let Some(window) = sub_matches.value_of("window") {
+        match window.parse::<f32>() {
+            Ok(window) if window >= 3.0 => config = config.with_window_duration(window),
+            _ => return Err(format!("Invalid --window value '{}', expected at least 3 seconds", window)),
+        }
+    }
+    if let Some(durations) = sub_matches.value_of("retry") {
+        config = config.with_retry_durations(retry_durations(durations)?);
+    }
+    if let Some(shifts) = sub_matches.value_of("pitch-shifts") {
+        config = config.with_pitch_shifts(pitch_shifts(shifts)?);
+    }
+    if let Some(client) = sub_matches.value_of("jack") {
+        config = config.with_jack_client(client);
+    }
+    Ok(config)
+}
+
+/// Parse `--retry`: seconds separated by commas, or none
+fn retry_durations(value: &str) -> Result<Vec<f32>, String> {
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+    value
+        .split(',')
+        .map(|seconds| seconds.trim().parse().map_err(|_| format!("Invalid --retry value '{}', expected seconds such as 18,24 or none", value)))
+        .collect()
+}
+
+/// Parse `--pitch-shifts`: percentages separated by commas
+fn pitch_shifts(value: &str) -> Result<Vec<f32>, String> {
+    value
+        .split(',')
+        .map(|percent| match percent.trim().trim_end_matches('%').parse::<f32>() {
+            Ok(percent) if percent > 0.0 && percent < 50.0 => Ok(percent),
+            _ => Err(format!("Invalid --pitch-shifts value '{}', expected percentages below 50 such as 4,8", value)),
+        })
+        .collect()
+}
+
+/// Re-read the `--config` file and apply it to the running stream, keeping the current settings on error
+fn reload_config(stream: &RecognitionStream, sub_matches: &ArgMatches) {
+    match listen_config(sub_matches) {
+        Ok(config) => {
+            if !config.quiet_mode {
+                eprintln!("Configuration reloaded");
+            }
+            stream.reload(config);
+        }
+        Err(e) => eprintln!("{}, keeping the previous configuration", e),
+    }
+}
+
+/// How often the listen loops check for a reload request when nothing arrives
+const RELOAD_CHECK: Duration = Duration::from_millis(500);
+
+/// Set when SIGHUP is received, only registered when a configuration file is given
+struct ReloadSignal(Arc<AtomicBool>);
+
+impl ReloadSignal {
+    fn new(enabled: bool) -> Self {
+        let flag = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        if enabled {
+            if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&flag)) {
+                eprintln!("Could not register the SIGHUP handler: {}", e);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = enabled;
+        Self(flag)
+    }
+
+    /// Whether a reload was requested since the last call
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
+
+fn open_track_page(result: &songrec::RecognitionResult) {
+    match result.track_url() {
+        Some(url) => {
+            if let Err(e) = open::that(url) {
+                eprintln!("Could not open {}: {}", url, e);
+            }
+        }
+        None => eprintln!("No page available for {} - {}", result.artist_name, result.song_name),
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) {
+    // On Linux the clipboard belongs to the copying program and empties when it exits,
+    // so the results are served until another program takes the clipboard over
+    #[cfg(target_os = "linux")]
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| {
+        use arboard::SetExtLinux;
+        eprintln!("Results copied, keeping them on the clipboard until something else is copied (Ctrl+C to quit)");
+        clipboard.set().wait().text(text)
+    });
+    #[cfg(not(target_os = "linux"))]
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    if let Err(e) = copied {
+        eprintln!("Could not copy to the clipboard: {}", e);
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: &str) {
+    eprintln!("Clipboard copy is not available: rebuild with --features clipboard");
+}
+
+#[cfg(feature = "analysis")]
+fn track_analysis(config: Config) -> Config {
+    config.with_track_analysis(true)
+}
+
+#[cfg(not(feature = "analysis"))]
+fn track_analysis(config: Config) -> Config {
+    eprintln!("Track analysis is not available: rebuild with --features analysis");
+    config
+}
+
+#[cfg(feature = "playback")]
+fn play_preview_clip(songrec: &SongRec, result: &songrec::RecognitionResult) {
+    if let Err(e) = songrec.play_preview(result) {
+        eprintln!("Could not play preview: {}", e);
+    }
+}
+
+#[cfg(not(feature = "playback"))]
+fn play_preview_clip(_songrec: &SongRec, _result: &songrec::RecognitionResult) {
+    eprintln!("Preview playback is not available: rebuild with --features playback");
+}
@@ -0,0 +1,183 @@
+//! Results of the files recognized by previous runs, so recognizing them again is instant
+//!
+//! Unlike the ledger, which only tells which files were processed, the cache keeps the
+//! whole result of each file, keyed by its path and the SHA-256 of its content, along
+//! with the settings it was recognized with.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::ledger::{file_hash, file_stamp};
+use crate::songrec::RecognitionResult;
+use crate::state::StateDir;
+use crate::{Result, SongRecError};
+
+const CACHE_FILE_NAME: &str = "results.jsonl";
+
+/// Result of one recognized file, stored as a JSON line in the cache file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Canonical path of the file when it was recognized
+    pub path: PathBuf,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Modification time in seconds since the Unix epoch
+    pub modified: u64,
+
+    /// SHA-256 of the content, a file changed since it was recognized is recognized again
+    pub content_hash: String,
+
+    /// Hash of the settings changing results, see `ResultCache::with_config`
+    #[serde(default)]
+    pub config_hash: String,
+
+    pub result: RecognitionResult,
+
+    pub cached_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Persistent results of recognized files, used by `BatchJob::with_cache`
+///
+/// Only matches are cached: a file without a match is looked up again, in case the
+/// catalog or the configuration changed. Entries are appended as they are stored,
+/// the last entry of a path wins.
+pub struct ResultCache {
+    path: PathBuf,
+    entries: HashMap<PathBuf, CacheEntry>,
+    config_hash: String,
+}
+
+impl ResultCache {
+    /// Load the cache at `path`, a missing file is an empty cache
+    ///
+    /// Unreadable lines (e.g. a line cut short by a crash) are ignored.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let mut entries = HashMap::new();
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                for entry in content.lines().filter_map(|line| serde_json::from_str::<CacheEntry>(line).ok()) {
+                    entries.insert(entry.path.clone(), entry);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SongRecError::ConfigError(format!("Could not read result cache '{}': {}", path.display(), e))),
+        }
+
+        Ok(Self { path, entries, config_hash: config_hash(&Config::default()) })
+    }
+
+    /// Only return results recognized with the settings of `config` that change them,
+    /// such as the metadata language or the spectral weighting, the default configuration otherwise
+    ///
+    /// Done by `BatchJob::with_cache` with the configuration of its `SongRec`.
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.config_hash = config_hash(config);
+        self
+    }
+
+    /// Load the cache shared by all runs, in the state directory
+    pub fn open_default() -> Result<Self> {
+        Self::open_in(&StateDir::require_default("result cache")?)
+    }
+
+    /// Load the cache kept in a state directory, e.g. `StateDir::for_config`
+    pub fn open_in(dir: &StateDir) -> Result<Self> {
+        Self::open(dir.file(CACHE_FILE_NAME))
+    }
+
+    /// Cached result of a file, None if it was not recognized, with other settings,
+    /// or its content changed since
+    ///
+    /// A file with the size and modification time it had when cached is not read again;
+    /// otherwise its content is hashed, so a file only touched still hits the cache.
+    pub fn get(&self, file: &Path) -> Option<&RecognitionResult> {
+        let entry = self.entries.get(&canonical(file)).filter(|entry| entry.config_hash == self.config_hash)?;
+        let stamp = file_stamp(file)?;
+        if (entry.size, entry.modified) == stamp || file_hash(file)? == entry.content_hash {
+            Some(&entry.result)
+        } else {
+            None
+        }
+    }
+
+    /// Number of files in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Store the result of a file
+    pub fn store(&mut self, file: &Path, result: &RecognitionResult) -> Result<()> {
+        let cannot_read = || SongRecError::InvalidInput(format!("Cannot read '{}'", file.display()));
+        let (size, modified) = file_stamp(file).ok_or_else(cannot_read)?;
+        let content_hash = file_hash(file).ok_or_else(cannot_read)?;
+
+        let entry = CacheEntry {
+            path: canonical(file),
+            size,
+            modified,
+            content_hash,
+            config_hash: self.config_hash.clone(),
+            result: result.clone(),
+            cached_at: chrono::Utc::now(),
+        };
+
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not serialize cache entry: {}", e)))?;
+        self.append(&line)?;
+
+        self.entries.insert(entry.path.clone(), entry);
+        Ok(())
+    }
+
+    fn append(&self, line: &str) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SongRecError::ConfigError(format!("Could not create '{}': {}", parent.display(), e)))?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut cache| writeln!(cache, "{}", line))
+            .map_err(|e| SongRecError::ConfigError(format!("Could not write result cache '{}': {}", self.path.display(), e)))
+    }
+}
+
+/// Path a file is cached under, the same whatever the working directory or links used to reach it
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+/// SHA-256 of the settings changing the result of a recognition
+fn config_hash(config: &Config) -> String {
+    let settings = serde_json::json!([
+        config.sensitivity,
+        config.min_audio_duration,
+        config.max_audio_duration,
+        config.spectral_weighting,
+        config.channel_mode,
+        config.analyze_tracks,
+        config.music_only,
+        config.retry_durations,
+        config.pitch_shifts,
+        config.metadata_language,
+        config.normalize_genres,
+        config.genre_mapping,
+    ]);
+    Sha256::digest(settings.to_string().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,249 @@
+//! Compact binary encodings of results and signatures, for queueing them through Kafka,
+//! Redis and the like without the size and parse cost of JSON
+//!
+//! MessagePack is always available; bincode, smaller and faster but only readable by
+//! Rust programs using the same layout, needs the `bincode` feature.
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::songrec::{PipelineEvent, RecognitionResult};
+use crate::{Result, SongRecError};
+
+impl RecognitionResult {
+    /// Encode as MessagePack, fields keyed by name like the JSON output
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(self).map_err(encode_error)
+    }
+
+    pub fn from_msgpack(data: &[u8]) -> Result<Self> {
+        rmp_serde::from_slice(data).map_err(decode_error)
+    }
+
+    /// Encode with bincode
+    ///
+    /// The raw response is stored as JSON text, as bincode cannot hold JSON values.
+    #[cfg(feature = "bincode")]
+    pub fn to_bincode(&self) -> Result<Vec<u8>> {
+        let raw_response = serde_json::to_string(&self.raw_response).map_err(encode_error)?;
+        bincode::serialize(&bincode_layout::Result::new(self, raw_response)).map_err(encode_error)
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn from_bincode(data: &[u8]) -> Result<Self> {
+        bincode::deserialize::<bincode_layout::Result>(data)
+            .map_err(decode_error)?
+            .into_result()
+    }
+}
+
+impl DecodedSignature {
+    /// Encode as MessagePack, holding the binary signature
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(self).map_err(encode_error)
+    }
+
+    pub fn from_msgpack(data: &[u8]) -> Result<Self> {
+        rmp_serde::from_slice(data).map_err(decode_error)
+    }
+
+    /// Encode with bincode, holding the binary signature
+    #[cfg(feature = "bincode")]
+    pub fn to_bincode(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self).map_err(encode_error)
+    }
+
+    #[cfg(feature = "bincode")]
+    pub fn from_bincode(data: &[u8]) -> Result<Self> {
+        bincode::deserialize(data).map_err(decode_error)
+    }
+}
+
+impl PipelineEvent {
+    /// Encode as MessagePack, tagged with `event` like the JSON output
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(self).map_err(encode_error)
+    }
+
+    pub fn from_msgpack(data: &[u8]) -> Result<Self> {
+        rmp_serde::from_slice(data).map_err(decode_error)
+    }
+}
+
+/// Encoding of the events published to message brokers, see the `publish` module
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventEncoding {
+    /// The JSON of `PipelineEvent`, e.g. `{"event":"match","result":{...}}`
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl EventEncoding {
+    pub fn encode(&self, event: &PipelineEvent) -> Result<Vec<u8>> {
+        match self {
+            EventEncoding::Json => serde_json::to_vec(event).map_err(encode_error),
+            EventEncoding::MessagePack => event.to_msgpack(),
+        }
+    }
+}
+
+/// The binary signature in binary formats, the data URI in human-readable ones such as JSON
+impl Serialize for DecodedSignature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.encode_to_uri().map_err(serde::ser::Error::custom)?)
+        } else {
+            serializer.serialize_bytes(&self.encode_to_binary().map_err(serde::ser::Error::custom)?)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DecodedSignature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SignatureVisitor)
+        } else {
+            deserializer.deserialize_bytes(SignatureVisitor)
+        }
+    }
+}
+
+struct SignatureVisitor;
+
+impl<'de> Visitor<'de> for SignatureVisitor {
+    type Value = DecodedSignature;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a binary Shazam signature or its data URI")
+    }
+
+    fn visit_str<E: de::Error>(self, uri: &str) -> std::result::Result<Self::Value, E> {
+        DecodedSignature::decode_from_uri(uri).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, data: &[u8]) -> std::result::Result<Self::Value, E> {
+        DecodedSignature::decode_from_binary(data).map_err(E::custom)
+    }
+
+    /// Formats without a byte type hold the signature as a sequence of numbers
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(crate::fingerprinting::signature_format::MAX_SIGNATURE_SIZE));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            data.push(byte);
+        }
+        self.visit_bytes(&data)
+    }
+}
+
+fn encode_error(error: impl fmt::Display) -> SongRecError {
+    SongRecError::Internal(format!("Could not encode: {}", error))
+}
+
+fn decode_error(error: impl fmt::Display) -> SongRecError {
+    SongRecError::InvalidInput(format!("Could not decode: {}", error))
+}
+
+/// Layout of results in bincode, which needs every field in order and no self-describing
+/// values: fields skipped when empty, the JSON raw response and the tagged source are not
+#[cfg(feature = "bincode")]
+mod bincode_layout {
+    use serde::{Deserialize, Serialize};
+
+    use crate::analysis::TrackAnalysis;
+    use crate::audio::loudness::AudioStats;
+    use crate::credits::ArtistCredit;
+    use crate::songrec::{RecognitionResult, RecognitionSource, RecognitionTimings};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Result {
+        song_name: String,
+        artist_name: String,
+        artists: Vec<ArtistCredit>,
+        album_name: Option<String>,
+        track_key: String,
+        release_year: Option<String>,
+        genre: Option<String>,
+        genre_normalized: Option<String>,
+        track_number: Option<u32>,
+        disc_number: Option<u32>,
+        recognition_timestamp: chrono::DateTime<chrono::Utc>,
+        raw_response: String,
+        timings: RecognitionTimings,
+        source: Source,
+        analysis: Option<TrackAnalysis>,
+        audio_stats: Option<AudioStats>,
+        record_attempts: Option<u32>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Source {
+        File { path: String },
+        Device { name: Option<String> },
+        Stream { url: String },
+        Bytes,
+        Unknown,
+    }
+
+    impl Result {
+        pub(super) fn new(result: &RecognitionResult, raw_response: String) -> Self {
+            Self {
+                song_name: result.song_name.clone(),
+                artist_name: result.artist_name.clone(),
+                artists: result.artists.clone(),
+                album_name: result.album_name.clone(),
+                track_key: result.track_key.clone(),
+                release_year: result.release_year.clone(),
+                genre: result.genre.clone(),
+                genre_normalized: result.genre_normalized.clone(),
+                track_number: result.track_number,
+                disc_number: result.disc_number,
+                recognition_timestamp: result.recognition_timestamp,
+                raw_response,
+                timings: result.timings,
+                source: match result.source.clone() {
+                    RecognitionSource::File { path } => Source::File { path },
+                    RecognitionSource::Device { name } => Source::Device { name },
+                    RecognitionSource::Stream { url } => Source::Stream { url },
+                    RecognitionSource::Bytes => Source::Bytes,
+                    RecognitionSource::Unknown => Source::Unknown,
+                },
+                analysis: result.analysis,
+                audio_stats: result.audio_stats,
+                record_attempts: result.record_attempts,
+            }
+        }
+
+        pub(super) fn into_result(self) -> crate::Result<RecognitionResult> {
+            Ok(RecognitionResult {
+                song_name: self.song_name,
+                artist_name: self.artist_name,
+                artists: self.artists,
+                album_name: self.album_name,
+                track_key: self.track_key,
+                release_year: self.release_year,
+                genre: self.genre,
+                genre_normalized: self.genre_normalized,
+                track_number: self.track_number,
+                disc_number: self.disc_number,
+                recognition_timestamp: self.recognition_timestamp,
+                raw_response: serde_json::from_str(&self.raw_response).map_err(super::decode_error)?,
+                timings: self.timings,
+                source: match self.source {
+                    Source::File { path } => RecognitionSource::File { path },
+                    Source::Device { name } => RecognitionSource::Device { name },
+                    Source::Stream { url } => RecognitionSource::Stream { url },
+                    Source::Bytes => RecognitionSource::Bytes,
+                    Source::Unknown => RecognitionSource::Unknown,
+                },
+                audio_snippet: None,
+                analysis: self.analysis,
+                audio_stats: self.audio_stats,
+                record_attempts: self.record_attempts,
+            })
+        }
+    }
+}
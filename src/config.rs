@@ -1,7 +1,16 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
+use crate::logging::{LogCallback, LogLevel};
+
+/// Prefix of the environment variables overriding settings, see `Config::with_env_overrides`
+pub const ENV_PREFIX: &str = "SONGREC_";
+
 /// Configuration for SongRec
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Recognition sensitivity (0.0 to 1.0)
     pub sensitivity: f32,
@@ -9,11 +18,24 @@ pub struct Config {
     /// Timeout for network requests in seconds
     pub network_timeout: u64,
     
+    /// Maximum number of idle connections kept open to the API
+    pub pool_max_idle_per_host: usize,
+    
+    /// Time in seconds after which an idle pooled connection is closed
+    pub pool_idle_timeout: u64,
+    
+    /// TCP keepalive interval in seconds for pooled connections
+    pub tcp_keepalive: u64,
+    
     /// Minimum duration of audio to analyze (in seconds)
     pub min_audio_duration: f32,
     
     /// Maximum duration of audio to analyze (in seconds)  
     pub max_audio_duration: f32,
+
+    /// Length of the windows fingerprinted in continuous mode (in seconds); windows shorter
+    /// than 3 seconds are too sparse to be looked up and are fingerprinted as 3 seconds
+    pub window_duration: f32,
     
     /// Sample rate for audio processing
     pub sample_rate: u32,
@@ -24,9 +46,26 @@ pub struct Config {
     /// Whether to enable continuous recognition
     pub continuous_recognition: bool,
     
-    /// Interval between recognition attempts in continuous mode (seconds)
+    /// Interval between recognition attempts in continuous mode (seconds); the audio between
+    /// two windows is not fingerprinted when this is longer than `window_duration`
     pub recognition_interval: f32,
     
+    /// Number of captured sample batches buffered before new ones are dropped
+    pub sample_queue_size: usize,
+    
+    /// Number of worker threads sending signatures to the API in continuous mode
+    pub recognition_workers: usize,
+    
+    /// Number of signatures waiting for a free worker before new ones are skipped
+    pub recognition_queue_size: usize,
+    
+    /// Maximum number of API lookups in flight at once, unlimited if unset; batch
+    /// recognition also recognizes this many files at once, one if unset
+    pub max_concurrent_lookups: Option<usize>,
+    
+    /// Minimum delay between the start of two API lookups (seconds), not counting retries
+    pub lookup_delay: f32,
+    
     /// Whether to suppress verbose debug output
     pub quiet_mode: bool,
     
@@ -35,6 +74,235 @@ pub struct Config {
     
     /// Time in seconds to remember signatures for deduplication
     pub deduplication_cache_duration: u64,
+
+    /// Installation UUID sent with requests, overriding the persisted one
+    pub install_id: Option<String>,
+
+    /// Directory where raw API requests and responses are written for troubleshooting
+    pub api_dump_dir: Option<PathBuf>,
+
+    /// Directory for state kept between runs, the platform one if None, see `StateDir`
+    pub state_dir: Option<PathBuf>,
+
+    /// Spectral weighting applied before peak detection, an experiment for noisy recordings
+    pub spectral_weighting: SpectralWeighting,
+
+    /// Channel of stereo audio that is fingerprinted, both averaged by default
+    pub channel_mode: ChannelMode,
+
+    /// Attach the audio each match was made from to results, see `RecognitionResult::audio_snippet`
+    pub keep_audio_snippets: bool,
+
+    /// Estimate the tempo and key of matches, see `RecognitionResult::analysis` (requires the analysis feature)
+    pub analyze_tracks: bool,
+
+    /// Only submit windows classified as music in continuous mode, skipping speech and silence
+    pub music_only: bool,
+
+    /// Look up extra windows around track changes in tracklist mode, to place them within a few seconds
+    pub refine_tracklist_boundaries: bool,
+
+    /// Longer samples, in seconds, looked up in turn when a 12-second window does not match
+    pub retry_durations: Vec<f32>,
+
+    /// Speed changes, in percent both ways, looked up when nothing else matched, none by default
+    pub pitch_shifts: Vec<f32>,
+
+    /// Windows `SongRec::recognize_from_device` records at most, unusable ones being recorded again
+    pub record_attempts: u32,
+
+    /// Files shorter than this, in seconds, are skipped in batch and watch modes, e.g. ringtones
+    pub min_file_duration: Option<f32>,
+
+    /// Files longer than this, in seconds, are skipped in batch and watch modes or recognized as tracklists, see `long_files`
+    pub max_file_duration: Option<f32>,
+
+    /// What batch and watch modes do with files longer than `max_file_duration`
+    pub long_files: LongFileAction,
+
+    /// Language of titles and section text in API responses, as a tag like "de-DE"
+    pub metadata_language: String,
+
+    /// Set `RecognitionResult::genre_normalized` from the genre of the API, see `crate::genre`
+    pub normalize_genres: bool,
+
+    /// Genres of the API and the name they are normalized to, before the built-in rules, e.g. `[genre_mapping]`
+    pub genre_mapping: BTreeMap<String, String>,
+
+    /// Log what the integrations and sinks would do instead of doing it, recognition still runs
+    pub dry_run: bool,
+
+    /// Receives the diagnostics otherwise printed on stderr, see `Config::with_log_callback`
+    #[serde(skip)]
+    pub log_callback: Option<LogCallback>,
+
+    /// Audio input device of continuous recognition when none is given, the default device if None
+    pub device: Option<String>,
+
+    /// Record from the input ports of a JACK client with this name instead of an audio device (requires the jack feature)
+    ///
+    /// The ports are left unconnected, to be routed from any application with a patchbay.
+    /// PipeWire provides JACK too, through pipewire-jack.
+    pub jack_client: Option<String>,
+
+    /// Spotify playlist to append recognized songs to in continuous mode (requires the spotify feature)
+    pub spotify: Option<SpotifyConfig>,
+
+    /// Discord webhook updated with the currently playing song in continuous mode (requires the discord feature)
+    pub discord: Option<DiscordConfig>,
+
+    /// OSC destination notified of track changes in continuous mode (requires the osc feature)
+    pub osc: Option<OscConfig>,
+
+    /// ListenBrainz account the tracks heard long enough in continuous mode are submitted to,
+    /// as set when the recognition starts
+    pub listenbrainz: Option<ListenBrainzConfig>,
+
+    /// Named station profiles, e.g. `[stations.bbc6]`, see `Config::station_config`
+    pub stations: BTreeMap<String, StationConfig>,
+
+    /// Named presets of any of these settings, e.g. `[profile.quiet-mic]`, see `Config::profile_config`
+    #[serde(rename = "profile")]
+    pub profiles: BTreeMap<String, toml::value::Table>,
+}
+
+/// Weighting of the spectrum before peak detection, see `Config::with_spectral_weighting`
+///
+/// Phone speakers and crowded rooms bury the melody under bass and rumble, de-emphasizing
+/// low frequencies can leave more usable peaks. Gains are 1 at 1 kHz.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpectralWeighting {
+    /// Spectrum used as is, as by the official clients
+    #[default]
+    None,
+    /// First-order pre-emphasis filter, `y[n] = x[n] - 0.97 x[n-1]`
+    PreEmphasis,
+    /// A-weighting curve (IEC 61672), close to the sensitivity of the human ear
+    AWeighting,
+}
+
+/// Channel of stereo audio that is fingerprinted, see `Config::with_channel_mode`
+///
+/// Karaoke tracks and some DJ feeds carry the vocals and the music on different
+/// channels, which the average of both can blur. Mono audio is used as is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelMode {
+    /// Average of all channels
+    #[default]
+    Mid,
+    Left,
+    Right,
+    /// Half the difference of left and right, which cancels what is centered such as lead vocals
+    Side,
+}
+
+impl ChannelMode {
+    /// Mono sample of an interleaved frame, converted to f32 by `to_f32`
+    pub(crate) fn mix<T: Copy>(self, frame: &[T], to_f32: impl Fn(T) -> f32) -> f32 {
+        let (first, second) = match frame {
+            [] => return 0.0,
+            [mono] => return to_f32(*mono),
+            [first, second, ..] => (to_f32(*first), to_f32(*second)),
+        };
+        match self {
+            ChannelMode::Mid => frame.iter().map(|&sample| to_f32(sample)).sum::<f32>() / frame.len() as f32,
+            ChannelMode::Left => first,
+            ChannelMode::Right => second,
+            ChannelMode::Side => (first - second) / 2.0,
+        }
+    }
+}
+
+/// What batch and watch modes do with files longer than `Config::max_file_duration`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LongFileAction {
+    /// Leave them out, e.g. hours of ambience recordings
+    #[default]
+    Skip,
+    /// Recognize every track of them, as `SongRec::recognize_tracklist` does for DJ mixes
+    Tracklist,
+}
+
+/// Audio source and settings of a monitored radio station
+///
+/// Unset settings fall back to the ones of the enclosing `Config`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StationConfig {
+    /// MP3 stream of the station (Icecast/Shoutcast), used instead of `device` when set
+    pub stream_url: Option<String>,
+
+    /// Audio input device receiving the station, the default one if neither this nor `stream_url` is set
+    pub device: Option<String>,
+
+    /// Language of the song metadata, as a tag like "de-DE"
+    pub metadata_language: Option<String>,
+
+    /// History database the plays of this station are recorded in (requires the history feature)
+    pub history_db: Option<PathBuf>,
+
+    pub spotify: Option<SpotifyConfig>,
+
+    pub discord: Option<DiscordConfig>,
+
+    pub osc: Option<OscConfig>,
+}
+
+/// OAuth credentials and target playlist for the Spotify integration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotifyConfig {
+    /// Spotify application client ID
+    pub client_id: String,
+
+    /// Spotify application client secret, read from the `SecretStore` if empty
+    #[serde(default)]
+    pub client_secret: String,
+
+    /// Refresh token of a user who granted the playlist-modify scopes, read from the `SecretStore` if empty
+    #[serde(default)]
+    pub refresh_token: String,
+
+    /// ID of the playlist recognized songs are appended to
+    pub playlist_id: String,
+}
+
+/// Discord webhook used to publish the currently playing song
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscordConfig {
+    /// Webhook URL as given by the Discord channel integration settings, read from the `SecretStore` if empty
+    #[serde(default)]
+    pub webhook_url: String,
+
+    /// Seconds without a new match after which the "now playing" message is removed
+    pub stale_after: u64,
+}
+
+/// ListenBrainz account of the scrobbling integration, see `scrobble::ListenBrainzSink`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListenBrainzConfig {
+    /// User token, read from the `SecretStore` if empty
+    #[serde(default)]
+    pub token: String,
+
+    /// Server implementing the ListenBrainz API, the official one if None
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// OSC destination for track change messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OscConfig {
+    /// Host of the OSC receiver (lighting console, VJ software)
+    pub host: String,
+
+    /// UDP port of the OSC receiver
+    pub port: u16,
+
+    /// OSC address pattern, `{event}` is replaced by the event name (e.g. "/songrec/{event}")
+    pub address_template: String,
 }
 
 impl Default for Config {
@@ -42,15 +310,52 @@ impl Default for Config {
         Self {
             sensitivity: 0.5,
             network_timeout: 20,
+            pool_max_idle_per_host: 4,
+            pool_idle_timeout: 90,
+            tcp_keepalive: 60,
             min_audio_duration: 3.0,
             max_audio_duration: 12.0,
+            window_duration: 12.0,
             sample_rate: 16000,
             buffer_size: 4096,
             continuous_recognition: false,
             recognition_interval: 5.0,
+            sample_queue_size: 64, // About 16 seconds of audio with the default buffer size
+            recognition_workers: 2,
+            recognition_queue_size: 2,
+            max_concurrent_lookups: None,
+            lookup_delay: 0.0,
             quiet_mode: true, // Default to quiet mode for clean output
             deduplicate_requests: true,
             deduplication_cache_duration: 300, // 5 minutes
+            install_id: None,
+            api_dump_dir: None,
+            state_dir: None,
+            spectral_weighting: SpectralWeighting::None,
+            channel_mode: ChannelMode::Mid,
+            keep_audio_snippets: false,
+            analyze_tracks: false,
+            music_only: false,
+            refine_tracklist_boundaries: true,
+            retry_durations: vec![18.0, 24.0],
+            pitch_shifts: Vec::new(),
+            record_attempts: 3,
+            min_file_duration: None,
+            max_file_duration: None,
+            long_files: LongFileAction::Skip,
+            metadata_language: "en-US".to_string(),
+            normalize_genres: false,
+            genre_mapping: BTreeMap::new(),
+            dry_run: false,
+            log_callback: None,
+            device: None,
+            jack_client: None,
+            spotify: None,
+            discord: None,
+            osc: None,
+            listenbrainz: None,
+            stations: BTreeMap::new(),
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -73,6 +378,24 @@ impl Config {
         self
     }
     
+    /// Set the maximum number of idle pooled connections
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+    
+    /// Set how long idle pooled connections are kept open
+    pub fn with_pool_idle_timeout(mut self, timeout: u64) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+    
+    /// Set the TCP keepalive interval
+    pub fn with_tcp_keepalive(mut self, interval: u64) -> Self {
+        self.tcp_keepalive = interval;
+        self
+    }
+    
     /// Set the minimum audio duration
     pub fn with_min_audio_duration(mut self, duration: f32) -> Self {
         self.min_audio_duration = duration;
@@ -84,6 +407,15 @@ impl Config {
         self.max_audio_duration = duration;
         self
     }
+
+    /// Set the length of the windows fingerprinted in continuous mode, 12 seconds by default
+    /// and at least 3
+    ///
+    /// Shorter windows use less memory and report sooner, but match less often.
+    pub fn with_window_duration(mut self, duration: f32) -> Self {
+        self.window_duration = duration;
+        self
+    }
     
     /// Set the sample rate
     pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
@@ -103,12 +435,58 @@ impl Config {
         self
     }
     
-    /// Set the recognition interval for continuous mode
+    /// Set the recognition interval for continuous mode, e.g. 30 to fingerprint a 12 second
+    /// window every 30 seconds and leave the CPU idle in between
     pub fn with_recognition_interval(mut self, interval: f32) -> Self {
         self.recognition_interval = interval;
         self
     }
     
+    /// Settings for continuous monitoring on Raspberry Pi Zero-class devices
+    ///
+    /// A window is fingerprinted every 30 seconds at most, by a single lookup worker, and
+    /// windows that do not match are not fingerprinted again with longer or pitch shifted
+    /// audio. Songs still get recognized, some seconds later on average.
+    pub fn with_low_resource_mode(mut self) -> Self {
+        self.recognition_interval = self.recognition_interval.max(30.0);
+        self.recognition_workers = 1;
+        self.recognition_queue_size = 1;
+        self.sample_queue_size = self.sample_queue_size.min(16);
+        self.retry_durations.clear();
+        self.pitch_shifts.clear();
+        self
+    }
+
+    /// Set how many captured sample batches may wait for processing before new ones are dropped
+    pub fn with_sample_queue_size(mut self, size: usize) -> Self {
+        self.sample_queue_size = size;
+        self
+    }
+    
+    /// Set the number of concurrent API lookups in continuous mode
+    pub fn with_recognition_workers(mut self, workers: usize) -> Self {
+        self.recognition_workers = workers.max(1);
+        self
+    }
+    
+    /// Set how many signatures may wait for a worker before new ones are skipped
+    pub fn with_recognition_queue_size(mut self, size: usize) -> Self {
+        self.recognition_queue_size = size;
+        self
+    }
+    
+    /// Set how many lookups a `SongRec` sends at once, and how many files are recognized at once in batch mode
+    pub fn with_max_concurrent_lookups(mut self, lookups: usize) -> Self {
+        self.max_concurrent_lookups = Some(lookups.max(1));
+        self
+    }
+    
+    /// Set the minimum delay between two API lookups, to go easy on the API with large libraries
+    pub fn with_lookup_delay(mut self, delay: f32) -> Self {
+        self.lookup_delay = delay.max(0.0);
+        self
+    }
+    
     /// Enable or disable quiet mode (suppress verbose output)
     pub fn with_quiet_mode(mut self, quiet: bool) -> Self {
         self.quiet_mode = quiet;
@@ -127,6 +505,305 @@ impl Config {
         self
     }
     
+    /// Use a fixed installation UUID instead of the persisted one (e.g. to rotate identity)
+    pub fn with_install_id(mut self, install_id: &str) -> Self {
+        self.install_id = Some(install_id.to_string());
+        self
+    }
+    
+    /// Write every raw API request and response to timestamped files in the given directory
+    pub fn with_api_dump_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.api_dump_dir = Some(dir.into());
+        self
+    }
+    
+    /// Keep the installation identity and other state in this directory instead of the platform one
+    pub fn with_state_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.state_dir = Some(dir.into());
+        self
+    }
+    
+    /// Weight the spectrum before peak detection (experimental, may lower match rates on clean audio)
+    pub fn with_spectral_weighting(mut self, weighting: SpectralWeighting) -> Self {
+        self.spectral_weighting = weighting;
+        self
+    }
+    
+    /// Fingerprint one channel of stereo audio, or their difference, instead of their average
+    pub fn with_channel_mode(mut self, mode: ChannelMode) -> Self {
+        self.channel_mode = mode;
+        self
+    }
+    
+    /// Keep the audio window behind each match, e.g. to store it with the history
+    pub fn with_audio_snippets(mut self, enabled: bool) -> Self {
+        self.keep_audio_snippets = enabled;
+        self
+    }
+    
+    /// Estimate the tempo and key of each match from its audio (requires the analysis feature)
+    pub fn with_track_analysis(mut self, enabled: bool) -> Self {
+        self.analyze_tracks = enabled;
+        self
+    }
+    
+    /// Skip windows that sound like speech or silence in continuous mode, e.g. for talk radio
+    pub fn with_music_only(mut self, enabled: bool) -> Self {
+        self.music_only = enabled;
+        self
+    }
+    
+    /// Locate track changes in tracklist mode more precisely than the interval between
+    /// windows, at the cost of about four extra lookups per change (enabled by default)
+    pub fn with_boundary_refinement(mut self, enabled: bool) -> Self {
+        self.refine_tracklist_boundaries = enabled;
+        self
+    }
+    
+    /// Escalate lookups that do not match to longer samples, e.g. `vec![18.0, 24.0]` (the default)
+    ///
+    /// Durations are in seconds and tried in increasing order, each one costing a lookup.
+    /// Files use a longer part of their middle, live audio the last seconds heard. An
+    /// empty list gives up after the first lookup.
+    pub fn with_retry_durations(mut self, durations: Vec<f32>) -> Self {
+        let mut durations: Vec<f32> = durations.into_iter().filter(|&seconds| seconds > 12.0).collect();
+        durations.sort_by(f32::total_cmp);
+        durations.dedup();
+        self.retry_durations = durations;
+        self
+    }
+    
+    /// Also match audio played faster or slower than the original, e.g. `vec![4.0, 8.0]` for ±4% and ±8%
+    ///
+    /// When no lookup matched, the audio is fingerprinted again at each speed, smallest
+    /// shifts first, and looked up until one matches: up to two more lookups per shift.
+    /// Shifts must be between 0 and 50%.
+    pub fn with_pitch_shifts(mut self, shifts: Vec<f32>) -> Self {
+        let mut shifts: Vec<f32> = shifts.into_iter().filter(|&percent| percent > 0.0 && percent < 50.0).collect();
+        shifts.sort_by(f32::total_cmp);
+        shifts.dedup();
+        self.pitch_shifts = shifts;
+        self
+    }
+    
+    /// Record up to `attempts` windows in `SongRec::recognize_from_device` when they are unusable
+    ///
+    /// A window is unusable when it is silent or holds almost no frequency peaks, e.g. the
+    /// microphone was muted for a moment; it is recorded again instead of being looked up.
+    pub fn with_record_attempts(mut self, attempts: u32) -> Self {
+        self.record_attempts = attempts.max(1);
+        self
+    }
+    
+    /// Skip files shorter than `min` or longer than `max` seconds in batch and watch modes
+    ///
+    /// Durations are read from the headers of files, which are not decoded when skipped.
+    /// Files whose headers do not tell their duration are always processed.
+    pub fn with_file_duration_limits(mut self, min: Option<f32>, max: Option<f32>) -> Self {
+        self.min_file_duration = min.filter(|&seconds| seconds > 0.0);
+        self.max_file_duration = max.filter(|&seconds| seconds > 0.0);
+        self
+    }
+    
+    /// Recognize files longer than the maximum file duration as tracklists instead of skipping them
+    pub fn with_long_files(mut self, action: LongFileAction) -> Self {
+        self.long_files = action;
+        self
+    }
+    
+    /// Only log the playlist additions, webhook calls and other side effects of matches
+    ///
+    /// Sinks that do more than print (see `Sink::is_dry_run_safe`) are not called either.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+    
+    /// Send diagnostics to `callback` instead of stderr, e.g. to the logging system of a host application
+    ///
+    /// The callback gets every message, whatever the quiet mode, from the threads of the
+    /// library, so it should return quickly.
+    pub fn with_log_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(LogLevel, &str) + Send + Sync + 'static,
+    {
+        self.log_callback = Some(LogCallback::new(callback));
+        self
+    }
+    
+    /// Request metadata in another language, e.g. "de-DE" or "pt-BR" (default "en-US")
+    pub fn with_metadata_language(mut self, language: &str) -> Self {
+        self.metadata_language = language.to_string();
+        self
+    }
+    
+    /// Normalize the genres of results onto a small vocabulary, e.g. "Hip-Hop/Rap" to "Hip-Hop"
+    pub fn with_genre_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_genres = enabled;
+        self
+    }
+    
+    /// Normalize the genre `from` of the API, compared without case, to `to` instead of the built-in rules
+    pub fn with_genre_mapping(mut self, from: &str, to: &str) -> Self {
+        self.genre_mapping.insert(from.to_string(), to.to_string());
+        self
+    }
+    
+    /// Set the Spotify playlist integration credentials
+    pub fn with_spotify(mut self, spotify: SpotifyConfig) -> Self {
+        self.spotify = Some(spotify);
+        self
+    }
+    
+    /// Set the Discord "now playing" webhook
+    pub fn with_discord(mut self, discord: DiscordConfig) -> Self {
+        self.discord = Some(discord);
+        self
+    }
+    
+    /// Set the OSC destination for track change messages
+    pub fn with_osc(mut self, osc: OscConfig) -> Self {
+        self.osc = Some(osc);
+        self
+    }
+    
+    /// Submit the tracks heard in continuous mode to ListenBrainz, see `ScrobblePolicy` for when
+    pub fn with_listenbrainz(mut self, listenbrainz: ListenBrainzConfig) -> Self {
+        self.listenbrainz = Some(listenbrainz);
+        self
+    }
+    
+    /// Use this input device for continuous recognition when none is given
+    pub fn with_device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_string());
+        self
+    }
+    
+    /// Record from the input ports of a JACK client named `name`, e.g. "songrec" (requires the jack feature)
+    pub fn with_jack_client(mut self, name: &str) -> Self {
+        self.jack_client = Some(name.to_string());
+        self
+    }
+    
+    /// Add a named station profile
+    pub fn with_station(mut self, name: &str, station: StationConfig) -> Self {
+        self.stations.insert(name.to_string(), station);
+        self
+    }
+
+    /// This configuration with the settings of a station applied, None if there is no such station
+    pub fn station_config(&self, name: &str) -> Option<Self> {
+        let station = self.stations.get(name)?;
+        let mut config = self.clone();
+        if let Some(language) = &station.metadata_language {
+            config.metadata_language = language.clone();
+        }
+        if station.spotify.is_some() {
+            config.spotify = station.spotify.clone();
+        }
+        if station.discord.is_some() {
+            config.discord = station.discord.clone();
+        }
+        if station.osc.is_some() {
+            config.osc = station.osc.clone();
+        }
+        Some(config)
+    }
+
+    /// This configuration with the settings of a profile merged over it
+    ///
+    /// A profile holds any of the settings of the file, e.g. `[profile.radio]` with
+    /// `device = "Monitor of Built-in Audio"`; nested tables like `[profile.radio.discord]`
+    /// are merged key by key. An empty string or table unsets an optional setting, e.g.
+    /// `device = ""` to record from the default device or `discord = {}`. Settings the
+    /// file cannot hold, such as the log callback, are kept.
+    pub fn profile_config(&self, name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| format!("No profile '{}', expected a [profile.{}] table", name, name))?;
+        let settings = toml::Value::try_from(self)?;
+        let mut config = self.clone();
+        for (key, value) in profile {
+            let value = match (settings.get(key), value) {
+                (Some(current @ toml::Value::Table(_)), toml::Value::Table(table)) if !is_unset(value) => {
+                    let mut merged = current.clone();
+                    merge_settings(&mut merged, table);
+                    merged
+                }
+                _ => value.clone(),
+            };
+            config
+                .apply_setting(key, value)
+                .map_err(|e| format!("Invalid '{}' in profile '{}': {}", key, name, e))?;
+        }
+        Ok(config)
+    }
+
+    /// Set the setting named `key` of the file, ignoring unknown ones as files do
+    fn apply_setting(&mut self, key: &str, value: toml::Value) -> Result<(), toml::de::Error> {
+        macro_rules! settings {
+            ($($field:ident),*; optional $($optional:ident),*) => {
+                match key {
+                    $(stringify!($field) => self.$field = value.try_into()?,)*
+                    $(stringify!($optional) => self.$optional = if is_unset(&value) { None } else { Some(value.try_into()?) },)*
+                    _ => {}
+                }
+            };
+        }
+        settings!(
+            sensitivity, network_timeout, pool_max_idle_per_host, pool_idle_timeout, tcp_keepalive,
+            min_audio_duration, max_audio_duration, window_duration, sample_rate, buffer_size, continuous_recognition,
+            recognition_interval, sample_queue_size, recognition_workers, recognition_queue_size,
+            lookup_delay, quiet_mode, deduplicate_requests, deduplication_cache_duration,
+            spectral_weighting, channel_mode, keep_audio_snippets, analyze_tracks, music_only,
+            refine_tracklist_boundaries, retry_durations, pitch_shifts, record_attempts, long_files,
+            metadata_language, normalize_genres, genre_mapping, dry_run, stations;
+            optional max_concurrent_lookups, install_id, api_dump_dir, state_dir, min_file_duration,
+            max_file_duration, device, jack_client, spotify, discord, osc, listenbrainz
+        );
+        Ok(())
+    }
+
+    /// Default configuration overridden by the `SONGREC_*` environment variables
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        Config::default().with_env_overrides()
+    }
+
+    /// Override settings with the `SONGREC_*` environment variables, e.g. in a container
+    /// configured without files
+    ///
+    /// A variable is named after its setting in upper case, with `__` between the keys of
+    /// nested tables: `SONGREC_NETWORK_TIMEOUT=20`, `SONGREC_SPOTIFY__PLAYLIST_ID=...`.
+    /// Values are read as TOML, e.g. `[8.0, 12.0]`, or as strings when the setting holds one.
+    /// Variables naming no setting are ignored.
+    pub fn with_env_overrides(self) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut settings = toml::Value::try_from(&self)?;
+
+        for (name, raw) in std::env::vars() {
+            let Some(path) = name.strip_prefix(ENV_PREFIX).filter(|path| !path.is_empty()) else {
+                continue;
+            };
+            let keys: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+
+            // "2" is a number for `recognition_workers` but a string for `device`
+            let parsed = format!("value = {}", raw)
+                .parse::<toml::Value>()
+                .ok()
+                .and_then(|table| table.get("value").cloned())
+                .filter(|value| {
+                    let mut candidate = settings.clone();
+                    merge_settings(&mut candidate, &nested(&keys, value.clone()));
+                    candidate.try_into::<Config>().is_ok()
+                });
+            let value = parsed.unwrap_or(toml::Value::String(raw));
+            merge_settings(&mut settings, &nested(&keys, value));
+        }
+
+        let config: Config = settings.try_into()?;
+        Ok(Config { log_callback: self.log_callback, ..config })
+    }
+
     /// Load configuration from a TOML file
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(path)?;
@@ -136,8 +813,46 @@ impl Config {
     
     /// Save configuration to a TOML file
     pub fn to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let content = toml::to_string_pretty(self)?;
+        // Through a value so that the plain settings of profiles are written before their tables
+        let content = toml::to_string_pretty(&toml::Value::try_from(self)?)?;
         std::fs::write(path, content)?;
         Ok(())
     }
 }
+
+/// Table holding `value` under the nested `keys`, e.g. `{ stations = { radio = { stream_url = value } } }`
+fn nested(keys: &[String], value: toml::Value) -> toml::value::Table {
+    let (last, tables) = keys.split_last().expect("split yields at least one key");
+    let mut table = toml::value::Table::new();
+    table.insert(last.clone(), value);
+    for key in tables.iter().rev() {
+        let mut outer = toml::value::Table::new();
+        outer.insert(key.clone(), toml::Value::Table(table));
+        table = outer;
+    }
+    table
+}
+
+/// Whether a profile value unsets an optional setting: an empty string or table
+fn is_unset(value: &toml::Value) -> bool {
+    match value {
+        toml::Value::String(text) => text.is_empty(),
+        toml::Value::Table(table) => table.is_empty(),
+        _ => false,
+    }
+}
+
+/// Merge the keys of `profile` into `settings`, replacing values and merging tables
+fn merge_settings(settings: &mut toml::Value, profile: &toml::value::Table) {
+    let toml::Value::Table(settings) = settings else {
+        return;
+    };
+    for (key, value) in profile {
+        match (settings.get_mut(key), value) {
+            (Some(current @ toml::Value::Table(_)), toml::Value::Table(table)) => merge_settings(current, table),
+            _ => {
+                settings.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
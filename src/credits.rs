@@ -0,0 +1,81 @@
+//! Splitting of artist credits such as "Artist feat. Other" into the artists they name
+//!
+//! The main part of a credit is kept whole, as "Simon & Garfunkel" and "Earth, Wind & Fire"
+//! cannot be told apart from collaborations; only featured artists are split.
+
+use serde::{Deserialize, Serialize};
+
+/// Part an artist plays in a song, as far as its credit tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtistRole {
+    /// Artist, or artists, the song is credited to
+    Main,
+    /// Artist credited with "feat.", "ft." or "featuring", in the artist or the song title
+    Featured,
+}
+
+/// An artist named by the credit of a song
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtistCredit {
+    pub name: String,
+    pub role: ArtistRole,
+}
+
+/// Words introducing featured artists, followed by a space
+const FEATURING: [&str; 5] = ["feat.", "feat", "ft.", "ft", "featuring"];
+
+/// Artists of a song, the main credit first, from its artist credit and title
+///
+/// Featured artists named in the title, e.g. "Song (feat. Other)", come after the ones of the
+/// artist credit and are not repeated.
+pub fn split(artist: &str, title: &str) -> Vec<ArtistCredit> {
+    let (main, featured) = match featuring(artist) {
+        Some((start, names_start)) => (&artist[..start], Some(&artist[names_start..])),
+        None => (artist, None),
+    };
+
+    let mut credits = Vec::new();
+    let main = main.trim();
+    if !main.is_empty() {
+        credits.push(ArtistCredit { name: main.to_string(), role: ArtistRole::Main });
+    }
+
+    let in_title = featuring(title).map(|(_, names_start)| &title[names_start..]);
+    for names in featured.into_iter().chain(in_title) {
+        for name in split_names(names) {
+            if !credits.iter().any(|credit| credit.name.eq_ignore_ascii_case(&name)) {
+                credits.push(ArtistCredit { name, role: ArtistRole::Featured });
+            }
+        }
+    }
+
+    credits
+}
+
+/// Byte offsets of the word introducing featured artists and of the names after it
+///
+/// The word may be in parentheses or brackets, e.g. "Artist (ft. Other)".
+fn featuring(text: &str) -> Option<(usize, usize)> {
+    let lowercase = text.to_ascii_lowercase();
+    lowercase
+        .char_indices()
+        .filter(|&(index, _)| index == 0 || matches!(lowercase.as_bytes()[index - 1], b' ' | b'(' | b'['))
+        .find_map(|(index, _)| {
+            let word = FEATURING.iter().find(|word| lowercase[index..].starts_with(&format!("{} ", word)))?;
+            let start = if index > 0 && matches!(lowercase.as_bytes()[index - 1], b'(' | b'[') { index - 1 } else { index };
+            Some((start, index + word.len() + 1))
+        })
+}
+
+/// Names of a list such as "A, B & C", without a closing parenthesis or bracket
+fn split_names(names: &str) -> Vec<String> {
+    let names = names.split([')', ']']).next().unwrap_or("");
+    names
+        .split(", ")
+        .flat_map(|part| part.split(" & "))
+        .flat_map(|part| part.split(" and "))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
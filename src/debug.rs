@@ -0,0 +1,419 @@
+//! Debugging helpers for inspecting raw Shazam API responses
+
+use serde_json::Value;
+
+/// Produce a human-readable, exhaustive description of a raw Shazam API response
+///
+/// This is meant for troubleshooting (e.g. with responses written by `Config::with_api_dump_dir`)
+/// and is never called from the recognition path.
+pub fn describe_response(response: &Value) -> String {
+    let mut inspector = ResponseInspector::default();
+    inspector.describe(response);
+    inspector.output
+}
+
+/// Walks a response and accumulates a description of every known and unknown field
+#[derive(Default)]
+struct ResponseInspector {
+    output: String,
+}
+
+impl ResponseInspector {
+    fn line<S: AsRef<str>>(&mut self, line: S) {
+        self.output.push_str(line.as_ref());
+        self.output.push('\n');
+    }
+
+    fn describe(&mut self, response: &Value) {
+        self.line("\n🔍 EXHAUSTIVE RESPONSE ANALYSIS 🔍");
+        self.line("═══════════════════════════════════════");
+
+        // Top-level response metadata
+        self.line("\n📊 RESPONSE METADATA:");
+        self.value_info(response, "tagid", "Tag ID");
+        self.value_info(response, "timestamp", "Timestamp");
+        self.value_info(response, "timezone", "Timezone");
+        self.value_info(response, "retailer", "Retailer");
+        self.value_info(response, "server", "Server");
+        self.value_info(response, "uuid", "UUID");
+        self.value_info(response, "version", "API Version");
+        self.value_info(response, "track", "Track (top-level)");
+        self.value_info(response, "status", "Status");
+        self.value_info(response, "error", "Error");
+        self.value_info(response, "message", "Message");
+
+        // Location information
+        if let Some(location) = response.get("location") {
+            self.line("\n📍 LOCATION DATA:");
+            self.value_info(location, "latitude", "Latitude");
+            self.value_info(location, "longitude", "Longitude");
+            self.value_info(location, "altitude", "Altitude");
+            self.value_info(location, "accuracy", "Accuracy");
+            self.value_info(location, "country", "Country");
+            self.value_info(location, "city", "City");
+            self.value_info(location, "region", "Region");
+            self.value_info(location, "timezone", "Location Timezone");
+            self.value_info(location, "ip", "IP Address");
+            self.value_info(location, "provider", "Location Provider");
+            // Any additional location fields
+            if let Some(obj) = location.as_object() {
+                for (key, value) in obj {
+                    if !["latitude", "longitude", "altitude", "accuracy", "country", "city", "region", "timezone", "ip", "provider"].contains(&key.as_str()) {
+                        self.line(format!("   🏷️  Location {}: {}", key, value));
+                    }
+                }
+            }
+        }
+
+        // Matches array - complete analysis
+        if let Some(matches) = response.get("matches").and_then(|m| m.as_array()) {
+            self.line(format!("\n🎵 MATCHES FOUND: {}", matches.len()));
+
+            if matches.is_empty() {
+                self.line("   ❌ No songs recognized");
+            } else {
+                for (i, match_obj) in matches.iter().enumerate() {
+                    self.line(format!("\n🎶 MATCH #{} - COMPLETE DETAILS:", i + 1));
+                    self.line("─────────────────────────────────────");
+
+                    // Match-level information
+                    self.value_info(match_obj, "id", "Match ID");
+                    self.value_info(match_obj, "offset", "Offset");
+                    self.value_info(match_obj, "timeskew", "Time Skew");
+                    self.value_info(match_obj, "frequencyskew", "Frequency Skew");
+
+                    // Track information - comprehensive extraction
+                    if let Some(track) = match_obj.get("track") {
+                        self.line("\n🎼 TRACK INFORMATION:");
+
+                        // Basic track info
+                        self.value_info(track, "key", "Track Key");
+                        self.value_info(track, "title", "Title");
+                        self.value_info(track, "subtitle", "Artist/Subtitle");
+                        self.value_info(track, "layout", "Layout");
+                        self.value_info(track, "type", "Type");
+                        self.value_info(track, "isrc", "ISRC");
+                        self.value_info(track, "albumadamid", "Album Adam ID");
+                        self.value_info(track, "artistadamid", "Artist Adam ID");
+                        self.value_info(track, "trackadamid", "Track Adam ID");
+                        self.value_info(track, "myshazam", "MyShazam");
+
+                        // Images
+                        if let Some(images) = track.get("images") {
+                            self.line("\n🖼️  IMAGES:");
+                            self.images_info(images);
+                        }
+
+                        // Share information
+                        if let Some(share) = track.get("share") {
+                            self.line("\n🔗 SHARE INFORMATION:");
+                            self.value_info(share, "subject", "Subject");
+                            self.value_info(share, "text", "Text");
+                            self.value_info(share, "href", "Share Link");
+                            self.value_info(share, "image", "Share Image");
+                            self.value_info(share, "twitter", "Twitter");
+                            self.value_info(share, "html", "HTML");
+                            self.value_info(share, "avatar", "Avatar");
+                            self.value_info(share, "snapchat", "Snapchat");
+                            self.value_info(share, "facebook", "Facebook");
+                            self.value_info(share, "whatsapp", "WhatsApp");
+                            self.value_info(share, "telegram", "Telegram");
+                            self.value_info(share, "instagram", "Instagram");
+                            self.value_info(share, "pinterest", "Pinterest");
+                            self.value_info(share, "linkedin", "LinkedIn");
+                            self.value_info(share, "reddit", "Reddit");
+                            self.value_info(share, "tumblr", "Tumblr");
+                            self.value_info(share, "discord", "Discord");
+                            self.value_info(share, "email", "Email");
+                            self.value_info(share, "sms", "SMS");
+                            self.value_info(share, "copy", "Copy Link");
+
+                            // Any unknown share fields
+                            if let Some(share_obj) = share.as_object() {
+                                let known_share_fields = [
+                                    "subject", "text", "href", "image", "twitter", "html", "avatar", "snapchat",
+                                    "facebook", "whatsapp", "telegram", "instagram", "pinterest", "linkedin",
+                                    "reddit", "tumblr", "discord", "email", "sms", "copy"
+                                ];
+                                for (key, value) in share_obj {
+                                    if !known_share_fields.contains(&key.as_str()) {
+                                        self.line(format!("   🆕 UNKNOWN SHARE FIELD {}: {}", key, value));
+                                    }
+                                }
+                            }
+                        }
+
+                        // Hub information
+                        if let Some(hub) = track.get("hub") {
+                            self.line("\n🎧 HUB INFORMATION:");
+                            self.value_info(hub, "type", "Hub Type");
+                            self.value_info(hub, "image", "Hub Image");
+                            self.value_info(hub, "displayname", "Display Name");
+                            self.value_info(hub, "explicit", "Explicit");
+                            self.value_info(hub, "uri", "URI");
+                            self.value_info(hub, "name", "Hub Name");
+
+                            if let Some(actions) = hub.get("actions").and_then(|a| a.as_array()) {
+                                self.line("\n🎯 HUB ACTIONS:");
+                                for (j, action) in actions.iter().enumerate() {
+                                    self.line(format!("   Action #{}: {}", j + 1, pretty(action)));
+                                }
+                            }
+
+                            if let Some(options) = hub.get("options").and_then(|o| o.as_array()) {
+                                self.line("\n⚙️  HUB OPTIONS:");
+                                for (j, option) in options.iter().enumerate() {
+                                    self.line(format!("   Option #{}: {}", j + 1, pretty(option)));
+                                }
+                            }
+
+                            if let Some(providers) = hub.get("providers").and_then(|p| p.as_array()) {
+                                self.line("\n🏢 PROVIDERS:");
+                                for (j, provider) in providers.iter().enumerate() {
+                                    self.line(format!("   Provider #{}: {}", j + 1, pretty(provider)));
+                                }
+                            }
+
+                            // Any unknown hub fields
+                            self.line("\n🔍 ALL HUB FIELDS:");
+                            if let Some(hub_obj) = hub.as_object() {
+                                let known_hub_fields = [
+                                    "type", "image", "displayname", "explicit", "uri", "name",
+                                    "actions", "options", "providers"
+                                ];
+                                for (key, value) in hub_obj {
+                                    if !known_hub_fields.contains(&key.as_str()) {
+                                        self.line(format!("   🆕 UNKNOWN HUB FIELD {}: {}", key, value));
+                                    }
+                                }
+                            }
+                        }
+
+                        // Sections - detailed analysis
+                        if let Some(sections) = track.get("sections").and_then(|s| s.as_array()) {
+                            self.line(format!("\n📚 SECTIONS ({} found):", sections.len()));
+
+                            for (j, section) in sections.iter().enumerate() {
+                                self.line(format!("\n   📄 SECTION #{}: ", j + 1));
+                                self.value_info(section, "type", "   Type");
+                                self.value_info(section, "metapages", "   Metapages");
+                                self.value_info(section, "tabname", "   Tab Name");
+                                self.value_info(section, "text", "   Text");
+                                self.value_info(section, "url", "   URL");
+                                self.value_info(section, "youtubeurl", "   YouTube URL");
+                                self.value_info(section, "actions", "   Actions");
+                                self.value_info(section, "options", "   Options");
+                                self.value_info(section, "footer", "   Footer");
+                                self.value_info(section, "header", "   Header");
+                                self.value_info(section, "subtitle", "   Subtitle");
+                                self.value_info(section, "title", "   Title");
+
+                                // Metadata within sections
+                                if let Some(metadata) = section.get("metadata").and_then(|m| m.as_array()) {
+                                    self.line(format!("      📋 METADATA ({} items):", metadata.len()));
+                                    for (k, meta_item) in metadata.iter().enumerate() {
+                                        self.line(format!("         Metadata #{}: {}", k + 1, pretty(meta_item)));
+                                    }
+                                }
+
+                                // Beacons
+                                if let Some(beacons) = section.get("beacons").and_then(|b| b.as_array()) {
+                                    self.line(format!("      🚨 BEACONS ({} items):", beacons.len()));
+                                    for (k, beacon) in beacons.iter().enumerate() {
+                                        self.line(format!("         Beacon #{}: {}", k + 1, pretty(beacon)));
+                                    }
+                                }
+
+                                // Unknown section fields
+                                self.line("      🔍 ALL SECTION FIELDS:");
+                                if let Some(section_obj) = section.as_object() {
+                                    let known_section_fields = [
+                                        "type", "metapages", "tabname", "text", "url", "youtubeurl", 
+                                        "actions", "options", "footer", "header", "subtitle", "title",
+                                        "metadata", "beacons"
+                                    ];
+                                    for (key, value) in section_obj {
+                                        if !known_section_fields.contains(&key.as_str()) {
+                                            self.line(format!("         🆕 UNKNOWN SECTION FIELD {}: {}", key, value));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        // URL links
+                        if let Some(url) = track.get("url") {
+                            self.line(format!("\n🌐 TRACK URL: {}", url));
+                        }
+
+                        // Additional track fields - enhanced search
+                        self.value_info(track, "genres", "Genres");
+                        self.value_info(track, "label", "Label");
+                        self.value_info(track, "copyright", "Copyright");
+                        self.value_info(track, "releasedate", "Release Date");
+                        self.value_info(track, "duration", "Duration");
+                        self.value_info(track, "albumname", "Album Name");
+                        self.value_info(track, "artistname", "Artist Name");
+                        self.value_info(track, "trackname", "Track Name");
+                        self.value_info(track, "explicit", "Explicit Content");
+                        self.value_info(track, "preview", "Preview");
+                        self.value_info(track, "popularity", "Popularity");
+                        self.value_info(track, "rank", "Rank");
+                        self.value_info(track, "year", "Year");
+                        self.value_info(track, "bpm", "BPM");
+                        self.value_info(track, "mood", "Mood");
+                        self.value_info(track, "energy", "Energy");
+                        self.value_info(track, "danceability", "Danceability");
+                        self.value_info(track, "acousticness", "Acousticness");
+                        self.value_info(track, "instrumentalness", "Instrumentalness");
+                        self.value_info(track, "liveness", "Liveness");
+                        self.value_info(track, "loudness", "Loudness");
+                        self.value_info(track, "speechiness", "Speechiness");
+                        self.value_info(track, "valence", "Valence");
+                        self.value_info(track, "tempo", "Tempo");
+                        self.value_info(track, "time_signature", "Time Signature");
+                        self.value_info(track, "key_signature", "Key Signature");
+                        self.value_info(track, "mode", "Mode");
+                        self.value_info(track, "camelot", "Camelot Key");
+                        self.value_info(track, "open_key", "Open Key");
+                        self.value_info(track, "created_at", "Created At");
+                        self.value_info(track, "updated_at", "Updated At");
+                        self.value_info(track, "language", "Language");
+                        self.value_info(track, "lyrics", "Lyrics");
+                        self.value_info(track, "credits", "Credits");
+                        self.value_info(track, "composer", "Composer");
+                        self.value_info(track, "producer", "Producer");
+                        self.value_info(track, "writer", "Writer");
+                        self.value_info(track, "publisher", "Publisher");
+                        self.value_info(track, "recordingdate", "Recording Date");
+                        self.value_info(track, "studio", "Studio");
+                        self.value_info(track, "originalyear", "Original Year");
+                        self.value_info(track, "remix", "Remix");
+                        self.value_info(track, "version", "Version");
+                        self.value_info(track, "featuring", "Featuring");
+                        self.value_info(track, "collaborations", "Collaborations");
+                        self.value_info(track, "samples", "Samples");
+                        self.value_info(track, "covers", "Covers");
+                        self.value_info(track, "tags", "Tags");
+                        self.value_info(track, "similar", "Similar Tracks");
+                        self.value_info(track, "recommendations", "Recommendations");
+                        self.value_info(track, "playlists", "Playlists");
+                        self.value_info(track, "charts", "Charts");
+
+                        // Any other fields in track - expanded exclusion list
+                        self.line("\n🔍 ALL TRACK FIELDS:");
+                        if let Some(obj) = track.as_object() {
+                            let known_fields = [
+                                "key", "title", "subtitle", "layout", "type", "isrc", "images", "share", "hub", "sections", "url",
+                                "genres", "label", "copyright", "releasedate", "duration", "albumname", "artistname", "trackname",
+                                "albumadamid", "artistadamid", "trackadamid", "myshazam", "explicit", "preview", "popularity",
+                                "rank", "year", "bpm", "mood", "energy", "danceability", "acousticness", "instrumentalness",
+                                "liveness", "loudness", "speechiness", "valence", "tempo", "time_signature", "key_signature",
+                                "mode", "camelot", "open_key", "created_at", "updated_at", "language", "lyrics", "credits",
+                                "composer", "producer", "writer", "publisher", "recordingdate", "studio", "originalyear",
+                                "remix", "version", "featuring", "collaborations", "samples", "covers", "tags", "similar",
+                                "recommendations", "playlists", "charts"
+                            ];
+
+                            for (key, value) in obj {
+                                if !known_fields.contains(&key.as_str()) {
+                                    self.line(format!("   🆕 UNKNOWN TRACK FIELD {}: {}", key, value));
+                                }
+                            }
+                        }
+                    }
+
+                    // Any other fields in the match - enhanced
+                    self.line("\n🔍 ALL MATCH FIELDS:");
+                    if let Some(obj) = match_obj.as_object() {
+                        let known_match_fields = ["id", "offset", "timeskew", "frequencyskew", "track"];
+                        for (key, value) in obj {
+                            if !known_match_fields.contains(&key.as_str()) {
+                                self.line(format!("   🆕 UNKNOWN MATCH FIELD {}: {}", key, value));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Check for top-level track information (alternative response format)
+        if let Some(track) = response.get("track").filter(|track| track.is_object()) {
+            self.line("\n🎼 TOP-LEVEL TRACK INFORMATION:");
+            self.value_info(track, "key", "Track Key");
+            self.value_info(track, "title", "Title");
+            self.value_info(track, "subtitle", "Artist/Subtitle");
+            self.value_info(track, "isrc", "ISRC");
+        }
+
+        // Top-level fields we haven't covered - enhanced analysis
+        self.line("\n🔍 ALL TOP-LEVEL FIELDS:");
+        if let Some(obj) = response.as_object() {
+            let known_top_level_fields = [
+                "tagid", "timestamp", "timezone", "retailer", "server", "uuid", "location", 
+                "matches", "version", "track", "status", "error", "message"
+            ];
+
+            for (key, value) in obj {
+                if !known_top_level_fields.contains(&key.as_str()) {
+                    self.line(format!("   🆕 UNKNOWN TOP-LEVEL FIELD {}: {}", key, value));
+                }
+            }
+
+            // Additional checks for arrays or objects we may have missed
+            self.line("\n🔍 COMPREHENSIVE FIELD TYPE ANALYSIS:");
+            for (key, value) in obj {
+                match value {
+                    Value::Array(arr) if !arr.is_empty() => {
+                        self.line(format!("   📋 Array field '{}' with {} items - first item: {}", 
+                            key, arr.len(), 
+                            serde_json::to_string_pretty(&arr[0]).unwrap_or_else(|_| "unparseable".to_string())));
+                    },
+                    Value::Object(obj) if !obj.is_empty() => {
+                        self.line(format!("   📦 Object field '{}' with keys: {:?}", key, obj.keys().collect::<Vec<_>>()));
+                    },
+                    _ => {} // Already handled in known fields above
+                }
+            }
+        }
+
+        self.line("\n═══════════════════════════════════════");
+        self.line("🏁 END OF COMPLETE RESPONSE ANALYSIS");
+    }
+
+    fn value_info(&mut self, obj: &Value, key: &str, label: &str) {
+        if let Some(value) = obj.get(key) {
+            match value {
+                Value::String(s) => self.line(format!("   🏷️ {}: {}", label, s)),
+                Value::Number(n) => self.line(format!("   🔢 {}: {}", label, n)),
+                Value::Bool(b) => self.line(format!("   ✅ {}: {}", label, b)),
+                Value::Array(arr) => {
+                    self.line(format!("   📋 {} (array, {} items):", label, arr.len()));
+                    for (i, item) in arr.iter().enumerate() {
+                        self.line(format!("      [{}]: {}", i, item));
+                    }
+                },
+                Value::Object(_) => self.line(format!("   📦 {} (object): {}", label, pretty(value))),
+                Value::Null => self.line(format!("   ❌ {}: null", label)),
+            }
+        }
+    }
+
+    fn images_info(&mut self, images: &Value) {
+        if let Some(obj) = images.as_object() {
+            for (key, value) in obj {
+                self.line(format!("      🖼️  {} Image: {}", key, value));
+            }
+        } else if let Some(arr) = images.as_array() {
+            for (i, image) in arr.iter().enumerate() {
+                self.line(format!("      🖼️  Image #{}: {}", i + 1, image));
+            }
+        } else {
+            self.line(format!("      🖼️  Image: {}", images));
+        }
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| "Failed to serialize".to_string())
+}
@@ -0,0 +1,164 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ChannelMode, SpectralWeighting};
+use crate::fingerprinting::algorithm::{decode_error, SignatureGenerator};
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::library::{is_audio_file, list_files};
+use crate::{Result, SongRecError};
+
+/// Similarity above which two files are reported as the same recording by default
+pub const DEFAULT_DUPLICATE_THRESHOLD: f32 = 0.5;
+
+/// Extensions of the formats that lose nothing, preferred as keepers
+const LOSSLESS_EXTENSIONS: [&str; 2] = ["flac", "wav"];
+
+/// Audio files under a directory, recursively, sorted by path
+pub fn audio_files<P: AsRef<Path>>(directory: P) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = list_files(directory.as_ref()).into_iter().filter(|path| is_audio_file(path)).collect();
+    files.sort();
+    files
+}
+
+/// Fingerprint of a local audio file, with what is needed to pick a keeper among duplicates
+#[derive(Debug, Clone)]
+pub struct FileFingerprint {
+    pub path: PathBuf,
+
+    /// Length of the decoded audio, in seconds
+    pub duration: f32,
+
+    /// Size of the file, in bytes
+    pub size: u64,
+
+    /// Fingerprint of the middle of the file
+    pub signature: DecodedSignature,
+}
+
+impl FileFingerprint {
+    /// Decode and fingerprint a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file_path = path.to_string_lossy();
+        let samples = SignatureGenerator::decode_file(&file_path, ChannelMode::Mid).map_err(decode_error)?;
+        let signature = SignatureGenerator::make_signature_from_recording(&samples, &format!("file '{}'", file_path), SpectralWeighting::None)
+            .map_err(|e| SongRecError::FingerprintingError(e.to_string()))?;
+        let size = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            duration: samples.len() as f32 / 16000.0,
+            size,
+            signature,
+        })
+    }
+
+    /// Average bitrate of the file, in bytes per second of audio
+    pub fn bytes_per_second(&self) -> f32 {
+        if self.duration > 0.0 { self.size as f32 / self.duration } else { 0.0 }
+    }
+
+    /// Whether the file is in a lossless format, judged by its extension
+    pub fn is_lossless(&self) -> bool {
+        self.path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| LOSSLESS_EXTENSIONS.contains(&extension.as_str()))
+    }
+
+    /// Order in which files are preferred as keeper: lossless first, then the highest bitrate,
+    /// the longest audio and the shortest path
+    fn keeper_order(&self, other: &Self) -> Ordering {
+        other.is_lossless()
+            .cmp(&self.is_lossless())
+            .then(other.bytes_per_second().total_cmp(&self.bytes_per_second()))
+            .then(other.duration.total_cmp(&self.duration))
+            .then(self.path.as_os_str().len().cmp(&other.path.as_os_str().len()))
+            .then(self.path.cmp(&other.path))
+    }
+}
+
+/// File of a `DuplicateGroup`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateFile {
+    pub path: PathBuf,
+    pub duration: f32,
+    pub size: u64,
+
+    /// Similarity of the file with the keeper, 1 for the keeper itself
+    pub similarity: f32,
+}
+
+/// Files holding the same recording, e.g. different encodes of one track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    /// Suggested file to keep
+    pub keeper: PathBuf,
+
+    /// Every file of the group, the keeper first
+    pub files: Vec<DuplicateFile>,
+}
+
+impl DuplicateGroup {
+    /// Files that could be removed in favour of the keeper
+    pub fn redundant(&self) -> impl Iterator<Item = &DuplicateFile> {
+        self.files.iter().skip(1)
+    }
+}
+
+/// Group files whose fingerprints are more similar than `threshold`
+///
+/// Every pair of files is compared. A file similar to any member of a group joins it, so
+/// a group can hold two files below the threshold linked through a third one. Groups are
+/// sorted by keeper path, files without any duplicate are left out.
+pub fn find_duplicates(fingerprints: &[FileFingerprint], threshold: f32) -> Vec<DuplicateGroup> {
+    // Union-find over the indices of the fingerprints
+    let mut parents: Vec<usize> = (0..fingerprints.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    for first in 0..fingerprints.len() {
+        for second in first + 1..fingerprints.len() {
+            if fingerprints[first].signature.similarity(&fingerprints[second].signature) >= threshold {
+                let (first_root, second_root) = (root(&mut parents, first), root(&mut parents, second));
+                parents[second_root] = first_root;
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<&FileFingerprint>> = vec![Vec::new(); fingerprints.len()];
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        let group = root(&mut parents, index);
+        members[group].push(fingerprint);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by(|first, second| first.keeper_order(second));
+            let keeper = members[0];
+            DuplicateGroup {
+                keeper: keeper.path.clone(),
+                files: members
+                    .iter()
+                    .map(|member| DuplicateFile {
+                        path: member.path.clone(),
+                        duration: member.duration,
+                        size: member.size,
+                        similarity: if std::ptr::eq(*member, keeper) { 1.0 } else { keeper.signature.similarity(&member.signature) },
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    groups.sort_by(|first, second| first.keeper.cmp(&second.keeper));
+    groups
+}
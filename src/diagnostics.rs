@@ -0,0 +1,108 @@
+//! Why a lookup did not match, attached to `SongRecError::NoMatch`
+//!
+//! The API only answers "no match", so the audio the signature was made from is measured
+//! instead: a quiet, short, speech-only or band-limited window is the usual culprit.
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::classifier::{AudioClass, AudioFeatures};
+use crate::fingerprinting::signature_format::{DecodedSignature, FrequencyBand};
+
+/// Windows quieter than this are too quiet to match reliably, in dBFS
+const QUIET_THRESHOLD_DBFS: f32 = -40.0;
+
+/// Windows shorter than this hold too few peaks to match reliably, in seconds
+const SHORT_WINDOW_SECS: f32 = 6.0;
+
+/// Peaks per second below which the audio has too few distinctive sounds; music gives about 70
+const SPARSE_PEAKS_PER_SEC: f32 = 20.0;
+
+/// Peaks per second of a band below which the band counts as empty
+const BAND_PEAKS_PER_SEC: f32 = 1.0;
+
+/// Peaks per second below which a window cannot match at all
+const UNUSABLE_PEAKS_PER_SEC: f32 = 5.0;
+
+/// Measurements of the audio of a lookup that did not match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoMatchDiagnostics {
+    /// Frequency peaks in the signature, across all bands
+    pub peak_count: usize,
+
+    /// Peaks in each band of the signature: 250-520 Hz, 520-1450 Hz, 1450-3500 Hz and 3500-5500 Hz
+    pub band_peaks: [usize; 4],
+
+    /// Share of the bands holding peaks, from 0 to 1; a phone speaker leaves the lowest band empty
+    pub band_coverage: f32,
+
+    /// Unweighted RMS level of the audio, in dBFS
+    pub rms_dbfs: f32,
+
+    /// Length of the audio the signature was made from, in seconds
+    pub window_secs: f32,
+
+    /// What the audio sounds like, None when the audio was not kept to be classified
+    pub class: Option<AudioClass>,
+
+    /// Most likely reason for the missing match, e.g. "audio too quiet"
+    pub hint: String,
+
+    /// Windows recorded by `SongRec::recognize_from_device`, unusable ones being recorded again
+    #[serde(default = "one")]
+    pub attempts: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl NoMatchDiagnostics {
+    /// Measure `window`, the audio `signature` was made from
+    pub fn measure(signature: &DecodedSignature, window: &[i16]) -> Self {
+        let features = AudioFeatures::measure(window, signature.sample_rate_hz);
+        Self::new(signature, features.rms_dbfs, Some(features.class()))
+    }
+
+    /// Diagnose `signature` from the level and class of its audio
+    pub(crate) fn new(signature: &DecodedSignature, rms_dbfs: f32, class: Option<AudioClass>) -> Self {
+        let bands = [FrequencyBand::_250_520, FrequencyBand::_520_1450, FrequencyBand::_1450_3500, FrequencyBand::_3500_5500];
+        let band_peaks = bands.map(|band| signature.frequency_band_to_sound_peaks.get(&band).map_or(0, Vec::len));
+        let peak_count = band_peaks.iter().sum();
+        let window_secs = signature.number_samples as f32 / signature.sample_rate_hz.max(1) as f32;
+
+        let per_second = |peaks: usize| peaks as f32 / window_secs.max(1.0);
+        let band_coverage = band_peaks.iter().filter(|&&peaks| per_second(peaks) >= BAND_PEAKS_PER_SEC).count() as f32 / 4.0;
+
+        let hint = if class == Some(AudioClass::Silence) {
+            "audio is silent, check the input device and its volume"
+        } else if rms_dbfs < QUIET_THRESHOLD_DBFS {
+            "audio too quiet, move closer to the source or raise the input volume"
+        } else if class == Some(AudioClass::Speech) {
+            "mostly speech, try again while music plays"
+        } else if window_secs < SHORT_WINDOW_SECS {
+            "audio too short, at least 10 seconds match best"
+        } else if per_second(peak_count) < SPARSE_PEAKS_PER_SEC {
+            "few distinctive sounds, the audio may be muffled, distorted or very quiet music"
+        } else if band_coverage < 1.0 {
+            "frequencies missing, e.g. from a phone speaker or heavy filtering"
+        } else {
+            "the audio looks usable, the song may be missing from the catalog or covered by noise"
+        };
+
+        Self {
+            peak_count,
+            band_peaks,
+            band_coverage,
+            rms_dbfs,
+            window_secs,
+            class,
+            hint: hint.to_string(),
+            attempts: 1,
+        }
+    }
+
+    /// Whether the audio is silent or holds almost no peaks, so that looking it up is pointless
+    pub fn is_unusable(&self) -> bool {
+        self.class == Some(AudioClass::Silence) || self.peak_count as f32 / self.window_secs.max(1.0) < UNUSABLE_PEAKS_PER_SEC
+    }
+}
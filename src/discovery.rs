@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Short description of a track returned by Shazam's discovery endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackSummary {
+    pub track_key: String,
+    pub song_name: String,
+    pub artist_name: String,
+    pub cover_art_url: Option<String>,
+    pub shazam_url: Option<String>,
+}
+
+impl TrackSummary {
+    /// Build a summary from a single track object as found in Shazam responses
+    /// Returns None if the object has no track key
+    pub fn from_track_json(track: &Value) -> Option<Self> {
+        let track_key = track.get("key").and_then(|v| v.as_str())?.to_string();
+
+        let song_name = track
+            .get("title")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        let artist_name = track
+            .get("subtitle")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        let cover_art_url = track
+            .pointer("/images/coverart")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let shazam_url = track
+            .get("url")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        Some(TrackSummary {
+            track_key,
+            song_name,
+            artist_name,
+            cover_art_url,
+            shazam_url,
+        })
+    }
+
+    /// Parse the response of the related tracks endpoint into a list of summaries
+    pub fn list_from_related_response(response: &Value) -> Vec<Self> {
+        response
+            .get("tracks")
+            .and_then(|t| t.as_array())
+            .map(|tracks| tracks.iter().filter_map(Self::from_track_json).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Song entry from an artist's top songs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistSong {
+    pub song_name: String,
+    pub artist_name: String,
+    pub album_name: Option<String>,
+    pub isrc: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Artist metadata returned by the artist lookup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistInfo {
+    pub artist_id: String,
+    pub name: String,
+    pub bio: Option<String>,
+    pub genres: Vec<String>,
+    pub origin: Option<String>,
+    pub url: Option<String>,
+    pub top_songs: Vec<ArtistSong>,
+}
+
+impl ArtistInfo {
+    /// Parse the response of the artist endpoint
+    /// Returns None if the response does not contain any artist
+    pub fn from_artist_response(response: &Value) -> Option<Self> {
+        let artist = response.pointer("/data/0")?;
+        let attributes = artist.get("attributes")?;
+
+        let artist_id = artist
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        let name = attributes
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        let bio = attributes
+            .get("artistBio")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let genres = attributes
+            .get("genreNames")
+            .and_then(|v| v.as_array())
+            .map(|genres| genres.iter().filter_map(|g| g.as_str()).map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+
+        let origin = attributes
+            .get("origin")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let url = attributes
+            .get("url")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let top_songs = artist
+            .pointer("/views/top-songs/data")
+            .and_then(|v| v.as_array())
+            .map(|songs| songs.iter().filter_map(Self::parse_song).collect())
+            .unwrap_or_default();
+
+        Some(ArtistInfo {
+            artist_id,
+            name,
+            bio,
+            genres,
+            origin,
+            url,
+            top_songs,
+        })
+    }
+
+    fn parse_song(song: &Value) -> Option<ArtistSong> {
+        let attributes = song.get("attributes")?;
+
+        Some(ArtistSong {
+            song_name: attributes.get("name").and_then(|v| v.as_str())?.to_string(),
+            artist_name: attributes
+                .get("artistName")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Unknown")
+                .to_string(),
+            album_name: attributes.get("albumName").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            isrc: attributes.get("isrc").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            url: attributes.get("url").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        })
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::songrec::{RecognitionResult, SongRec};
+use crate::{Result, SongRecError};
+
+/// Labeled recording of an evaluation corpus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub path: PathBuf,
+
+    /// Track key or "Artist - Title" of the song in the recording, None if it should not match
+    pub expected: Option<String>,
+}
+
+impl EvalCase {
+    /// Whether `result` is the expected song, comparing titles case-insensitively
+    pub fn is_expected(&self, result: &RecognitionResult) -> bool {
+        let Some(expected) = &self.expected else {
+            return false;
+        };
+        let expected = expected.trim();
+        expected == result.track_key
+            || expected.eq_ignore_ascii_case(&format!("{} - {}", result.artist_name, result.song_name))
+    }
+}
+
+/// Read a CSV manifest of `file,expected` lines
+///
+/// Paths are relative to the manifest. An empty `expected` marks a recording that should
+/// not match. A `file,expected` header, blank lines and lines starting with `#` are skipped.
+pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<EvalCase>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| SongRecError::InvalidInput(format!("Could not read manifest '{}': {}", path.display(), e)))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let mut cases = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = parse_csv_line(line);
+        if cases.is_empty() && fields.first().is_some_and(|field| field.trim().eq_ignore_ascii_case("file")) {
+            continue;
+        }
+        let file = fields.first().map(|field| field.trim()).unwrap_or("");
+        if file.is_empty() || fields.len() > 2 {
+            return Err(SongRecError::InvalidInput(format!(
+                "Manifest '{}' line {}: expected file,expected",
+                path.display(),
+                index + 1
+            )));
+        }
+
+        cases.push(EvalCase {
+            path: base.join(file),
+            expected: fields.get(1).map(|field| field.trim().to_string()).filter(|field| !field.is_empty()),
+        });
+    }
+    Ok(cases)
+}
+
+/// Fields of a CSV line, with quoted fields and doubled quotes
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    fields
+}
+
+/// What one recording of the corpus was recognized as
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvalOutcome {
+    pub case: EvalCase,
+
+    /// Recognized song, as "Artist - Title"
+    pub recognized: Option<String>,
+    pub track_key: Option<String>,
+
+    /// Whether the expected song was recognized
+    pub correct: bool,
+
+    /// Recognition failed, e.g. the API could not be reached
+    pub error: Option<String>,
+
+    /// Time taken by the whole recognition, decoding included
+    pub latency_ms: u64,
+}
+
+/// Accuracy and latency of one configuration over a corpus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Name of the configuration
+    pub label: String,
+
+    pub cases: usize,
+
+    /// Recordings that should match a song
+    pub expected: usize,
+
+    /// Recordings recognized as some song
+    pub matched: usize,
+
+    /// Recordings recognized as their expected song
+    pub correct: usize,
+
+    /// Recordings that could not be recognized because of an error, not counted as misses
+    pub errors: usize,
+
+    /// Share of matches that were the expected song, 1 without any match
+    pub precision: f32,
+
+    /// Share of expected songs that were recognized, 1 without any expected song
+    pub recall: f32,
+
+    pub latency_p50_ms: u64,
+    pub latency_p90_ms: u64,
+    pub latency_p99_ms: u64,
+
+    pub outcomes: Vec<EvalOutcome>,
+}
+
+impl EvalReport {
+    /// Compute the metrics of a set of outcomes, latencies only count recognitions without error
+    pub fn from_outcomes(label: &str, outcomes: Vec<EvalOutcome>) -> Self {
+        let completed: Vec<&EvalOutcome> = outcomes.iter().filter(|outcome| outcome.error.is_none()).collect();
+        let expected = completed.iter().filter(|outcome| outcome.case.expected.is_some()).count();
+        let matched = completed.iter().filter(|outcome| outcome.recognized.is_some()).count();
+        let correct = completed.iter().filter(|outcome| outcome.correct).count();
+        let ratio = |count: usize, total: usize| if total == 0 { 1.0 } else { count as f32 / total as f32 };
+
+        let mut latencies: Vec<u64> = completed.iter().map(|outcome| outcome.latency_ms).collect();
+        latencies.sort_unstable();
+        let percentile = |percent: usize| -> u64 {
+            // Nearest rank
+            let rank = ((latencies.len() * percent) as f64 / 100.0).ceil().max(1.0) as usize;
+            latencies.get(rank - 1).copied().unwrap_or(0)
+        };
+
+        Self {
+            label: label.to_string(),
+            cases: outcomes.len(),
+            expected,
+            matched,
+            correct,
+            errors: outcomes.len() - completed.len(),
+            precision: ratio(correct, matched),
+            recall: ratio(correct, expected),
+            latency_p50_ms: percentile(50),
+            latency_p90_ms: percentile(90),
+            latency_p99_ms: percentile(99),
+            outcomes,
+        }
+    }
+}
+
+/// Recognize every recording of `cases` one after the other, calling `on_outcome` after each
+pub fn evaluate<F>(songrec: &SongRec, label: &str, cases: &[EvalCase], mut on_outcome: F) -> EvalReport
+where
+    F: FnMut(&EvalOutcome),
+{
+    let outcomes = cases
+        .iter()
+        .map(|case| {
+            let started = Instant::now();
+            let result = songrec.recognize_from_file(&case.path.to_string_lossy());
+            let latency_ms = started.elapsed().as_millis() as u64;
+
+            let outcome = match result {
+                Ok(result) => EvalOutcome {
+                    case: case.clone(),
+                    recognized: Some(format!("{} - {}", result.artist_name, result.song_name)),
+                    correct: case.is_expected(&result),
+                    track_key: Some(result.track_key),
+                    error: None,
+                    latency_ms,
+                },
+                Err(e) => EvalOutcome {
+                    case: case.clone(),
+                    recognized: None,
+                    track_key: None,
+                    correct: false,
+                    error: (!matches!(e, SongRecError::NoMatch(_))).then(|| e.to_string()),
+                    latency_ms,
+                },
+            };
+            on_outcome(&outcome);
+            outcome
+        })
+        .collect();
+
+    EvalReport::from_outcomes(label, outcomes)
+}
@@ -0,0 +1,191 @@
+//! File names built from song metadata, safe on the file systems of each platform
+//!
+//! Titles and artists hold anything: slashes, colons, emoji, right-to-left text and
+//! invisible direction marks. `sanitize_filename` turns them into a single valid path
+//! component, which `LibraryOrganizer` and tracklist splitting use for the names they make.
+
+/// File system rules a file name must follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Windows: no `<>:"/\|?*`, no trailing dot or space, no device names such as `CON` or `COM1`,
+    /// at most 255 UTF-16 code units
+    Windows,
+    /// macOS: no `/` nor `:`, which the Finder shows as `/`, at most 255 bytes of UTF-8
+    MacOs,
+    /// Linux and other Unix systems: no `/`, at most 255 bytes of UTF-8
+    Unix,
+    /// The rules of all the platforms above, for files that may be copied anywhere
+    Portable,
+}
+
+impl Platform {
+    /// Platform the library was built for
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else {
+            Platform::Unix
+        }
+    }
+
+    fn is_reserved(self, c: char) -> bool {
+        match self {
+            Platform::Windows | Platform::Portable => matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'),
+            Platform::MacOs => matches!(c, '/' | ':'),
+            Platform::Unix => c == '/',
+        }
+    }
+
+    /// Length of a name in the unit the file system limits
+    fn length(self, name: &str) -> usize {
+        match self {
+            Platform::Windows => name.encode_utf16().count(),
+            // Both limits hold, and UTF-8 is never shorter than UTF-16
+            Platform::MacOs | Platform::Unix | Platform::Portable => name.len(),
+        }
+    }
+}
+
+/// What replaces the characters a platform does not allow in file names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    /// This character, e.g. '-'; '_' if it is not allowed either
+    Char(char),
+    /// Nothing, the characters are dropped
+    Remove,
+    /// A Unicode character that looks the same, e.g. '∕' (U+2215) for '/', so names read as the metadata
+    LookAlike,
+}
+
+impl Default for Replacement {
+    fn default() -> Self {
+        Replacement::Char('_')
+    }
+}
+
+/// Longest file name of every supported platform, in bytes or UTF-16 code units
+pub const MAX_FILENAME_LENGTH: usize = 255;
+
+/// Device names Windows reserves whatever the extension, e.g. "aux.mp3"
+const WINDOWS_DEVICES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest extension kept when a name is shortened, including the dot
+const MAX_EXTENSION_LENGTH: usize = 16;
+
+/// Make `name` a valid file name on `platform`, replacing disallowed characters with '_'
+///
+/// See `sanitize_filename_with` for what is changed. An empty result becomes "Unknown".
+pub fn sanitize_filename(name: &str, platform: Platform) -> String {
+    sanitize_filename_with(name, platform, Replacement::default())
+}
+
+/// Make `name` a valid file name on `platform`, replacing disallowed characters as asked
+///
+/// - Characters reserved by the platform are replaced, control characters are dropped
+/// - Bidirectional control characters are dropped: a right-to-left override could show
+///   "song\u{202E}3pm.exe" as "songexe.mp3". Right-to-left letters are kept
+/// - Leading and trailing spaces, and trailing dots, are removed
+/// - Windows device names get a '_' appended, e.g. "CON_.mp3"
+/// - Names over `MAX_FILENAME_LENGTH` are shortened before their extension, without splitting
+///   an emoji sequence or a letter from its accents
+pub fn sanitize_filename_with(name: &str, platform: Platform, replacement: Replacement) -> String {
+    let is_dropped = |c: char| c.is_control() || is_bidi_control(c);
+    let replacement = match replacement {
+        Replacement::Char(c) if is_dropped(c) || platform.is_reserved(c) => Replacement::default(),
+        replacement => replacement,
+    };
+
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if is_dropped(c) {
+            continue;
+        }
+        if !platform.is_reserved(c) {
+            sanitized.push(c);
+            continue;
+        }
+        match replacement {
+            Replacement::Char(replacement) => sanitized.push(replacement),
+            Replacement::Remove => {}
+            Replacement::LookAlike => sanitized.push(look_alike(c)),
+        }
+    }
+
+    let mut sanitized = sanitized.trim_start().trim_end_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+
+    if matches!(platform, Platform::Windows | Platform::Portable) {
+        let stem = sanitized.split('.').next().unwrap_or("");
+        if WINDOWS_DEVICES.iter().any(|device| stem.eq_ignore_ascii_case(device)) {
+            sanitized.insert(stem.len(), '_');
+        }
+    }
+
+    let sanitized = shorten(&sanitized, platform);
+    if sanitized.is_empty() { "Unknown".to_string() } else { sanitized }
+}
+
+/// Shorten a name to the length limit of the platform, keeping a short extension
+fn shorten(name: &str, platform: Platform) -> String {
+    if platform.length(name) <= MAX_FILENAME_LENGTH {
+        return name.to_string();
+    }
+
+    let extension = name
+        .rfind('.')
+        .filter(|&dot| dot > 0 && name.len() - dot <= MAX_EXTENSION_LENGTH)
+        .map_or("", |dot| &name[dot..]);
+    let stem = &name[..name.len() - extension.len()];
+    let budget = MAX_FILENAME_LENGTH - platform.length(extension);
+
+    let mut end = 0;
+    let mut length = 0;
+    let mut characters = stem.char_indices().peekable();
+    while let Some((index, c)) = characters.next() {
+        length += platform.length(c.encode_utf8(&mut [0; 4]));
+        if length > budget {
+            break;
+        }
+        // Only cut between two characters that do not form a single symbol
+        let next = characters.peek().map(|&(_, next)| next);
+        if c != '\u{200D}' && !next.is_some_and(is_joined_to_previous) {
+            end = index + c.len_utf8();
+        }
+    }
+
+    let stem = stem[..end].trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    format!("{}{}", stem, extension)
+}
+
+/// Characters shown as part of the character before them: accents, variation selectors,
+/// emoji skin tones and tags, and the character after a zero-width joiner
+fn is_joined_to_previous(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}' | '\u{FE20}'..='\u{FE2F}' | '\u{200D}' | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Invisible characters that change the direction text is displayed in
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+fn look_alike(c: char) -> char {
+    match c {
+        '/' => '∕',
+        '\\' => '⧵',
+        ':' => '꞉',
+        '*' => '∗',
+        '?' => '？',
+        '"' => '＂',
+        '<' => '‹',
+        '>' => '›',
+        '|' => '∣',
+        c => c,
+    }
+}
@@ -1,327 +1,348 @@
 use chfft::RFft1D;
+use songrec_core::peaks::{Fft, FFT_SIZE};
 use std::error::Error;
-use std::io::BufReader;
-use std::collections::HashMap;
-
-use crate::fingerprinting::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
-use crate::fingerprinting::signature_format::{DecodedSignature, FrequencyBand, FrequencyPeak};
-
-
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::audio::decoder;
+use crate::audio::drm::{drm_protection, DRM_PROBE_BYTES};
+use crate::audio::opus::{self, OggCodec};
+use crate::config::{ChannelMode, SpectralWeighting};
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::SongRecError;
+
+/// Loudest sample of audio considered silent, about -80 dBFS
+const SILENCE_PEAK: u16 = 3;
+
+/// 128-sample steps in one second of 16 KHz audio, used to pace progress reports
+const STEPS_PER_SECOND: usize = 16000 / 128;
+
+/// Generates Shazam signatures from mono 16 KHz audio
+///
+/// Either fingerprint a complete recording at once with `make_signature_from_buffer`,
+/// or stream audio through `feed()` and call `finalize()` once `ready()` returns true.
+/// Audio can be fed in slices of any length; splitting it into the 128-sample steps of
+/// the algorithm and the warm-up of the first steps are handled internally.
 pub struct SignatureGenerator {
+    core: songrec_core::SignatureGenerator<ChFft>,
 
-    // Used when processing input:
-
-    ring_buffer_of_samples: Vec<i16>,
-    /// Ring buffer.
-    ring_buffer_of_samples_index: usize,
-
-    reordered_ring_buffer_of_samples: Vec<f32>,
-    /// Reordered, temporary version of the ring buffer above, with floats for precision because we applied Hanning window.
-
-    fft_outputs: Vec<Vec<f32>>,
-    /// Ring buffer. Lists of 1025 floats, premultiplied with a Hanning function before being passed through FFT, computed from the ring buffer every new 128 samples
-    fft_outputs_index: usize,
-
-    fft_object: RFft1D<f32>,
-
-    spread_fft_outputs: Vec<Vec<f32>>,
-    /// Ring buffer.
-    spread_fft_outputs_index: usize,
-
-    num_spread_ffts_done: u32,
-
-    signature: DecodedSignature,
+    /// Sample rate written in signatures, only changed by the deprecated `do_fft`
+    sample_rate_hz: u32,
 }
 
 impl SignatureGenerator {
     pub fn make_signature_from_file(file_path: &str) -> Result<DecodedSignature, Box<dyn Error>> {
+        let raw_pcm_samples = Self::decode_file(file_path, ChannelMode::Mid)?;
+        Self::make_signature_from_recording(&raw_pcm_samples, &format!("file '{}'", file_path), SpectralWeighting::None)
+    }
+
+    /// Decode a complete .WAV, .MP3, .OGG, .FLAC, .M4A or video file held in memory
+    pub fn make_signature_from_bytes(data: &[u8]) -> Result<DecodedSignature, Box<dyn Error>> {
+        let raw_pcm_samples = Self::decode_bytes(data, ChannelMode::Mid)?;
+        Self::make_signature_from_recording(&raw_pcm_samples, "audio data", SpectralWeighting::None)
+    }
+
+    /// Decode an audio file to mono 16 KHz samples
+    pub(crate) fn decode_file(file_path: &str, channel_mode: ChannelMode) -> Result<Vec<i16>, Box<dyn Error>> {
+        Self::decode_file_with_progress(file_path, channel_mode, &mut |_| {})
+    }
+
+    /// Same as `decode_file`, reporting the share of the file read so far as a percentage
+    pub(crate) fn decode_file_with_progress(file_path: &str, channel_mode: ChannelMode, progress: &mut dyn FnMut(f32)) -> Result<Vec<i16>, Box<dyn Error>> {
         // Check if file exists
         if !std::path::Path::new(file_path).exists() {
             return Err(format!("File not found: {}", file_path).into());
         }
 
-        // Decode the .WAV, .MP3, .OGG or .FLAC file
-        let file = std::fs::File::open(file_path)
+        // Decode the .WAV, .MP3, .OGG, .FLAC or .M4A file, or the audio of an MP4, MOV, WebM or MKV video
+        let mut file = std::fs::File::open(file_path)
             .map_err(|e| format!("Failed to open file '{}': {}", file_path, e))?;
-        
-        let decoder = rodio::Decoder::new(BufReader::new(file))
-            .map_err(|e| format!("Failed to decode audio file '{}': {}. Note: M4A/AAC format may not be fully supported on all platforms.", file_path, e))?;
-        
-        // Downsample the raw PCM samples to 16 KHz, and skip to the middle of the file
-        // in order to increase recognition odds. Take 12 seconds of sample.
 
-        let converted_file = rodio::source::UniformSourceIterator::new(decoder, 1, 16000);
-
-        let raw_pcm_samples: Vec<i16> = converted_file.collect();
-        
-        // Check if we got any samples
-        if raw_pcm_samples.is_empty() {
-            return Err(format!("No audio samples could be extracted from file '{}'. The file may be corrupted or in an unsupported format.", file_path).into());
+        let mut start = Vec::new();
+        (&mut file).take(DRM_PROBE_BYTES).read_to_end(&mut start)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .map_err(|e| format!("Failed to read file '{}': {}", file_path, e))?;
+        if let Some(drm) = drm_protection(&start) {
+            return Err(Box::new(SongRecError::UnsupportedMedia(format!("'{}' is protected by {} DRM", file_path, drm))));
         }
 
-        let mut raw_pcm_samples_slice: &[i16] = &raw_pcm_samples;
-
-        let slice_len = raw_pcm_samples_slice.len().min(12 * 16000);
-        
-        // Check if we have enough samples for fingerprinting (at least 3 seconds)
-        if slice_len < 3 * 16000 {
-            return Err(format!("Audio file '{}' is too short for fingerprinting. Need at least 3 seconds of audio, but only got {:.2} seconds.", 
-                file_path, slice_len as f32 / 16000.0).into());
-        }
+        let file_size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
 
-        if raw_pcm_samples_slice.len() > 12 * 16000 {
-            let middle = raw_pcm_samples.len() / 2;
+        // The decoder does not know the length of every format, so progress is
+        // measured by how far into the file it has read
+        let position = Arc::new(AtomicU64::new(0));
+        let reader = PositionTrackingReader { inner: file, position: position.clone() };
 
-            raw_pcm_samples_slice = &raw_pcm_samples_slice[middle - (6 * 16000)..middle + (6 * 16000)];
-        }
+        let mut report = |_: usize| {
+            if file_size > 0 {
+                progress((position.load(Ordering::Relaxed) as f32 * 100.0 / file_size as f32).min(100.0));
+            }
+        };
+        let samples = Self::decode_to_mono_16khz(BufReader::new(reader), channel_mode, &mut report)
+            .map_err(|e| format!("Failed to decode audio file '{}': {}. Note: the audio of videos must be AAC, Vorbis or PCM.", file_path, e))?;
+        Self::check_audible(&samples, &format!("'{}'", file_path))?;
+        progress(100.0);
 
-        Ok(SignatureGenerator::make_signature_from_buffer(&raw_pcm_samples_slice[..slice_len]))
+        Ok(samples)
     }
 
-    pub fn make_signature_from_buffer(s16_mono_16khz_buffer: &[i16]) -> DecodedSignature {
-        let mut this = SignatureGenerator {
-            ring_buffer_of_samples: vec![0i16; 2048],
-            ring_buffer_of_samples_index: 0,
-
-            reordered_ring_buffer_of_samples: vec![0.0f32; 2048],
-
-            fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            fft_outputs_index: 0,
-
-            fft_object: RFft1D::<f32>::new(2048),
-
-            spread_fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            spread_fft_outputs_index: 0,
-
-            num_spread_ffts_done: 0,
-
-            signature: DecodedSignature {
-                sample_rate_hz: 16000,
-                number_samples: s16_mono_16khz_buffer.len() as u32,
-                frequency_band_to_sound_peaks: HashMap::new(),
-            },
-        };        for chunk in s16_mono_16khz_buffer.chunks_exact(128) {
-            this.do_fft_internal(chunk);
-
-            this.do_peak_spreading();
-
-            this.num_spread_ffts_done += 1;
-
-            if this.num_spread_ffts_done >= 46 {
-                this.do_peak_recognition();
-            }
+    /// Decode an encoded audio file held in memory to mono 16 KHz samples
+    pub(crate) fn decode_bytes(data: &[u8], channel_mode: ChannelMode) -> Result<Vec<i16>, Box<dyn Error>> {
+        if let Some(drm) = drm_protection(data) {
+            return Err(Box::new(SongRecError::UnsupportedMedia(format!("the audio data is protected by {} DRM", drm))));
         }
-
-        this.signature
+        Ok(Self::decode_to_mono_16khz(Cursor::new(data.to_vec()), channel_mode, &mut |_| {})
+            .map_err(|e| format!("Failed to decode audio data: {}", e))?)
     }
 
-    /// Create a new SignatureGenerator instance for streaming recognition
-    pub fn new() -> Self {
-        Self {
-            ring_buffer_of_samples: vec![0i16; 2048],
-            ring_buffer_of_samples_index: 0,
-            reordered_ring_buffer_of_samples: vec![0.0f32; 2048],
-            fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            fft_outputs_index: 0,
-            fft_object: RFft1D::<f32>::new(2048),
-            spread_fft_outputs: vec![vec![0.0f32; 1025]; 256],
-            spread_fft_outputs_index: 0,
-            num_spread_ffts_done: 0,
-            signature: DecodedSignature {
-                sample_rate_hz: 16000,
-                number_samples: 0,
-                frequency_band_to_sound_peaks: HashMap::new(),
-            },
+    /// Reject decoded audio that holds no sound, which no lookup could match
+    pub(crate) fn check_audible(samples: &[i16], source_name: &str) -> Result<(), SongRecError> {
+        if samples.is_empty() {
+            Err(SongRecError::UnsupportedMedia(format!("{} holds no audio that could be decoded", source_name)))
+        } else if samples.iter().all(|sample| sample.unsigned_abs() <= SILENCE_PEAK) {
+            Err(SongRecError::UnsupportedMedia(format!("{} is silent", source_name)))
+        } else {
+            Ok(())
         }
     }
 
-    /// Process audio samples and update the signature
-    /// This is a public version of do_fft that also updates sample count
-    pub fn do_fft(&mut self, s16_mono_16khz_buffer: &[i16], sample_rate: u32) {
-        // Update sample count
-        self.signature.number_samples += s16_mono_16khz_buffer.len() as u32;
-        self.signature.sample_rate_hz = sample_rate;
-
-        // Call the internal FFT processing
-        self.do_fft_internal(s16_mono_16khz_buffer);
-        
-        self.do_peak_spreading();
-        self.num_spread_ffts_done += 1;
-
-        if self.num_spread_ffts_done >= 46 {
-            self.do_peak_recognition();
+    /// `on_second` is called with the number of samples decoded so far, after each second of audio
+    fn decode_to_mono_16khz<R>(mut reader: R, channel_mode: ChannelMode, on_second: &mut dyn FnMut(usize)) -> Result<Vec<i16>, String>
+    where
+        R: BufRead + Seek + Send + Sync + 'static,
+    {
+        // Downsample the raw PCM samples to 16 KHz, the middle of the recording is
+        // selected later in order to increase recognition odds
+
+        let mut samples = Vec::new();
+        let mut push = |sample: i16| {
+            samples.push(sample);
+            if samples.len() % 16000 == 0 {
+                on_second(samples.len());
+            }
+        };
+
+        // Opus and Speex are found in Ogg files like Vorbis, but symphonia only reads Vorbis
+        if let Some(codec) = OggCodec::detect(reader.fill_buf().map_err(|e| e.to_string())?) {
+            opus::decode(reader, codec, channel_mode, &mut push)?;
+        } else {
+            decoder::decode_to_mono_16khz(reader, channel_mode, &mut push)?;
         }
+        Ok(samples)
     }
 
-    /// Get the current signature
-    pub fn get_signature(&self) -> DecodedSignature {
-        self.signature.clone()
+    /// Fingerprint up to 12 seconds from the middle of a complete mono 16 KHz recording
+    /// (skipping to the middle increases recognition odds)
+    pub(crate) fn make_signature_from_recording(raw_pcm_samples: &[i16], source_name: &str, weighting: SpectralWeighting) -> Result<DecodedSignature, Box<dyn Error>> {
+        Self::make_signature_from_recording_with_progress(raw_pcm_samples, source_name, weighting, &mut |_| {})
     }
 
-    fn do_fft_internal(&mut self, s16_mono_16khz_buffer: &[i16]) {
-
-        // Copy the 128 input s16le samples to the local ring buffer
-
-        self.ring_buffer_of_samples[self.ring_buffer_of_samples_index..self.ring_buffer_of_samples_index + 128].copy_from_slice(s16_mono_16khz_buffer);
-
-        self.ring_buffer_of_samples_index += 128;
-        self.ring_buffer_of_samples_index &= 2047;
-
-        // Reorder the items (put the latest data at end) and apply Hanning window
-
-        for (index, multiplier) in HANNING_WINDOW_2048_MULTIPLIERS.iter().enumerate() {
-            self.reordered_ring_buffer_of_samples[index] =
-                self.ring_buffer_of_samples[(index + self.ring_buffer_of_samples_index) & 2047] as f32 *
-                    multiplier;
+    /// Same as `make_signature_from_recording`, reporting the fingerprinted percentage
+    pub(crate) fn make_signature_from_recording_with_progress(raw_pcm_samples: &[i16], source_name: &str, weighting: SpectralWeighting, progress: &mut dyn FnMut(f32)) -> Result<DecodedSignature, Box<dyn Error>> {
+        // Check if we got any samples
+        if raw_pcm_samples.is_empty() {
+            return Err(format!("No audio samples could be extracted from {}. The file may be corrupted or in an unsupported format.", source_name).into());
         }
 
-        // Perform Fast Fourier transform
-
-        let complex_fft_results = self.fft_object.forward(&self.reordered_ring_buffer_of_samples);
-
-        assert_eq!(complex_fft_results.len(), 1025);
-
-        // Turn complex into reals, and put the results into a local array
+        let window = Self::recording_window(raw_pcm_samples);
 
-        let real_fft_results = &mut self.fft_outputs[self.fft_outputs_index];
-
-        for index in 0..=1024 {
-            real_fft_results[index] = (
-                (
-                    complex_fft_results[index].re.powi(2) +
-                        complex_fft_results[index].im.powi(2)
-                ) / ((1 << 17) as f32)
-            ).max(0.0000000001);
+        // Check if we have enough samples for fingerprinting (at least 3 seconds)
+        if window.len() < 3 * 16000 {
+            return Err(format!("Audio {} is too short for fingerprinting. Need at least 3 seconds of audio, but only got {:.2} seconds.", 
+                source_name, window.len() as f32 / 16000.0).into());
         }
 
-        self.fft_outputs_index += 1;
-        self.fft_outputs_index &= 255;
+        Ok(SignatureGenerator::make_signature_from_buffer_with_progress(window, weighting, progress))
     }
 
-    fn do_peak_spreading(&mut self) {
-        let real_fft_results = &self.fft_outputs[((self.fft_outputs_index as i32 - 1) & 255) as usize];
-
-        let spread_fft_results = &mut self.spread_fft_outputs[self.spread_fft_outputs_index];
-
-        // Perform frequency-domain spreading of peak values
+    /// The 12 seconds from the middle of a recording fingerprinted by `make_signature_from_recording`
+    pub(crate) fn recording_window(raw_pcm_samples: &[i16]) -> &[i16] {
+        if raw_pcm_samples.len() > 12 * 16000 {
+            let middle = raw_pcm_samples.len() / 2;
+            &raw_pcm_samples[middle - (6 * 16000)..middle + (6 * 16000)]
+        } else {
+            raw_pcm_samples
+        }
+    }
 
-        spread_fft_results.copy_from_slice(real_fft_results);
+    pub fn make_signature_from_buffer(s16_mono_16khz_buffer: &[i16]) -> DecodedSignature {
+        Self::make_signature_from_buffer_with_progress(s16_mono_16khz_buffer, SpectralWeighting::None, &mut |_| {})
+    }
 
-        for position in 0..=1022 {
-            spread_fft_results[position] = spread_fft_results[position]
-                .max(spread_fft_results[position + 1])
-                .max(spread_fft_results[position + 2]);
-        }
+    /// Same as `make_signature_from_buffer`, weighting the spectrum before peak detection
+    pub fn make_signature_from_buffer_with_weighting(s16_mono_16khz_buffer: &[i16], weighting: SpectralWeighting) -> DecodedSignature {
+        Self::make_signature_from_buffer_with_progress(s16_mono_16khz_buffer, weighting, &mut |_| {})
+    }
 
-        // Perform time-domain spreading of peak values
+    /// Fingerprint the audio as if it played each of `shifts` percent faster then slower
+    /// than recorded, one shift at a time as the signatures are taken, along with the shift
+    ///
+    /// Speeding audio up raises all of its frequencies, so pitched vinyl, DJ tempo changes
+    /// and nightcore edits no longer line up with the original. A positive shift means the
+    /// recording plays faster than the original. Empty without shifts.
+    pub fn make_pitch_shifted_signatures<'a>(
+        s16_mono_16khz_buffer: &'a [i16],
+        shifts: &'a [f32],
+        weighting: SpectralWeighting,
+    ) -> impl Iterator<Item = (f32, DecodedSignature)> + 'a {
+        shifts.iter().flat_map(|&percent| [percent, -percent]).map(move |percent| {
+            let restored = stretch(s16_mono_16khz_buffer, 1.0 + percent as f64 / 100.0);
+            (percent, Self::make_signature_from_buffer_with_weighting(&restored, weighting))
+        })
+    }
 
-        let spread_fft_results_copy = spread_fft_results.clone(); // Avoid mutable+mutable borrow of self.spread_fft_outputs
+    /// Same as `make_signature_from_buffer`, reporting the processed percentage about once per second of audio
+    pub(crate) fn make_signature_from_buffer_with_progress(s16_mono_16khz_buffer: &[i16], weighting: SpectralWeighting, progress: &mut dyn FnMut(f32)) -> DecodedSignature {
+        let mut this = SignatureGenerator::new().with_weighting(weighting);
 
-        for position in 0..=1024 {
-            for former_fft_number in &[1, 3, 6] {
-                let former_fft_output = &mut self.spread_fft_outputs[((self.spread_fft_outputs_index as i32 - *former_fft_number) & 255) as usize];
+        // Fed a second at a time, leftover samples are not fingerprinted either way
+        let total_steps = s16_mono_16khz_buffer.len() / 128;
+        for (index, second) in s16_mono_16khz_buffer.chunks(128 * STEPS_PER_SECOND).enumerate() {
+            this.core.feed(second);
 
-                former_fft_output[position] = former_fft_output[position]
-                    .max(spread_fft_results_copy[position]);
+            if second.len() == 128 * STEPS_PER_SECOND {
+                progress(((index + 1) * STEPS_PER_SECOND) as f32 * 100.0 / total_steps as f32);
             }
         }
+        progress(100.0);
 
-        self.spread_fft_outputs_index += 1;
-        self.spread_fft_outputs_index &= 255;
+        this.finalize()
     }
 
-    fn do_peak_recognition(&mut self) {
-
-        // Note: when substracting an array index, casting to signed is needed
-        // to avoid underflow panics at runtime.
-
-        let fft_minus_46 = &self.fft_outputs[((self.fft_outputs_index as i32 - 46) & 255) as usize];
-        let fft_minus_49 = &self.spread_fft_outputs[((self.spread_fft_outputs_index as i32 - 49) & 255) as usize];
-
-        for bin_position in 10..=1014 {
-
-            // Ensure that the bin is large enough to be a peak
-
-            if fft_minus_46[bin_position] >= 1.0 / 64.0 &&
-                fft_minus_46[bin_position] >= fft_minus_49[bin_position - 1] {
-
-                // Ensure that it is frequency-domain local minimum
-
-                let mut max_neighbor_in_fft_minus_49: f32 = 0.0;
-
-                for neighbor_offset in &[-10, -7, -4, -3, 1, 2, 5, 8] {
-                    max_neighbor_in_fft_minus_49 = max_neighbor_in_fft_minus_49
-                        .max(fft_minus_49[(bin_position as i32 + *neighbor_offset) as usize]);
-                }
-
-                if fft_minus_46[bin_position] > max_neighbor_in_fft_minus_49 {
-
-                    // Ensure that it is a time-domain local minimum
-
-                    let mut max_neighbor_in_other_adjacent_ffts = max_neighbor_in_fft_minus_49;
+    /// Create a new SignatureGenerator instance for streaming recognition
+    pub fn new() -> Self {
+        Self {
+            core: songrec_core::SignatureGenerator::new(ChFft(RFft1D::<f32>::new(FFT_SIZE))),
+            sample_rate_hz: 16000,
+        }
+    }
 
-                    for other_offset in &[-53, -45,
-                        165, 172, 179, 186, 193, 200,
-                        214, 221, 228, 235, 242, 249] {
-                        let other_fft = &self.spread_fft_outputs[((self.spread_fft_outputs_index as i32 + other_offset) & 255) as usize];
+    /// Weight the spectrum before peak detection, see `SpectralWeighting`
+    pub fn with_weighting(mut self, weighting: SpectralWeighting) -> Self {
+        self.core = self.core.with_bin_gains(bin_gains(weighting));
+        self
+    }
 
-                        max_neighbor_in_other_adjacent_ffts = max_neighbor_in_other_adjacent_ffts
-                            .max(other_fft[bin_position - 1]);
-                    }
+    /// Feed mono 16 KHz samples
+    ///
+    /// Slices of any length are accepted: samples are buffered internally and processed
+    /// in the 128-sample steps the algorithm works with.
+    pub fn feed(&mut self, s16_mono_16khz_buffer: &[i16]) {
+        self.core.feed(s16_mono_16khz_buffer);
+    }
 
-                    if fft_minus_46[bin_position] > max_neighbor_in_other_adjacent_ffts {
+    /// Whether enough audio was fed for a signature worth sending
+    pub fn ready(&self) -> bool {
+        self.core.ready()
+    }
 
-                        // This is a peak, store the peak
+    /// Return the signature of all audio fed so far and start over with an empty one
+    pub fn finalize(&mut self) -> DecodedSignature {
+        let mut signature = DecodedSignature::from(self.core.finalize());
+        signature.sample_rate_hz = self.sample_rate_hz;
+        signature
+    }
 
-                        let fft_pass_number = self.num_spread_ffts_done - 46;
+    /// Forget all audio fed so far, keeping the allocated buffers and FFT plan
+    pub fn reset(&mut self) {
+        self.core.reset();
+    }
 
-                        let peak_magnitude: f32 = fft_minus_46[bin_position].ln().max(1.0 / 64.0) * 1477.3 + 6144.0;
-                        let peak_magnitude_before: f32 = fft_minus_46[bin_position - 1].ln().max(1.0 / 64.0) * 1477.3 + 6144.0;
-                        let peak_magnitude_after: f32 = fft_minus_46[bin_position + 1].ln().max(1.0 / 64.0) * 1477.3 + 6144.0;
+    /// Process audio samples and update the signature
+    #[deprecated(since = "0.5.4", note = "use feed() with mono 16 KHz samples")]
+    pub fn do_fft(&mut self, s16_mono_16khz_buffer: &[i16], sample_rate: u32) {
+        self.feed(s16_mono_16khz_buffer);
+        self.sample_rate_hz = sample_rate;
+    }
 
-                        let peak_variation_1: f32 = peak_magnitude * 2.0 - peak_magnitude_before - peak_magnitude_after;
-                        let peak_variation_2: f32 = (peak_magnitude_after - peak_magnitude_before) * 32.0 / peak_variation_1;
+    /// Get the current signature
+    #[deprecated(since = "0.5.4", note = "use finalize() once ready() returns true")]
+    pub fn get_signature(&self) -> DecodedSignature {
+        let mut signature = DecodedSignature::from(self.core.signature());
+        signature.sample_rate_hz = self.sample_rate_hz;
+        signature
+    }
+}
 
-                        let corrected_peak_frequency_bin: u16 = ((bin_position as i32 * 64) + (peak_variation_2 as i32)) as u16;
+impl Default for SignatureGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-                        assert!(peak_variation_1 >= 0.0);
+/// The FFT of the fingerprinting core, computed with chfft
+struct ChFft(RFft1D<f32>);
 
-                        // Convert back a FFT bin to a frequency, given a 16 KHz sample
-                        // rate, 1024 useful bins and the multiplication by 64 made before
-                        // storing the information
+impl Fft for ChFft {
+    fn forward(&mut self, input: &[f32], output: &mut [(f32, f32)]) {
+        for (value, complex) in output.iter_mut().zip(self.0.forward(input)) {
+            *value = (complex.re, complex.im);
+        }
+    }
+}
 
-                        let frequency_hz: f32 = corrected_peak_frequency_bin as f32 * (16000.0 / 2.0 / 1024.0 / 64.0);
+/// Power gain of each of the 1025 FFT bins of 16 KHz audio, normalized to 1 at 1 kHz
+fn bin_gains(weighting: SpectralWeighting) -> Option<Vec<f32>> {
+    let power_gain: fn(f64) -> f64 = match weighting {
+        SpectralWeighting::None => return None,
+        SpectralWeighting::PreEmphasis => |frequency| {
+            let coefficient = 0.97;
+            1.0 + coefficient * coefficient - 2.0 * coefficient * (std::f64::consts::TAU * frequency / 16000.0).cos()
+        },
+        SpectralWeighting::AWeighting => |frequency| {
+            let f2 = frequency * frequency;
+            let response = 12194f64.powi(2) * f2 * f2
+                / ((f2 + 20.6f64.powi(2)) * ((f2 + 107.7f64.powi(2)) * (f2 + 737.9f64.powi(2))).sqrt() * (f2 + 12194f64.powi(2)));
+            response * response
+        },
+    };
+
+    let reference = power_gain(1000.0);
+    Some((0..=1024).map(|bin| (power_gain(bin as f64 * 16000.0 / 2048.0) / reference) as f32).collect())
+}
 
-                        // Ignore peaks outside the 250 Hz-5.5 KHz range, store them into
-                        // a lookup table that will be used to generate the binary fingerprint
-                        // otherwise
+/// Reader that publishes its current position, so decoding progress can be followed
+struct PositionTrackingReader<R> {
+    inner: R,
+    position: Arc<AtomicU64>,
+}
 
-                        let frequency_band = match frequency_hz as i32 {
-                            250..=519 => FrequencyBand::_250_520,
-                            520..=1449 => FrequencyBand::_520_1450,
-                            1450..=3499 => FrequencyBand::_1450_3500,
-                            3500..=5500 => FrequencyBand::_3500_5500,
-                            _ => { continue; }
-                        };
+impl<R: Read> Read for PositionTrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
 
-                        // In Rust, the entry method returns an Entry object,
-                        // which represents a cell in a HashMap that is either occupied or vacant.
-                        // You can use or_default to insert a value if the key is missing,
-                        // which avoids a double search of the key in the hash map.
-                        self.signature.frequency_band_to_sound_peaks
-                            .entry(frequency_band)
-                            .or_default();
+impl<R: Seek> Seek for PositionTrackingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.position.store(position, Ordering::Relaxed);
+        Ok(position)
+    }
+}
 
-                        self.signature.frequency_band_to_sound_peaks.get_mut(&frequency_band).unwrap().push(
-                            FrequencyPeak {
-                                fft_pass_number,
-                                peak_magnitude: peak_magnitude as u16,
-                                corrected_peak_frequency_bin
-                            }
-                        );
-                    }
-                }
-            }
-        }
+/// Error of the decode functions as a `SongRecError`, keeping `UnsupportedMedia` as is
+pub(crate) fn decode_error(error: Box<dyn Error>) -> SongRecError {
+    match error.downcast::<SongRecError>() {
+        Ok(error) => *error,
+        Err(error) => SongRecError::FingerprintingError(error.to_string()),
     }
 }
+
+/// Resample audio to `factor` times its length with linear interpolation, which slows
+/// it down and lowers its pitch above 1
+fn stretch(samples: &[i16], factor: f64) -> Vec<i16> {
+    let length = (samples.len() as f64 * factor) as usize;
+    (0..length)
+        .map(|index| {
+            let position = index as f64 / factor;
+            let before = position as usize;
+            let after = (before + 1).min(samples.len() - 1);
+            let fraction = position - before as f64;
+            (samples[before] as f64 * (1.0 - fraction) + samples[after] as f64 * fraction) as i16
+        })
+        .collect()
+}
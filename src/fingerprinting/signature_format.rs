@@ -1,38 +1,12 @@
 use std::collections::HashMap;
 use std::error::Error;
-use std::io::{Cursor, Seek, SeekFrom, Write};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::cmp::Ordering;
+use std::io::{Cursor, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt};
 use crc32fast::Hasher;
 
-const DATA_URI_PREFIX: &str = "data:audio/vnd.shazam.sig;base64,";
+use songrec_core::signature::{encode_binary, DATA_URI_PREFIX, HEADER_SIZE};
 
-#[derive(Clone)]
-pub struct FrequencyPeak {
-    pub fft_pass_number: u32,
-    pub peak_magnitude: u16,
-    pub corrected_peak_frequency_bin: u16
-}
-
-#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
-pub enum FrequencyBand {
-    _250_520 = 0,
-    _520_1450 = 1,
-    _1450_3500 = 2,
-    _3500_5500 = 3
-}
-
-impl Ord for FrequencyBand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        (*self as i32).cmp(&(*other as i32))
-    }
-}
-
-impl PartialOrd for FrequencyBand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some((*self as i32).cmp(&(*other as i32)))
-    }
-}
+pub use songrec_core::signature::{FrequencyBand, FrequencyPeak, MAX_SIGNATURE_SIZE};
 
 struct RawSignatureHeader {
     
@@ -48,7 +22,7 @@ struct RawSignatureHeader {
     
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct DecodedSignature {
     
     pub sample_rate_hz: u32,
@@ -57,11 +31,30 @@ pub struct DecodedSignature {
 
 }
 
+impl From<songrec_core::Signature> for DecodedSignature {
+    fn from(signature: songrec_core::Signature) -> Self {
+        DecodedSignature {
+            sample_rate_hz: signature.sample_rate_hz,
+            number_samples: signature.number_samples,
+            frequency_band_to_sound_peaks: signature.frequency_band_to_sound_peaks.into_iter().collect(),
+        }
+    }
+}
+
 impl DecodedSignature {
     
+    /// Decode a binary signature
+    ///
+    /// Signatures may come from untrusted sources, so malformed input is reported as an
+    /// error rather than a panic, and inputs larger than `MAX_SIGNATURE_SIZE` are rejected.
     pub fn decode_from_binary(data: &[u8]) -> Result<Self, Box<dyn Error>> {
         
-        assert!(data.len() > 48 + 8);
+        if data.len() < HEADER_SIZE + 8 {
+            return Err(format!("Signature too short: {} bytes", data.len()).into());
+        }
+        if data.len() > MAX_SIGNATURE_SIZE {
+            return Err(format!("Signature too large: {} bytes", data.len()).into());
+        }
         
         let mut cursor = Cursor::new(data);
         
@@ -79,10 +72,15 @@ impl DecodedSignature {
         
         let mut hasher = Hasher::new();
         hasher.update(&data[8..]);
-        assert_eq!(header.magic1, 0xcafe2580);
-        assert_eq!(header.size_minus_header as usize, data.len() - 48);
-        assert_eq!(header.crc32, hasher.finalize());
-        assert_eq!(header.magic2, 0x94119c00);
+        if header.magic1 != 0xcafe2580 || header.magic2 != 0x94119c00 {
+            return Err("Invalid magic numbers in Shazam signature".into());
+        }
+        if header.size_minus_header as usize != data.len() - HEADER_SIZE {
+            return Err("Invalid size in Shazam signature header".into());
+        }
+        if header.crc32 != hasher.finalize() {
+            return Err("CRC-32 mismatch in Shazam signature".into());
+        }
         
         let sample_rate_hz: u32 = match header.shifted_sample_rate_id >> 27 {
             1 => 8000,
@@ -91,18 +89,21 @@ impl DecodedSignature {
             4 => 32000,
             5 => 44100,
             6 => 48000,
-            _ => { panic!("Invalid sample rate in decoded Shazam packet"); }
+            _ => { return Err("Invalid sample rate in decoded Shazam packet".into()); }
         };
         
-        let number_samples: u32 = header.number_samples_plus_divided_sample_rate - (sample_rate_hz as f32 * 0.24) as u32;
+        let number_samples: u32 = header.number_samples_plus_divided_sample_rate
+            .checked_sub((sample_rate_hz as f32 * 0.24) as u32)
+            .ok_or("Invalid sample count in decoded Shazam packet")?;
         
         // Read the type-length-value sequence that follows the header
         
         // The first chunk is fixed and has no value, but instead just repeats
         // the length of the message size minus the header:
         
-        assert_eq!(cursor.read_u32::<LittleEndian>()?, 0x40000000);
-        assert_eq!(cursor.read_u32::<LittleEndian>()? as usize, data.len() - 48);
+        if cursor.read_u32::<LittleEndian>()? != 0x40000000 || cursor.read_u32::<LittleEndian>()? as usize != data.len() - HEADER_SIZE {
+            return Err("Invalid first chunk in Shazam signature".into());
+        }
         
         // Then, lists of frequency peaks for respective bands follow
         
@@ -111,20 +112,24 @@ impl DecodedSignature {
         while cursor.position() < data.len() as u64 {
             
             let frequency_band_id = cursor.read_u32::<LittleEndian>()?;
-            let frequency_peaks_size = cursor.read_u32::<LittleEndian>()?;
+            let frequency_peaks_size = cursor.read_u32::<LittleEndian>()? as usize;
             
             let frequency_peaks_padding = (4 - frequency_peaks_size % 4) % 4;
             
-            let mut frequency_peaks_cursor = Cursor::new(&data[cursor.position() as usize..(cursor.position() as u32 + frequency_peaks_size) as usize]);
+            let frequency_peaks_start = cursor.position() as usize;
+            let frequency_peaks_data = frequency_peaks_start.checked_add(frequency_peaks_size)
+                .and_then(|end| data.get(frequency_peaks_start..end))
+                .ok_or("Frequency peaks chunk exceeds the signature size")?;
+            let mut frequency_peaks_cursor = Cursor::new(frequency_peaks_data);
             
             // Decode frequency peaks
             
-            let frequency_band = match frequency_band_id - 0x60030040 {
+            let frequency_band = match frequency_band_id.wrapping_sub(0x60030040) {
                 0 => FrequencyBand::_250_520,
                 1 => FrequencyBand::_520_1450,
                 2 => FrequencyBand::_1450_3500,
                 3 => FrequencyBand::_3500_5500,
-                _ => { panic!("Invalid frequency band in decoded Shazam packet"); }
+                _ => { return Err("Invalid frequency band in decoded Shazam packet".into()); }
             };
             
             let mut fft_pass_number: u32 = 0;
@@ -138,19 +143,16 @@ impl DecodedSignature {
                         fft_pass_number = frequency_peaks_cursor.read_u32::<LittleEndian>()?;
                     },
                     _ => {
-                        fft_pass_number += fft_pass_offset as u32;
+                        fft_pass_number = fft_pass_number.checked_add(fft_pass_offset as u32)
+                            .ok_or("FFT pass number overflow in decoded Shazam packet")?;
                         
-                        if !frequency_band_to_sound_peaks.contains_key(&frequency_band) {
-                            frequency_band_to_sound_peaks.insert(frequency_band, vec![]);
-                        }
+                        let frequency_peak = FrequencyPeak {
+                            fft_pass_number,
+                            peak_magnitude: frequency_peaks_cursor.read_u16::<LittleEndian>()?,
+                            corrected_peak_frequency_bin: frequency_peaks_cursor.read_u16::<LittleEndian>()?
+                        };
 
-                        frequency_band_to_sound_peaks.get_mut(&frequency_band).unwrap().push(
-                            FrequencyPeak {
-                                fft_pass_number,
-                                peak_magnitude: frequency_peaks_cursor.read_u16::<LittleEndian>()?,
-                                corrected_peak_frequency_bin: frequency_peaks_cursor.read_u16::<LittleEndian>()?
-                            }
-                        );
+                        frequency_band_to_sound_peaks.entry(frequency_band).or_default().push(frequency_peak);
                     }
                 };
                 
@@ -163,107 +165,34 @@ impl DecodedSignature {
         // Return the decoded object
         
         Ok(DecodedSignature {
-            sample_rate_hz: sample_rate_hz,
-            number_samples: number_samples,
-            frequency_band_to_sound_peaks: frequency_band_to_sound_peaks
+            sample_rate_hz,
+            number_samples,
+            frequency_band_to_sound_peaks
         })
         
     }
     
     pub fn decode_from_uri(uri: &str) -> Result<Self, Box<dyn Error>> {
         
-        assert!(uri.starts_with(DATA_URI_PREFIX));
+        let encoded = uri.strip_prefix(DATA_URI_PREFIX)
+            .ok_or("Signature URI does not start with the Shazam data URI prefix")?;
+        
+        // Base64 encodes 3 bytes in 4 characters, reject oversized input before decoding it
+        if encoded.len() > MAX_SIGNATURE_SIZE / 3 * 4 + 4 {
+            return Err(format!("Signature URI too long: {} characters", uri.len()).into());
+        }
         
-        Ok(DecodedSignature::decode_from_binary(&base64::decode(&uri[DATA_URI_PREFIX.len()..])?)?)
+        DecodedSignature::decode_from_binary(&base64::decode(encoded)?)
         
     }
     
     pub fn encode_to_binary(&self) -> Result<Vec<u8>, Box<dyn Error>> {
         
-        let mut cursor = Cursor::new(vec![]);
-        
-        // Please see the RawSignatureHeader structure definition above for
-        // information about the following fields.
-        
-        cursor.write_u32::<LittleEndian>(0xcafe2580)?; // magic1
-        cursor.write_u32::<LittleEndian>(0)?; // crc32 - Will write later
-        cursor.write_u32::<LittleEndian>(0)?; // size_minus_header - Will write later
-        cursor.write_u32::<LittleEndian>(0x94119c00)?; // magic2
-        cursor.write_u32::<LittleEndian>(0)?; // void1
-        cursor.write_u32::<LittleEndian>(0)?;
-        cursor.write_u32::<LittleEndian>(0)?;
-        cursor.write_u32::<LittleEndian>(match self.sample_rate_hz {
-            8000 => 1,
-            11025 => 2,
-            16000 => 3,
-            32000 => 4,
-            44100 => 5,
-            48000 => 6,
-            _ => { panic!("Invalid sample rate passed when encoding Shazam packet"); }
-        } << 27)?; // shifted_sample_rate_id
-        cursor.write_u32::<LittleEndian>(0)?; // void2
-        cursor.write_u32::<LittleEndian>(0)?;
-        cursor.write_u32::<LittleEndian>(self.number_samples + (self.sample_rate_hz as f32 * 0.24) as u32)?; // number_samples_plus_divided_sample_rate
-        cursor.write_u32::<LittleEndian>((15 << 19) + 0x40000)?; // fixed_value
-        
-        cursor.write_u32::<LittleEndian>(0x40000000)?;
-        cursor.write_u32::<LittleEndian>(0)?; // size_minus_header - Will write later
-        
-        let mut sorted_iterator: Vec<_> = self.frequency_band_to_sound_peaks.iter().collect();
-        sorted_iterator.sort_by(|x, y| x.0.cmp(y.0));
-        
-        for (frequency_band, frequency_peaks) in sorted_iterator {
-            
-            let mut peaks_cursor = Cursor::new(vec![]);
-            
-            let mut fft_pass_number = 0;
-            
-            for frequency_peak in frequency_peaks {
-                
-                assert!(frequency_peak.fft_pass_number >= fft_pass_number);
-                
-                if frequency_peak.fft_pass_number - fft_pass_number >= 255 {
-                    
-                    peaks_cursor.write_u8(0xff)?;
-                    peaks_cursor.write_u32::<LittleEndian>(frequency_peak.fft_pass_number)?;
-                    
-                    fft_pass_number = frequency_peak.fft_pass_number;
-                }
-                
-                peaks_cursor.write_u8((frequency_peak.fft_pass_number - fft_pass_number) as u8)?;
-                
-                peaks_cursor.write_u16::<LittleEndian>(frequency_peak.peak_magnitude)?;
-                peaks_cursor.write_u16::<LittleEndian>(frequency_peak.corrected_peak_frequency_bin)?;
-                
-                fft_pass_number = frequency_peak.fft_pass_number;
-                
-            }
-            
-            let peaks_buffer = peaks_cursor.into_inner();
-            
-            cursor.write_u32::<LittleEndian>(0x60030040 + *frequency_band as u32)?;
-            cursor.write_u32::<LittleEndian>(peaks_buffer.len() as u32)?;
-            cursor.write_all(&peaks_buffer)?;
-            for _padding_index in 0..((4 - peaks_buffer.len() as u32 % 4) % 4) {
-                cursor.write_u8(0)?;
-            }
-            
-        }
-        
-        let buffer_size = cursor.position() as u32;
-        
-        cursor.seek(SeekFrom::Start(8))?;
-        cursor.write_u32::<LittleEndian>(buffer_size - 48)?;
-        
-        cursor.seek(SeekFrom::Start(48 + 4))?;
-        cursor.write_u32::<LittleEndian>(buffer_size - 48)?;
-        
-        cursor.seek(SeekFrom::Start(4))?;
-        let mut hasher = Hasher::new();
-        hasher.update(&cursor.get_ref()[8..]);
-        cursor.write_u32::<LittleEndian>(hasher.finalize())?; // crc32
-
-        Ok(cursor.into_inner())
+        Ok(encode_binary(
+            self.sample_rate_hz,
+            self.number_samples,
+            self.frequency_band_to_sound_peaks.iter().map(|(band, peaks)| (*band, peaks.as_slice())),
+        )?)
     }
     
     pub fn encode_to_uri(&self) -> Result<String, Box<dyn Error>> {
@@ -271,5 +200,56 @@ impl DecodedSignature {
         Ok(format!("{}{}", DATA_URI_PREFIX, base64::encode(self.encode_to_binary()?)))
         
     }
+
+    /// Number of peaks in all frequency bands
+    pub fn peak_count(&self) -> usize {
+        self.frequency_band_to_sound_peaks.values().map(Vec::len).sum()
+    }
+
+    /// How much of the audio of two signatures is the same, from 0 (unrelated) to 1
+    ///
+    /// Peaks of the same band and frequency are paired and the time offsets between them
+    /// counted: recordings of the same audio agree on one offset, whatever the position of
+    /// each clip in the song. The score is the share of peaks of the shorter signature that
+    /// agree on the best offset. Works locally, without any network call.
+    pub fn similarity(&self, other: &DecodedSignature) -> f32 {
+        // Frequencies in 1/64 FFT bins, peaks half a bin apart still pair up
+        const FREQUENCY_TOLERANCE: i64 = 32;
+
+        let smallest = self.peak_count().min(other.peak_count());
+        if smallest == 0 {
+            return 0.0;
+        }
+
+        // Passes of the other signature's peaks, by band and FFT bin
+        let mut other_peaks: HashMap<(FrequencyBand, i64), Vec<&FrequencyPeak>> = HashMap::new();
+        for (band, peaks) in &other.frequency_band_to_sound_peaks {
+            for peak in peaks {
+                other_peaks.entry((*band, peak.corrected_peak_frequency_bin as i64 / 64)).or_default().push(peak);
+            }
+        }
+
+        let mut offsets: HashMap<i64, usize> = HashMap::new();
+        for (band, peaks) in &self.frequency_band_to_sound_peaks {
+            for peak in peaks {
+                let frequency = peak.corrected_peak_frequency_bin as i64;
+                for bin in frequency / 64 - 1..=frequency / 64 + 1 {
+                    for other_peak in other_peaks.get(&(*band, bin)).into_iter().flatten() {
+                        if (other_peak.corrected_peak_frequency_bin as i64 - frequency).abs() <= FREQUENCY_TOLERANCE {
+                            *offsets.entry(other_peak.fft_pass_number as i64 - peak.fft_pass_number as i64).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Resampled or re-encoded audio can be off by one pass
+        let best = offsets
+            .keys()
+            .map(|offset| (offset - 1..=offset + 1).filter_map(|offset| offsets.get(&offset)).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        (best as f32 / smallest as f32).min(1.0)
+    }
     
 }
@@ -0,0 +1,87 @@
+//! Normalization of the genres of the API onto a small controlled vocabulary
+//!
+//! The API names genres inconsistently ("Hip-Hop/Rap", "Rap", "Hip Hop"), sometimes in the
+//! language of the metadata, which scatters a library organized by genre. Genres are mapped
+//! by keywords, after the exact names of a user mapping, see `Config::with_genre_mapping`.
+
+use std::collections::BTreeMap;
+
+/// Genres the built-in rules normalize to
+pub const GENRES: [&str; 19] = [
+    "Alternative", "Blues", "Children", "Christian", "Classical", "Country", "Dance", "Electronic", "Folk", "Hip-Hop",
+    "Jazz", "Latin", "Metal", "Pop", "R&B", "Reggae", "Rock", "Soundtrack", "World",
+];
+
+/// Keywords looked for in lowercase genres and the genre they map to, the first match wins
+///
+/// Compound genres come before their parts, e.g. "dancehall" before "dance" and
+/// "latin" before "pop" for "Latin Pop".
+const RULES: &[(&str, &str)] = &[
+    ("children", "Children"),
+    ("christian", "Christian"),
+    ("gospel", "Christian"),
+    ("soundtrack", "Soundtrack"),
+    ("film", "Soundtrack"),
+    ("anime", "Soundtrack"),
+    ("hip-hop", "Hip-Hop"),
+    ("hip hop", "Hip-Hop"),
+    ("rap", "Hip-Hop"),
+    ("r&b", "R&B"),
+    ("rhythm and blues", "R&B"),
+    ("soul", "R&B"),
+    ("funk", "R&B"),
+    ("reggaeton", "Latin"),
+    ("latin", "Latin"),
+    ("salsa", "Latin"),
+    ("dancehall", "Reggae"),
+    ("reggae", "Reggae"),
+    ("metal", "Metal"),
+    ("alternativ", "Alternative"),
+    ("indie", "Alternative"),
+    ("punk", "Rock"),
+    ("rock", "Rock"),
+    ("electro", "Electronic"),
+    ("elektro", "Electronic"),
+    ("house", "Electronic"),
+    ("techno", "Electronic"),
+    ("trance", "Electronic"),
+    ("drum & bass", "Electronic"),
+    ("dubstep", "Electronic"),
+    ("ambient", "Electronic"),
+    ("dance", "Dance"),
+    ("disco", "Dance"),
+    ("jazz", "Jazz"),
+    ("blues", "Blues"),
+    ("classical", "Classical"),
+    ("klassik", "Classical"),
+    ("classique", "Classical"),
+    ("opera", "Classical"),
+    ("country", "Country"),
+    ("folk", "Folk"),
+    ("singer/songwriter", "Folk"),
+    ("pop", "Pop"),
+    ("schlager", "Pop"),
+    ("world", "World"),
+    ("afro", "World"),
+];
+
+/// Normalized name of a genre of the API, None if no rule knows it
+///
+/// `mapping` holds exact genre names, compared without case, and their normalized name;
+/// it takes precedence over the built-in rules and may name genres outside `GENRES`.
+pub fn normalize(genre: &str, mapping: &BTreeMap<String, String>) -> Option<String> {
+    let genre = genre.trim();
+    if genre.is_empty() {
+        return None;
+    }
+
+    let lowercase = genre.to_lowercase();
+    if let Some((_, name)) = mapping.iter().find(|(from, _)| from.trim().to_lowercase() == lowercase) {
+        return Some(name.clone());
+    }
+
+    RULES
+        .iter()
+        .find(|(keyword, _)| lowercase.contains(keyword))
+        .map(|(_, name)| name.to_string())
+}
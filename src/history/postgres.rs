@@ -0,0 +1,318 @@
+//! PostgreSQL backend of the history, so monitors on several machines can share one database
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::DateTime;
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions, PgRow};
+use sqlx::{Postgres, Row, Transaction};
+
+use super::{contains_pattern, HistoryEntry, HistoryFilter, LastPlay, NewPlay, PlayRecord, Store, StoredSnippet, PLAY_COLUMNS};
+use crate::{Result, SongRecError};
+
+/// The tables of the SQLite history, with PostgreSQL types
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS plays (
+    id BIGSERIAL PRIMARY KEY,
+    first_seen BIGINT NOT NULL,
+    last_seen BIGINT NOT NULL,
+    detections BIGINT NOT NULL,
+    song_name TEXT NOT NULL,
+    artist_name TEXT NOT NULL,
+    album_name TEXT,
+    track_key TEXT NOT NULL,
+    device TEXT,
+    source TEXT NOT NULL,
+    agent TEXT,
+    signature TEXT,
+    previous_id BIGINT
+);
+ALTER TABLE plays ADD COLUMN IF NOT EXISTS agent TEXT;
+ALTER TABLE plays ADD COLUMN IF NOT EXISTS signature TEXT;
+ALTER TABLE plays ADD COLUMN IF NOT EXISTS previous_id BIGINT;
+CREATE INDEX IF NOT EXISTS plays_last_seen ON plays (last_seen);
+CREATE TABLE IF NOT EXISTS snippets (
+    id BIGSERIAL PRIMARY KEY,
+    play_id BIGINT NOT NULL,
+    detected_at BIGINT NOT NULL,
+    path TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS snippets_play_id ON snippets (play_id);
+CREATE TABLE IF NOT EXISTS metadata (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// History in PostgreSQL, queried through sqlx on a runtime of its own so the history API
+/// stays blocking like the SQLite one
+pub(super) struct PostgresStore {
+    runtime: tokio::runtime::Runtime,
+    pool: PgPool,
+    /// Transaction started by `begin`, rolled back if dropped before `commit`
+    transaction: Mutex<Option<Transaction<'static, Postgres>>>,
+}
+
+impl PostgresStore {
+    pub(super) fn connect(url: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| SongRecError::Internal(format!("Could not start the PostgreSQL runtime: {}", e)))?;
+
+        let pool = runtime
+            .block_on(PgPoolOptions::new().max_connections(1).acquire_timeout(Duration::from_secs(10)).connect(url))
+            .map_err(|e| SongRecError::ConfigError(format!("Could not connect to the PostgreSQL history: {}", e)))?;
+        runtime.block_on(sqlx::raw_sql(SCHEMA).execute(&pool)).map_err(database_error)?;
+
+        Ok(Self { runtime, pool, transaction: Mutex::new(None) })
+    }
+
+    /// Run `query` on the connection of the transaction in progress, or on one of the pool
+    fn run<T>(&self, query: impl FnOnce(&mut PgConnection) -> sqlx::Result<T>) -> Result<T> {
+        // Connections go back to the pool from a task of the runtime
+        let _runtime = self.runtime.enter();
+        let mut transaction = self.transaction.lock().unwrap_or_else(|e| e.into_inner());
+        match transaction.as_mut() {
+            Some(transaction) => query(transaction),
+            None => self.runtime.block_on(self.pool.acquire()).and_then(|mut connection| query(&mut connection)),
+        }
+        .map_err(database_error)
+    }
+}
+
+impl Store for PostgresStore {
+    fn last_play(&self, source: &str, agent: Option<&str>) -> Result<Option<LastPlay>> {
+        let query = sqlx::query(
+            "SELECT id, last_seen, song_name, artist_name, track_key FROM plays
+             WHERE source = $1 AND agent IS NOT DISTINCT FROM $2 ORDER BY last_seen DESC, id DESC LIMIT 1",
+        )
+        .bind(source)
+        .bind(agent);
+
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_optional(connection)))?;
+        row.map(|row| {
+            Ok(LastPlay {
+                id: row.try_get(0)?,
+                last_seen: row.try_get(1)?,
+                song_name: row.try_get(2)?,
+                artist_name: row.try_get(3)?,
+                track_key: row.try_get(4)?,
+            })
+        })
+        .transpose()
+        .map_err(database_error)
+    }
+
+    fn extend_play(&self, id: i64, seen: i64) -> Result<()> {
+        let query = sqlx::query("UPDATE plays SET last_seen = $1, detections = detections + 1 WHERE id = $2")
+            .bind(seen)
+            .bind(id);
+        self.run(|connection| self.runtime.block_on(query.execute(connection)))?;
+        Ok(())
+    }
+
+    fn insert_play(&self, play: &NewPlay) -> Result<i64> {
+        let query = sqlx::query(
+            "INSERT INTO plays (first_seen, last_seen, detections, song_name, artist_name, album_name, track_key, device, source, agent)
+             VALUES ($1, $1, 1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(play.seen)
+        .bind(&play.result.song_name)
+        .bind(&play.result.artist_name)
+        .bind(&play.result.album_name)
+        .bind(&play.result.track_key)
+        .bind(play.device)
+        .bind(play.source)
+        .bind(play.agent);
+
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_one(connection)))?;
+        row.try_get(0).map_err(database_error)
+    }
+
+    fn insert_snippet(&self, play_id: i64, detected_at: i64, path: &str) -> Result<()> {
+        let query = sqlx::query("INSERT INTO snippets (play_id, detected_at, path) VALUES ($1, $2, $3)")
+            .bind(play_id)
+            .bind(detected_at)
+            .bind(path);
+        self.run(|connection| self.runtime.block_on(query.execute(connection)))?;
+        Ok(())
+    }
+
+    fn snippets(&self, play_id: i64) -> Result<Vec<StoredSnippet>> {
+        let query = sqlx::query("SELECT id, play_id, detected_at, path FROM snippets WHERE play_id = $1 ORDER BY detected_at, id")
+            .bind(play_id);
+
+        let rows = self.run(|connection| self.runtime.block_on(query.fetch_all(connection)))?;
+        rows.iter()
+            .map(|row| {
+                Ok(StoredSnippet {
+                    id: row.try_get(0)?,
+                    play_id: row.try_get(1)?,
+                    detected_at: DateTime::from_timestamp(row.try_get(2)?, 0).unwrap_or_default(),
+                    path: PathBuf::from(row.try_get::<String, _>(3)?),
+                })
+            })
+            .collect::<sqlx::Result<Vec<_>>>()
+            .map_err(database_error)
+    }
+
+    fn expired_snippets(&self, oldest: Option<i64>, max_count: Option<i64>) -> Result<Vec<(i64, String)>> {
+        let query = sqlx::query(
+            "SELECT id, path FROM snippets
+             WHERE ($1::BIGINT IS NOT NULL AND detected_at < $1)
+                OR id NOT IN (SELECT id FROM snippets ORDER BY detected_at DESC, id DESC LIMIT $2)",
+        )
+        .bind(oldest)
+        .bind(max_count);
+
+        let rows = self.run(|connection| self.runtime.block_on(query.fetch_all(connection)))?;
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect::<sqlx::Result<Vec<_>>>()
+            .map_err(database_error)
+    }
+
+    fn delete_snippet(&self, id: i64) -> Result<()> {
+        let query = sqlx::query("DELETE FROM snippets WHERE id = $1").bind(id);
+        self.run(|connection| self.runtime.block_on(query.execute(connection)))?;
+        Ok(())
+    }
+
+    /// Same as the SQLite query, with ILIKE as LIKE is case-sensitive in PostgreSQL
+    fn list(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+        let query = sqlx::query(
+            "SELECT id, first_seen, last_seen, detections, song_name, artist_name, album_name, track_key, source, agent FROM (
+                 SELECT * FROM plays
+                 WHERE ($1::BIGINT IS NULL OR last_seen >= $1)
+                   AND ($2::TEXT IS NULL OR artist_name ILIKE $2 ESCAPE '\\')
+                   AND ($3::TEXT IS NULL OR device ILIKE $3 ESCAPE '\\')
+                   AND ($5::TEXT IS NULL OR agent = $5)
+                 ORDER BY first_seen DESC, id DESC
+                 LIMIT $4
+             ) AS recent ORDER BY first_seen, id",
+        )
+        .bind(filter.since.map(|since| since.timestamp()))
+        .bind(filter.artist.as_deref().map(contains_pattern))
+        .bind(filter.device.as_deref().map(contains_pattern))
+        .bind(filter.limit.map(|limit| limit as i64))
+        .bind(&filter.agent);
+
+        let rows = self.run(|connection| self.runtime.block_on(query.fetch_all(connection)))?;
+        rows.iter().map(history_entry).collect::<sqlx::Result<Vec<_>>>().map_err(database_error)
+    }
+
+    fn len(&self) -> Result<usize> {
+        let query = sqlx::query("SELECT COUNT(*) FROM plays");
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_one(connection)))?;
+        row.try_get::<i64, _>(0).map(|count| count as usize).map_err(database_error)
+    }
+
+    fn play(&self, id: i64) -> Result<PlayRecord> {
+        let sql = format!("SELECT {} FROM plays WHERE id = $1", PLAY_COLUMNS);
+        let query = sqlx::query(&sql).bind(id);
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_one(connection)))?;
+        play_record(&row).map_err(database_error)
+    }
+
+    fn plays(&self) -> Result<Vec<PlayRecord>> {
+        let sql = format!("SELECT {} FROM plays ORDER BY id", PLAY_COLUMNS);
+        let rows = self.run(|connection| self.runtime.block_on(sqlx::query(&sql).fetch_all(connection)))?;
+        rows.iter().map(play_record).collect::<sqlx::Result<Vec<_>>>().map_err(database_error)
+    }
+
+    fn set_signature(&self, id: i64, previous_id: Option<i64>, signature: &str) -> Result<()> {
+        let query = sqlx::query("UPDATE plays SET signature = $1, previous_id = $2 WHERE id = $3")
+            .bind(signature)
+            .bind(previous_id)
+            .bind(id);
+        self.run(|connection| self.runtime.block_on(query.execute(connection)))?;
+        Ok(())
+    }
+
+    fn last_id(&self) -> Result<Option<i64>> {
+        let query = sqlx::query("SELECT MAX(id) FROM plays");
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_one(connection)))?;
+        row.try_get(0).map_err(database_error)
+    }
+
+    fn last_signed_id(&self) -> Result<Option<i64>> {
+        let query = sqlx::query("SELECT MAX(id) FROM plays WHERE signature IS NOT NULL");
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_one(connection)))?;
+        row.try_get(0).map_err(database_error)
+    }
+
+    fn metadata(&self, key: &str) -> Result<Option<String>> {
+        let query = sqlx::query("SELECT value FROM metadata WHERE key = $1").bind(key);
+        let row = self.run(|connection| self.runtime.block_on(query.fetch_optional(connection)))?;
+        row.map(|row| row.try_get(0)).transpose().map_err(database_error)
+    }
+
+    fn insert_metadata(&self, key: &str, value: &str) -> Result<()> {
+        let query = sqlx::query("INSERT INTO metadata (key, value) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING")
+            .bind(key)
+            .bind(value);
+        self.run(|connection| self.runtime.block_on(query.execute(connection)))?;
+        Ok(())
+    }
+
+    fn begin(&self) -> Result<()> {
+        let transaction = self.runtime.block_on(self.pool.begin()).map_err(database_error)?;
+        *self.transaction.lock().unwrap_or_else(|e| e.into_inner()) = Some(transaction);
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<()> {
+        match self.transaction.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(transaction) => self.runtime.block_on(transaction.commit()).map_err(database_error),
+            None => Ok(()),
+        }
+    }
+
+    fn rollback(&self) -> Result<()> {
+        match self.transaction.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(transaction) => self.runtime.block_on(transaction.rollback()).map_err(database_error),
+            None => Ok(()),
+        }
+    }
+}
+
+fn play_record(row: &PgRow) -> sqlx::Result<PlayRecord> {
+    Ok(PlayRecord {
+        id: row.try_get(0)?,
+        first_seen: row.try_get(1)?,
+        last_seen: row.try_get(2)?,
+        detections: row.try_get(3)?,
+        song_name: row.try_get(4)?,
+        artist_name: row.try_get(5)?,
+        album_name: row.try_get(6)?,
+        track_key: row.try_get(7)?,
+        device: row.try_get(8)?,
+        source: row.try_get(9)?,
+        agent: row.try_get(10)?,
+        signature: row.try_get(11)?,
+        previous_id: row.try_get(12)?,
+    })
+}
+
+fn history_entry(row: &PgRow) -> sqlx::Result<HistoryEntry> {
+    let timestamp = |index| row.try_get::<i64, _>(index).map(|seconds| DateTime::from_timestamp(seconds, 0).unwrap_or_default());
+    let source: String = row.try_get(8)?;
+    Ok(HistoryEntry {
+        id: row.try_get(0)?,
+        first_seen: timestamp(1)?,
+        last_seen: timestamp(2)?,
+        detections: row.try_get::<i64, _>(3)? as usize,
+        song_name: row.try_get(4)?,
+        artist_name: row.try_get(5)?,
+        album_name: row.try_get(6)?,
+        track_key: row.try_get(7)?,
+        source: serde_json::from_str(&source).unwrap_or_default(),
+        agent: row.try_get(9)?,
+    })
+}
+
+fn database_error(error: sqlx::Error) -> SongRecError {
+    SongRecError::ConfigError(format!("History database error: {}", error))
+}
@@ -0,0 +1,195 @@
+//! Signatures of the plays of a history, so compliance reports built from it can be shown
+//! to match what the monitor recorded
+
+use std::fmt;
+use std::path::Path;
+
+use ed25519_dalek::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+
+use super::PlayRecord;
+use crate::state::StateDir;
+use crate::{Result, SongRecError};
+
+const KEY_FILE_NAME: &str = "history-signing.key";
+
+/// First field of the signed messages, changed along with their other fields
+const MESSAGE_VERSION: &str = "songrec-play-v2";
+
+/// First field of the signed id of the first signed play
+const SIGNING_START_VERSION: &str = "songrec-signing-start-v1";
+
+/// Ed25519 key signing the plays of a history, see `History::with_signing_key`
+///
+/// Kept as base64 in a file only readable by its owner.
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    pub fn generate() -> Self {
+        let mut seed = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut seed);
+        Self(ed25519_dalek::SigningKey::from_bytes(&seed))
+    }
+
+    /// Load the key kept at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not read signing key '{}': {}", path.display(), e)))?;
+        let seed: [u8; ed25519_dalek::SECRET_KEY_LENGTH] = base64::decode(content.trim())
+            .ok()
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or_else(|| SongRecError::ConfigError(format!("Invalid signing key '{}'", path.display())))?;
+        Ok(Self(ed25519_dalek::SigningKey::from_bytes(&seed)))
+    }
+
+    /// Load the key kept at `path`, generating it on first use
+    pub fn load_or_create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let key = Self::generate();
+        write_private(path, &base64::encode(key.0.to_bytes()))?;
+        Ok(key)
+    }
+
+    /// Load the key of the state directory
+    pub fn load_default() -> Result<Self> {
+        Self::load(StateDir::require_default("signing key")?.file(KEY_FILE_NAME))
+    }
+
+    /// Load the key of the state directory, generating it on first use
+    pub fn load_or_create_default() -> Result<Self> {
+        Self::load_or_create(StateDir::require_default("signing key")?.file(KEY_FILE_NAME))
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+
+    /// Signature of a play as base64
+    pub(super) fn sign(&self, play: &PlayRecord) -> String {
+        base64::encode(self.0.sign(&message(play)).to_bytes())
+    }
+
+    /// Signature of the id of the first signed play as base64
+    pub(super) fn sign_signing_start(&self, signed_from: i64) -> String {
+        base64::encode(self.0.sign(&signing_start_message(signed_from)).to_bytes())
+    }
+}
+
+/// Public half of a `SigningKey`, all auditors need to verify a history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    /// Parse a key printed by `to_base64`
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let invalid = || SongRecError::InvalidInput(format!("Invalid public key '{}'", key));
+        let bytes: [u8; ed25519_dalek::PUBLIC_KEY_LENGTH] = base64::decode(key.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(invalid)?;
+        ed25519_dalek::VerifyingKey::from_bytes(&bytes).map(Self).map_err(|_| invalid())
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.0.to_bytes())
+    }
+
+    /// Whether the signature of a play was made by this key over its current content
+    pub(super) fn verifies(&self, play: &PlayRecord, signature: &str) -> bool {
+        self.verifies_message(&message(play), signature)
+    }
+
+    /// Whether the id of the first signed play was signed by this key
+    pub(super) fn verifies_signing_start(&self, signed_from: i64, signature: &str) -> bool {
+        self.verifies_message(&signing_start_message(signed_from), signature)
+    }
+
+    fn verifies_message(&self, message: &[u8], signature: &str) -> bool {
+        let Some(signature) = base64::decode(signature).ok().and_then(|bytes| ed25519_dalek::Signature::from_slice(&bytes).ok()) else {
+            return false;
+        };
+        self.0.verify(message, &signature).is_ok()
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+/// Outcome of `History::verify`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Verification {
+    /// Number of plays whose signature matches
+    pub verified: usize,
+
+    /// Plays recorded without a signing key before the first signed one, or any unsigned
+    /// play when the record of when signing began is missing or was not signed by the key
+    pub unsigned: Vec<i64>,
+
+    /// Plays changed since they were signed, signed with another key, or without a
+    /// signature although recorded after signing began
+    pub invalid: Vec<i64>,
+
+    /// Plays named as previous play by a signed play but no longer there, so deleted; of
+    /// several plays deleted in a row only the last is named, and deleting the last plays
+    /// goes unnoticed
+    pub missing: Vec<i64>,
+}
+
+impl Verification {
+    /// Whether every play is signed and unchanged, and no play was deleted
+    pub fn is_intact(&self) -> bool {
+        self.unsigned.is_empty() && self.invalid.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Every column of the play but its signature, as a JSON array
+fn message(play: &PlayRecord) -> Vec<u8> {
+    serde_json::json!([
+        MESSAGE_VERSION,
+        play.id,
+        play.first_seen,
+        play.last_seen,
+        play.detections,
+        play.song_name,
+        play.artist_name,
+        play.album_name,
+        play.track_key,
+        play.device,
+        play.source,
+        play.agent,
+        play.previous_id,
+    ])
+    .to_string()
+    .into_bytes()
+}
+
+fn signing_start_message(signed_from: i64) -> Vec<u8> {
+    serde_json::json!([SIGNING_START_VERSION, signed_from]).to_string().into_bytes()
+}
+
+/// Write a new file only readable by its owner
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+
+    let error = |e: std::io::Error| SongRecError::ConfigError(format!("Could not write signing key '{}': {}", path.display(), e));
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).and_then(|mut file| file.write_all(format!("{}\n", content).as_bytes())).map_err(error)
+}
@@ -0,0 +1,504 @@
+//! Central aggregation of the results of remote agents, for monitoring several locations
+//! into one history
+//!
+//! Agents (`listen --report-to URL`) post each result, not its audio, as JSON to the
+//! `/results` path of the hub with `HubReporter`. The hub (`HubServer`, CLI `hub`) records
+//! them into its history with the name of the agent. Results the hub does not take, because
+//! it is down or unreachable, are kept by the agent in an outbox file and sent again, in
+//! order, with the next result.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::Sink;
+use crate::songrec::{PipelineEvent, RecognitionResult};
+use crate::state::StateDir;
+use crate::{Result, SongRecError};
+
+const OUTBOX_FILE_NAME: &str = "hub-outbox.jsonl";
+
+/// Wait before `HubSink` sends the results kept for a hub that was down, without a new result
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Path results are posted to
+pub const RESULTS_PATH: &str = "/results";
+
+/// Longest agent name the hub accepts
+pub const MAX_AGENT_NAME_LENGTH: usize = 128;
+
+/// Largest report the hub accepts, in bytes of JSON; results are a few kilobytes
+pub const MAX_REPORT_SIZE: u64 = 1024 * 1024;
+
+/// Body of the requests posted to the hub
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentReport {
+    /// Name of the agent, the same across restarts, e.g. its host name
+    pub agent: String,
+    pub result: RecognitionResult,
+}
+
+/// Agent side: posts results to a hub, keeping those it could not deliver for later
+///
+/// `into_sink` turns it into a `Sink` reporting every match.
+pub struct HubReporter {
+    url: String,
+    agent: String,
+    token: Option<String>,
+    client: reqwest::blocking::Client,
+    outbox: Option<PathBuf>,
+    /// Reports not delivered yet, oldest first, as JSON
+    pending: VecDeque<String>,
+}
+
+impl HubReporter {
+    /// Reporter to the hub at `url`, e.g. "http://hub.local:8470"
+    pub fn new(url: &str, agent: &str) -> Result<Self> {
+        if agent.is_empty() || agent.len() > MAX_AGENT_NAME_LENGTH {
+            return Err(SongRecError::InvalidInput(format!("Invalid agent name '{}'", agent)));
+        }
+
+        Ok(Self {
+            url: format!("{}{}", url.trim_end_matches('/'), RESULTS_PATH),
+            agent: agent.to_string(),
+            token: None,
+            client: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_else(|_| reqwest::blocking::Client::new()),
+            outbox: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Token the hub was started with, sent as a bearer token
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Keep undelivered results in `path`, so they survive restarts of the agent
+    ///
+    /// Results already in the file are sent before the next one.
+    pub fn with_outbox<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
+        let path = path.into();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => self.pending = content.lines().filter(|line| !line.is_empty()).map(String::from).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SongRecError::ConfigError(format!("Could not read hub outbox '{}': {}", path.display(), e))),
+        }
+        self.outbox = Some(path);
+        Ok(self)
+    }
+
+    /// Keep undelivered results in the outbox of a state directory, see `with_outbox`
+    pub fn with_outbox_in(self, dir: &StateDir) -> Result<Self> {
+        self.with_outbox(dir.file(OUTBOX_FILE_NAME))
+    }
+
+    /// Number of results waiting for the hub
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Send `result` after the results waiting for the hub
+    ///
+    /// When the hub cannot be reached, the result is kept and an error returned.
+    pub fn report(&mut self, result: &RecognitionResult) -> Result<()> {
+        let report = AgentReport { agent: self.agent.clone(), result: result.clone() };
+        let report = serde_json::to_string(&report)
+            .map_err(|e| SongRecError::Internal(format!("Could not serialize report: {}", e)))?;
+        self.pending.push_back(report);
+
+        let delivered = self.flush();
+        self.save_outbox()?;
+        delivered
+    }
+
+    /// Send the results waiting for the hub, stopping at the first failure
+    pub fn flush(&mut self) -> Result<()> {
+        while let Some(report) = self.pending.front() {
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(report.clone());
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+
+            let unreachable = |e: &dyn std::fmt::Display, pending: usize| {
+                SongRecError::NetworkError(format!("Hub {} unreachable, {} results kept: {}", self.url, pending, e))
+            };
+            let response = request.send().map_err(|e| unreachable(&e, self.pending.len()))?;
+            let status = response.status();
+            if status.is_success() {
+                self.pending.pop_front();
+            } else if status.is_client_error() && !matches!(status.as_u16(), 401 | 403 | 408 | 429) {
+                // Sending a report the hub rejects again would hold back the others forever
+                self.pending.pop_front();
+                let message = response.text().unwrap_or_default();
+                return Err(SongRecError::InvalidInput(format!("Hub rejected a report ({}): {}", status, message.trim())));
+            } else {
+                return Err(unreachable(&status, self.pending.len()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sink reporting every match from a thread of its own, so an unreachable hub does not
+    /// hold back the other sinks
+    pub fn into_sink(self) -> HubSink {
+        let (result_tx, result_rx) = mpsc::channel();
+        let (error_tx, error_rx) = mpsc::channel();
+        let worker = thread::Builder::new()
+            .name("songrec-hub-reporter".to_string())
+            .spawn(move || self.report_all(result_rx, error_tx))
+            .ok();
+        HubSink { results: Some(result_tx), errors: error_rx, worker }
+    }
+
+    /// Report the results received until the sink is dropped, retrying the pending ones
+    /// every `RETRY_INTERVAL` while no result arrives
+    fn report_all(mut self, results: Receiver<RecognitionResult>, errors: Sender<SongRecError>) {
+        loop {
+            let delivered = match results.recv_timeout(RETRY_INTERVAL) {
+                Ok(result) => self.report(&result),
+                Err(RecvTimeoutError::Timeout) if !self.pending.is_empty() => {
+                    let delivered = self.flush();
+                    self.save_outbox().and(delivered)
+                }
+                Err(RecvTimeoutError::Timeout) => Ok(()),
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            if let Err(e) = delivered {
+                let _ = errors.send(e);
+            }
+        }
+    }
+
+    /// Write the pending results to the outbox, replacing it
+    fn save_outbox(&self) -> Result<()> {
+        let Some(path) = &self.outbox else {
+            return Ok(());
+        };
+        if self.pending.is_empty() && !path.exists() {
+            return Ok(());
+        }
+
+        let content: String = self.pending.iter().map(|report| format!("{}\n", report)).collect();
+        let temporary = path.with_extension("jsonl.tmp");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        std::fs::write(&temporary, content)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|e| SongRecError::ConfigError(format!("Could not write hub outbox '{}': {}", path.display(), e)))
+    }
+}
+
+/// Reports every match to a hub from a thread of its own, see `HubReporter::into_sink`
+///
+/// Errors of a report are returned by the next `emit`, the result staying in the outbox.
+/// Dropping the sink waits for the results not reported yet, which end up in the outbox
+/// if the hub cannot take them.
+pub struct HubSink {
+    results: Option<Sender<RecognitionResult>>,
+    errors: Receiver<SongRecError>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl Sink for HubSink {
+    fn emit(&mut self, event: &PipelineEvent) -> std::result::Result<(), Box<dyn Error>> {
+        if let (PipelineEvent::Match { result }, Some(results)) = (event, &self.results) {
+            results
+                .send((**result).clone())
+                .map_err(|_| SongRecError::Internal("The hub reporter stopped".to_string()))?;
+        }
+        match self.errors.try_recv() {
+            Ok(e) => Err(Box::new(e)),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl Drop for HubSink {
+    fn drop(&mut self) {
+        self.results.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(feature = "history")]
+pub use server::HubServer;
+
+#[cfg(feature = "history")]
+mod server {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use sha2::{Digest, Sha256};
+
+    use super::{AgentReport, MAX_AGENT_NAME_LENGTH, MAX_REPORT_SIZE, RESULTS_PATH};
+    use crate::history::History;
+    use crate::{Result, SongRecError};
+
+    /// Largest request line and headers accepted
+    const MAX_HEADER_SIZE: u64 = 16 * 1024;
+
+    /// Bytes of a report too large read after answering, so the agent gets the answer
+    /// rather than a reset connection
+    const MAX_DISCARDED_SIZE: u64 = 4 * MAX_REPORT_SIZE;
+
+    /// Connections handled at once, the next ones wait to be accepted
+    const MAX_CONNECTIONS: usize = 16;
+
+    /// Time an agent has to send its whole request, however slowly its bytes arrive
+    const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+
+    /// Hub side: records the results posted by agents into a history (requires the history feature)
+    ///
+    /// A minimal HTTP/1.1 server handling one request per connection, on a thread per
+    /// connection up to `MAX_CONNECTIONS` at once. Connections are plain text, put it behind
+    /// a reverse proxy for TLS.
+    pub struct HubServer {
+        listener: TcpListener,
+        history: Arc<Mutex<History>>,
+        token: Option<String>,
+    }
+
+    /// What the connection threads share
+    struct Recorder {
+        history: Arc<Mutex<History>>,
+        token: Option<String>,
+    }
+
+    impl HubServer {
+        /// Listen on `address`, e.g. "127.0.0.1:8470"; agents on other hosts need an
+        /// address they can reach, along with `with_token`
+        pub fn bind(address: &str, history: History) -> Result<Self> {
+            let listener = TcpListener::bind(address)
+                .map_err(|e| SongRecError::ConfigError(format!("Could not listen on {}: {}", address, e)))?;
+            Ok(Self { listener, history: Arc::new(Mutex::new(history)), token: None })
+        }
+
+        /// Only accept agents sending this bearer token
+        pub fn with_token(mut self, token: &str) -> Self {
+            self.token = Some(token.to_string());
+            self
+        }
+
+        pub fn local_addr(&self) -> Result<SocketAddr> {
+            self.listener
+                .local_addr()
+                .map_err(|e| SongRecError::Internal(format!("No local address: {}", e)))
+        }
+
+        /// Serve agents until the listener fails
+        pub fn serve(&self) -> Result<()> {
+            let recorder = Arc::new(Recorder { history: Arc::clone(&self.history), token: self.token.clone() });
+            let slots = Arc::new(Slots { active: Mutex::new(0), freed: Condvar::new() });
+            loop {
+                slots.acquire();
+                let slot = Slot(Arc::clone(&slots));
+                let (connection, _) = self
+                    .listener
+                    .accept()
+                    .map_err(|e| SongRecError::NetworkError(format!("Could not accept agents: {}", e)))?;
+
+                let recorder = Arc::clone(&recorder);
+                let spawned = thread::Builder::new().name("songrec-hub-connection".to_string()).spawn(move || {
+                    let _slot = slot;
+                    // An agent dropping its connection only concerns that agent, which sends again
+                    let _ = recorder.handle(connection);
+                });
+                if let Err(e) = spawned {
+                    return Err(SongRecError::Internal(format!("Could not handle an agent: {}", e)));
+                }
+            }
+        }
+    }
+
+    impl Recorder {
+        fn handle(&self, connection: TcpStream) -> std::io::Result<()> {
+            connection.set_write_timeout(Some(REQUEST_DEADLINE))?;
+
+            let deadline = Instant::now() + REQUEST_DEADLINE;
+            let mut reader = BufReader::new(DeadlineReader { connection: &connection, deadline }.take(MAX_HEADER_SIZE));
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line)?;
+            let mut parts = request_line.split_whitespace();
+            let (method, path) = (parts.next().unwrap_or_default().to_string(), parts.next().unwrap_or_default().to_string());
+
+            let mut content_length = None;
+            let mut authorization = None;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header)? == 0 {
+                    return Ok(());
+                }
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    match name.trim().to_ascii_lowercase().as_str() {
+                        "content-length" => content_length = value.trim().parse::<u64>().ok(),
+                        "authorization" => authorization = Some(value.trim().to_string()),
+                        _ => {}
+                    }
+                }
+            }
+
+            // Read before answering, closing with unread data would reset the connection; the
+            // body may have been buffered along with the headers
+            let body = match content_length {
+                Some(length) if length <= MAX_REPORT_SIZE => {
+                    let buffered = reader.buffer().len() as u64;
+                    reader.get_mut().set_limit(length.saturating_sub(buffered));
+                    let mut body = vec![0; length as usize];
+                    reader.read_exact(&mut body)?;
+                    Some(body)
+                }
+                _ => None,
+            };
+
+            let (status, message) = if path != RESULTS_PATH {
+                (404, "Not found".to_string())
+            } else if method != "POST" {
+                (405, "Results are posted".to_string())
+            } else if !self.is_authorized(authorization.as_deref()) {
+                (401, "Invalid token".to_string())
+            } else {
+                match &body {
+                    Some(body) => self.record(body),
+                    None if content_length.is_none() => (411, "Content-Length required".to_string()),
+                    None => (413, "Report too large".to_string()),
+                }
+            };
+
+            let mut connection = &connection;
+            write!(
+                connection,
+                "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                reason(status),
+                message.len(),
+                message
+            )?;
+            connection.flush()?;
+
+            // Discard the body of a report too large while the agent reads the answer, up to the
+            // deadline of the request
+            if body.is_none() && content_length.is_some() {
+                connection.shutdown(Shutdown::Write)?;
+                reader.get_mut().set_limit(MAX_DISCARDED_SIZE);
+                std::io::copy(&mut reader, &mut std::io::sink())?;
+            }
+            Ok(())
+        }
+
+        fn is_authorized(&self, authorization: Option<&str>) -> bool {
+            match &self.token {
+                Some(token) => authorization
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .is_some_and(|sent| same_secret(sent, token)),
+                None => true,
+            }
+        }
+
+        /// Record a report, with the status and message of the response
+        fn record(&self, body: &[u8]) -> (u16, String) {
+            let report: AgentReport = match serde_json::from_slice(body) {
+                Ok(report) => report,
+                Err(e) => return (400, format!("Invalid report: {}", e)),
+            };
+            if report.agent.is_empty() || report.agent.len() > MAX_AGENT_NAME_LENGTH {
+                return (400, format!("Invalid agent name '{}'", report.agent));
+            }
+
+            // Plays are merged with the last one of the agent, one report is recorded at a time
+            let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+            match history.record_from_agent(&report.agent, &report.result) {
+                Ok(()) => (200, "Recorded".to_string()),
+                Err(e) => (500, e.to_string()),
+            }
+        }
+    }
+
+    /// Whether two secrets are equal, in a time that does not depend on where they differ
+    ///
+    /// Hashes are compared, so neither does it depend on their lengths.
+    fn same_secret(sent: &str, expected: &str) -> bool {
+        let (sent, expected) = (Sha256::digest(sent.as_bytes()), Sha256::digest(expected.as_bytes()));
+        sent.iter().zip(expected.iter()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+    }
+
+    /// Connections being handled, up to `MAX_CONNECTIONS`
+    struct Slots {
+        active: Mutex<usize>,
+        freed: Condvar,
+    }
+
+    impl Slots {
+        /// Wait for a connection to end if all slots are taken, then take one
+        fn acquire(&self) {
+            let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+            while *active >= MAX_CONNECTIONS {
+                active = self.freed.wait(active).unwrap_or_else(|e| e.into_inner());
+            }
+            *active += 1;
+        }
+    }
+
+    /// Slot of a connection, given back when its thread ends, even by a panic
+    struct Slot(Arc<Slots>);
+
+    impl Drop for Slot {
+        fn drop(&mut self) {
+            *self.0.active.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+            self.0.freed.notify_one();
+        }
+    }
+
+    /// Reads of a connection failing once the deadline of its request is past, so an agent
+    /// sending a byte now and then cannot hold a connection
+    struct DeadlineReader<'a> {
+        connection: &'a TcpStream,
+        deadline: Instant,
+    }
+
+    impl Read for DeadlineReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let remaining = self.deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Request deadline passed"));
+            }
+            self.connection.set_read_timeout(Some(remaining))?;
+            self.connection.read(buf)
+        }
+    }
+
+    fn reason(status: u16) -> &'static str {
+        match status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            411 => "Length Required",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
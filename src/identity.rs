@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::logging::{log, LogLevel};
+use crate::state::StateDir;
+
+const IDENTITY_FILE_NAME: &str = "identity.json";
+
+/// Client identity sent along with recognition requests
+///
+/// Real Shazam clients keep the same installation UUID for their whole lifetime and
+/// only generate a new tag UUID per recognition, so the identity is persisted in the
+/// state directory instead of being regenerated for every request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    /// Installation UUID, stable across runs
+    pub install_id: String,
+
+    /// Number of tags (recognition requests) sent with this installation UUID
+    pub tag_count: u64,
+}
+
+/// Identity in use, with the file it is persisted in
+static IDENTITY: Mutex<Option<(Option<PathBuf>, Identity)>> = Mutex::new(None);
+
+impl Identity {
+    /// Return the identity to use for the next request and generate its tag UUID
+    ///
+    /// An install ID set in the config always takes precedence over the persisted one.
+    pub fn next_tag(config: &Config) -> (String, String) {
+        let tag_id = Uuid::new_v4().to_hyphenated().to_string().to_uppercase();
+
+        if let Some(install_id) = &config.install_id {
+            return (tag_id, install_id.clone());
+        }
+
+        // The identity is loaded again when a configuration uses another state directory
+        let path = StateDir::for_config(config).map(|dir| dir.file(IDENTITY_FILE_NAME));
+        let mut current = IDENTITY.lock().unwrap_or_else(|e| e.into_inner());
+        if current.as_ref().map(|(current_path, _)| current_path) != Some(&path) {
+            *current = Some((path.clone(), Self::load_or_create(path.as_deref(), config)));
+        }
+        let Some((_, identity)) = current.as_mut() else {
+            unreachable!("the identity was just loaded");
+        };
+
+        identity.tag_count += 1;
+        if let Err(e) = identity.save(path.as_deref()) {
+            log(config, LogLevel::Warn, format_args!("Could not persist client identity: {}", e));
+        }
+
+        (tag_id, identity.install_id.clone())
+    }
+
+    /// Load the persisted identity, or create a new one if none exists yet
+    fn load_or_create(path: Option<&std::path::Path>, config: &Config) -> Self {
+        let loaded = path
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        loaded.unwrap_or_else(|| {
+            log(config, LogLevel::Info, format_args!("Generating a new installation identity"));
+            Identity {
+                install_id: Uuid::new_v4().to_hyphenated().to_string(),
+                tag_count: 0,
+            }
+        })
+    }
+
+    fn save(&self, path: Option<&std::path::Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.ok_or("No state directory available")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
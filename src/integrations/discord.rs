@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::config::DiscordConfig;
+use crate::songrec::RecognitionResult;
+
+/// Keeps a single "now playing" message up to date in a Discord channel through a webhook
+pub struct DiscordNowPlaying {
+    config: DiscordConfig,
+    client: reqwest::blocking::Client,
+    current_track_key: Option<String>,
+    message_id: Option<String>,
+    last_seen: Instant,
+}
+
+impl DiscordNowPlaying {
+    /// Create a new webhook publisher
+    pub fn new(config: DiscordConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::blocking::Client::new(),
+            current_track_key: None,
+            message_id: None,
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Publish a recognized song; repeated matches of the same song only refresh the staleness timer
+    pub fn update(&mut self, result: &RecognitionResult) -> Result<(), Box<dyn Error>> {
+        self.last_seen = Instant::now();
+
+        if self.current_track_key.as_deref() == Some(result.track_key.as_str()) {
+            return Ok(());
+        }
+
+        let payload = json!({
+            "content": format!("🎵 Now playing: **{}** by {}", result.song_name, result.artist_name)
+        });
+
+        match &self.message_id {
+            Some(message_id) => {
+                self.client
+                    .patch(format!("{}/messages/{}", self.config.webhook_url, message_id))
+                    .json(&payload)
+                    .send()?
+                    .error_for_status()?;
+            }
+            None => {
+                let response: Value = self.client
+                    .post(&self.config.webhook_url)
+                    .query(&[("wait", "true")])
+                    .json(&payload)
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+
+                self.message_id = response.get("id").and_then(|v| v.as_str()).map(|s| s.to_string());
+            }
+        }
+
+        self.current_track_key = Some(result.track_key.clone());
+        Ok(())
+    }
+
+    /// Remove the "now playing" message once no match was seen for the configured duration
+    pub fn clear_if_stale(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.last_seen.elapsed() < Duration::from_secs(self.config.stale_after) {
+            return Ok(());
+        }
+
+        if let Some(message_id) = self.message_id.take() {
+            self.current_track_key = None;
+            self.client
+                .delete(format!("{}/messages/{}", self.config.webhook_url, message_id))
+                .send()?
+                .error_for_status()?;
+        }
+
+        Ok(())
+    }
+}
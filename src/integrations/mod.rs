@@ -0,0 +1,10 @@
+//! Optional integrations notified of each match in continuous mode
+
+#[cfg(feature = "spotify")]
+pub mod spotify;
+
+#[cfg(feature = "discord")]
+pub mod discord;
+
+#[cfg(feature = "osc")]
+pub mod osc;
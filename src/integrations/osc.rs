@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::net::UdpSocket;
+
+use crate::config::OscConfig;
+use crate::songrec::RecognitionResult;
+
+/// Sends an OSC message to a lighting console or VJ tool whenever the recognized song changes
+///
+/// The message is sent to the configured address with `{event}` replaced by `track`,
+/// and carries the song name, artist, album, genre and track key as string arguments.
+pub struct OscSender {
+    config: OscConfig,
+    socket: UdpSocket,
+    current_track_key: Option<String>,
+}
+
+impl OscSender {
+    /// Create a new sender bound to an ephemeral local UDP port
+    pub fn new(config: OscConfig) -> Result<Self, Box<dyn Error>> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect((config.host.as_str(), config.port))?;
+
+        Ok(Self {
+            config,
+            socket,
+            current_track_key: None,
+        })
+    }
+
+    /// Send a track change message if the recognized song differs from the previous one
+    pub fn update(&mut self, result: &RecognitionResult) -> Result<(), Box<dyn Error>> {
+        if self.current_track_key.as_deref() == Some(result.track_key.as_str()) {
+            return Ok(());
+        }
+
+        let address = self.config.address_template.replace("{event}", "track");
+        let packet = encode_message(&address, &[
+            &result.song_name,
+            &result.artist_name,
+            result.album_name.as_deref().unwrap_or(""),
+            result.genre.as_deref().unwrap_or(""),
+            &result.track_key,
+        ]);
+
+        self.socket.send(&packet)?;
+        self.current_track_key = Some(result.track_key.clone());
+        Ok(())
+    }
+}
+
+/// Encode an OSC 1.0 message whose arguments are all strings
+fn encode_message(address: &str, arguments: &[&str]) -> Vec<u8> {
+    let mut packet = Vec::new();
+
+    write_padded_string(&mut packet, address);
+    write_padded_string(&mut packet, &format!(",{}", "s".repeat(arguments.len())));
+    for argument in arguments {
+        write_padded_string(&mut packet, argument);
+    }
+
+    packet
+}
+
+/// OSC strings are null-terminated and padded with nulls to a multiple of 4 bytes
+fn write_padded_string(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    packet.resize(packet.len() + padding, 0);
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::config::SpotifyConfig;
+use crate::songrec::RecognitionResult;
+
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const API_URL: &str = "https://api.spotify.com/v1";
+
+/// Appends recognized songs to a Spotify playlist
+pub struct SpotifyPlaylist {
+    config: SpotifyConfig,
+    client: reqwest::blocking::Client,
+    access_token: Option<(String, Instant)>,
+    added_uris: HashSet<String>,
+}
+
+impl SpotifyPlaylist {
+    /// Create a new playlist writer from the given credentials
+    pub fn new(config: SpotifyConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::blocking::Client::new(),
+            access_token: None,
+            added_uris: HashSet::new(),
+        }
+    }
+
+    /// Search Spotify for the recognized song and append it to the playlist
+    /// Returns the Spotify URI of the added track, or None if it was not found or was already added
+    pub fn add_track(&mut self, result: &RecognitionResult) -> Result<Option<String>, Box<dyn Error>> {
+        let uri = match self.search_track(result)? {
+            Some(uri) => uri,
+            None => return Ok(None),
+        };
+
+        if self.added_uris.contains(&uri) {
+            return Ok(None);
+        }
+
+        let token = self.access_token()?;
+        let response = self.client
+            .post(format!("{}/playlists/{}/tracks", API_URL, self.config.playlist_id))
+            .bearer_auth(token)
+            .json(&json!({ "uris": [uri] }))
+            .send()?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Spotify playlist update failed: HTTP {}", status.as_u16()).into());
+        }
+
+        self.added_uris.insert(uri.clone());
+        Ok(Some(uri))
+    }
+
+    /// Find the Spotify URI of a recognized song, preferring an exact ISRC match
+    fn search_track(&mut self, result: &RecognitionResult) -> Result<Option<String>, Box<dyn Error>> {
+        if let Some(isrc) = result.isrc() {
+            if let Some(uri) = self.search(&format!("isrc:{}", isrc))? {
+                return Ok(Some(uri));
+            }
+        }
+
+        self.search(&format!("track:{} artist:{}", result.song_name, result.primary_artist()))
+    }
+
+    fn search(&mut self, query: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let token = self.access_token()?;
+        let response: Value = self.client
+            .get(format!("{}/search", API_URL))
+            .bearer_auth(token)
+            .query(&[("q", query), ("type", "track"), ("limit", "1")])
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        Ok(response
+            .pointer("/tracks/items/0/uri")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()))
+    }
+
+    /// Return a valid access token, refreshing it when expired
+    fn access_token(&mut self) -> Result<String, Box<dyn Error>> {
+        if let Some((token, expires_at)) = &self.access_token {
+            if Instant::now() < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let response: Value = self.client
+            .post(TOKEN_URL)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", self.config.refresh_token.as_str()),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        let token = response
+            .get("access_token")
+            .and_then(|v| v.as_str())
+            .ok_or("Spotify token response has no access_token")?
+            .to_string();
+
+        // Refresh a minute early so a token never expires mid-request
+        let expires_in = response.get("expires_in").and_then(|v| v.as_u64()).unwrap_or(3600);
+        let expires_at = Instant::now() + Duration::from_secs(expires_in.saturating_sub(60));
+
+        self.access_token = Some((token.clone(), expires_at));
+        Ok(token)
+    }
+}
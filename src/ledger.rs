@@ -0,0 +1,227 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::state::StateDir;
+use crate::songrec::RecognitionResult;
+use crate::{Result, SongRecError};
+
+const LEDGER_FILE_NAME: &str = "processed.jsonl";
+
+/// Size and modification time of a file
+pub(crate) type FileStamp = (u64, u64);
+
+/// How files are matched against the ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileIdentity {
+    /// Same path, size and modification time
+    Path,
+    /// Same SHA-256 of the content, so renamed, moved and duplicate files are known too
+    Content,
+}
+
+/// One processed file, stored as a JSON line in the ledger file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Path of the file when it was processed
+    pub path: PathBuf,
+
+    /// File size in bytes, a file replaced by a different one is processed again
+    pub size: u64,
+
+    /// Modification time in seconds since the Unix epoch
+    pub modified: u64,
+
+    /// Track key of the match, None if the file was not recognized
+    pub track_key: Option<String>,
+
+    /// SHA-256 of the content, only computed with `FileIdentity::Content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+
+    pub processed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Persistent record of the files already processed, so they are skipped after a restart
+///
+/// Entries are appended to the file as they are recorded, nothing is lost if the
+/// process is interrupted.
+pub struct ProcessedLedger {
+    path: PathBuf,
+    entries: HashMap<PathBuf, LedgerEntry>,
+    hashes: HashMap<String, PathBuf>,
+    identity: FileIdentity,
+    /// Records are kept in memory only
+    dry_run: bool,
+    /// Hash of the last file checked, so recording it right after does not read it again
+    last_hash: RefCell<Option<(PathBuf, FileStamp, String)>>,
+}
+
+impl ProcessedLedger {
+    /// Load the ledger at `path`, a missing file is an empty ledger
+    ///
+    /// Unreadable lines (e.g. a line cut short by a crash) are ignored.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let mut entries = HashMap::new();
+        let mut hashes = HashMap::new();
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                for entry in content.lines().filter_map(|line| serde_json::from_str::<LedgerEntry>(line).ok()) {
+                    if let Some(hash) = &entry.content_hash {
+                        hashes.insert(hash.clone(), entry.path.clone());
+                    }
+                    entries.insert(entry.path.clone(), entry);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(SongRecError::ConfigError(format!("Could not read ledger '{}': {}", path.display(), e))),
+        }
+
+        Ok(Self {
+            path,
+            entries,
+            hashes,
+            identity: FileIdentity::Path,
+            dry_run: false,
+            last_hash: RefCell::new(None),
+        })
+    }
+
+    /// Load the ledger shared by all runs, in the state directory
+    pub fn open_default() -> Result<Self> {
+        Self::open_in(&StateDir::require_default("ledger")?)
+    }
+
+    /// Load the ledger kept in a state directory, e.g. `StateDir::for_config`
+    pub fn open_in(dir: &StateDir) -> Result<Self> {
+        Self::open(dir.file(LEDGER_FILE_NAME))
+    }
+
+    /// How files are matched, by path by default
+    pub fn with_identity(mut self, identity: FileIdentity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Remember the files recorded during this run only, leaving the ledger file untouched
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Whether this file was already processed
+    pub fn contains(&self, file: &Path) -> bool {
+        let stamp = match file_stamp(file) {
+            Some(stamp) => stamp,
+            None => return false,
+        };
+
+        // An unchanged file at the same path is known without reading it
+        if self.entries.get(file).is_some_and(|entry| (entry.size, entry.modified) == stamp) {
+            return true;
+        }
+
+        match self.identity {
+            FileIdentity::Path => false,
+            FileIdentity::Content => self.content_hash(file, stamp).is_some_and(|hash| self.hashes.contains_key(&hash)),
+        }
+    }
+
+    /// Previous entry of a file, even if it changed since
+    pub fn get(&self, file: &Path) -> Option<&LedgerEntry> {
+        self.entries.get(file)
+    }
+
+    /// Number of files in the ledger
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Record a processed file along with its match, if any
+    pub fn record(&mut self, file: &Path, result: Option<&RecognitionResult>) -> Result<()> {
+        let (size, modified) = file_stamp(file)
+            .ok_or_else(|| SongRecError::InvalidInput(format!("Cannot read metadata of '{}'", file.display())))?;
+
+        let content_hash = match self.identity {
+            FileIdentity::Path => None,
+            FileIdentity::Content => Some(
+                self.content_hash(file, (size, modified))
+                    .ok_or_else(|| SongRecError::InvalidInput(format!("Cannot read '{}'", file.display())))?,
+            ),
+        };
+
+        let entry = LedgerEntry {
+            path: file.to_path_buf(),
+            size,
+            modified,
+            track_key: result.map(|result| result.track_key.clone()),
+            content_hash,
+            processed_at: chrono::Utc::now(),
+        };
+
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not serialize ledger entry: {}", e)))?;
+
+        if !self.dry_run {
+            self.append(&line)?;
+        }
+
+        if let Some(hash) = &entry.content_hash {
+            self.hashes.insert(hash.clone(), entry.path.clone());
+        }
+        self.entries.insert(entry.path.clone(), entry);
+        Ok(())
+    }
+
+    fn append(&self, line: &str) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SongRecError::ConfigError(format!("Could not create '{}': {}", parent.display(), e)))?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut ledger| writeln!(ledger, "{}", line))
+            .map_err(|e| SongRecError::ConfigError(format!("Could not write ledger '{}': {}", self.path.display(), e)))
+    }
+
+    /// SHA-256 of a file, reusing the last one computed if the file did not change
+    fn content_hash(&self, file: &Path, stamp: FileStamp) -> Option<String> {
+        if let Some((path, last_stamp, hash)) = self.last_hash.borrow().as_ref() {
+            if path == file && *last_stamp == stamp {
+                return Some(hash.clone());
+            }
+        }
+
+        let hash = file_hash(file)?;
+        *self.last_hash.borrow_mut() = Some((file.to_path_buf(), stamp, hash.clone()));
+        Some(hash)
+    }
+}
+
+pub(crate) fn file_stamp(file: &Path) -> Option<FileStamp> {
+    let metadata = std::fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some((metadata.len(), modified))
+}
+
+/// SHA-256 of the content of a file, in hexadecimal
+pub(crate) fn file_hash(file: &Path) -> Option<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(file).ok()?, &mut hasher).ok()?;
+    Some(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
@@ -31,6 +31,41 @@ pub mod config;
 pub mod recognition;
 pub mod audio;
 pub mod output;
+pub mod discovery;
+pub mod integrations;
+pub mod identity;
+pub mod debug;
+pub mod ledger;
+pub mod library;
+pub mod batch;
+pub mod service;
+pub mod eval;
+pub mod dedupe;
+pub mod analysis;
+pub mod tracklist;
+pub mod scrobble;
+pub mod state;
+pub mod secrets;
+pub mod logging;
+pub mod genre;
+pub mod credits;
+pub mod filename;
+pub mod diagnostics;
+pub mod cache;
+pub mod codec;
+pub mod hub;
+
+#[cfg(feature = "audio-devices")]
+pub mod recognizer;
+
+#[cfg(feature = "watch")]
+pub mod watch;
+
+#[cfg(feature = "history")]
+pub mod history;
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+pub mod publish;
 
 // Re-export fingerprinting modules
 pub mod fingerprinting {
@@ -43,9 +78,27 @@ pub mod fingerprinting {
 
 // Core API
 mod songrec;
-pub use songrec::{SongRec, RecognitionResult, RecognitionStream};
-pub use config::Config;
-pub use output::{OutputFormat, RecognitionOutput};
+mod pipeline;
+mod limiter;
+pub use songrec::{SongRec, RecognitionResult, RecognitionSource, RecognitionTimings, RecognitionPhase, RecognitionProgress, RecognitionStream, PipelineStats, PipelineHealth, PipelineEvent};
+#[cfg(feature = "audio-devices")]
+pub use recognizer::{RecognizerHandle, RecognizerState};
+pub use config::{Config, SpotifyConfig, DiscordConfig, OscConfig, ListenBrainzConfig, StationConfig, SpectralWeighting, ChannelMode, LongFileAction};
+pub use output::{OutputFormat, RecognitionOutput, Sink, StdoutSink, FileSink, CommandSink, HttpSink};
+pub use discovery::{TrackSummary, ArtistInfo, ArtistSong};
+pub use audio::pcm::{PcmBuffer, PcmFormat};
+pub use audio::snippet::AudioSnippet;
+pub use audio::loudness::AudioStats;
+pub use audio::classifier::AudioClass;
+pub use audio::media::MediaInfo;
+pub use analysis::{TrackAnalysis, MusicalKey, KeyMode};
+pub use tracklist::{Tracklist, TracklistEntry, ChapterFormat};
+pub use state::StateDir;
+pub use secrets::{SecretStore, SecretLocation};
+pub use logging::LogLevel;
+pub use credits::{ArtistCredit, ArtistRole};
+pub use filename::sanitize_filename;
+pub use diagnostics::NoMatchDiagnostics;
 
 // Re-export key types for convenience
 pub use fingerprinting::signature_format::DecodedSignature;
@@ -56,12 +109,20 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Error types for the library
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum SongRecError {
     AudioError(String),
     NetworkError(String),
     FingerprintingError(String),
     InvalidInput(String),
     ConfigError(String),
+    /// The API answered but did not recognize the audio, with measurements of the audio
+    /// explaining why when it was looked up by this library
+    NoMatch(Option<Box<NoMatchDiagnostics>>),
+    /// A bug in the library, such as a panic in one of its threads
+    Internal(String),
+    /// The file cannot be recognized whatever the settings, e.g. it is DRM-protected or silent
+    UnsupportedMedia(String),
 }
 
 impl std::fmt::Display for SongRecError {
@@ -72,11 +133,31 @@ impl std::fmt::Display for SongRecError {
             SongRecError::FingerprintingError(msg) => write!(f, "Fingerprinting error: {}", msg),
             SongRecError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             SongRecError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            SongRecError::NoMatch(Some(diagnostics)) => write!(f, "No match found: {}", diagnostics.hint),
+            SongRecError::NoMatch(None) => write!(f, "No match found"),
+            SongRecError::Internal(msg) => write!(f, "Internal error: {}", msg),
+            SongRecError::UnsupportedMedia(msg) => write!(f, "Unsupported media: {}", msg),
         }
     }
 }
 
 impl std::error::Error for SongRecError {}
 
+impl SongRecError {
+    /// Short name of the error category, e.g. "network", used to group errors in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SongRecError::AudioError(_) => "audio",
+            SongRecError::NetworkError(_) => "network",
+            SongRecError::FingerprintingError(_) => "fingerprinting",
+            SongRecError::InvalidInput(_) => "invalid-input",
+            SongRecError::ConfigError(_) => "config",
+            SongRecError::NoMatch(_) => "no-match",
+            SongRecError::Internal(_) => "internal",
+            SongRecError::UnsupportedMedia(_) => "unsupported-media",
+        }
+    }
+}
+
 /// Result type for the library
 pub type Result<T> = std::result::Result<T, SongRecError>;
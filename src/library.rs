@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::filename::{sanitize_filename, Platform};
+use crate::genre;
+use crate::songrec::RecognitionResult;
+use crate::{Result, SongRecError};
+
+/// What to do with a recognized file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrganizeMode {
+    /// Rename the file to "Artist - Title" in its own directory
+    Rename,
+    /// Move the file to "Artist/Album/Artist - Title" under the library root
+    Move,
+    /// Copy the file to "Artist/Album/Artist - Title" under the library root, leaving the original in place
+    Copy,
+}
+
+/// Sorts recognized files into a library layout
+#[derive(Debug, Clone)]
+pub struct LibraryOrganizer {
+    root: PathBuf,
+    mode: OrganizeMode,
+    write_tags: bool,
+    dry_run: bool,
+    genre_folders: bool,
+    track_numbers: bool,
+}
+
+impl LibraryOrganizer {
+    /// Organize files under `root` (ignored in `Rename` mode)
+    pub fn new<P: Into<PathBuf>>(root: P, mode: OrganizeMode) -> Self {
+        Self {
+            root: root.into(),
+            mode,
+            write_tags: false,
+            dry_run: false,
+            genre_folders: false,
+            track_numbers: false,
+        }
+    }
+
+    /// Also write the title, artist, album, year, genre, track and disc number to the file tags (requires the tagging feature)
+    ///
+    /// Only MP3 and WAV files are tagged, other formats are organized without tags.
+    pub fn with_tags(mut self, enabled: bool) -> Self {
+        self.write_tags = enabled;
+        self
+    }
+
+    /// Leave files and directories untouched, `organize` only returns where files would go
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Sort the library by genre first, as `root/Genre/Artist/Album/` (ignored in `Rename` mode)
+    ///
+    /// The genre is `RecognitionResult::genre_normalized`, or the genre normalized by the
+    /// built-in rules, so that spellings of a genre share a folder.
+    pub fn with_genre_folders(mut self, enabled: bool) -> Self {
+        self.genre_folders = enabled;
+        self
+    }
+
+    /// Start the names of files in album folders with their track number, as "03 - Artist - Title" or
+    /// "2-03 - Artist - Title" on a second disc, when the response carries it (ignored in `Rename` mode)
+    pub fn with_track_numbers(mut self, enabled: bool) -> Self {
+        self.track_numbers = enabled;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Library root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn mode(&self) -> OrganizeMode {
+        self.mode
+    }
+
+    /// Where a recognized file belongs, keeping its extension
+    pub fn destination(&self, source: &Path, result: &RecognitionResult) -> PathBuf {
+        let mut file_name = sanitize(&format!("{} - {}", result.artist_name, result.song_name));
+        if let Some(extension) = source.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+
+        match self.mode {
+            OrganizeMode::Rename => source.with_file_name(file_name),
+            OrganizeMode::Move | OrganizeMode::Copy => {
+                if let Some(track) = result.track_number.filter(|_| self.track_numbers) {
+                    file_name = match result.disc_number.filter(|&disc| disc > 1) {
+                        Some(disc) => format!("{}-{:02} - {}", disc, track, file_name),
+                        None => format!("{:02} - {}", track, file_name),
+                    };
+                }
+                let album = result.album_name.as_deref().filter(|album| !album.trim().is_empty()).unwrap_or("Unknown Album");
+                let mut directory = self.root.clone();
+                if self.genre_folders {
+                    let genre = result.genre_normalized.clone()
+                        .or_else(|| result.genre.as_deref().and_then(|genre| genre::normalize(genre, &BTreeMap::new())))
+                        .unwrap_or_else(|| "Unknown Genre".to_string());
+                    directory.push(sanitize(&genre));
+                }
+                directory.join(sanitize(&result.artist_name)).join(sanitize(album)).join(file_name)
+            }
+        }
+    }
+
+    /// Rename, move or copy a recognized file then tag it, returns its new path
+    ///
+    /// An existing file at the destination is never overwritten, a number is appended instead.
+    pub fn organize(&self, source: &Path, result: &RecognitionResult) -> Result<PathBuf> {
+        if self.write_tags && !cfg!(feature = "tagging") {
+            return Err(SongRecError::ConfigError("Writing tags requires the tagging feature".to_string()));
+        }
+
+        let mut destination = self.destination(source, result);
+        if destination != source {
+            destination = unused_path(destination);
+            if self.dry_run {
+                return Ok(destination);
+            }
+
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| SongRecError::InvalidInput(format!("Could not create '{}': {}", parent.display(), e)))?;
+            }
+
+            let outcome = match self.mode {
+                OrganizeMode::Copy => std::fs::copy(source, &destination).map(|_| ()),
+                OrganizeMode::Rename | OrganizeMode::Move => move_file(source, &destination),
+            };
+            outcome.map_err(|e| SongRecError::InvalidInput(format!("Could not move '{}' to '{}': {}", source.display(), destination.display(), e)))?;
+        }
+
+        // Tags are written to the organized file, a copied original stays untouched
+        if self.write_tags && !self.dry_run {
+            write_tags(&destination, result)?;
+        }
+
+        Ok(destination)
+    }
+}
+
+/// Rename, falling back to copy and delete across file systems
+fn move_file(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if std::fs::rename(source, destination).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(source, destination)?;
+    std::fs::remove_file(source)
+}
+
+/// `path`, or `path` with " (2)", " (3)"... before the extension if it already exists
+fn unused_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+
+    (2..)
+        .map(|index| path.with_file_name(format!("{} ({}){}", stem, index, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Extensions of the audio and video files looked for in directories
+const AUDIO_EXTENSIONS: [&str; 11] = ["wav", "mp3", "ogg", "opus", "flac", "m4a", "mp4", "m4v", "mov", "webm", "mkv"];
+
+/// Whether the extension of `path` is one of `AUDIO_EXTENSIONS`
+pub(crate) fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// All files under a directory, recursively
+pub(crate) fn list_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+}
+
+/// File name valid on every platform, libraries and split files may be copied anywhere
+pub(crate) fn sanitize(name: &str) -> String {
+    sanitize_filename(name, Platform::Portable)
+}
+
+#[cfg(feature = "tagging")]
+fn write_tags(file: &Path, result: &RecognitionResult) -> Result<()> {
+    use id3::TagLike;
+
+    let extension = file.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    if !matches!(extension.as_deref(), Some("mp3") | Some("wav")) {
+        return Ok(());
+    }
+
+    // Only the recognized fields are replaced, other tags such as cover art are kept
+    let mut tag = id3::Tag::read_from_path(file).unwrap_or_default();
+    tag.set_title(result.song_name.clone());
+    tag.set_artist(result.artist_name.clone());
+    if let Some(album) = &result.album_name {
+        tag.set_album(album.clone());
+    }
+    if let Some(year) = result.release_year.as_deref().and_then(|year| year.parse().ok()) {
+        tag.set_year(year);
+    }
+    if let Some(genre) = result.genre_normalized.as_ref().or(result.genre.as_ref()) {
+        tag.set_genre(genre.clone());
+    }
+    if let Some(track) = result.track_number {
+        tag.set_track(track);
+    }
+    if let Some(disc) = result.disc_number {
+        tag.set_disc(disc);
+    }
+
+    tag.write_to_path(file, id3::Version::Id3v24).map_err(|e| SongRecError::InvalidInput(format!("Could not write tags to '{}': {}", file.display(), e)))
+}
+
+/// Never called, `organize` rejects tagging before touching the file
+#[cfg(not(feature = "tagging"))]
+fn write_tags(_file: &Path, _result: &RecognitionResult) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limits the number of API lookups in flight and spaces out their start
+///
+/// Shared by every lookup of a `SongRec`, including the workers of its continuous recognition.
+pub(crate) struct LookupLimiter {
+    max_in_flight: usize,
+    delay: Duration,
+    state: Mutex<LimiterState>,
+    released: Condvar,
+    /// Number of lookups started so far
+    started: AtomicU64,
+}
+
+struct LimiterState {
+    in_flight: usize,
+    next_start: Instant,
+}
+
+/// Slot of a lookup, released when dropped
+pub(crate) struct LookupPermit<'a> {
+    limiter: &'a LookupLimiter,
+}
+
+impl LookupLimiter {
+    /// Limiter letting `max_in_flight` lookups run at once, any number if `None`
+    pub(crate) fn new(max_in_flight: Option<usize>, delay: Duration) -> Self {
+        Self {
+            max_in_flight: max_in_flight.map_or(usize::MAX, |max| max.max(1)),
+            delay,
+            state: Mutex::new(LimiterState { in_flight: 0, next_start: Instant::now() }),
+            released: Condvar::new(),
+            started: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn started(&self) -> u64 {
+        self.started.load(Ordering::Relaxed)
+    }
+
+    /// Wait for a free slot and for the delay since the previous lookup to pass
+    pub(crate) fn acquire(&self) -> LookupPermit<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.in_flight >= self.max_in_flight {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+
+        // Reserve the start time before sleeping, so concurrent callers queue up behind it
+        let start = state.next_start.max(Instant::now());
+        state.next_start = start + self.delay;
+        state.in_flight += 1;
+        drop(state);
+
+        let wait = start.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+
+        self.started.fetch_add(1, Ordering::Relaxed);
+        LookupPermit { limiter: self }
+    }
+}
+
+impl Drop for LookupPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_flight -= 1;
+        self.limiter.released.notify_one();
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Severity of a diagnostic message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Error => write!(f, "error"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+type LogFn = dyn Fn(LogLevel, &str) + Send + Sync;
+
+/// Receiver of the diagnostics of the library, see `Config::with_log_callback`
+#[derive(Clone)]
+pub struct LogCallback(Arc<LogFn>);
+
+impl LogCallback {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(LogLevel, &str) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for LogCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LogCallback")
+    }
+}
+
+/// Pass a message to the log callback, or print it on stderr unless in quiet mode
+pub(crate) fn log(config: &Config, level: LogLevel, message: fmt::Arguments<'_>) {
+    match &config.log_callback {
+        Some(callback) => (callback.0)(level, &message.to_string()),
+        None if !config.quiet_mode => eprintln!("{}", message),
+        None => {}
+    }
+}
+
+/// Like `log` but printed even in quiet mode, for output asked for like dry-run plans
+pub(crate) fn notice(config: &Config, message: fmt::Arguments<'_>) {
+    match &config.log_callback {
+        Some(callback) => (callback.0)(LogLevel::Info, &message.to_string()),
+        None => eprintln!("{}", message),
+    }
+}
@@ -1,10 +1,17 @@
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
-use crate::songrec::RecognitionResult;
+use crate::songrec::{PipelineEvent, RecognitionResult};
 
 /// Output format for recognition results
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputFormat {
-    /// Simple song name format: "Artist - Song"
+    /// Simple song name format: "Artist - Song", followed by the tempo and key of analyzed tracks
     Simple,
     /// Full JSON with all metadata
     Json,
@@ -27,7 +34,20 @@ impl RecognitionOutput {
     pub fn format_result(result: &RecognitionResult, format: OutputFormat) -> Self {
         let content = match format {
             OutputFormat::Simple => {
-                format!("{} - {}", result.artist_name, result.song_name)
+                let mut content = format!("{} - {}", result.artist_name, result.song_name);
+                if let Some(analysis) = &result.analysis {
+                    let mut details = Vec::new();
+                    if let Some(bpm) = analysis.bpm {
+                        details.push(format!("{:.0} BPM", bpm));
+                    }
+                    if let Some(key) = analysis.key {
+                        details.push(format!("{}, {}", key, key.camelot()));
+                    }
+                    if !details.is_empty() {
+                        content.push_str(&format!(" ({})", details.join(", ")));
+                    }
+                }
+                content
             },
             OutputFormat::Json => {
                 serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string()) // Avoid verbose error messages
@@ -35,11 +55,11 @@ impl RecognitionOutput {
             OutputFormat::Csv => {
                 format!(
                     "\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\"",
-                    result.song_name,
-                    result.artist_name,
-                    result.album_name.as_deref().unwrap_or(""),
-                    result.release_year.as_deref().unwrap_or(""),
-                    result.genre.as_deref().unwrap_or(""),
+                    csv_field(&result.song_name),
+                    csv_field(&result.artist_name),
+                    csv_field(result.album_name.as_deref().unwrap_or("")),
+                    csv_field(result.release_year.as_deref().unwrap_or("")),
+                    csv_field(result.genre.as_deref().unwrap_or("")),
                     result.recognition_timestamp.format("%Y-%m-%d %H:%M:%S UTC")
                 )
             },
@@ -56,13 +76,23 @@ impl RecognitionOutput {
     }
 
     /// Format using a custom template with placeholders
-    fn format_custom(result: &RecognitionResult, template: &str) -> String {
+    pub(crate) fn format_custom(result: &RecognitionResult, template: &str) -> String {
+        let analysis = result.analysis.as_ref();
+        let key = analysis.and_then(|analysis| analysis.key);
         template
             .replace("{song}", &result.song_name)
             .replace("{artist}", &result.artist_name)
             .replace("{album}", result.album_name.as_deref().unwrap_or("Unknown"))
             .replace("{year}", result.release_year.as_deref().unwrap_or("Unknown"))
             .replace("{genre}", result.genre.as_deref().unwrap_or("Unknown"))
+            .replace("{genre_normalized}", result.genre_normalized.as_deref().unwrap_or("Unknown"))
+            .replace("{track_number}", &result.track_number.map(|track| track.to_string()).unwrap_or_default())
+            .replace("{disc_number}", &result.disc_number.map(|disc| disc.to_string()).unwrap_or_default())
+            .replace("{bpm}", &analysis.and_then(|analysis| analysis.bpm).map(|bpm| format!("{:.0}", bpm)).unwrap_or_default())
+            .replace("{key}", &key.map(|key| key.name()).unwrap_or_default())
+            .replace("{camelot}", &key.map(|key| key.camelot()).unwrap_or_default())
+            .replace("{position}", &result.song_position().map(format_minutes).unwrap_or_default())
+            .replace("{duration}", &result.song_duration().map(format_minutes).unwrap_or_default())
             .replace("{timestamp}", &result.recognition_timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string())
     }
 
@@ -72,6 +102,21 @@ impl RecognitionOutput {
     }
 }
 
+/// Time within a song, e.g. "1:23"
+fn format_minutes(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Escape a value for a quoted CSV field, keeping each record on a single line
+pub(crate) fn csv_field(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+        .collect::<String>()
+        .replace('"', "\"\"")
+}
+
 impl std::fmt::Display for RecognitionOutput {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.content)
@@ -88,3 +133,149 @@ impl std::fmt::Display for OutputFormat {
         }
     }
 }
+
+/// Destination of the events of a continuous recognition, see `SongRec::start_continuous_recognition_with_sinks`
+///
+/// Sinks are called one at a time from a thread of their own, in the order events happen.
+/// An error is reported on stderr unless in quiet mode, and the sink keeps receiving events.
+pub trait Sink: Send {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>>;
+
+    /// Whether the sink only prints, so it keeps receiving events with `Config::with_dry_run`
+    fn is_dry_run_safe(&self) -> bool {
+        false
+    }
+}
+
+/// Prints every match to stdout
+pub struct StdoutSink {
+    format: OutputFormat,
+}
+
+impl StdoutSink {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl Sink for StdoutSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        if let PipelineEvent::Match { result } = event {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", RecognitionOutput::format_result(result, self.format))?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    fn is_dry_run_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Appends every match to a file, one line each
+pub struct FileSink {
+    file: File,
+    format: OutputFormat,
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it if needed; a new CSV file starts with the header
+    pub fn open<P: AsRef<Path>>(path: P, format: OutputFormat) -> Result<Self, Box<dyn Error>> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if format == OutputFormat::Csv && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", RecognitionOutput::csv_header())?;
+        }
+        Ok(Self { file, format })
+    }
+}
+
+impl Sink for FileSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        if let PipelineEvent::Match { result } = event {
+            writeln!(self.file, "{}", RecognitionOutput::format_result(result, self.format))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a command for every match, with the result as JSON on its standard input
+///
+/// The command also gets `SONGREC_ARTIST`, `SONGREC_TITLE`, `SONGREC_ALBUM` and
+/// `SONGREC_TRACK_KEY` in its environment. Events wait for the command to exit.
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSink {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl Sink for CommandSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        let PipelineEvent::Match { result } = event else {
+            return Ok(());
+        };
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("SONGREC_ARTIST", &result.artist_name)
+            .env("SONGREC_TITLE", &result.song_name)
+            .env("SONGREC_ALBUM", result.album_name.as_deref().unwrap_or(""))
+            .env("SONGREC_TRACK_KEY", &result.track_key)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may not read its input, which is fine
+            let _ = serde_json::to_writer(&mut stdin, result.as_ref());
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("'{}' exited with {}", self.program, status).into());
+        }
+        Ok(())
+    }
+}
+
+/// Posts every match as JSON to a URL
+pub struct HttpSink {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl HttpSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_else(|_| reqwest::blocking::Client::new()),
+        }
+    }
+}
+
+impl Sink for HttpSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        if let PipelineEvent::Match { result } = event {
+            self.client.post(&self.url).json(result.as_ref()).send()?.error_for_status()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1150 @@
+use std::io::Read;
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::panic::{self, AssertUnwindSafe};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "audio-devices")]
+use crate::audio::processor::AudioProcessor;
+use crate::audio::snippet::AudioSnippet;
+use crate::audio::loudness::AudioStats;
+use crate::audio::classifier::{classify, AudioClass};
+#[cfg(feature = "audio-devices")]
+use crate::audio::recorder::{AudioError, AudioRecorder};
+use crate::config::{Config, ListenBrainzConfig};
+use crate::diagnostics::NoMatchDiagnostics;
+use crate::fingerprinting::algorithm::{decode_error, SignatureGenerator};
+use crate::fingerprinting::communication::recognize_song_from_signature_with_client;
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::limiter::LookupLimiter;
+use crate::logging::{log, notice, LogLevel};
+use crate::output::Sink;
+use crate::scrobble::{ListenBrainzSink, ScrobbleFilter};
+#[cfg(feature = "discord")]
+use crate::integrations::discord::DiscordNowPlaying;
+#[cfg(feature = "osc")]
+use crate::integrations::osc::OscSender;
+#[cfg(feature = "spotify")]
+use crate::integrations::spotify::SpotifyPlaylist;
+use crate::songrec::{PipelineEvent, PipelineHealth, PipelineStats, RecognitionResult, RecognitionSource, RecognitionTimings};
+use crate::{Result, SongRecError};
+
+/// How often the dispatcher wakes up when no result arrives, to expire stale integrations
+const DISPATCH_TICK: Duration = Duration::from_secs(1);
+
+/// A track not recognized for this long has ended
+const TRACK_GAP: Duration = Duration::from_secs(45);
+
+/// Time after the expected end of a track before it is considered over
+const TRACK_END_GRACE: Duration = Duration::from_secs(10);
+
+/// How often the capture thread checks for stream errors when no audio arrives
+#[cfg(feature = "audio-devices")]
+const CAPTURE_TICK: Duration = Duration::from_millis(500);
+
+/// A pause this long between two passes of the capture loop means the system was suspended
+const CLOCK_JUMP: Duration = Duration::from_secs(10);
+
+/// Seconds of a network stream fingerprinted at once, a little more than a signature needs
+const STREAM_CHUNK_SECONDS: usize = 14;
+
+/// Bitrate assumed for network streams that do not announce theirs, in kbit/s
+const DEFAULT_STREAM_BITRATE: usize = 128;
+
+/// Wait before reconnecting to a network stream that failed
+const STREAM_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Last seconds of audio fingerprinted again at other speeds, see `Config::with_pitch_shifts`
+const PITCH_SHIFT_WINDOW: usize = 12 * 16000;
+
+/// Where the pipeline takes its audio from
+#[derive(Debug, Clone)]
+pub(crate) enum Input {
+    /// Audio input device, the default one if None
+    #[cfg(feature = "audio-devices")]
+    Device(Option<String>),
+    /// HTTP audio stream, such as an Icecast radio
+    Stream(String),
+}
+
+/// Overrun counters shared between the pipeline threads and the stream
+#[derive(Debug, Default)]
+pub(crate) struct PipelineCounters {
+    dropped_sample_batches: Arc<AtomicU64>,
+    skipped_signatures: AtomicU64,
+    gated_windows: AtomicU64,
+    pending_results: AtomicUsize,
+    /// Signatures handed to the lookup workers and taken by them, the difference is the queue depth
+    queued_signatures: AtomicU64,
+    taken_signatures: AtomicU64,
+    last_audio: Mutex<Option<chrono::DateTime<chrono::Utc>>>,
+    last_lookup: Mutex<Option<chrono::DateTime<chrono::Utc>>>,
+    errors_by_kind: Mutex<BTreeMap<String, u64>>,
+    /// Number of clock jumps detected by the capture thread, see `ClockWatch`
+    resumes: AtomicU64,
+}
+
+impl PipelineCounters {
+    /// Number of results sent by the pipeline but not received yet
+    pub(crate) fn pending(&self) -> usize {
+        self.pending_results.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn result_received(&self) {
+        let _ = self.pending_results.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+    }
+
+    fn signature_queued(&self) {
+        self.queued_signatures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn signature_taken(&self) {
+        self.taken_signatures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn audio_received(&self) {
+        *self.last_audio.lock().unwrap_or_else(|e| e.into_inner()) = Some(chrono::Utc::now());
+    }
+
+    /// The API answered, whether or not it found a match
+    fn lookup_answered(&self) {
+        *self.last_lookup.lock().unwrap_or_else(|e| e.into_inner()) = Some(chrono::Utc::now());
+    }
+
+    /// Count a result sent to the stream
+    fn result_sent(&self, outcome: &Result<RecognitionResult>) {
+        self.pending_results.fetch_add(1, Ordering::AcqRel);
+        if let Some(error) = outcome.as_ref().err().filter(|error| !matches!(error, SongRecError::NoMatch(_))) {
+            *self.errors_by_kind.lock().unwrap_or_else(|e| e.into_inner()).entry(error.kind().to_string()).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn health(&self, alive: bool) -> PipelineHealth {
+        PipelineHealth {
+            alive,
+            last_audio_at: *self.last_audio.lock().unwrap_or_else(|e| e.into_inner()),
+            last_lookup_at: *self.last_lookup.lock().unwrap_or_else(|e| e.into_inner()),
+            // A worker can take a signature before the capture thread counted it as queued
+            queued_signatures: self.queued_signatures.load(Ordering::Relaxed).saturating_sub(self.taken_signatures.load(Ordering::Relaxed)) as usize,
+            pending_results: self.pending(),
+            errors_by_kind: self.errors_by_kind.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            stats: self.snapshot(),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> PipelineStats {
+        PipelineStats {
+            dropped_sample_batches: self.dropped_sample_batches.load(Ordering::Relaxed),
+            skipped_signatures: self.skipped_signatures.load(Ordering::Relaxed),
+            gated_windows: self.gated_windows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Configuration read by the running pipeline, replaced by `RecognitionStream::reload`
+#[derive(Debug)]
+pub(crate) struct LiveConfig {
+    config: RwLock<Config>,
+    version: AtomicU64,
+}
+
+impl LiveConfig {
+    fn new(config: Config) -> Self {
+        Self {
+            config: RwLock::new(config),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self) -> Config {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn set(&self, config: Config) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+}
+
+/// Optional subscriber of the pipeline state changes, shared by every stage
+#[derive(Clone, Default)]
+struct Events(Option<Sender<PipelineEvent>>);
+
+impl Events {
+    fn emit(&self, event: PipelineEvent) {
+        if let Some(events) = &self.0 {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Report a match, a no-match or an error, only cloning the result if someone listens
+    fn outcome(&self, outcome: &Result<RecognitionResult>) {
+        if self.0.is_some() {
+            self.emit(match outcome {
+                Ok(result) => PipelineEvent::Match { result: Box::new(result.clone()) },
+                Err(SongRecError::NoMatch(diagnostics)) => PipelineEvent::NoMatch { diagnostics: diagnostics.clone() },
+                Err(e) => PipelineEvent::Error { message: e.to_string() },
+            });
+        }
+    }
+}
+
+/// Detects the capture thread being paused, e.g. by a system suspend
+///
+/// Both clocks are checked: the monotonic clock stops during a suspend on Linux but not
+/// on every platform, while the wall clock always moves on.
+struct ClockWatch {
+    instant: Instant,
+    wall: SystemTime,
+}
+
+impl ClockWatch {
+    fn new() -> Self {
+        Self { instant: Instant::now(), wall: SystemTime::now() }
+    }
+
+    /// Time since the last check if it is a jump rather than a normal pass of the loop
+    fn jump(&mut self) -> Option<Duration> {
+        let (instant, wall) = (Instant::now(), SystemTime::now());
+        // A wall clock set back is not a pause
+        let gap = instant.duration_since(self.instant).max(wall.duration_since(self.wall).unwrap_or_default());
+        (self.instant, self.wall) = (instant, wall);
+        (gap >= CLOCK_JUMP).then_some(gap)
+    }
+}
+
+/// Report a clock jump detected by the capture thread
+fn resumed(config: &Config, gap: Duration, counters: &PipelineCounters, events: &Events) {
+    log(config, LogLevel::Info, format_args!("Capture resumed after {:.0} seconds, dropping buffered audio", gap.as_secs_f32()));
+    counters.resumes.fetch_add(1, Ordering::AcqRel);
+    events.emit(PipelineEvent::Resumed { gap_ms: gap.as_millis() as u64 });
+}
+
+/// Signature waiting for a lookup worker, with the time spent producing it
+struct PendingSignature {
+    signature: DecodedSignature,
+    fingerprint_time: Duration,
+    ready_at: Instant,
+    /// Audio of the signature, kept for audio snippets and track analysis
+    snippet: Option<AudioSnippet>,
+    audio_stats: Option<AudioStats>,
+    /// Last seconds heard, up to the longest retry duration, empty without retries
+    recent_audio: Vec<i16>,
+}
+
+/// Signatures shared by the lookup workers, with the key of the last match of any of them
+/// and the limiter of the `SongRec` their lookups go through
+struct LookupQueue {
+    signatures: Mutex<Receiver<PendingSignature>>,
+    last_track: Mutex<Option<String>>,
+    limiter: Arc<LookupLimiter>,
+}
+
+/// Running pipeline, turned into a `RecognitionStream` by the caller
+pub(crate) struct Pipeline {
+    pub(crate) receiver: Receiver<Result<RecognitionResult>>,
+    pub(crate) counters: Arc<PipelineCounters>,
+    pub(crate) handles: Vec<thread::JoinHandle<()>>,
+    pub(crate) readiness: ReadinessFd,
+    pub(crate) live_config: Arc<LiveConfig>,
+}
+
+/// Write side of the stream's readiness descriptor, one byte per result
+struct ReadinessSignal {
+    #[cfg(unix)]
+    writer: Option<UnixStream>,
+}
+
+/// Read side of the stream's readiness descriptor
+///
+/// Becomes readable whenever a result is pending, so the stream can be added to a
+/// select/poll loop. A wake-up may occasionally be spurious.
+pub(crate) struct ReadinessFd {
+    #[cfg(unix)]
+    reader: Option<UnixStream>,
+}
+
+fn readiness_pair() -> (ReadinessSignal, ReadinessFd) {
+    #[cfg(unix)]
+    {
+        let pair = UnixStream::pair().and_then(|(reader, writer)| {
+            reader.set_nonblocking(true)?;
+            writer.set_nonblocking(true)?;
+            Ok((reader, writer))
+        });
+        match pair {
+            Ok((reader, writer)) => (ReadinessSignal { writer: Some(writer) }, ReadinessFd { reader: Some(reader) }),
+            Err(_) => (ReadinessSignal { writer: None }, ReadinessFd { reader: None }),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        (ReadinessSignal {}, ReadinessFd {})
+    }
+}
+
+impl ReadinessSignal {
+    fn notify(&self) {
+        #[cfg(unix)]
+        if let Some(mut writer) = self.writer.as_ref() {
+            // A full socket buffer already means readable, so failures can be ignored
+            let _ = writer.write(&[1]);
+        }
+    }
+}
+
+impl ReadinessFd {
+    /// Consume the wake-up byte of one received result
+    pub(crate) fn consume(&self) {
+        #[cfg(unix)]
+        if let Some(mut reader) = self.reader.as_ref() {
+            let _ = reader.read(&mut [0u8; 1]);
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn raw_fd(&self) -> Option<RawFd> {
+        self.reader.as_ref().map(|reader| reader.as_raw_fd())
+    }
+}
+
+/// Continuous recognition pipeline
+///
+/// Audio capture and signature generation run on their own thread and never wait for
+/// the network: signatures are handed to a pool of lookup workers through a bounded
+/// queue, and when every worker is busy and the queue is full new signatures are
+/// skipped. Results are funnelled through a single dispatcher thread that drives the
+/// integrations, so those never see concurrent updates.
+///
+/// Every queue between the stages is bounded. Stages that must stay real-time (the
+/// audio callback and signature generation) drop the newest item instead of waiting,
+/// and count it in the stream's `PipelineStats`.
+///
+/// Lookup workers and the dispatcher pick up a reloaded configuration on their next
+/// signature or result. Audio capture keeps the settings it was started with.
+///
+/// Sinks get every event on a thread of their own, so a slow sink delays other sinks
+/// and the events subscriber but never the capture or the lookups.
+pub(crate) fn spawn(
+    config: Config,
+    http_client: reqwest::blocking::Client,
+    limiter: Arc<LookupLimiter>,
+    input: Input,
+    events: Option<Sender<PipelineEvent>>,
+    mut sinks: Vec<Box<dyn Sink>>,
+) -> Pipeline {
+    if let Some(listenbrainz) = &config.listenbrainz {
+        sinks.push(Box::new(listenbrainz_scrobbler(listenbrainz)));
+    }
+    let mut handles = Vec::new();
+    let events = if sinks.is_empty() {
+        Events(events)
+    } else {
+        let (sink_tx, sink_rx) = mpsc::channel();
+        let sink_config = config.clone();
+        let panic_config = config.clone();
+        handles.push(spawn_named(
+            "songrec-sinks".to_string(),
+            move || feed_sinks(sinks, sink_rx, events, &sink_config),
+            // Nothing reads from the sinks, the panic can only be logged
+            move |error| log(&panic_config, LogLevel::Error, format_args!("{}", error)),
+        ));
+        Events(Some(sink_tx))
+    };
+    let live_config = Arc::new(LiveConfig::new(config.clone()));
+    let counters = Arc::new(PipelineCounters::default());
+    let (readiness_signal, readiness) = readiness_pair();
+    let readiness_signal = Arc::new(readiness_signal);
+    let (result_tx, result_rx) = mpsc::channel();
+    let (outcome_tx, outcome_rx) = mpsc::channel();
+    let (signature_tx, signature_rx) = mpsc::sync_channel(config.recognition_queue_size);
+    let queue = Arc::new(LookupQueue {
+        signatures: Mutex::new(signature_rx),
+        last_track: Mutex::new(None),
+        limiter,
+    });
+
+    let source = match &input {
+        #[cfg(feature = "audio-devices")]
+        Input::Device(name) => RecognitionSource::Device { name: name.clone() },
+        Input::Stream(url) => RecognitionSource::Stream { url: url.clone() },
+    };
+
+    handles.push({
+        let live_config = Arc::clone(&live_config);
+        let outcome_tx = outcome_tx.clone();
+        let counters = Arc::clone(&counters);
+        let events = events.clone();
+        let panic_tx = outcome_tx.clone();
+        spawn_named(
+            "songrec-capture".to_string(),
+            move || match input {
+                #[cfg(feature = "audio-devices")]
+                Input::Device(device_name) => capture(&live_config, device_name, signature_tx, outcome_tx, &counters, &events),
+                Input::Stream(url) => capture_stream(&live_config, url, signature_tx, outcome_tx, &counters, &events),
+            },
+            move |error| {
+                let _ = panic_tx.send(Err(error));
+            },
+        )
+    });
+
+    for worker in 0..config.recognition_workers.max(1) {
+        let live_config = Arc::clone(&live_config);
+        let http_client = http_client.clone();
+        let queue = Arc::clone(&queue);
+        let outcome_tx = outcome_tx.clone();
+        let events = events.clone();
+        let source = source.clone();
+        let counters = Arc::clone(&counters);
+        let panic_tx = outcome_tx.clone();
+        handles.push(spawn_named(
+            format!("songrec-lookup-{}", worker),
+            move || lookup_worker(&live_config, http_client, source, &queue, outcome_tx, &counters, &events),
+            move |error| {
+                let _ = panic_tx.send(Err(error));
+            },
+        ));
+    }
+
+    // The dispatcher stops once the capture thread and every worker are gone
+    drop(outcome_tx);
+    handles.push({
+        let counters = Arc::clone(&counters);
+        let live_config = Arc::clone(&live_config);
+        let panic_counters = Arc::clone(&counters);
+        let panic_readiness = Arc::clone(&readiness_signal);
+        let panic_tx = result_tx.clone();
+        let panic_events = events.clone();
+        spawn_named(
+            "songrec-dispatch".to_string(),
+            move || dispatch(&live_config, outcome_rx, result_tx, &counters, &readiness_signal, &events),
+            // The stream is reached directly, the dispatcher is what normally forwards errors
+            move |error| {
+                panic_events.emit(PipelineEvent::Error { message: error.to_string() });
+                let outcome = Err(error);
+                panic_counters.result_sent(&outcome);
+                if panic_tx.send(outcome).is_ok() {
+                    panic_readiness.notify();
+                }
+            },
+        )
+    });
+
+    Pipeline {
+        receiver: result_rx,
+        counters,
+        handles,
+        readiness,
+        live_config,
+    }
+}
+
+/// Spawn a thread named `name`, a panic in `work` is handed to `on_panic` as an internal error
+///
+/// Without this a panicking pipeline thread would only end the stream, with no hint why.
+/// Submits the tracks heard long enough to the ListenBrainz account of the configuration
+fn listenbrainz_scrobbler(listenbrainz: &ListenBrainzConfig) -> ScrobbleFilter {
+    let mut sink = ListenBrainzSink::new(&listenbrainz.token);
+    if let Some(url) = &listenbrainz.url {
+        sink = sink.with_url(url);
+    }
+    ScrobbleFilter::new(Box::new(sink))
+}
+
+pub(crate) fn spawn_named<F, P>(name: String, work: F, on_panic: P) -> thread::JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+    P: FnOnce(SongRecError) + Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(work)) {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                on_panic(SongRecError::Internal(format!("Thread '{}' panicked: {}", name, message)));
+            }
+        })
+        .expect("failed to spawn thread")
+}
+
+/// Hand every event to the sinks, then to the events subscriber if any
+fn feed_sinks(mut sinks: Vec<Box<dyn Sink>>, events: Receiver<PipelineEvent>, subscriber: Option<Sender<PipelineEvent>>, config: &Config) {
+    let dry_run = config.dry_run;
+    for event in events {
+        if dry_run {
+            let skipped = sinks.iter().filter(|sink| !sink.is_dry_run_safe()).count();
+            if let (PipelineEvent::Match { result } | PipelineEvent::TrackEnded { result, .. }, true) = (&event, skipped > 0) {
+                notice(config, format_args!("Dry run: would pass {} - {} to {} sink(s)", result.artist_name, result.song_name, skipped));
+            }
+        }
+        for sink in sinks.iter_mut().filter(|sink| !dry_run || sink.is_dry_run_safe()) {
+            if let Err(e) = sink.emit(&event) {
+                log(config, LogLevel::Error, format_args!("Sink error: {}", e));
+            }
+        }
+        if let Some(subscriber) = &subscriber {
+            let _ = subscriber.send(event);
+        }
+    }
+}
+
+/// Record audio and turn it into signatures
+///
+/// A reloaded configuration applies from the next samples, see `RecognitionStream::reload`.
+#[cfg(feature = "audio-devices")]
+fn capture(
+    live_config: &LiveConfig,
+    device_name: Option<String>,
+    signature_tx: SyncSender<PendingSignature>,
+    outcome_tx: Sender<Result<RecognitionResult>>,
+    counters: &PipelineCounters,
+    events: &Events,
+) {
+    let mut version = live_config.version();
+    let mut config = live_config.get();
+    let (_control_tx, control_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+    let mut recorder = AudioRecorder::new(config.clone())
+        .with_overrun_counter(Arc::clone(&counters.dropped_sample_batches))
+        .with_error_sender(error_tx);
+
+    let sample_rx = match recorder.start_recording(device_name.clone(), control_rx) {
+        Ok(sample_rx) => sample_rx,
+        Err(e) => {
+            let _ = outcome_tx.send(Err(SongRecError::AudioError(e.to_string())));
+            return;
+        }
+    };
+    events.emit(PipelineEvent::Listening { device: device_name });
+
+    let mut processor = AudioProcessor::with_config(config.clone());
+    let mut reported_overruns = 0;
+    let mut fingerprint_time = Duration::ZERO;
+    // Kept for longer lookups when a window does not match, see `Config::with_retry_durations`
+    // and `Config::with_pitch_shifts`
+    let mut recent_length = recent_audio_length(&config);
+    let mut recent_audio: Vec<i16> = Vec::new();
+    let mut clock = ClockWatch::new();
+
+    loop {
+        // Audio buffered before a suspend is stale and would be fingerprinted with the new audio
+        if let Some(gap) = clock.jump() {
+            while sample_rx.try_recv().is_ok() {}
+            processor.reset();
+            recent_audio.clear();
+            fingerprint_time = Duration::ZERO;
+            resumed(&config, gap, counters, events);
+        }
+
+        // Stream errors are reported even when the device stopped sending audio
+        if let Ok(error) = error_rx.try_recv() {
+            let device_lost = matches!(error, AudioError::DeviceError(_));
+            if device_lost {
+                events.emit(PipelineEvent::DeviceLost { message: error.to_string() });
+            }
+            if outcome_tx.send(Err(SongRecError::AudioError(error.to_string()))).is_err() || device_lost {
+                break;
+            }
+        }
+
+        let samples = match sample_rx.recv_timeout(CAPTURE_TICK) {
+            Ok(samples) => samples,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        counters.audio_received();
+
+        if live_config.version() != version {
+            version = live_config.version();
+            config = live_config.get();
+            processor.set_config(config.clone());
+            recent_length = recent_audio_length(&config);
+        }
+
+        let overruns = recorder.dropped_batches();
+        if overruns > reported_overruns {
+            log(&config, LogLevel::Warn, format_args!("Audio processing fell behind, {} sample batches dropped so far", overruns));
+            reported_overruns = overruns;
+        }
+
+        if recent_length > 0 {
+            recent_audio.extend_from_slice(&samples);
+            let excess = recent_audio.len().saturating_sub(recent_length);
+            recent_audio.drain(..excess);
+        }
+
+        let processing_start = Instant::now();
+        let processed = processor.process_samples(&samples);
+        fingerprint_time += processing_start.elapsed();
+
+        match processed {
+            Ok(Some(signature)) => {
+                let snippet = processor.take_snippet();
+                let audio_stats = processor.take_audio_stats();
+                let fingerprint_time = std::mem::take(&mut fingerprint_time);
+                if snippet.as_ref().is_some_and(|snippet| is_gated(&config, &snippet.samples, snippet.sample_rate, counters, events)) {
+                    continue;
+                }
+
+                match signature_tx.try_send(PendingSignature {
+                    signature,
+                    fingerprint_time,
+                    ready_at: Instant::now(),
+                    snippet,
+                    audio_stats,
+                    recent_audio: recent_audio.clone(),
+                }) {
+                    Ok(()) => counters.signature_queued(),
+                    Err(TrySendError::Full(_)) => {
+                        counters.skipped_signatures.fetch_add(1, Ordering::Relaxed);
+                        log(&config, LogLevel::Warn, format_args!("All recognition workers are busy, skipping signature"));
+                    }
+                    Err(TrySendError::Disconnected(_)) => break, // Workers stopped, stop recording
+                }
+            }
+            Ok(None) => {
+                // Not enough samples yet, continue
+                events.emit(PipelineEvent::Buffering { percent: processor.get_progress() * 100.0 });
+            }
+            Err(e) => {
+                let error = SongRecError::FingerprintingError(e.to_string());
+                if outcome_tx.send(Err(error)).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Samples of recent audio kept by `capture` for retries and pitch shifts
+#[cfg(feature = "audio-devices")]
+fn recent_audio_length(config: &Config) -> usize {
+    let length = config.retry_durations.last().map_or(0, |&seconds| (seconds * 16000.0) as usize);
+    if config.pitch_shifts.is_empty() {
+        length
+    } else {
+        length.max(PITCH_SHIFT_WINDOW)
+    }
+}
+
+/// Fingerprint consecutive chunks of a network stream, reconnecting when it fails
+///
+/// Each chunk is decoded on its own, which works for streams whose frames carry their own
+/// headers such as MP3. Stops once the lookup workers are gone. A reloaded configuration
+/// applies from the next chunk, see `RecognitionStream::reload`.
+fn capture_stream(
+    live_config: &LiveConfig,
+    url: String,
+    signature_tx: SyncSender<PendingSignature>,
+    outcome_tx: Sender<Result<RecognitionResult>>,
+    counters: &PipelineCounters,
+    events: &Events,
+) {
+    // The client used for lookups has an overall timeout, which would cut the stream
+    let client = reqwest::blocking::Client::builder()
+        .timeout(None)
+        .connect_timeout(Duration::from_secs(live_config.get().network_timeout))
+        .user_agent("SongRec/0.4.3")
+        .build();
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            let _ = outcome_tx.send(Err(SongRecError::NetworkError(e.to_string())));
+            return;
+        }
+    };
+
+    loop {
+        let error = match read_stream(live_config, &client, &url, &signature_tx, &outcome_tx, counters, events) {
+            Ok(()) => return, // Workers stopped
+            Err(e) => e,
+        };
+        if outcome_tx.send(Err(error)).is_err() {
+            return;
+        }
+        thread::sleep(STREAM_RETRY_DELAY);
+    }
+}
+
+/// Read a network stream until it fails, Ok once the lookup workers or the dispatcher are gone
+///
+/// A chunk that cannot be decoded is reported and skipped, the connection is kept.
+fn read_stream(
+    live_config: &LiveConfig,
+    client: &reqwest::blocking::Client,
+    url: &str,
+    signature_tx: &SyncSender<PendingSignature>,
+    outcome_tx: &Sender<Result<RecognitionResult>>,
+    counters: &PipelineCounters,
+    events: &Events,
+) -> Result<()> {
+    let network_error = |e: &dyn std::fmt::Display| SongRecError::NetworkError(format!("Stream '{}': {}", url, e));
+
+    let mut response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| network_error(&e))?;
+
+    // Icecast and Shoutcast announce the bitrate, e.g. "icy-br: 128"
+    let bitrate = response
+        .headers()
+        .get("icy-br")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next()?.trim().parse::<usize>().ok())
+        .filter(|&bitrate| bitrate > 0)
+        .unwrap_or(DEFAULT_STREAM_BITRATE);
+    let chunk_len = bitrate * 1000 / 8 * STREAM_CHUNK_SECONDS;
+
+    events.emit(PipelineEvent::Listening { device: Some(url.to_string()) });
+
+    let mut chunk = Vec::with_capacity(chunk_len);
+    let mut buffer = [0u8; 16 * 1024];
+    let mut clock = ClockWatch::new();
+    loop {
+        let read = response.read(&mut buffer).map_err(|e| network_error(&e))?;
+        if read == 0 {
+            return Err(network_error(&"stream ended"));
+        }
+        counters.audio_received();
+        if let Some(gap) = clock.jump() {
+            chunk.clear();
+            resumed(&live_config.get(), gap, counters, events);
+        }
+        chunk.extend_from_slice(&buffer[..read]);
+        if chunk.len() < chunk_len {
+            events.emit(PipelineEvent::Buffering { percent: chunk.len() as f32 * 100.0 / chunk_len as f32 });
+            continue;
+        }
+
+        let config = &live_config.get();
+        let fingerprint_start = Instant::now();
+        let signature = SignatureGenerator::decode_bytes(&chunk, config.channel_mode).and_then(|samples| {
+            let signature = SignatureGenerator::make_signature_from_recording(&samples, url, config.spectral_weighting)?;
+            let window = SignatureGenerator::recording_window(&samples);
+            let gated = is_gated(config, window, signature.sample_rate_hz, counters, events);
+            let snippet = (config.keep_audio_snippets || config.analyze_tracks)
+                .then(|| AudioSnippet::new(window, signature.sample_rate_hz));
+            let audio_stats = AudioStats::measure(window, signature.sample_rate_hz);
+            Ok((signature, snippet, audio_stats, gated))
+        });
+        chunk.clear();
+        let (signature, snippet, audio_stats) = match signature {
+            Ok((_, _, _, true)) => continue,
+            Ok((signature, snippet, audio_stats, false)) => (signature, snippet, audio_stats),
+            Err(e) => {
+                if outcome_tx.send(Err(decode_error(e))).is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+
+        match signature_tx.try_send(PendingSignature {
+            signature,
+            fingerprint_time: fingerprint_start.elapsed(),
+            ready_at: Instant::now(),
+            snippet,
+            audio_stats: Some(audio_stats),
+            recent_audio: Vec::new(),
+        }) {
+            Ok(()) => counters.signature_queued(),
+            Err(TrySendError::Full(_)) => {
+                counters.skipped_signatures.fetch_add(1, Ordering::Relaxed);
+                log(config, LogLevel::Warn, format_args!("All recognition workers are busy, skipping signature"));
+            }
+            Err(TrySendError::Disconnected(_)) => return Ok(()),
+        }
+    }
+}
+
+/// Whether the music gate keeps `window` from being looked up, see `Config::with_music_only`
+fn is_gated(config: &Config, window: &[i16], sample_rate: u32, counters: &PipelineCounters, events: &Events) -> bool {
+    if !config.music_only {
+        return false;
+    }
+    let class = classify(window, sample_rate);
+    if class == AudioClass::Music {
+        return false;
+    }
+
+    counters.gated_windows.fetch_add(1, Ordering::Relaxed);
+    log(config, LogLevel::Debug, format_args!("Window classified as {:?}, not submitted", class));
+    events.emit(PipelineEvent::Gated { class });
+    true
+}
+
+/// Look signatures up until the capture thread or the dispatcher goes away
+///
+/// A window that does not match is looked up again with the longer recent audio sent
+/// along, see `Config::with_retry_durations`. A longer sample matching the last match of
+/// any worker only reached back into a song already reported, and counts as no match.
+/// The window is then looked up at each other speed until one matches, with the same
+/// rule, see `Config::with_pitch_shifts`.
+fn lookup_worker(
+    live_config: &LiveConfig,
+    http_client: reqwest::blocking::Client,
+    source: RecognitionSource,
+    queue: &LookupQueue,
+    outcome_tx: Sender<Result<RecognitionResult>>,
+    counters: &PipelineCounters,
+    events: &Events,
+) {
+    loop {
+        // Only hold the lock while waiting, so other workers can pick up the next signature
+        let pending = match queue.signatures.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+            Ok(pending) => pending,
+            Err(_) => break,
+        };
+        counters.signature_taken();
+
+        let config = live_config.get();
+        let mut network_ms = 0;
+        let mut lookup = |signature: &DecodedSignature| {
+            events.emit(PipelineEvent::RequestSent);
+            let network_start = Instant::now();
+            let permit = queue.limiter.acquire();
+            let response = recognize_song_from_signature_with_client(signature, &config, &http_client);
+            drop(permit);
+            network_ms += RecognitionTimings::ms_since(network_start);
+            let outcome = response
+                .map_err(|e| SongRecError::NetworkError(e.to_string()))
+                .and_then(|response| RecognitionResult::from_localized_response(response, &config.metadata_language))
+                .map(|mut result| {
+                    result.normalize_genre(&config);
+                    result
+                });
+            if matches!(outcome, Ok(_) | Err(SongRecError::NoMatch(_))) {
+                counters.lookup_answered();
+            }
+            outcome
+        };
+
+        let mut outcome = lookup(&pending.signature);
+        for &seconds in &config.retry_durations {
+            let length = (seconds * 16000.0) as usize;
+            if !matches!(outcome, Err(SongRecError::NoMatch(_))) || length > pending.recent_audio.len() {
+                break;
+            }
+            let recent = &pending.recent_audio[pending.recent_audio.len() - length..];
+            let signature = SignatureGenerator::make_signature_from_buffer_with_weighting(recent, config.spectral_weighting);
+            outcome = unless_last_track(lookup(&signature), queue);
+        }
+
+        let window = &pending.recent_audio[pending.recent_audio.len().saturating_sub(PITCH_SHIFT_WINDOW)..];
+        let mut shifted = SignatureGenerator::make_pitch_shifted_signatures(window, &config.pitch_shifts, config.spectral_weighting);
+        while matches!(outcome, Err(SongRecError::NoMatch(_))) {
+            let Some((_, signature)) = shifted.next() else {
+                break;
+            };
+            outcome = unless_last_track(lookup(&signature), queue);
+        }
+
+        // The audio is only classified when kept for snippets or analysis
+        if matches!(outcome, Err(SongRecError::NoMatch(None))) {
+            let diagnostics = match &pending.snippet {
+                Some(snippet) => NoMatchDiagnostics::measure(&pending.signature, &snippet.samples),
+                None => NoMatchDiagnostics::new(&pending.signature, pending.audio_stats.map_or(-120.0, |stats| stats.rms_dbfs), None),
+            };
+            outcome = Err(SongRecError::NoMatch(Some(Box::new(diagnostics))));
+        }
+
+        let outcome = outcome.map(|mut result| {
+            let fingerprint_ms = pending.fingerprint_time.as_millis() as u64;
+            result.timings = RecognitionTimings {
+                decode_ms: 0,
+                fingerprint_ms,
+                network_ms,
+                total_ms: fingerprint_ms + RecognitionTimings::ms_since(pending.ready_at),
+            };
+            result.source = source.clone();
+            #[cfg(feature = "analysis")]
+            if config.analyze_tracks {
+                result.analysis = pending.snippet.as_ref().map(|snippet| crate::analysis::analyze(&snippet.samples, snippet.sample_rate));
+            }
+            if config.keep_audio_snippets {
+                result.audio_snippet = pending.snippet.clone();
+            }
+            result.audio_stats = pending.audio_stats;
+            *queue.last_track.lock().unwrap_or_else(|e| e.into_inner()) = Some(result.track_key.clone());
+            result
+        });
+
+        if outcome_tx.send(outcome).is_err() {
+            break; // Dispatcher stopped
+        }
+    }
+}
+
+/// Outcome of a retry, which reaches back into earlier audio: a match of the last track
+/// of any worker is a song already reported, and counts as no match
+fn unless_last_track(outcome: Result<RecognitionResult>, queue: &LookupQueue) -> Result<RecognitionResult> {
+    let last_track = queue.last_track.lock().unwrap_or_else(|e| e.into_inner());
+    outcome.and_then(|result| {
+        if last_track.as_deref() == Some(result.track_key.as_str()) { Err(SongRecError::NoMatch(None)) } else { Ok(result) }
+    })
+}
+
+/// Run the integrations on every match and forward outcomes to the stream
+fn dispatch(
+    live_config: &LiveConfig,
+    outcome_rx: Receiver<Result<RecognitionResult>>,
+    result_tx: Sender<Result<RecognitionResult>>,
+    counters: &PipelineCounters,
+    readiness: &ReadinessSignal,
+    events: &Events,
+) {
+    let send = |outcome: Result<RecognitionResult>| {
+        events.outcome(&outcome);
+        counters.result_sent(&outcome);
+        if result_tx.send(outcome).is_err() {
+            return false;
+        }
+        readiness.notify();
+        true
+    };
+
+    let mut tracks = TrackChanges::default();
+    let mut version = live_config.version();
+    let mut resumes = 0;
+    let mut integrations = match Integrations::new(&live_config.get()) {
+        Ok(integrations) => integrations,
+        Err(e) => {
+            send(Err(e));
+            return;
+        }
+    };
+
+    loop {
+        let received = outcome_rx.recv_timeout(DISPATCH_TICK);
+        if let Err(mpsc::RecvTimeoutError::Disconnected) = received {
+            break;
+        }
+
+        if live_config.version() != version {
+            version = live_config.version();
+            if let Err(e) = integrations.reload(&live_config.get()) {
+                send(Err(e));
+            }
+        }
+
+        // The track heard before a suspend is over, its play time stops at its last match
+        let resumed = counters.resumes.load(Ordering::Acquire);
+        if resumed != resumes {
+            resumes = resumed;
+            tracks.end(events);
+        }
+
+        integrations.tick();
+        tracks.tick(events);
+        let Ok(outcome) = received else {
+            continue;
+        };
+        if let Ok(result) = &outcome {
+            tracks.update(result, events);
+            integrations.update(result);
+        }
+
+        if !send(outcome) {
+            break; // Receiver dropped, stop processing
+        }
+    }
+    tracks.end(events);
+}
+
+/// Track playing in continuous mode, see `TrackChanges`
+struct PlayingTrack {
+    result: RecognitionResult,
+    first_seen: Instant,
+    last_seen: Instant,
+    /// When the track should end according to the position of its last match, if its duration is known
+    expected_end: Option<Instant>,
+}
+
+/// Turns the matches of continuous mode into `TrackStarted` and `TrackEnded` events
+///
+/// Consecutive matches of a track are one play. It ends when another track is recognized,
+/// when it has not been recognized for `TRACK_GAP`, or shortly after its expected end
+/// when the response gives its duration.
+#[derive(Default)]
+struct TrackChanges {
+    playing: Option<PlayingTrack>,
+}
+
+impl TrackChanges {
+    fn update(&mut self, result: &RecognitionResult, events: &Events) {
+        let now = Instant::now();
+        let expected_end = result.remaining_time().map(|remaining| now + remaining);
+        if let Some(playing) = self.playing.as_mut().filter(|playing| playing.result.track_key == result.track_key) {
+            playing.result = result.clone();
+            playing.last_seen = now;
+            playing.expected_end = expected_end;
+            return;
+        }
+
+        self.end(events);
+        events.emit(PipelineEvent::TrackStarted { result: Box::new(result.clone()) });
+        self.playing = Some(PlayingTrack {
+            result: result.clone(),
+            first_seen: now,
+            last_seen: now,
+            expected_end,
+        });
+    }
+
+    /// End the playing track if it is over
+    fn tick(&mut self, events: &Events) {
+        let Some(playing) = &self.playing else {
+            return;
+        };
+        let mut deadline = playing.last_seen + TRACK_GAP;
+        if let Some(expected_end) = playing.expected_end {
+            deadline = deadline.min(expected_end + TRACK_END_GRACE);
+        }
+        if Instant::now() >= deadline {
+            self.end(events);
+        }
+    }
+
+    fn end(&mut self, events: &Events) {
+        if let Some(playing) = self.playing.take() {
+            events.emit(PipelineEvent::TrackEnded {
+                played_ms: playing.last_seen.duration_since(playing.first_seen).as_millis() as u64,
+                result: Box::new(playing.result),
+            });
+        }
+    }
+}
+
+/// Feature-gated integrations notified of every match in continuous mode
+struct Integrations {
+    #[cfg(feature = "spotify")]
+    spotify: Option<SpotifyPlaylist>,
+    #[cfg(feature = "discord")]
+    discord: Option<DiscordNowPlaying>,
+    #[cfg(feature = "osc")]
+    osc: Option<OscSender>,
+    /// Settings the integrations were created from, compared on reload
+    config: Config,
+}
+
+impl Integrations {
+    fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            #[cfg(feature = "spotify")]
+            spotify: config.spotify.clone().map(SpotifyPlaylist::new),
+            #[cfg(feature = "discord")]
+            discord: config.discord.clone().map(DiscordNowPlaying::new),
+            #[cfg(feature = "osc")]
+            osc: config
+                .osc
+                .clone()
+                .map(OscSender::new)
+                .transpose()
+                .map_err(|e| SongRecError::ConfigError(format!("Invalid OSC destination: {}", e)))?,
+            config: config.clone(),
+        })
+    }
+
+    /// Apply a reloaded configuration, recreating only the integrations whose settings changed
+    fn reload(&mut self, config: &Config) -> Result<()> {
+        #[cfg(feature = "spotify")]
+        if config.spotify != self.config.spotify {
+            self.spotify = config.spotify.clone().map(SpotifyPlaylist::new);
+        }
+        #[cfg(feature = "discord")]
+        if config.discord != self.config.discord {
+            self.discord = config.discord.clone().map(DiscordNowPlaying::new);
+        }
+        #[cfg(feature = "osc")]
+        if config.osc != self.config.osc {
+            self.osc = config
+                .osc
+                .clone()
+                .map(OscSender::new)
+                .transpose()
+                .map_err(|e| SongRecError::ConfigError(format!("Invalid OSC destination: {}", e)))?;
+        }
+        self.config = config.clone();
+        Ok(())
+    }
+
+    /// Periodic housekeeping, called even when nothing is recognized
+    fn tick(&mut self) {
+        #[cfg(feature = "discord")]
+        if let Some(discord) = self.discord.as_mut() {
+            if let Err(e) = discord.clear_if_stale() {
+                self.report("Discord webhook update failed", e);
+            }
+        }
+    }
+
+    fn update(&mut self, result: &RecognitionResult) {
+        if self.config.dry_run {
+            self.log_dry_run(result);
+        } else {
+            #[cfg(feature = "spotify")]
+            if let Some(spotify) = self.spotify.as_mut() {
+                if let Err(e) = spotify.add_track(result) {
+                    self.report("Spotify playlist update failed", e);
+                }
+            }
+
+            #[cfg(feature = "discord")]
+            if let Some(discord) = self.discord.as_mut() {
+                if let Err(e) = discord.update(result) {
+                    self.report("Discord webhook update failed", e);
+                }
+            }
+
+            #[cfg(feature = "osc")]
+            if let Some(osc) = self.osc.as_mut() {
+                if let Err(e) = osc.update(result) {
+                    self.report("OSC message failed", e);
+                }
+            }
+        }
+    }
+
+    /// Describe the updates `update` would make
+    fn log_dry_run(&self, result: &RecognitionResult) {
+        for target in self.targets() {
+            notice(&self.config, format_args!("Dry run: would send {} - {} to {}", result.artist_name, result.song_name, target));
+        }
+    }
+
+    /// The integrations set up, e.g. "the Discord webhook"
+    fn targets(&self) -> impl Iterator<Item = String> + '_ {
+        let targets = std::iter::empty();
+        #[cfg(feature = "spotify")]
+        let targets = targets.chain(self.config.spotify.iter().map(|spotify| format!("the Spotify playlist {}", spotify.playlist_id)));
+        #[cfg(feature = "discord")]
+        let targets = targets.chain(self.discord.is_some().then(|| "the Discord webhook".to_string()));
+        #[cfg(feature = "osc")]
+        let targets = targets.chain(self.config.osc.iter().map(|osc| format!("OSC {}:{}", osc.host, osc.port)));
+        targets
+    }
+
+    #[cfg(any(feature = "spotify", feature = "discord", feature = "osc"))]
+    fn report(&self, what: &str, error: Box<dyn std::error::Error>) {
+        log(&self.config, LogLevel::Error, format_args!("{}: {}", what, error));
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::pipeline::spawn_named;
+use crate::songrec::{PipelineEvent, RecognitionResult, SongRec};
+use crate::Result;
+
+/// How often the monitor thread checks whether it was stopped when no event arrives
+const STOP_CHECK: Duration = Duration::from_millis(200);
+
+/// State of a `RecognizerHandle`
+#[derive(Debug, Clone)]
+pub enum RecognizerState {
+    /// Not started, stopped, or capture ended
+    Idle,
+    /// Capturing audio, waiting for enough of it to make a signature
+    Listening,
+    /// Collecting audio for the next signature, from 0 to 100
+    Buffering(f32),
+    /// Waiting for the API
+    Querying,
+    /// Last lookup found a song, until the next lookup starts
+    Matched(Box<RecognitionResult>),
+    /// Last lookup or the audio device failed, capture stops if the device was lost
+    Error(String),
+}
+
+impl RecognizerState {
+    /// The state after `event`, None if the event does not change it
+    ///
+    /// Capture keeps buffering while a lookup runs, so buffering progress only shows
+    /// while nothing more interesting is going on.
+    fn after(&self, event: PipelineEvent) -> Option<Self> {
+        match event {
+            PipelineEvent::Listening { .. } => Some(RecognizerState::Listening),
+            PipelineEvent::Buffering { percent } => match self {
+                RecognizerState::Listening | RecognizerState::Buffering(_) | RecognizerState::Error(_) => {
+                    Some(RecognizerState::Buffering(percent))
+                }
+                _ => None,
+            },
+            PipelineEvent::Resumed { .. } => Some(RecognizerState::Listening),
+            PipelineEvent::RequestSent => Some(RecognizerState::Querying),
+            PipelineEvent::Match { result } => Some(RecognizerState::Matched(result)),
+            PipelineEvent::NoMatch { .. } => match self {
+                RecognizerState::Querying => Some(RecognizerState::Listening),
+                _ => None,
+            },
+            PipelineEvent::Gated { .. } | PipelineEvent::TrackStarted { .. } | PipelineEvent::TrackEnded { .. } => None,
+            PipelineEvent::Error { message } | PipelineEvent::DeviceLost { message } => Some(RecognizerState::Error(message)),
+        }
+    }
+}
+
+struct Shared {
+    /// Incremented on every start and stop, so a superseded monitor thread stops updating the state
+    generation: u64,
+    state: RecognizerState,
+    subscribers: Vec<Sender<RecognizerState>>,
+}
+
+impl Shared {
+    fn set(&mut self, state: RecognizerState) {
+        self.subscribers.retain(|subscriber| subscriber.send(state.clone()).is_ok());
+        self.state = state;
+    }
+}
+
+/// Continuous recognition driven as a state machine, for GUIs
+///
+/// Owns the recognition threads: `start` and `stop` can be called from any thread,
+/// state changes are sent to every subscriber.
+pub struct RecognizerHandle {
+    songrec: SongRec,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl RecognizerHandle {
+    pub fn new(songrec: SongRec) -> Self {
+        Self {
+            songrec,
+            shared: Arc::new(Mutex::new(Shared {
+                generation: 0,
+                state: RecognizerState::Idle,
+                subscribers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Start listening on a device, or the default one, restarting if already started
+    pub fn start(&self, device_name: Option<String>) -> Result<()> {
+        let (event_tx, event_rx) = mpsc::channel();
+
+        let generation = {
+            let mut shared = self.lock();
+            shared.generation += 1;
+            shared.generation
+        };
+
+        let stream = self.songrec.start_continuous_recognition_with_events(device_name, event_tx)?;
+        let shared = Arc::clone(&self.shared);
+        let panic_shared = Arc::clone(&self.shared);
+
+        let monitor = move || {
+            loop {
+                let event = match event_rx.recv_timeout(STOP_CHECK) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+                if shared.generation != generation {
+                    return; // Stopped or restarted, dropping the stream stops the pipeline
+                }
+                if let Some(state) = event.and_then(|event| shared.state.after(event)) {
+                    shared.set(state);
+                }
+                drop(shared);
+
+                // Outcomes are tracked through events, the stream only needs to be drained
+                while stream.try_next().is_some() {}
+            }
+
+            // Capture ended on its own, an error stays visible
+            let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+            if shared.generation == generation && !matches!(shared.state, RecognizerState::Error(_)) {
+                shared.set(RecognizerState::Idle);
+            }
+        };
+
+        spawn_named("songrec-recognizer".to_string(), monitor, move |error| {
+            let mut shared = panic_shared.lock().unwrap_or_else(|e| e.into_inner());
+            if shared.generation == generation {
+                shared.set(RecognizerState::Error(error.to_string()));
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Stop listening, the state becomes `Idle` right away
+    pub fn stop(&self) {
+        let mut shared = self.lock();
+        shared.generation += 1;
+        if !matches!(shared.state, RecognizerState::Idle) {
+            shared.set(RecognizerState::Idle);
+        }
+    }
+
+    pub fn state(&self) -> RecognizerState {
+        self.lock().state.clone()
+    }
+
+    /// Receive every state change, starting with the current state
+    pub fn subscribe(&self) -> mpsc::Receiver<RecognizerState> {
+        let (state_tx, state_rx) = mpsc::channel();
+        let mut shared = self.lock();
+        let _ = state_tx.send(shared.state.clone());
+        shared.subscribers.push(state_tx);
+        state_rx
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for RecognizerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::output::Sink;
+use crate::songrec::{PipelineEvent, RecognitionResult};
+
+/// Default submission endpoint of ListenBrainz
+pub const LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+/// When a play counts as a listen, by default the rule of Last.fm and ListenBrainz: the
+/// track lasts over 30 seconds and was heard for half its duration or 4 minutes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrobblePolicy {
+    /// Shorter tracks are never scrobbled
+    pub min_track_duration: Duration,
+
+    /// Share of the track that must be heard
+    pub min_fraction: f32,
+
+    /// Play time after which a long track is scrobbled anyway
+    pub max_required: Duration,
+
+    /// Play time required when the response does not give the duration of the track
+    pub required_without_duration: Duration,
+}
+
+impl Default for ScrobblePolicy {
+    fn default() -> Self {
+        Self {
+            min_track_duration: Duration::from_secs(30),
+            min_fraction: 0.5,
+            max_required: Duration::from_secs(240),
+            required_without_duration: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ScrobblePolicy {
+    /// Share of the track that must be heard, from 0 to 1
+    pub fn with_min_fraction(mut self, fraction: f32) -> Self {
+        self.min_fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Play time after which a long track is scrobbled anyway
+    pub fn with_max_required(mut self, required: Duration) -> Self {
+        self.max_required = required;
+        self
+    }
+
+    /// Play time required for tracks of unknown duration
+    pub fn with_required_without_duration(mut self, required: Duration) -> Self {
+        self.required_without_duration = required;
+        self
+    }
+
+    /// Whether `result`, heard for `played`, is a listen
+    pub fn is_eligible(&self, result: &RecognitionResult, played: Duration) -> bool {
+        match result.song_duration() {
+            Some(duration) => duration > self.min_track_duration && played >= duration.mul_f32(self.min_fraction).min(self.max_required),
+            None => played >= self.required_without_duration,
+        }
+    }
+}
+
+/// Passes events to a scrobbling sink, leaving out the `TrackEnded` events of tracks not heard long enough
+///
+/// Scrobbling sinks submit a listen for every `TrackEnded` they get, so they should be
+/// wrapped in a filter rather than added directly.
+pub struct ScrobbleFilter {
+    inner: Box<dyn Sink>,
+    policy: ScrobblePolicy,
+}
+
+impl ScrobbleFilter {
+    pub fn new(inner: Box<dyn Sink>) -> Self {
+        Self {
+            inner,
+            policy: ScrobblePolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ScrobblePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl Sink for ScrobbleFilter {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        if let PipelineEvent::TrackEnded { result, played_ms } = event {
+            if !self.policy.is_eligible(result, Duration::from_millis(*played_ms)) {
+                return Ok(());
+            }
+        }
+        self.inner.emit(event)
+    }
+
+    fn is_dry_run_safe(&self) -> bool {
+        self.inner.is_dry_run_safe()
+    }
+}
+
+/// Submits tracks to ListenBrainz: playing now when they start, a listen when they end
+///
+/// Wrap it in a `ScrobbleFilter` so only tracks heard long enough become listens.
+pub struct ListenBrainzSink {
+    token: String,
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl ListenBrainzSink {
+    /// Sink submitting with a user token, see https://listenbrainz.org/settings/
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+            url: LISTENBRAINZ_URL.to_string(),
+            client: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_else(|_| reqwest::blocking::Client::new()),
+        }
+    }
+
+    /// Submit to another server implementing the ListenBrainz API
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    fn submit(&self, listen_type: &str, listen: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.client
+            .post(&self.url)
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&serde_json::json!({ "listen_type": listen_type, "payload": [listen] }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Sink for ListenBrainzSink {
+    fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn Error>> {
+        match event {
+            PipelineEvent::TrackStarted { result } => self.submit("playing_now", serde_json::json!({ "track_metadata": track_metadata(result) })),
+            PipelineEvent::TrackEnded { result, played_ms } => {
+                // Listens are dated by when they started
+                let listened_at = chrono::Utc::now().timestamp() - (*played_ms / 1000) as i64;
+                self.submit("single", serde_json::json!({ "listened_at": listened_at, "track_metadata": track_metadata(result) }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Track description of a ListenBrainz submission
+fn track_metadata(result: &RecognitionResult) -> serde_json::Value {
+    let mut additional_info = serde_json::json!({ "submission_client": "songrec" });
+    if let Some(isrc) = result.isrc() {
+        additional_info["isrc"] = isrc.into();
+    }
+    if let Some(duration) = result.song_duration() {
+        additional_info["duration_ms"] = (duration.as_millis() as u64).into();
+    }
+
+    let mut metadata = serde_json::json!({
+        "artist_name": result.primary_artist(),
+        "track_name": result.song_name,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &result.album_name {
+        metadata["release_name"] = album.as_str().into();
+    }
+    metadata
+}
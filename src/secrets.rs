@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::state::StateDir;
+use crate::{Result, SongRecError};
+
+/// Client secret of the Spotify application
+pub const SPOTIFY_CLIENT_SECRET: &str = "spotify-client-secret";
+
+/// Refresh token of the Spotify user
+pub const SPOTIFY_REFRESH_TOKEN: &str = "spotify-refresh-token";
+
+/// URL of the Discord webhook, which is enough to post to the channel
+pub const DISCORD_WEBHOOK: &str = "discord-webhook";
+
+/// User token of ListenBrainz, see `scrobble::ListenBrainzSink`
+pub const LISTENBRAINZ_TOKEN: &str = "listenbrainz-token";
+
+/// Secrets file of the state directory, used when the keyring is unavailable
+const SECRETS_FILE_NAME: &str = "secrets.json";
+
+/// Service name of the keyring entries
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "songrec";
+
+/// Where a secret was stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretLocation {
+    /// OS keyring: Secret Service, macOS Keychain or Windows Credential Manager
+    Keyring,
+    /// File of the state directory only readable by the current user
+    File(PathBuf),
+}
+
+/// Secrets of the integrations, kept out of configuration files
+///
+/// Secrets go to the OS keyring with the keyring feature, and to `secrets.json` in the
+/// state directory otherwise or when no keyring is running (e.g. on a headless server).
+/// That file is created readable by its owner only but is not encrypted.
+pub struct SecretStore {
+    file: Option<PathBuf>,
+}
+
+impl SecretStore {
+    /// Store of the state directory of `config`
+    pub fn for_config(config: &Config) -> Self {
+        Self {
+            file: StateDir::for_config(config).map(|dir| dir.file(SECRETS_FILE_NAME)),
+        }
+    }
+
+    /// Store with its fallback file in `dir`
+    pub fn in_dir(dir: &StateDir) -> Self {
+        Self {
+            file: Some(dir.file(SECRETS_FILE_NAME)),
+        }
+    }
+
+    /// The secret stored under `name`, if any
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        if let Ok(Some(secret)) = keyring_get(name) {
+            return Ok(Some(secret));
+        }
+        Ok(self.read_file()?.remove(name))
+    }
+
+    /// Store a secret, in the keyring if possible
+    pub fn set(&self, name: &str, secret: &str) -> Result<SecretLocation> {
+        if keyring_set(name, secret).is_ok() {
+            // An older copy in the file would still be readable
+            self.remove_from_file(name)?;
+            return Ok(SecretLocation::Keyring);
+        }
+
+        let path = self.file_path()?;
+        let mut secrets = self.read_file()?;
+        secrets.insert(name.to_string(), secret.to_string());
+        write_private(path, &secrets)?;
+        Ok(SecretLocation::File(path.to_path_buf()))
+    }
+
+    /// Forget a secret, wherever it is stored
+    pub fn remove(&self, name: &str) -> Result<()> {
+        keyring_remove(name);
+        self.remove_from_file(name)
+    }
+
+    /// `config` with the secrets left empty in its Spotify, Discord and ListenBrainz settings filled from the store
+    pub fn apply(&self, mut config: Config) -> Result<Config> {
+        if let Some(spotify) = config.spotify.as_mut() {
+            self.fill(&mut spotify.client_secret, SPOTIFY_CLIENT_SECRET)?;
+            self.fill(&mut spotify.refresh_token, SPOTIFY_REFRESH_TOKEN)?;
+        }
+        if let Some(discord) = config.discord.as_mut() {
+            self.fill(&mut discord.webhook_url, DISCORD_WEBHOOK)?;
+        }
+        if let Some(listenbrainz) = config.listenbrainz.as_mut() {
+            self.fill(&mut listenbrainz.token, LISTENBRAINZ_TOKEN)?;
+        }
+        Ok(config)
+    }
+
+    fn fill(&self, field: &mut String, name: &str) -> Result<()> {
+        if field.is_empty() {
+            *field = self
+                .get(name)?
+                .ok_or_else(|| SongRecError::ConfigError(format!("No '{}' stored, see `songrec-cli auth`", name)))?;
+        }
+        Ok(())
+    }
+
+    fn file_path(&self) -> Result<&Path> {
+        self.file
+            .as_deref()
+            .ok_or_else(|| SongRecError::ConfigError("No state directory available for the secrets".to_string()))
+    }
+
+    fn read_file(&self) -> Result<BTreeMap<String, String>> {
+        let Some(path) = &self.file else {
+            return Ok(BTreeMap::new());
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| SongRecError::ConfigError(format!("Could not read secrets '{}': {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(SongRecError::ConfigError(format!("Could not read secrets '{}': {}", path.display(), e))),
+        }
+    }
+
+    fn remove_from_file(&self, name: &str) -> Result<()> {
+        let mut secrets = self.read_file()?;
+        if secrets.remove(name).is_some() {
+            write_private(self.file_path()?, &secrets)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the secrets file, only readable by its owner
+fn write_private(path: &Path, secrets: &BTreeMap<String, String>) -> Result<()> {
+    use std::io::Write;
+
+    let error = |e: std::io::Error| SongRecError::ConfigError(format!("Could not write secrets '{}': {}", path.display(), e));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(error)?;
+        }
+    }
+
+    let content = serde_json::to_string_pretty(secrets)
+        .map_err(|e| SongRecError::ConfigError(format!("Could not serialize secrets: {}", e)))?;
+    options.open(path).and_then(|mut file| file.write_all(content.as_bytes())).map_err(error)
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_get(name: &str) -> std::result::Result<Option<String>, keyring::Error> {
+    match keyring::Entry::new(KEYRING_SERVICE, name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_get(_name: &str) -> std::result::Result<Option<String>, ()> {
+    Err(())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_set(name: &str, secret: &str) -> std::result::Result<(), keyring::Error> {
+    keyring::Entry::new(KEYRING_SERVICE, name)?.set_password(secret)
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_set(_name: &str, _secret: &str) -> std::result::Result<(), ()> {
+    Err(())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_remove(name: &str) {
+    if let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, name) {
+        let _ = entry.delete_credential();
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_remove(_name: &str) {}
@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+
+use crate::{Result, SongRecError};
+
+/// Name of the Windows service and of the systemd unit
+pub const SERVICE_NAME: &str = "songrec";
+
+/// Label of the launchd agent
+pub const LAUNCHD_LABEL: &str = "com.github.marin-m.songrec";
+
+/// Background service running the CLI in listen mode
+///
+/// On Linux it is a systemd user unit, on macOS a launchd agent and on Windows a
+/// service started with the system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDefinition {
+    /// Path of the CLI executable
+    pub executable: PathBuf,
+
+    /// Arguments of the `listen` subcommand, e.g. `["--device", "USB Audio", "-f", "json"]`
+    pub arguments: Vec<String>,
+}
+
+impl ServiceDefinition {
+    pub fn new<P: Into<PathBuf>>(executable: P, arguments: Vec<String>) -> Self {
+        Self {
+            executable: executable.into(),
+            arguments,
+        }
+    }
+
+    /// Arguments run by systemd and launchd: `listen` followed by the listen arguments
+    pub fn listen_arguments(&self) -> Vec<String> {
+        std::iter::once("listen".to_string()).chain(self.arguments.iter().cloned()).collect()
+    }
+
+    /// systemd user unit, results go to the journal
+    pub fn systemd_unit(&self) -> String {
+        let command: Vec<String> = std::iter::once(self.executable.to_string_lossy().into_owned())
+            .chain(self.listen_arguments())
+            .map(|argument| systemd_quote(&argument))
+            .collect();
+
+        format!(
+            r#"[Unit]
+Description=SongRec continuous song recognition
+After=sound.target network-online.target
+
+[Service]
+ExecStart={}
+Restart=on-failure
+RestartSec=10
+
+[Install]
+WantedBy=default.target
+"#,
+            command.join(" ")
+        )
+    }
+
+    /// launchd agent property list, results and errors are appended to `log_file`
+    pub fn launchd_plist(&self, log_file: &Path) -> String {
+        let arguments: String = std::iter::once(self.executable.to_string_lossy().into_owned())
+            .chain(self.listen_arguments())
+            .map(|argument| format!("        <string>{}</string>\n", xml_escape(&argument)))
+            .collect();
+        let log_file = xml_escape(&log_file.to_string_lossy());
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log_file}</string>
+    <key>StandardErrorPath</key>
+    <string>{log_file}</string>
+</dict>
+</plist>
+"#,
+            label = LAUNCHD_LABEL,
+            arguments = arguments,
+            log_file = log_file
+        )
+    }
+
+    /// Register and start the service, returns the unit or plist file written, if any
+    #[cfg(target_os = "linux")]
+    pub fn install(&self) -> Result<Option<PathBuf>> {
+        let path = systemd_unit_path()?;
+        write_file(&path, &self.systemd_unit())?;
+        run(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+        run(Command::new("systemctl").args(["--user", "enable", "--now", SERVICE_NAME]))?;
+        Ok(Some(path))
+    }
+
+    /// Register and start the service, returns the unit or plist file written, if any
+    #[cfg(target_os = "macos")]
+    pub fn install(&self) -> Result<Option<PathBuf>> {
+        let path = launchd_plist_path()?;
+        write_file(&path, &self.launchd_plist(&launchd_log_path()?))?;
+        run(Command::new("launchctl").arg("load").arg("-w").arg(&path))?;
+        Ok(Some(path))
+    }
+
+    /// Register and start the service, returns the unit or plist file written, if any
+    ///
+    /// The service runs `<executable> service run -- <arguments>` and requires administrator rights.
+    /// The output of listen mode is appended to `windows_log_path`.
+    #[cfg(windows)]
+    pub fn install(&self) -> Result<Option<PathBuf>> {
+        use std::ffi::OsString;
+        use windows_service::service::{ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType};
+        use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+        let launch_arguments = ["service", "run", "--"]
+            .iter()
+            .map(OsString::from)
+            .chain(self.arguments.iter().map(OsString::from))
+            .collect();
+
+        let info = ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("SongRec"),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: self.executable.clone(),
+            launch_arguments,
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+        };
+
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+            .map_err(windows_error)?;
+        let service = manager
+            .create_service(&info, ServiceAccess::CHANGE_CONFIG | ServiceAccess::START)
+            .map_err(windows_error)?;
+        service.set_description("Continuous song recognition").map_err(windows_error)?;
+        service.start::<&str>(&[]).map_err(windows_error)?;
+        Ok(None)
+    }
+
+    /// Register and start the service, returns the unit or plist file written, if any
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    pub fn install(&self) -> Result<Option<PathBuf>> {
+        Err(SongRecError::ConfigError("Services are not supported on this platform".to_string()))
+    }
+}
+
+/// Stop and remove the service installed by `ServiceDefinition::install`
+#[cfg(target_os = "linux")]
+pub fn uninstall() -> Result<()> {
+    let path = systemd_unit_path()?;
+    run(Command::new("systemctl").args(["--user", "disable", "--now", SERVICE_NAME]))?;
+    remove_file(&path)?;
+    run(Command::new("systemctl").args(["--user", "daemon-reload"]))
+}
+
+/// Stop and remove the service installed by `ServiceDefinition::install`
+#[cfg(target_os = "macos")]
+pub fn uninstall() -> Result<()> {
+    let path = launchd_plist_path()?;
+    run(Command::new("launchctl").arg("unload").arg("-w").arg(&path))?;
+    remove_file(&path)
+}
+
+/// Stop and remove the service installed by `ServiceDefinition::install`
+#[cfg(windows)]
+pub fn uninstall() -> Result<()> {
+    use windows_service::service::{ServiceAccess, ServiceState};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT).map_err(windows_error)?;
+    let service = manager
+        .open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE)
+        .map_err(windows_error)?;
+    if service.query_status().map_err(windows_error)?.current_state != ServiceState::Stopped {
+        service.stop().map_err(windows_error)?;
+    }
+    service.delete().map_err(windows_error)
+}
+
+/// Stop and remove the service installed by `ServiceDefinition::install`
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn uninstall() -> Result<()> {
+    Err(SongRecError::ConfigError("Services are not supported on this platform".to_string()))
+}
+
+/// Quote an argument of a systemd `ExecStart` line
+fn systemd_quote(argument: &str) -> String {
+    // Specifiers and variables are expanded even inside quotes
+    let escaped = argument.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty() && !escaped.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';')) {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Log of the launchd agent, `~/Library/Logs/songrec.log` with the home directory spelled
+/// out since launchd does not expand `~`
+pub fn launchd_log_path() -> Result<PathBuf> {
+    Ok(home_dir()?.join("Library/Logs/songrec.log"))
+}
+
+/// Log of the Windows service, in the program data directory since the service does not
+/// run as the user who installed it
+#[cfg(windows)]
+pub fn windows_log_path() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join("SongRec")
+        .join("songrec.log")
+}
+
+fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| SongRecError::ConfigError("HOME is not set".to_string()))
+}
+
+#[cfg(target_os = "linux")]
+fn systemd_unit_path() -> Result<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".config"),
+    };
+    Ok(config_dir.join("systemd/user").join(format!("{}.service", SERVICE_NAME)))
+}
+
+#[cfg(target_os = "macos")]
+fn launchd_plist_path() -> Result<PathBuf> {
+    Ok(home_dir()?.join("Library/LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not create '{}': {}", parent.display(), e)))?;
+    }
+    std::fs::write(path, content).map_err(|e| SongRecError::ConfigError(format!("Could not write '{}': {}", path.display(), e)))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(SongRecError::ConfigError(format!("Could not remove '{}': {}", path.display(), e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .map_err(|e| SongRecError::ConfigError(format!("Could not run {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(SongRecError::ConfigError(format!("{} failed with {}", program, status)));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn windows_error(error: windows_service::Error) -> SongRecError {
+    SongRecError::ConfigError(format!("Windows service error: {}", error))
+}
@@ -1,176 +1,778 @@
-use std::sync::mpsc;
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::config::Config;
-use crate::fingerprinting::algorithm::SignatureGenerator;
-use crate::fingerprinting::communication::{recognize_song_from_signature_with_config, recognize_song_from_signature};
+use crate::credits::{self, ArtistCredit, ArtistRole};
+use crate::diagnostics::NoMatchDiagnostics;
+use crate::genre;
+use crate::fingerprinting::algorithm::{decode_error, SignatureGenerator};
+use crate::fingerprinting::signature_format::DecodedSignature;
+use crate::fingerprinting::communication::{recognize_song_from_signature_with_client, build_http_client, fetch_related_tracks, fetch_artist_info, MetadataLocale};
+use crate::discovery::{TrackSummary, ArtistInfo};
+use crate::audio::pcm::PcmBuffer;
+#[cfg(feature = "audio-devices")]
 use crate::audio::recorder::AudioRecorder;
-use crate::audio::processor::AudioProcessor;
+use crate::audio::snippet::AudioSnippet;
+use crate::audio::loudness::AudioStats;
+use crate::audio::classifier::AudioClass;
+use crate::analysis::TrackAnalysis;
+use crate::pipeline::{self, Input, LiveConfig, PipelineCounters, ReadinessFd};
+use crate::limiter::LookupLimiter;
+use crate::logging::{log, LogLevel};
+use crate::tracklist::Tracklist;
+use crate::output::Sink;
 use crate::{Result, SongRecError};
 
+/// Audio fingerprinted at each position of a tracklist, in samples at 16 kHz
+const TRACKLIST_WINDOW: usize = 12 * 16000;
+
+/// Shortest window at the end of a recording worth looking up
+const TRACKLIST_MIN_WINDOW: usize = 3 * 16000;
+
+/// Precision of refined track changes, in samples at 16 kHz
+const TRACKLIST_RESOLUTION: usize = 3 * 16000;
+
+/// Audio recorded by `SongRec::recognize_from_device` for one window, in samples at 16 kHz
+#[cfg(feature = "audio-devices")]
+const RECORD_WINDOW: usize = 12 * 16000;
+
+/// Time without audio after which `SongRec::recognize_from_device` gives up
+#[cfg(feature = "audio-devices")]
+const RECORD_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Main SongRec struct for audio recognition
 pub struct SongRec {
     config: Config,
+    http_client: reqwest::blocking::Client,
+    lookup_limiter: Arc<LookupLimiter>,
 }
 
 /// Result of a song recognition
+///
+/// Fields are added as the API is better understood, build results with `RecognitionResult::new`.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[non_exhaustive]
 pub struct RecognitionResult {
     pub song_name: String,
     pub artist_name: String,
+    /// Artists named by the artist credit and the title, the main credit first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<ArtistCredit>,
     pub album_name: Option<String>,
     pub track_key: String,
     pub release_year: Option<String>,
     pub genre: Option<String>,
+    /// Genre mapped onto a small vocabulary, only with `Config::with_genre_normalization`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre_normalized: Option<String>,
+    /// Position of the song on its album, from the Apple Music metadata or the song section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<u32>,
+    /// Disc of the album holding the song
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disc_number: Option<u32>,
     pub recognition_timestamp: chrono::DateTime<chrono::Utc>,
     pub raw_response: serde_json::Value,
+    #[serde(default)]
+    pub timings: RecognitionTimings,
+    /// Where the recognized audio came from
+    #[serde(default)]
+    pub source: RecognitionSource,
+    /// Audio the signature was made from, only kept with `Config::with_audio_snippets`
+    #[serde(skip)]
+    pub audio_snippet: Option<AudioSnippet>,
+    /// Tempo and key estimated locally, only with `Config::with_track_analysis`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<TrackAnalysis>,
+    /// Loudness of the audio the signature was made from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_stats: Option<AudioStats>,
+    /// Windows `SongRec::recognize_from_device` recorded, more than 1 when unusable ones were recorded again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_attempts: Option<u32>,
+}
+
+/// Origin of the audio of a `RecognitionResult`
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RecognitionSource {
+    /// Audio file, as passed to `SongRec::recognize_from_file`
+    File { path: String },
+    /// Live capture, `name` is None for the default input device
+    Device { name: Option<String> },
+    /// Network stream, set by the caller with `RecognitionResult::with_source`
+    Stream { url: String },
+    /// Audio held in memory: encoded bytes, PCM or raw samples
+    Bytes,
+    /// Parsed from a raw response, or saved before sources were recorded
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for RecognitionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecognitionSource::File { path } => write!(f, "file:{}", path),
+            RecognitionSource::Device { name } => write!(f, "device:{}", name.as_deref().unwrap_or("default")),
+            RecognitionSource::Stream { url } => write!(f, "stream:{}", url),
+            RecognitionSource::Bytes => write!(f, "bytes"),
+            RecognitionSource::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Time spent in each stage of a recognition, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecognitionTimings {
+    /// Decoding and resampling the input (0 for live audio and raw samples)
+    pub decode_ms: u64,
+
+    /// Generating the signature
+    pub fingerprint_ms: u64,
+
+    /// Waiting for the API, including retries
+    pub network_ms: u64,
+
+    /// Whole recognition, including time spent waiting for a free worker in continuous mode
+    pub total_ms: u64,
+}
+
+impl RecognitionTimings {
+    pub(crate) fn ms_since(start: Instant) -> u64 {
+        start.elapsed().as_millis() as u64
+    }
+}
+
+/// Stage of a file recognition, see `SongRec::recognize_from_file_with_progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecognitionPhase {
+    /// Reading and resampling the file
+    Decode,
+    /// Generating the signature
+    Fingerprint,
+    /// Waiting for the API
+    Lookup,
+}
+
+/// Progress update of a file recognition
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecognitionProgress {
+    pub phase: RecognitionPhase,
+
+    /// Completion of the current phase, from 0 to 100
+    pub percent: f32,
 }
 
 /// Stream of recognition results for continuous monitoring
 pub struct RecognitionStream {
     receiver: mpsc::Receiver<Result<RecognitionResult>>,
-    _handles: Vec<thread::JoinHandle<()>>, // Keep handles to prevent threads from being dropped
+    counters: Arc<PipelineCounters>,
+    readiness: ReadinessFd,
+    live_config: Arc<LiveConfig>,
+    handles: Vec<thread::JoinHandle<()>>, // Keep handles to prevent threads from being dropped
+}
+
+/// Overrun counters of a continuous recognition stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PipelineStats {
+    /// Captured sample batches dropped because fingerprinting did not keep up
+    pub dropped_sample_batches: u64,
+
+    /// Signatures skipped because every recognition worker was busy
+    pub skipped_signatures: u64,
+
+    /// Windows not submitted because they were classified as speech or silence
+    #[serde(default)]
+    pub gated_windows: u64,
+}
+
+/// Liveness of a continuous recognition stream, see `RecognitionStream::health`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PipelineHealth {
+    /// Whether any pipeline thread is still running
+    pub alive: bool,
+
+    /// When audio last arrived from the device or the network stream
+    pub last_audio_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// When the API last answered, with a match or not
+    pub last_lookup_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Signatures waiting for a recognition worker
+    pub queued_signatures: usize,
+
+    /// Results waiting to be received from the stream
+    pub pending_results: usize,
+
+    /// Errors sent through the stream by category, see `SongRecError::kind`
+    pub errors_by_kind: BTreeMap<String, u64>,
+
+    pub stats: PipelineStats,
+}
+
+impl PipelineHealth {
+    /// Whether the pipeline runs and received audio within `max_silence`, never receiving any is unhealthy
+    pub fn is_healthy(&self, max_silence: Duration) -> bool {
+        // A clock set back gives a negative age, which counts as recent
+        let recent = |at: chrono::DateTime<chrono::Utc>| (chrono::Utc::now() - at).to_std().map_or(true, |age| age <= max_silence);
+        self.alive && self.last_audio_at.is_some_and(recent)
+    }
+}
+
+/// State change of a continuous recognition, see `SongRec::start_continuous_recognition_with_events`
+///
+/// Serialized with an `event` tag, e.g. `{"event":"buffering","percent":37.5}`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum PipelineEvent {
+    /// Audio capture started, `device` is the input device or the stream URL, None for the default device
+    Listening { device: Option<String> },
+    /// Audio collected for the next signature, from 0 to 100
+    Buffering { percent: f32 },
+    /// A signature was sent to the API
+    RequestSent,
+    Match { result: Box<RecognitionResult> },
+    /// The API did not recognize the window, `diagnostics` tell why
+    NoMatch {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        diagnostics: Option<Box<NoMatchDiagnostics>>,
+    },
+    /// A window classified as speech or silence was not submitted, see `Config::with_music_only`
+    Gated { class: AudioClass },
+    /// Recognition failed, capture goes on
+    Error { message: String },
+    /// The audio device disappeared, capture stopped
+    DeviceLost { message: String },
+    /// Capture was paused for `gap_ms` milliseconds, e.g. by a system suspend, and its buffered audio dropped
+    Resumed { gap_ms: u64 },
+    /// A different track than the one playing was recognized, sent before its `Match`
+    TrackStarted { result: Box<RecognitionResult> },
+    /// The track stopped playing: another one started, it was not recognized for a while,
+    /// it reached its end, or capture stopped
+    TrackEnded {
+        /// Last match of the track
+        result: Box<RecognitionResult>,
+        /// Time between the first and the last match of the track, in milliseconds
+        played_ms: u64,
+    },
 }
 
 impl SongRec {
     /// Create a new SongRec instance with the given configuration
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let http_client = build_http_client(&config)
+            .unwrap_or_else(|_| reqwest::blocking::Client::new());
+        let lookup_limiter = Arc::new(LookupLimiter::new(config.max_concurrent_lookups, Duration::try_from_secs_f32(config.lookup_delay).unwrap_or_default()));
+        Self { config, http_client, lookup_limiter }
+    }
+
+    /// Configuration this instance was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Number of API lookups sent so far, continuous recognition included
+    pub fn lookup_count(&self) -> u64 {
+        self.lookup_limiter.started()
     }
 
-    /// Recognize a song from an audio file
+    /// Recognize a song from an audio file, or from the audio of a video file
+    ///
+    /// MP4 and MOV videos with AAC or PCM audio and WebM and MKV videos with Vorbis
+    /// audio are read directly, the first audio track is used.
     pub fn recognize_from_file(&self, file_path: &str) -> Result<RecognitionResult> {
-        // Generate signature from file
-        let signature = SignatureGenerator::make_signature_from_file(file_path)
+        self.recognize_from_file_with_progress(file_path, |_| {})
+    }
+
+    /// Recognize a song from an audio file, calling `progress` as each phase advances
+    ///
+    /// Every phase reports 0% when it starts and 100% when it ends. Decoding, the
+    /// longest phase for large files, also reports intermediate steps.
+    pub fn recognize_from_file_with_progress<F>(&self, file_path: &str, mut progress: F) -> Result<RecognitionResult>
+    where
+        F: FnMut(RecognitionProgress),
+    {
+        let started = Instant::now();
+        let mut timings = RecognitionTimings::default();
+        let mut report = |phase, percent| progress(RecognitionProgress { phase, percent });
+
+        // Decode the file to mono 16 KHz samples
+        report(RecognitionPhase::Decode, 0.0);
+        let samples = SignatureGenerator::decode_file_with_progress(file_path, self.config.channel_mode, &mut |percent| report(RecognitionPhase::Decode, percent))
+            .map_err(decode_error)?;
+        timings.decode_ms = RecognitionTimings::ms_since(started);
+
+        // Generate signature from the decoded samples
+        report(RecognitionPhase::Fingerprint, 0.0);
+        let fingerprint_start = Instant::now();
+        let signature = SignatureGenerator::make_signature_from_recording_with_progress(
+            &samples,
+            &format!("file '{}'", file_path),
+            self.config.spectral_weighting,
+            &mut |percent| report(RecognitionPhase::Fingerprint, percent),
+        )
+        .map_err(|e| SongRecError::FingerprintingError(e.to_string()))?;
+        timings.fingerprint_ms = RecognitionTimings::ms_since(fingerprint_start);
+
+        report(RecognitionPhase::Lookup, 0.0);
+        let source = RecognitionSource::File { path: file_path.to_string() };
+        let result = self.lookup_recording(&samples, &signature, source, timings, started)?;
+        report(RecognitionPhase::Lookup, 100.0);
+
+        Ok(result)
+    }
+
+    /// Recognize every track of a long recording, such as a DJ mix, see `recognize_tracklist_with_progress`
+    pub fn recognize_tracklist(&self, file_path: &str, interval: Duration) -> Result<Tracklist> {
+        self.recognize_tracklist_with_progress(file_path, interval, |_| {})
+    }
+
+    /// Recognize every track of a long recording, fingerprinting 12 seconds every `interval`
+    ///
+    /// Lookups report their progress over the whole recording. Windows that do not match
+    /// are left out, any other error stops the recognition. Track changes are then located
+    /// within a few seconds, see `Config::with_boundary_refinement`.
+    pub fn recognize_tracklist_with_progress<F>(&self, file_path: &str, interval: Duration, mut progress: F) -> Result<Tracklist>
+    where
+        F: FnMut(RecognitionProgress),
+    {
+        let mut report = |phase, percent| progress(RecognitionProgress { phase, percent });
+        report(RecognitionPhase::Decode, 0.0);
+        let samples = SignatureGenerator::decode_file_with_progress(file_path, self.config.channel_mode, &mut |percent| report(RecognitionPhase::Decode, percent))
+            .map_err(decode_error)?;
+
+        let step = (interval.as_millis() as usize * 16).max(TRACKLIST_MIN_WINDOW);
+        let starts: Vec<usize> = (0..samples.len()).step_by(step).filter(|&start| samples.len() - start >= TRACKLIST_MIN_WINDOW).collect();
+        let mut tracklist = Tracklist::new(file_path, samples.len() as u64 / 16);
+        let source = RecognitionSource::File { path: file_path.to_string() };
+        // Start and track key of the last window that matched
+        let mut last_match: Option<(usize, String)> = None;
+
+        report(RecognitionPhase::Lookup, 0.0);
+        for (index, &start) in starts.iter().enumerate() {
+            let result = self.lookup_tracklist_window(&samples, start, &source)?;
+            let window_end = (start + TRACKLIST_WINDOW).min(samples.len());
+
+            let mut boundary = None;
+            if let Some(result) = &result {
+                if let Some((previous_start, previous_key)) = &last_match {
+                    if *previous_key != result.track_key && self.config.refine_tracklist_boundaries {
+                        boundary = Some(self.refine_boundary(&samples, &source, *previous_start, start, previous_key, &result.track_key)?);
+                    }
+                }
+                last_match = Some((start, result.track_key.clone()));
+            }
+
+            tracklist.add_window(start as u64 / 16, window_end as u64 / 16, result);
+            if let Some(boundary) = boundary {
+                tracklist.move_last_boundary(boundary as u64 / 16);
+            }
+            report(RecognitionPhase::Lookup, (index + 1) as f32 * 100.0 / starts.len() as f32);
+        }
+
+        Ok(tracklist)
+    }
+
+    /// Look up the window of a tracklist starting at sample `start`, None if it does not match
+    fn lookup_tracklist_window(&self, samples: &[i16], start: usize, source: &RecognitionSource) -> Result<Option<RecognitionResult>> {
+        let started = Instant::now();
+        let window = &samples[start..(start + TRACKLIST_WINDOW).min(samples.len())];
+        let signature = SignatureGenerator::make_signature_from_buffer_with_weighting(window, self.config.spectral_weighting);
+        let timings = RecognitionTimings { fingerprint_ms: RecognitionTimings::ms_since(started), ..Default::default() };
+
+        match self.lookup_signature(&signature, source.clone(), window, timings, started) {
+            Ok(result) => Ok(Some(result)),
+            Err(SongRecError::NoMatch(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sample where `after` replaces `before`, between windows starting at `before_start` and `after_start`
+    ///
+    /// A window matches the track playing during most of it, so the change is half a window
+    /// after the last window matching `before`, and no later than half a window after the
+    /// first one matching `after`. Windows in between are looked up by bisection until
+    /// both are a few seconds apart. Only a match of either track is trusted: a window
+    /// without a match or matching a third track, e.g. in a long blend, ends the search.
+    fn refine_boundary(
+        &self,
+        samples: &[i16],
+        source: &RecognitionSource,
+        mut before_start: usize,
+        mut after_start: usize,
+        before: &str,
+        after: &str,
+    ) -> Result<usize> {
+        while after_start - before_start > TRACKLIST_RESOLUTION {
+            let probe = before_start + (after_start - before_start) / 2;
+            match self.lookup_tracklist_window(samples, probe, source)? {
+                Some(result) if result.track_key == before => before_start = probe,
+                Some(result) if result.track_key == after => after_start = probe,
+                _ => break,
+            }
+        }
+        Ok((before_start + after_start) / 2 + TRACKLIST_WINDOW / 2)
+    }
+
+    /// Recognize a song from a complete encoded audio file held in memory (WAV, MP3, OGG, FLAC, M4A or a video)
+    ///
+    /// Useful for files received over the network, no temporary file is written.
+    pub fn recognize_from_bytes(&self, data: &[u8]) -> Result<RecognitionResult> {
+        let started = Instant::now();
+        let mut timings = RecognitionTimings::default();
+
+        let samples = SignatureGenerator::decode_bytes(data, self.config.channel_mode).map_err(decode_error)?;
+        SignatureGenerator::check_audible(&samples, "the audio data")?;
+        timings.decode_ms = RecognitionTimings::ms_since(started);
+
+        let fingerprint_start = Instant::now();
+        let signature = SignatureGenerator::make_signature_from_recording(&samples, "audio data", self.config.spectral_weighting)
             .map_err(|e| SongRecError::FingerprintingError(e.to_string()))?;
+        timings.fingerprint_ms = RecognitionTimings::ms_since(fingerprint_start);
 
-        // Recognize song from signature with config
-        let response = recognize_song_from_signature_with_config(&signature, &self.config)
-            .map_err(|e| SongRecError::NetworkError(e.to_string()))?;
+        self.lookup_recording(&samples, &signature, RecognitionSource::Bytes, timings, started)
+    }
 
-        // Parse response into RecognitionResult
-        self.parse_recognition_response(response)
+    /// Recognize a song from raw PCM audio in any common layout
+    ///
+    /// The audio is downmixed and resampled to 16 kHz internally. Descriptions that
+    /// cannot match the data (zero channels, truncated frames...) are rejected with
+    /// `SongRecError::InvalidInput`.
+    pub fn recognize_from_pcm(&self, pcm: &PcmBuffer) -> Result<RecognitionResult> {
+        let started = Instant::now();
+        let mut timings = RecognitionTimings::default();
+
+        let samples = pcm.to_mono_16khz_with(self.config.channel_mode)?;
+        timings.decode_ms = RecognitionTimings::ms_since(started);
+
+        let fingerprint_start = Instant::now();
+        let signature = SignatureGenerator::make_signature_from_recording(&samples, "PCM buffer", self.config.spectral_weighting)
+            .map_err(|e| SongRecError::FingerprintingError(e.to_string()))?;
+        timings.fingerprint_ms = RecognitionTimings::ms_since(fingerprint_start);
+
+        self.lookup_recording(&samples, &signature, RecognitionSource::Bytes, timings, started)
     }
 
     /// Recognize a song from raw audio samples
+    ///
+    /// Samples must already be mono at a rate close to 16 kHz, see `recognize_from_pcm` otherwise.
     pub fn recognize_from_samples(&self, samples: &[i16], sample_rate: u32) -> Result<RecognitionResult> {
+        let started = Instant::now();
+        let mut timings = RecognitionTimings::default();
+
         // Create signature generator and process samples
-        let mut generator = SignatureGenerator::new();
+        let mut generator = SignatureGenerator::new().with_weighting(self.config.spectral_weighting);
         
-        // Process the samples to generate a signature
-        for chunk in samples.chunks(128) {
-            generator.do_fft(chunk, sample_rate);
+        // Process the samples to generate a signature, a trailing partial step is buffered
+        generator.feed(samples);
+
+        let mut signature = generator.finalize();
+        signature.sample_rate_hz = sample_rate;
+        timings.fingerprint_ms = RecognitionTimings::ms_since(started);
+
+        self.lookup_signature(&signature, RecognitionSource::Bytes, samples, timings, started)
+    }
+
+    /// Record one window from an input device, or the default one, and recognize it (requires the audio-devices feature)
+    ///
+    /// A silent window, or one with almost no peaks, is recorded again instead of being looked
+    /// up, up to `Config::record_attempts` windows; `RecognitionResult::record_attempts` and
+    /// `NoMatchDiagnostics::attempts` tell how many were recorded.
+    #[cfg(feature = "audio-devices")]
+    pub fn recognize_from_device(&self, device_name: Option<String>) -> Result<RecognitionResult> {
+        let started = Instant::now();
+        let mut recorder = AudioRecorder::new(self.config.clone());
+        let (_control_tx, control_rx) = mpsc::channel();
+        let samples = recorder
+            .start_recording(device_name.clone(), control_rx)
+            .map_err(|e| SongRecError::AudioError(e.to_string()))?;
+        let source = RecognitionSource::Device { name: device_name };
+        let attempts = self.config.record_attempts.max(1);
+
+        let mut attempt = 1;
+        loop {
+            let mut window = Vec::with_capacity(RECORD_WINDOW);
+            while window.len() < RECORD_WINDOW {
+                let batch = samples
+                    .recv_timeout(RECORD_TIMEOUT)
+                    .map_err(|_| SongRecError::AudioError("No audio received from the input device".to_string()))?;
+                window.extend_from_slice(&batch);
+            }
+            window.truncate(RECORD_WINDOW);
+
+            let fingerprint_start = Instant::now();
+            let signature = SignatureGenerator::make_signature_from_buffer_with_weighting(&window, self.config.spectral_weighting);
+            let diagnostics = NoMatchDiagnostics::measure(&signature, &window);
+            let timings = RecognitionTimings { fingerprint_ms: RecognitionTimings::ms_since(fingerprint_start), ..Default::default() };
+
+            if diagnostics.is_unusable() {
+                if attempt < attempts {
+                    log(&self.config, LogLevel::Info, format_args!("Unusable window ({}), recording again", diagnostics.hint));
+                    attempt += 1;
+                    continue;
+                }
+                return Err(SongRecError::NoMatch(Some(Box::new(NoMatchDiagnostics { attempts, ..diagnostics }))));
+            }
+
+            return match self.lookup_signature(&signature, source, &window, timings, started) {
+                Ok(mut result) => {
+                    result.record_attempts = Some(attempt);
+                    Ok(result)
+                }
+                Err(SongRecError::NoMatch(Some(mut diagnostics))) => {
+                    diagnostics.attempts = attempt;
+                    Err(SongRecError::NoMatch(Some(diagnostics)))
+                }
+                Err(e) => Err(e),
+            };
         }
+    }
 
-        let signature = generator.get_signature();
+    /// Look up the signature of the middle of a recording, then longer parts of its middle
+    /// if it does not match, see `Config::with_retry_durations`, then its middle at each
+    /// other speed until one matches, see `Config::with_pitch_shifts`
+    fn lookup_recording(
+        &self,
+        samples: &[i16],
+        signature: &DecodedSignature,
+        source: RecognitionSource,
+        timings: RecognitionTimings,
+        started: Instant,
+    ) -> Result<RecognitionResult> {
+        let window = SignatureGenerator::recording_window(samples);
+        let mut outcome = self.lookup_signature(signature, source.clone(), window, timings, started);
 
-        // Recognize song from signature
-        let response = recognize_song_from_signature(&signature)
-            .map_err(|e| SongRecError::NetworkError(e.to_string()))?;
+        for &seconds in &self.config.retry_durations {
+            let length = (seconds * 16000.0) as usize;
+            if !matches!(outcome, Err(SongRecError::NoMatch(_))) || length > samples.len() {
+                break;
+            }
+            log(&self.config, LogLevel::Debug, format_args!("No match, trying again with {} seconds of audio", seconds));
+
+            let start = (samples.len() - length) / 2;
+            let window = &samples[start..start + length];
+            let fingerprint_start = Instant::now();
+            let signature = SignatureGenerator::make_signature_from_buffer_with_weighting(window, self.config.spectral_weighting);
+            let timings = RecognitionTimings { fingerprint_ms: timings.fingerprint_ms + RecognitionTimings::ms_since(fingerprint_start), ..timings };
+            outcome = self.lookup_signature(&signature, source.clone(), window, timings, started);
+        }
+
+        let mut shifted = SignatureGenerator::make_pitch_shifted_signatures(window, &self.config.pitch_shifts, self.config.spectral_weighting);
+        while matches!(outcome, Err(SongRecError::NoMatch(_))) {
+            let fingerprint_start = Instant::now();
+            let Some((percent, signature)) = shifted.next() else {
+                break;
+            };
+            log(&self.config, LogLevel::Debug, format_args!("No match, trying again as if the audio was sped up by {:+}%", percent));
+            let timings = RecognitionTimings { fingerprint_ms: timings.fingerprint_ms + RecognitionTimings::ms_since(fingerprint_start), ..timings };
+            outcome = self.lookup_signature(&signature, source.clone(), window, timings, started);
+        }
+        outcome
+    }
+
+    /// Send a signature to the API and parse the response
+    /// `window` is the audio fingerprinted, `timings` holds the stages done so far and
+    /// `started` is the start of the whole recognition
+    fn lookup_signature(
+        &self,
+        signature: &DecodedSignature,
+        source: RecognitionSource,
+        window: &[i16],
+        mut timings: RecognitionTimings,
+        started: Instant,
+    ) -> Result<RecognitionResult> {
+        // Time spent waiting for the limiter counts as network time
+        let network_start = Instant::now();
+        let permit = self.lookup_limiter.acquire();
+        let response = recognize_song_from_signature_with_client(signature, &self.config, &self.http_client)
+            .map_err(|e| SongRecError::NetworkError(e.to_string()));
+        drop(permit);
+        let response = response?;
+        timings.network_ms = RecognitionTimings::ms_since(network_start);
 
         // Parse response into RecognitionResult
-        self.parse_recognition_response(response)
+        let mut result = RecognitionResult::from_localized_response(response, &self.config.metadata_language)
+            .map_err(|e| match e {
+                SongRecError::NoMatch(None) => SongRecError::NoMatch(Some(Box::new(NoMatchDiagnostics::measure(signature, window)))),
+                e => e,
+            })?;
+        result.normalize_genre(&self.config);
+        timings.total_ms = RecognitionTimings::ms_since(started);
+        result.timings = timings;
+        result.source = source;
+        result.audio_stats = Some(AudioStats::measure(window, signature.sample_rate_hz));
+        if self.config.keep_audio_snippets {
+            result.audio_snippet = Some(AudioSnippet::new(window, signature.sample_rate_hz));
+        }
+        #[cfg(feature = "analysis")]
+        if self.config.analyze_tracks {
+            result.analysis = Some(crate::analysis::analyze(window, signature.sample_rate_hz));
+        }
+        Ok(result)
+    }
+
+    /// Look up tracks related to a recognized song
+    pub fn related_tracks(&self, result: &RecognitionResult) -> Result<Vec<TrackSummary>> {
+        if result.track_key.is_empty() {
+            return Err(SongRecError::InvalidInput("Recognition result has no track key".to_string()));
+        }
+
+        let response = fetch_related_tracks(&result.track_key, &self.config, &self.http_client)
+            .map_err(|e| SongRecError::NetworkError(e.to_string()))?;
+
+        Ok(TrackSummary::list_from_related_response(&response))
+    }
+
+    /// Look up the artist of a recognized song: biography, genres and top songs
+    pub fn artist_info(&self, result: &RecognitionResult) -> Result<ArtistInfo> {
+        let artist_id = result.artist_id()
+            .ok_or_else(|| SongRecError::InvalidInput("Recognition result has no artist id".to_string()))?;
+
+        let response = fetch_artist_info(artist_id, &self.config, &self.http_client)
+            .map_err(|e| SongRecError::NetworkError(e.to_string()))?;
+
+        ArtistInfo::from_artist_response(&response)
+            .ok_or_else(|| SongRecError::NetworkError("No artist found in response".to_string()))
     }
 
-    /// Start continuous recognition from the default audio device
+    /// Download and play the preview clip of a recognized song on the default output device
+    /// Blocks until playback is finished
+    #[cfg(feature = "playback")]
+    pub fn play_preview(&self, result: &RecognitionResult) -> Result<()> {
+        use crate::fingerprinting::communication::obtain_raw_preview_audio;
+
+        let url = result.preview_url()
+            .ok_or_else(|| SongRecError::InvalidInput("Recognition result has no preview clip".to_string()))?;
+
+        let audio = obtain_raw_preview_audio(url, &self.config, &self.http_client)
+            .map_err(|e| SongRecError::NetworkError(e.to_string()))?;
+
+        let (_stream, stream_handle) = rodio::OutputStream::try_default()
+            .map_err(|e| SongRecError::AudioError(format!("No output device available: {}", e)))?;
+        let sink = rodio::Sink::try_new(&stream_handle)
+            .map_err(|e| SongRecError::AudioError(format!("Failed to open output: {}", e)))?;
+        let source = rodio::Decoder::new(std::io::Cursor::new(audio))
+            .map_err(|e| SongRecError::AudioError(format!("Failed to decode preview clip: {}", e)))?;
+
+        sink.append(source);
+        sink.sleep_until_end();
+
+        Ok(())
+    }
+
+    /// Start continuous recognition from the default audio device (requires the audio-devices feature)
+    #[cfg(feature = "audio-devices")]
     pub fn start_continuous_recognition(&self) -> Result<RecognitionStream> {
         self.start_continuous_recognition_with_device(None)
     }
 
     /// Start continuous recognition from a specific audio device
+    #[cfg(feature = "audio-devices")]
     pub fn start_continuous_recognition_with_device(&self, device_name: Option<String>) -> Result<RecognitionStream> {
-        let (result_tx, result_rx) = mpsc::channel();
-        let (_control_tx, control_rx) = mpsc::channel();
-        
-        let config = self.config.clone();
-        
-        // Start audio recording thread
-        let recorder_handle = {
-            let result_tx = result_tx.clone();
-            let config_for_thread = config.clone();
-            
-            thread::spawn(move || {
-                let mut recorder = AudioRecorder::new(config_for_thread.clone());
-                
-                match recorder.start_recording(device_name, control_rx) {
-                    Ok(sample_rx) => {
-                        // Process audio samples
-                        let mut processor = AudioProcessor::with_config(config_for_thread.clone());
-                        
-                        for samples in sample_rx {
-                            match processor.process_samples(&samples) {
-                                Ok(Some(signature)) => {
-                                    // Try to recognize the signature with config
-                                    match recognize_song_from_signature_with_config(&signature, &config_for_thread) {
-                                        Ok(response) => {
-                                            // Parse and send result
-                                            match SongRec::parse_recognition_response_static(response) {
-                                                Ok(result) => {
-                                                    if result_tx.send(Ok(result)).is_err() {
-                                                        break; // Receiver dropped, stop processing
-                                                    }
-                                                },
-                                                Err(e) => {
-                                                    if result_tx.send(Err(e)).is_err() {
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                        },
-                                        Err(e) => {
-                                            let error = SongRecError::NetworkError(e.to_string());
-                                            if result_tx.send(Err(error)).is_err() {
-                                                break;
-                                            }
-                                        }
-                                    }
-                                },
-                                Ok(None) => {
-                                    // Not enough samples yet, continue
-                                },
-                                Err(e) => {
-                                    let error = SongRecError::FingerprintingError(e.to_string());
-                                    if result_tx.send(Err(error)).is_err() {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        let error = SongRecError::AudioError(e.to_string());
-                        let _ = result_tx.send(Err(error));
-                    }
-                }
-            })
-        };
+        self.spawn_pipeline(Input::Device(device_name), None, Vec::new())
+    }
+
+    /// Start continuous recognition, also sending every state change to `events`
+    ///
+    /// Matches, no-matches and errors are sent both as events and through the stream.
+    #[cfg(feature = "audio-devices")]
+    pub fn start_continuous_recognition_with_events(&self, device_name: Option<String>, events: mpsc::Sender<PipelineEvent>) -> Result<RecognitionStream> {
+        self.spawn_pipeline(Input::Device(device_name), Some(events), Vec::new())
+    }
+
+    /// Start continuous recognition, handing every state change to `sinks`
+    ///
+    /// Results are still sent through the stream, `RecognitionStream::wait` discards them
+    /// until the recognition stops.
+    #[cfg(feature = "audio-devices")]
+    pub fn start_continuous_recognition_with_sinks(&self, device_name: Option<String>, sinks: Vec<Box<dyn Sink>>) -> Result<RecognitionStream> {
+        self.spawn_pipeline(Input::Device(device_name), None, sinks)
+    }
+
+    /// Start continuous recognition from an HTTP audio stream, such as an Icecast radio
+    ///
+    /// Consecutive chunks of about 14 seconds are fingerprinted, so the stream must be in
+    /// a format that can be decoded from any point, such as MP3. Connection failures are
+    /// reported through the stream and followed by a new attempt a few seconds later.
+    pub fn start_stream_recognition(&self, url: &str) -> Result<RecognitionStream> {
+        self.spawn_pipeline(Input::Stream(url.to_string()), None, Vec::new())
+    }
+
+    /// Same as `start_stream_recognition`, also sending every state change to `events`
+    pub fn start_stream_recognition_with_events(&self, url: &str, events: mpsc::Sender<PipelineEvent>) -> Result<RecognitionStream> {
+        self.spawn_pipeline(Input::Stream(url.to_string()), Some(events), Vec::new())
+    }
+
+    /// Same as `start_stream_recognition`, handing every state change to `sinks`
+    pub fn start_stream_recognition_with_sinks(&self, url: &str, sinks: Vec<Box<dyn Sink>>) -> Result<RecognitionStream> {
+        self.spawn_pipeline(Input::Stream(url.to_string()), None, sinks)
+    }
+
+    fn spawn_pipeline(&self, input: Input, events: Option<mpsc::Sender<PipelineEvent>>, sinks: Vec<Box<dyn Sink>>) -> Result<RecognitionStream> {
+        let pipeline = pipeline::spawn(self.config.clone(), self.http_client.clone(), Arc::clone(&self.lookup_limiter), input, events, sinks);
 
         Ok(RecognitionStream {
-            receiver: result_rx,
-            _handles: vec![recorder_handle],
+            receiver: pipeline.receiver,
+            counters: pipeline.counters,
+            readiness: pipeline.readiness,
+            live_config: pipeline.live_config,
+            handles: pipeline.handles,
         })
     }
+}
 
-    /// Parse a recognition response from the API into a RecognitionResult
-    fn parse_recognition_response(&self, response: serde_json::Value) -> Result<RecognitionResult> {
-        Self::parse_recognition_response_static(response)
+impl RecognitionResult {
+    /// Result holding only a title, an artist and a track key, recognized now
+    ///
+    /// The other fields are public, e.g. to build results for tests or from another service.
+    pub fn new(song_name: &str, artist_name: &str, track_key: &str) -> Self {
+        RecognitionResult {
+            song_name: song_name.to_string(),
+            artist_name: artist_name.to_string(),
+            artists: Vec::new(),
+            album_name: None,
+            track_key: track_key.to_string(),
+            release_year: None,
+            genre: None,
+            genre_normalized: None,
+            track_number: None,
+            disc_number: None,
+            recognition_timestamp: chrono::Utc::now(),
+            raw_response: serde_json::json!({}),
+            timings: RecognitionTimings::default(),
+            source: RecognitionSource::Unknown,
+            audio_snippet: None,
+            analysis: None,
+            audio_stats: None,
+            record_attempts: None,
+        }
     }
 
-    /// Static version of parse_recognition_response for use in threads
-    fn parse_recognition_response_static(response: serde_json::Value) -> Result<RecognitionResult> {
-        // First check if we have any matches
-        let matches = response.get("matches")
-            .and_then(|m| m.as_array())
-            .ok_or_else(|| SongRecError::NetworkError("Invalid response format: no matches array".to_string()))?;
-            
-        if matches.is_empty() {
-            return Err(SongRecError::NetworkError("No track found in response".to_string()));
+    /// Parse a raw API response, as saved with `Config::with_api_dump_dir` or kept in `raw_response`
+    ///
+    /// The track may be at the top level of the response, with or without a `matches`
+    /// array, or inside the first match. Returns `SongRecError::NoMatch` without diagnostics if there is none.
+    /// English song sections are preferred, see `from_localized_response`.
+    pub fn from_raw_response(response: serde_json::Value) -> Result<Self> {
+        Self::from_localized_response(response, "en")
+    }
+
+    /// Parse a raw API response, taking the album and release year from the song section in `language`
+    ///
+    /// `language` is a tag such as "de" or "de-DE", see `Config::with_metadata_language`.
+    /// Responses merged from several locales hold one song section per language, tagged
+    /// with a `language` field; an untagged or English section is used when none matches.
+    /// Field titles are recognized in the languages of the API, e.g. "Veröffentlicht".
+    pub fn from_localized_response(response: serde_json::Value, language: &str) -> Result<Self> {
+        if !response.is_object() {
+            return Err(SongRecError::NetworkError("Invalid response format: not a JSON object".to_string()));
         }
-        
-        // The track info is at the top level of the response, not inside the matches
-        let track = response.get("track")
-            .ok_or_else(|| SongRecError::NetworkError("No track found in response".to_string()))?;
+
+        // An empty matches array, or no track at all, means the audio was not recognized
+        let track = find_track(&response).ok_or(SongRecError::NoMatch(None))?;
 
         // Extract song details from the track
         let song_name = track
@@ -185,10 +787,47 @@ impl SongRec {
             .unwrap_or("Unknown")
             .to_string();
 
-        let album_name = track
-            .pointer("/sections/0/metadata/0/text")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+        let artists = credits::split(&artist_name, &song_name);
+
+        let metadata = song_section(track, language)
+            .and_then(|section| section.get("metadata"))
+            .and_then(|metadata| metadata.as_array());
+        let field = |titles: &[&str]| {
+            metadata?
+                .iter()
+                .find(|item| item.get("title").and_then(|v| v.as_str()).is_some_and(|title| titles.contains(&title)))?
+                .get("text")?
+                .as_str()
+                .map(|s| s.to_string())
+        };
+
+        // The Apple Music metadata of the song, when the response carries it
+        let attributes = response
+            .pointer("/resources/songs")
+            .and_then(|songs| songs.as_object())
+            .and_then(|songs| songs.values().find_map(|song| song.get("attributes")));
+        let number = |name: &str, titles: &[&str]| {
+            attributes
+                .and_then(|attributes| attributes.get(name)?.as_u64())
+                .and_then(|number| u32::try_from(number).ok())
+                .filter(|&number| number > 0)
+                .or_else(|| field(titles).as_deref().and_then(leading_number).filter(|&number| number > 0))
+        };
+
+        // The album comes first when its title is not a known one
+        let other_titles = [RELEASED_TITLES, TRACK_TITLES, DISC_TITLES];
+        let album_name = field(ALBUM_TITLES)
+            .or_else(|| {
+                let first = metadata?.first()?;
+                let title = first.get("title").and_then(|v| v.as_str()).unwrap_or("");
+                if other_titles.iter().any(|titles| titles.contains(&title)) {
+                    return None;
+                }
+                first.get("text")?.as_str().map(|s| s.to_string())
+            })
+            .or_else(|| attributes?.get("albumName")?.as_str().map(|s| s.to_string()));
+        let track_number = number("trackNumber", TRACK_TITLES);
+        let disc_number = number("discNumber", DISC_TITLES);
 
         let track_key = track
             .get("key")
@@ -196,20 +835,7 @@ impl SongRec {
             .unwrap_or("")
             .to_string();
 
-        let release_year = track
-            .pointer("/sections/0/metadata")
-            .and_then(|metadata| {
-                if let Some(metadata_array) = metadata.as_array() {
-                    for item in metadata_array {
-                        if let Some(title) = item.pointer("/title").and_then(|v| v.as_str()) {
-                            if title == "Released" {
-                                return item.pointer("/text").and_then(|v| v.as_str()).map(|s| s.to_string());
-                            }
-                        }
-                    }
-                }
-                None
-            });
+        let release_year = field(RELEASED_TITLES);
 
         let genre = track
             .pointer("/genres/primary")
@@ -219,30 +845,275 @@ impl SongRec {
         Ok(RecognitionResult {
             song_name,
             artist_name,
+            artists,
             album_name,
             track_key,
             release_year,
             genre,
+            genre_normalized: None,
+            track_number,
+            disc_number,
             recognition_timestamp: chrono::Utc::now(),
             raw_response: response,
+            timings: RecognitionTimings::default(),
+            source: RecognitionSource::Unknown,
+            audio_snippet: None,
+            analysis: None,
+            audio_stats: None,
+            record_attempts: None,
         })
     }
+
+    /// Parse the stored raw response again, e.g. to pick up fields extracted by a newer version
+    ///
+    /// The recognition timestamp, timings and source of this result are kept.
+    pub fn reparse(&self) -> Result<Self> {
+        let mut result = Self::from_raw_response(self.raw_response.clone())?;
+        result.recognition_timestamp = self.recognition_timestamp;
+        result.timings = self.timings;
+        result.source = self.source.clone();
+        result.audio_snippet = self.audio_snippet.clone();
+        result.analysis = self.analysis;
+        result.audio_stats = self.audio_stats;
+        result.genre_normalized = self.genre_normalized.clone();
+        result.record_attempts = self.record_attempts;
+        Ok(result)
+    }
+
+    /// Set `genre_normalized` from the genre if the configuration asks for it
+    pub(crate) fn normalize_genre(&mut self, config: &Config) {
+        if config.normalize_genres {
+            self.genre_normalized = self.genre.as_deref().and_then(|genre| genre::normalize(genre, &config.genre_mapping));
+        }
+    }
+
+    /// Replace the source, e.g. to attribute samples decoded from a network stream
+    pub fn with_source(mut self, source: RecognitionSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Artist the song is credited to without its featured artists, e.g. for scrobbling
+    pub fn primary_artist(&self) -> &str {
+        self.artists
+            .iter()
+            .find(|credit| credit.role == ArtistRole::Main)
+            .map_or(&self.artist_name, |credit| &credit.name)
+    }
+
+    /// Apple Music artist id of the main artist, if present in the response
+    pub fn artist_id(&self) -> Option<&str> {
+        let track = find_track(&self.raw_response)?;
+        track
+            .pointer("/artists/0/adamid")
+            .or_else(|| track.pointer("/artists/0/id"))
+            .and_then(|v| v.as_str())
+    }
+
+    /// ISRC of the recognized recording, if present in the response
+    pub fn isrc(&self) -> Option<&str> {
+        find_track(&self.raw_response)?
+            .get("isrc")
+            .and_then(|v| v.as_str())
+    }
+
+    /// Shazam web page of the recognized track, if present in the response
+    pub fn track_url(&self) -> Option<&str> {
+        let track = find_track(&self.raw_response)?;
+        track
+            .get("url")
+            .or_else(|| track.pointer("/share/href"))
+            .and_then(|v| v.as_str())
+            .filter(|url| url.starts_with("http"))
+    }
+
+    /// Position in the song of the recognized audio, from the offset of the match
+    pub fn song_position(&self) -> Option<Duration> {
+        let offset = self.raw_response.pointer("/matches/0/offset")?.as_f64()?;
+        (offset.is_finite() && offset >= 0.0).then(|| Duration::from_secs_f64(offset))
+    }
+
+    /// Length of the song, if the response carries its Apple Music metadata
+    pub fn song_duration(&self) -> Option<Duration> {
+        self.raw_response
+            .pointer("/resources/songs")?
+            .as_object()?
+            .values()
+            .find_map(|song| song.pointer("/attributes/durationInMillis")?.as_u64())
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// Time left in the song now, assuming it kept playing since it was recognized
+    ///
+    /// An estimate: the position is the one of the recognized audio, which was heard a
+    /// few seconds before the recognition finished.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let elapsed = (chrono::Utc::now() - self.recognition_timestamp).to_std().unwrap_or_default();
+        Some(self.song_duration()?.saturating_sub(self.song_position()? + elapsed))
+    }
+
+    /// URL of the 30-second preview clip, if one of the hub actions provides it
+    pub fn preview_url(&self) -> Option<&str> {
+        find_track(&self.raw_response)?
+            .pointer("/hub/actions")
+            .and_then(|v| v.as_array())?
+            .iter()
+            .filter(|action| action.get("type").and_then(|v| v.as_str()) == Some("uri"))
+            .filter_map(|action| action.get("uri").and_then(|v| v.as_str()))
+            .find(|uri| uri.starts_with("http"))
+    }
+}
+
+/// Titles of the album field of song sections, in the languages the API answers in
+const ALBUM_TITLES: &[&str] = &["Album", "Álbum", "Albüm", "Альбом", "アルバム", "专辑", "專輯", "앨범"];
+
+/// Titles of the release year field of song sections, in the languages the API answers in
+const RELEASED_TITLES: &[&str] = &[
+    "Released", "Veröffentlicht", "Erschienen", "Sortie", "Date de sortie", "Lanzamiento", "Publicado",
+    "Lançamento", "Pubblicato", "Uitgebracht", "Utgiven", "Udgivet", "Utgitt", "Julkaistu", "Wydano",
+    "Vydáno", "Yayınlandı", "Выпущено", "Дата выпуска", "リリース", "发行", "發行", "발매",
+];
+
+/// Titles of the track number field of song sections
+const TRACK_TITLES: &[&str] = &["Track", "Track Number", "Titelnummer", "Piste", "Pista", "Faixa", "Traccia", "Трек", "トラック", "曲目"];
+
+/// Titles of the disc number field of song sections
+const DISC_TITLES: &[&str] = &["Disc", "Disc Number", "CD", "Disque", "Disco", "Диск", "ディスク", "碟片"];
+
+/// Number at the start of a field, e.g. 3 for "3/12" or "3 of 12"
+fn leading_number(text: &str) -> Option<u32> {
+    let digits: String = text.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Song section of a track in `language`, an untagged or English one, or the first section
+fn song_section<'a>(track: &'a serde_json::Value, language: &str) -> Option<&'a serde_json::Value> {
+    let sections = track.get("sections")?.as_array()?;
+    let song_sections: Vec<&serde_json::Value> = sections
+        .iter()
+        .filter(|section| section.get("type").and_then(|v| v.as_str()).is_none_or(|kind| kind == "SONG"))
+        .collect();
+    let section_language = |section: &serde_json::Value| {
+        section.get("language").and_then(|v| v.as_str()).map(|tag| MetadataLocale::parse(tag).language)
+    };
+
+    let wanted = MetadataLocale::parse(language).language;
+    song_sections
+        .iter()
+        .find(|section| section_language(section).as_deref() == Some(wanted.as_str()))
+        .or_else(|| song_sections.iter().find(|section| section_language(section).is_none_or(|language| language == "en")))
+        .copied()
+        .or_else(|| sections.first())
+}
+
+/// Track of a response, at the top level or inside the first match, `None` for a null track
+fn find_track(response: &serde_json::Value) -> Option<&serde_json::Value> {
+    let matches = response.get("matches").and_then(|m| m.as_array());
+    if matches.is_some_and(|matches| matches.is_empty()) {
+        return None;
+    }
+
+    // A top-level track, even null, takes precedence over the matches: null means no match
+    match response.get("track") {
+        Some(track) => Some(track),
+        None => matches?.first()?.get("track"),
+    }
+    .filter(|track| track.is_object())
+}
+
+impl TryFrom<serde_json::Value> for RecognitionResult {
+    type Error = SongRecError;
+
+    fn try_from(response: serde_json::Value) -> Result<Self> {
+        Self::from_raw_response(response)
+    }
 }
 
 impl RecognitionStream {
     /// Get the next recognition result from the stream
     pub fn next(&self) -> Option<Result<RecognitionResult>> {
-        self.receiver.recv().ok()
+        self.received(self.receiver.recv().ok())
     }
 
     /// Try to get the next recognition result without blocking
     pub fn try_next(&self) -> Option<Result<RecognitionResult>> {
-        self.receiver.try_recv().ok()
+        self.received(self.receiver.try_recv().ok())
     }
 
     /// Wait for the next recognition result with a timeout
     pub fn next_timeout(&self, timeout: Duration) -> Option<Result<RecognitionResult>> {
-        self.receiver.recv_timeout(timeout).ok()
+        self.received(self.receiver.recv_timeout(timeout).ok())
+    }
+
+    /// Iterate over results, ending as soon as no result arrives within `timeout`
+    pub fn iter_timeout(&self, timeout: Duration) -> impl Iterator<Item = Result<RecognitionResult>> + '_ {
+        std::iter::from_fn(move || self.next_timeout(timeout))
+    }
+
+    /// Discard results until the pipeline stops, e.g. when sinks handle them
+    pub fn wait(&self) {
+        while self.next().is_some() {}
+    }
+
+    /// Whether the pipeline is still running and may produce more results
+    ///
+    /// Results sent before the pipeline stopped can still be pending, see `pending_len()`.
+    pub fn is_alive(&self) -> bool {
+        self.handles.iter().any(|handle| !handle.is_finished())
+    }
+
+    /// Number of results waiting to be received
+    pub fn pending_len(&self) -> usize {
+        self.counters.pending()
+    }
+
+    /// File descriptor that becomes readable when a result is pending
+    ///
+    /// Register it in a select/poll/epoll loop and call `try_next()` when it is readable.
+    /// Wake-ups may occasionally be spurious, in which case `try_next()` returns None.
+    /// Returns None if the descriptor could not be created.
+    #[cfg(unix)]
+    pub fn readiness_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        self.readiness.raw_fd()
+    }
+
+    /// Take the underlying channel, e.g. to forward results into another event loop
+    ///
+    /// The pipeline keeps running until the returned receiver is dropped.
+    /// `pending_len()` and the readiness descriptor are no longer available afterwards.
+    pub fn into_receiver(self) -> mpsc::Receiver<Result<RecognitionResult>> {
+        self.receiver
+    }
+
+    /// Apply a new configuration without restarting audio capture
+    ///
+    /// Integrations (Spotify, Discord, OSC), `quiet_mode`, `api_dump_dir`,
+    /// `metadata_language` and the retry settings take effect from the next lookup.
+    /// `recognition_interval`, `window_duration`, `music_only` and the snippet and
+    /// analysis settings take effect from the next audio captured. Audio device, buffer,
+    /// worker and queue settings, and the channel mode of devices, keep the values the
+    /// stream was started with.
+    pub fn reload(&self, config: Config) {
+        self.live_config.set(config);
+    }
+
+    /// Snapshot of the data dropped so far because a pipeline stage fell behind
+    pub fn stats(&self) -> PipelineStats {
+        self.counters.snapshot()
+    }
+
+    /// Liveness of the pipeline, for supervisors restarting a stream that stopped receiving audio
+    pub fn health(&self) -> PipelineHealth {
+        self.counters.health(self.is_alive())
+    }
+
+    fn received(&self, item: Option<Result<RecognitionResult>>) -> Option<Result<RecognitionResult>> {
+        if item.is_some() {
+            self.counters.result_received();
+            self.readiness.consume();
+        }
+        item
     }
 }
 
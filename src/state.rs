@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::{Result, SongRecError};
+
+/// Environment variable overriding the platform state directory
+pub const STATE_DIR_ENV: &str = "SONGREC_STATE_DIR";
+
+/// Directory where state is kept between runs: installation identity, processed-files
+/// ledger, history database...
+///
+/// Defaults to `$XDG_STATE_HOME/songrec` (or `~/.local/state/songrec`) on Linux,
+/// `~/Library/Application Support/songrec` on macOS and `%APPDATA%\songrec` on Windows.
+/// `SONGREC_STATE_DIR` overrides the platform directory, `Config::with_state_dir` both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDir {
+    path: PathBuf,
+}
+
+impl StateDir {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The platform directory, None if the environment gives none (no HOME or APPDATA)
+    pub fn platform_default() -> Option<Self> {
+        if let Some(dir) = std::env::var_os(STATE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            return Some(Self::new(dir));
+        }
+
+        let path = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("songrec"))
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support/songrec"))
+        } else {
+            std::env::var_os("XDG_STATE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+                .map(|dir| dir.join("songrec"))
+        };
+        path.map(Self::new)
+    }
+
+    /// The directory set with `Config::with_state_dir`, or the platform one
+    pub fn for_config(config: &Config) -> Option<Self> {
+        config.state_dir.clone().map(Self::new).or_else(Self::platform_default)
+    }
+
+    /// The platform directory, or an error naming `what` needed it
+    pub(crate) fn require_default(what: &str) -> Result<Self> {
+        Self::platform_default().ok_or_else(|| SongRecError::ConfigError(format!("No state directory available for the {}", what)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of a file of the state directory, which may not exist yet
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Create the directory if needed
+    pub fn create(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.path)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not create '{}': {}", self.path.display(), e)))
+    }
+}
@@ -0,0 +1,314 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::library::sanitize;
+use crate::output::RecognitionOutput;
+use crate::songrec::RecognitionResult;
+use crate::{Result, SongRecError};
+
+/// Time between the fingerprinted windows of a long recording by default
+pub const DEFAULT_TRACKLIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Unrecognized audio shorter than this between two matches of the same track does not split it
+const MAX_GAP_MS: u64 = 60_000;
+
+/// Names of the files `Tracklist::split` cuts by default, `{number}` is the position in the tracklist
+pub const DEFAULT_SPLIT_TEMPLATE: &str = "{number} - {artist} - {song}";
+
+/// Chapter file formats a `Tracklist` can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterFormat {
+    /// Cue sheet, next to the recording
+    Cue,
+    /// Matroska chapter XML, as read by mkvmerge and mkvpropedit
+    MatroskaXml,
+    /// FFmpeg metadata file, which ffmpeg turns into MP4 or Matroska chapters
+    FfMetadata,
+}
+
+/// Track of a `Tracklist`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracklistEntry {
+    /// Position of the first window the track was recognized in, from the start of the recording
+    pub start_ms: u64,
+
+    /// End of the last window the track was recognized in, or the start of the next track
+    pub end_ms: u64,
+
+    /// First recognition of the track
+    pub result: RecognitionResult,
+}
+
+impl TracklistEntry {
+    /// Relative position, e.g. "00:37:12"
+    pub fn position(&self) -> String {
+        format_position(self.start_ms)
+    }
+
+    /// Wall-clock time the track started playing, for a recording started at `started_at`
+    pub fn played_at(&self, started_at: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> {
+        started_at + chrono::Duration::milliseconds(self.start_ms as i64)
+    }
+}
+
+/// Tracks recognized along a long recording, such as a DJ mix or an aircheck
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tracklist {
+    /// Recording the tracks were recognized in
+    pub file: String,
+
+    /// Length of the recording, in milliseconds
+    pub duration_ms: u64,
+
+    /// Wall-clock time the recording started, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    pub entries: Vec<TracklistEntry>,
+}
+
+impl Tracklist {
+    /// Empty tracklist of a recording
+    pub fn new(file: &str, duration_ms: u64) -> Self {
+        Self {
+            file: file.to_string(),
+            duration_ms,
+            started_at: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the wall-clock time the recording started, so entries get an absolute time
+    pub fn with_started_at(mut self, started_at: chrono::DateTime<chrono::Utc>) -> Self {
+        self.started_at = Some(started_at);
+        self
+    }
+
+    /// Add the recognition of the window from `start_ms` to `end_ms`, windows must come in order
+    ///
+    /// A window matching the last track extends it, a different track ends it. Windows
+    /// without a match leave a gap.
+    pub fn add_window(&mut self, start_ms: u64, end_ms: u64, result: Option<RecognitionResult>) {
+        let Some(result) = result else {
+            return;
+        };
+
+        if let Some(last) = self.entries.last_mut() {
+            if last.result.track_key == result.track_key && start_ms <= last.end_ms + MAX_GAP_MS {
+                last.end_ms = last.end_ms.max(end_ms);
+                return;
+            }
+            last.end_ms = last.end_ms.min(start_ms);
+        }
+        self.entries.push(TracklistEntry { start_ms, end_ms, result });
+    }
+
+    /// Move the start of the last track, and the end of the one before, to `start_ms`
+    pub(crate) fn move_last_boundary(&mut self, start_ms: u64) {
+        if let [.., previous, last] = &mut self.entries[..] {
+            let start_ms = start_ms.clamp(previous.start_ms, last.end_ms);
+            last.start_ms = start_ms;
+            previous.end_ms = start_ms;
+        }
+    }
+
+    /// One line per track, e.g. "00:37:12  21:37:12  Artist – Title"
+    ///
+    /// The wall-clock time, in local time, is only shown when the start of the recording is known.
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let played_at = self
+                    .started_at
+                    .map(|started_at| format!("{}  ", entry.played_at(started_at).with_timezone(&chrono::Local).format("%H:%M:%S")))
+                    .unwrap_or_default();
+                format!("{}  {}{} – {}\n", entry.position(), played_at, entry.result.artist_name, entry.result.song_name)
+            })
+            .collect()
+    }
+
+    /// Cue sheet of the recording, for DJ software and players with per-track navigation
+    pub fn to_cue(&self) -> String {
+        let file_name = Path::new(&self.file).file_name().map_or_else(|| self.file.clone(), |name| name.to_string_lossy().to_string());
+        let file_type = if file_name.to_lowercase().ends_with(".mp3") { "MP3" } else { "WAVE" };
+
+        let mut cue = format!("FILE \"{}\" {}\n", cue_text(&file_name), file_type);
+        for (number, entry) in self.entries.iter().enumerate() {
+            // Positions are in minutes, seconds and frames of 1/75 second
+            let frames = entry.start_ms * 75 / 1000;
+            cue.push_str(&format!("  TRACK {:02} AUDIO\n", number + 1));
+            cue.push_str(&format!("    TITLE \"{}\"\n", cue_text(&entry.result.song_name)));
+            cue.push_str(&format!("    PERFORMER \"{}\"\n", cue_text(&entry.result.artist_name)));
+            cue.push_str(&format!("    INDEX 01 {:02}:{:02}:{:02}\n", frames / 75 / 60, frames / 75 % 60, frames % 75));
+        }
+        cue
+    }
+
+    /// The tracklist in a chapter format
+    pub fn export(&self, format: ChapterFormat) -> String {
+        match format {
+            ChapterFormat::Cue => self.to_cue(),
+            ChapterFormat::MatroskaXml => self.to_matroska_chapters(),
+            ChapterFormat::FfMetadata => self.to_ffmetadata(),
+        }
+    }
+
+    /// Write the tracklist to a chapter file
+    pub fn write_chapters<P: AsRef<Path>>(&self, path: P, format: ChapterFormat) -> Result<()> {
+        std::fs::write(path.as_ref(), self.export(format))
+            .map_err(|e| SongRecError::InvalidInput(format!("Cannot write '{}': {}", path.as_ref().display(), e)))
+    }
+
+    /// Copy the recording to `output` with one chapter per track, without re-encoding
+    ///
+    /// The container follows the extension of `output`, e.g. .m4a, .mp4 or .mka. Requires
+    /// ffmpeg in the PATH.
+    pub fn embed_chapters<P: AsRef<Path>>(&self, output: P) -> Result<()> {
+        let mut ffmpeg = Command::new("ffmpeg");
+        ffmpeg
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(&self.file)
+            .args(["-f", "ffmetadata", "-i", "pipe:0", "-map", "0", "-map_chapters", "1", "-codec", "copy"])
+            .arg(output.as_ref());
+        run_ffmpeg(ffmpeg, Some(&self.to_ffmetadata()))
+    }
+
+    /// Cut the recording into one file per track in `directory`, without re-encoding
+    ///
+    /// Files are named with `template`, which takes the placeholders of `OutputFormat::Custom`
+    /// and `{number}`, and keep the extension of the recording. Each piece runs until the
+    /// next track, the first one starts with the recording so nothing is lost. Existing
+    /// files are overwritten. Requires ffmpeg in the PATH, returns the files written.
+    pub fn split<P: AsRef<Path>>(&self, directory: P, template: &str) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(directory.as_ref())
+            .map_err(|e| SongRecError::InvalidInput(format!("Cannot create '{}': {}", directory.as_ref().display(), e)))?;
+
+        let mut pieces = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let piece = directory.as_ref().join(self.piece_name(index, template));
+            let start_ms = if index == 0 { 0 } else { entry.start_ms };
+
+            let mut ffmpeg = Command::new("ffmpeg");
+            ffmpeg
+                .args(["-y", "-loglevel", "error", "-i"])
+                .arg(&self.file)
+                .args(["-ss", &seconds(start_ms), "-to", &seconds(self.chapter_end(index)), "-map", "0", "-codec", "copy"])
+                .arg(&piece);
+            run_ffmpeg(ffmpeg, None)?;
+            pieces.push(piece);
+        }
+        Ok(pieces)
+    }
+
+    /// File name `split` gives to the track at `index`
+    pub fn piece_name(&self, index: usize, template: &str) -> String {
+        let template = template.replace("{number}", &format!("{:02}", index + 1));
+        let mut file_name = sanitize(&RecognitionOutput::format_custom(&self.entries[index].result, &template));
+        if let Some(extension) = Path::new(&self.file).extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+        file_name
+    }
+
+    /// Matroska chapter XML, one chapter per track
+    pub fn to_matroska_chapters(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n<Chapters>\n  <EditionEntry>\n");
+        for (index, entry) in self.entries.iter().enumerate() {
+            xml.push_str("    <ChapterAtom>\n");
+            xml.push_str(&format!("      <ChapterTimeStart>{}</ChapterTimeStart>\n", matroska_time(entry.start_ms)));
+            xml.push_str(&format!("      <ChapterTimeEnd>{}</ChapterTimeEnd>\n", matroska_time(self.chapter_end(index))));
+            xml.push_str("      <ChapterDisplay>\n");
+            xml.push_str(&format!("        <ChapterString>{}</ChapterString>\n", xml_text(&chapter_title(entry))));
+            xml.push_str("      </ChapterDisplay>\n    </ChapterAtom>\n");
+        }
+        xml.push_str("  </EditionEntry>\n</Chapters>\n");
+        xml
+    }
+
+    /// FFmpeg metadata file, one chapter per track
+    pub fn to_ffmetadata(&self) -> String {
+        let mut metadata = String::from(";FFMETADATA1\n");
+        for (index, entry) in self.entries.iter().enumerate() {
+            metadata.push_str(&format!(
+                "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                entry.start_ms,
+                self.chapter_end(index),
+                ffmetadata_text(&chapter_title(entry))
+            ));
+        }
+        metadata
+    }
+
+    /// Chapters last until the next track, so the whole recording can be navigated
+    fn chapter_end(&self, index: usize) -> u64 {
+        self.entries
+            .get(index + 1)
+            .map_or(self.duration_ms.max(self.entries[index].end_ms), |next| next.start_ms)
+    }
+}
+
+/// Position from the start of a recording, e.g. "00:37:12"
+pub fn format_position(ms: u64) -> String {
+    let seconds = ms / 1000;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Cue sheet strings are quoted and cannot hold double quotes or line breaks
+fn cue_text(text: &str) -> String {
+    text.replace('"', "'").replace(['\r', '\n'], " ")
+}
+
+/// Run ffmpeg to completion, writing `input` to its standard input
+fn run_ffmpeg(mut command: Command, input: Option<&str>) -> Result<()> {
+    let mut ffmpeg = command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SongRecError::ConfigError(format!("Cannot run ffmpeg, which is needed to cut and tag recordings: {}", e)))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, ffmpeg.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| SongRecError::AudioError(format!("Cannot write to ffmpeg: {}", e)))?;
+    }
+    let finished = ffmpeg.wait_with_output().map_err(|e| SongRecError::AudioError(e.to_string()))?;
+    if !finished.status.success() {
+        return Err(SongRecError::AudioError(format!("ffmpeg failed: {}", String::from_utf8_lossy(&finished.stderr).trim())));
+    }
+    Ok(())
+}
+
+/// Time option of ffmpeg, e.g. "2232.500"
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// "Artist – Title"
+fn chapter_title(entry: &TracklistEntry) -> String {
+    format!("{} – {}", entry.result.artist_name, entry.result.song_name)
+}
+
+/// Matroska timestamps have nanoseconds, e.g. "00:37:12.500000000"
+fn matroska_time(ms: u64) -> String {
+    format!("{}.{:03}000000", format_position(ms), ms % 1000)
+}
+
+fn xml_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Special characters of FFmpeg metadata values are escaped with a backslash
+fn ffmetadata_text(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '=' | ';' | '#' | '\\' | '\n' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::batch::{BatchSummary, FileRoute};
+use crate::ledger::ProcessedLedger;
+use crate::library::{is_audio_file, list_files, LibraryOrganizer, OrganizeMode};
+use crate::logging::{log, LogLevel};
+use crate::songrec::{RecognitionResult, SongRec};
+use crate::tracklist::{Tracklist, DEFAULT_TRACKLIST_INTERVAL};
+use crate::{Result, SongRecError};
+
+/// How often files still being written are checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Outcome of a file picked up by a `DirectoryWatcher`
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The file was recognized, `destination` is its new path if it was organized
+    Recognized {
+        path: PathBuf,
+        result: Box<RecognitionResult>,
+        destination: Option<PathBuf>,
+    },
+    /// The file was longer than `Config::max_file_duration` and recognized as a tracklist
+    Tracklist { path: PathBuf, tracklist: Box<Tracklist> },
+    /// The file was left out by the duration limits of the configuration, `duration` being its length
+    OutOfLimits { path: PathBuf, duration: Duration },
+    /// The file could not be recognized or organized
+    Failed { path: PathBuf, error: SongRecError },
+}
+
+/// Recognizes audio files as they appear in a directory (requires the watch feature)
+///
+/// Files already in the directory when watching starts are processed too, unless the
+/// ledger records them as done. A file is only processed once its size stopped changing
+/// for the settle time, so files still being copied are not read half-written.
+pub struct DirectoryWatcher {
+    directory: PathBuf,
+    songrec: SongRec,
+    ledger: Option<ProcessedLedger>,
+    organizer: Option<LibraryOrganizer>,
+    settle_time: Duration,
+    force: bool,
+    library_root: Option<PathBuf>,
+    organized: HashSet<PathBuf>,
+    summary: Option<Arc<Mutex<BatchSummary>>>,
+}
+
+impl DirectoryWatcher {
+    /// Watch `directory` and its subdirectories
+    pub fn new<P: Into<PathBuf>>(directory: P, songrec: SongRec) -> Self {
+        Self {
+            directory: directory.into(),
+            songrec,
+            ledger: None,
+            organizer: None,
+            settle_time: Duration::from_secs(2),
+            force: false,
+            library_root: None,
+            organized: HashSet::new(),
+            summary: None,
+        }
+    }
+
+    /// Skip files recorded in the ledger and record the ones processed
+    pub fn with_ledger(mut self, ledger: ProcessedLedger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Rename, move or copy recognized files
+    pub fn with_organizer(mut self, organizer: LibraryOrganizer) -> Self {
+        self.organizer = Some(organizer);
+        self
+    }
+
+    /// Process files even if the ledger knows them, they are still recorded
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Time a file size must stay unchanged before the file is processed
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// Count the files processed and the API lookups in `summary`, e.g. to report them when watching stops
+    pub fn with_summary(mut self, summary: Arc<Mutex<BatchSummary>>) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    /// Watch until an error occurs, calling `on_event` for each processed file
+    pub fn run<F>(mut self, mut on_event: F) -> Result<()>
+    where
+        F: FnMut(WatchEvent),
+    {
+        // Paths from the scan and from notify must match the ones stored in the ledger
+        self.directory = self.directory.canonicalize()
+            .map_err(|e| SongRecError::InvalidInput(format!("Cannot watch '{}': {}", self.directory.display(), e)))?;
+
+        // Files moved or copied into a library inside the watched directory are not picked up again
+        if let Some(organizer) = self.organizer.as_ref().filter(|organizer| organizer.mode() != OrganizeMode::Rename) {
+            let root = organizer.root();
+            let library_root = if organizer.is_dry_run() {
+                // A dry run does not create the library, which then holds no file yet
+                root.canonicalize().unwrap_or_else(|_| root.to_path_buf())
+            } else {
+                std::fs::create_dir_all(root)
+                    .and_then(|_| root.canonicalize())
+                    .map_err(|e| SongRecError::InvalidInput(format!("Cannot use library '{}': {}", root.display(), e)))?
+            };
+            self.library_root = Some(library_root);
+        }
+
+        let started = Instant::now();
+        let lookups_before = self.songrec.lookup_count();
+        let (event_tx, event_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(event_tx)
+            .map_err(|e| SongRecError::ConfigError(format!("Could not create file watcher: {}", e)))?;
+        watcher
+            .watch(&self.directory, RecursiveMode::Recursive)
+            .map_err(|e| SongRecError::InvalidInput(format!("Could not watch '{}': {}", self.directory.display(), e)))?;
+
+        // Watching starts before the scan, so files created in between are not missed
+        let mut pending: BTreeMap<PathBuf, (Option<u64>, Instant)> = BTreeMap::new();
+        for path in list_files(&self.directory) {
+            self.track(&mut pending, path);
+        }
+
+        loop {
+            match event_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) => {
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        for path in event.paths {
+                            self.track(&mut pending, path);
+                        }
+                    }
+                }
+                Ok(Err(e)) => {
+                    log(self.songrec.config(), LogLevel::Warn, format_args!("File watcher error: {}", e));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(SongRecError::ConfigError("File watcher stopped".to_string()));
+                }
+            }
+
+            for path in self.settled(&mut pending) {
+                if let Some(event) = self.process(&path) {
+                    if let Some(summary) = &self.summary {
+                        let mut summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+                        match &event {
+                            WatchEvent::Recognized { path, .. } => summary.add(path, None),
+                            WatchEvent::Tracklist { path, tracklist } if tracklist.entries.is_empty() => summary.add(path, Some(&SongRecError::NoMatch(None))),
+                            WatchEvent::Tracklist { path, .. } => summary.add(path, None),
+                            WatchEvent::OutOfLimits { .. } => summary.add_skipped(),
+                            WatchEvent::Failed { path, error } => summary.add(path, Some(error)),
+                        }
+                        summary.api_calls = self.songrec.lookup_count() - lookups_before;
+                        summary.elapsed_ms = started.elapsed().as_millis() as u64;
+                    }
+                    on_event(event);
+                }
+            }
+        }
+    }
+
+    /// Start following the size of a new or modified audio file
+    fn track(&self, pending: &mut BTreeMap<PathBuf, (Option<u64>, Instant)>, path: PathBuf) {
+        let in_library = self.library_root.as_ref().is_some_and(|root| path.starts_with(root));
+        if is_audio_file(&path) && !in_library && !self.organized.contains(&path) {
+            pending.insert(path, (None, Instant::now()));
+        }
+    }
+
+    /// Remove and return the pending files whose size stopped changing
+    fn settled(&self, pending: &mut BTreeMap<PathBuf, (Option<u64>, Instant)>) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+
+        pending.retain(|path, (last_size, last_change)| {
+            let size = match std::fs::metadata(path) {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                _ => return false,
+            };
+
+            if *last_size != Some(size) {
+                *last_size = Some(size);
+                *last_change = Instant::now();
+                true
+            } else if last_change.elapsed() >= self.settle_time {
+                ready.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        ready
+    }
+
+    fn process(&mut self, path: &Path) -> Option<WatchEvent> {
+        if !self.force && self.ledger.as_ref().is_some_and(|ledger| ledger.contains(path)) {
+            return None;
+        }
+
+        // Skipped files are not recorded, so they are picked up again if the limits change
+        let outcome = match FileRoute::of(self.songrec.config(), path) {
+            Ok(FileRoute::Recognize) => self.songrec.recognize_from_file(&path.to_string_lossy()),
+            Ok(FileRoute::Tracklist) => {
+                return Some(match self.songrec.recognize_tracklist(&path.to_string_lossy(), DEFAULT_TRACKLIST_INTERVAL) {
+                    Ok(tracklist) => {
+                        self.record(path, None);
+                        WatchEvent::Tracklist { path: path.to_path_buf(), tracklist: Box::new(tracklist) }
+                    }
+                    Err(error) => self.failed(path, error),
+                });
+            }
+            Ok(FileRoute::Skip(duration)) => return Some(WatchEvent::OutOfLimits { path: path.to_path_buf(), duration }),
+            Err(error) => Err(error),
+        };
+        let result = match outcome {
+            Ok(result) => result,
+            Err(error) => return Some(self.failed(path, error)),
+        };
+
+        // Recorded before the file is moved away from its original path
+        self.record(path, Some(&result));
+
+        let destination = match self.organizer.as_ref().map(|organizer| organizer.organize(path, &result)) {
+            Some(Ok(destination)) => {
+                // Renamed files show up as new files, they must not be recognized again
+                self.organized.insert(destination.clone());
+                Some(destination)
+            }
+            Some(Err(error)) => return Some(WatchEvent::Failed { path: path.to_path_buf(), error }),
+            None => None,
+        };
+
+        Some(WatchEvent::Recognized { path: path.to_path_buf(), result: Box::new(result), destination })
+    }
+
+    fn failed(&mut self, path: &Path, error: SongRecError) -> WatchEvent {
+        // Undecodable and unknown files will not get better, network errors are retried on the next run
+        if !matches!(error, SongRecError::NetworkError(_)) {
+            self.record(path, None);
+        }
+        WatchEvent::Failed { path: path.to_path_buf(), error }
+    }
+
+    fn record(&mut self, path: &Path, result: Option<&RecognitionResult>) {
+        if let Some(ledger) = self.ledger.as_mut() {
+            if let Err(e) = ledger.record(path, result) {
+                log(self.songrec.config(), LogLevel::Error, format_args!("{}", e));
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! The CLI writes nothing but results to stdout in machine-readable formats
+//!
+//! Whatever happens (verbose logging, failed lookups, missing files), stdout must stay
+//! parseable as a whole so the output can be piped into other tools.
+
+use std::process::Command;
+
+const AUDIO_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_audio.wav");
+const MISSING_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/missing.wav");
+
+fn run_cli(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_songrec-lib-cli"))
+        .args(args)
+        .output()
+        .expect("Failed to run the CLI");
+    String::from_utf8(output.stdout).expect("stdout is not UTF-8")
+}
+
+/// Split a CSV record whose fields are all quoted
+fn csv_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        if chars.next()? != '"' {
+            return None;
+        }
+        let mut field = String::new();
+        loop {
+            match chars.next()? {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => break,
+                c => field.push(c),
+            }
+        }
+        fields.push(field);
+
+        match chars.next() {
+            Some(',') => continue,
+            None => return Some(fields),
+            Some(_) => return None,
+        }
+    }
+}
+
+#[test]
+fn test_json_stdout_is_pure() {
+    for verbose in [false, true] {
+        let mut args = vec!["recognize", "-f", "jsonl", AUDIO_FILE, MISSING_FILE];
+        if verbose {
+            args.push("-v");
+        }
+
+        for line in run_cli(&args).lines() {
+            let value: serde_json::Value = serde_json::from_str(line)
+                .unwrap_or_else(|e| panic!("Not a JSON line (verbose: {}): {:?}: {}", verbose, line, e));
+            assert!(value.get("song_name").is_some(), "Not a result: {}", line);
+        }
+    }
+}
+
+#[test]
+fn test_csv_stdout_is_pure() {
+    let stdout = run_cli(&["recognize", "-f", "csv", "-v", AUDIO_FILE, MISSING_FILE]);
+    let mut lines = stdout.lines();
+
+    assert_eq!(lines.next(), Some("\"Song\",\"Artist\",\"Album\",\"Year\",\"Genre\",\"Timestamp\""));
+    for line in lines {
+        let fields = csv_fields(line).unwrap_or_else(|| panic!("Not a CSV record: {:?}", line));
+        assert_eq!(fields.len(), 6, "Unexpected record: {:?}", line);
+    }
+}
+
+/// A directory of its own for a test, removed first
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("songrec_cli_output_{}_{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Every line of stdout as JSON, checking it has `key`
+fn json_lines(stdout: &str, key: &str) -> Vec<serde_json::Value> {
+    stdout
+        .lines()
+        .map(|line| {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap_or_else(|e| panic!("Not a JSON line: {:?}: {}", line, e));
+            assert!(value.get(key).is_some(), "No {} in {}", key, line);
+            value
+        })
+        .collect()
+}
+
+#[test]
+fn test_dedupe_json_stdout_is_pure() {
+    let dir = test_dir("dedupe");
+    std::fs::copy(AUDIO_FILE, dir.join("song.wav")).unwrap();
+    std::fs::copy(AUDIO_FILE, dir.join("song copy.wav")).unwrap();
+    std::fs::write(dir.join("broken.wav"), b"not audio").unwrap();
+
+    let groups = json_lines(&run_cli(&["dedupe", dir.to_str().unwrap(), "-f", "json"]), "keeper");
+    assert_eq!(groups.len(), 1);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_eval_json_stdout_is_pure() {
+    let dir = test_dir("eval");
+    let manifest = dir.join("manifest.csv");
+    std::fs::write(&manifest, format!("{},\n", MISSING_FILE)).unwrap();
+
+    for verbose in [false, true] {
+        let mut args = vec!["eval", manifest.to_str().unwrap(), "-f", "json"];
+        if verbose {
+            args.push("-v");
+        }
+        assert_eq!(json_lines(&run_cli(&args), "label").len(), 1);
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_tracklist_stdout_is_empty_on_error() {
+    for format in ["json", "cue", "ffmetadata"] {
+        assert_eq!(run_cli(&["tracklist", MISSING_FILE, "-f", format]), "", "format {}", format);
+    }
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn test_history_stdout_is_pure() {
+    let dir = test_dir("history");
+    let database = dir.join("history.sqlite3");
+    let history = songrec::history::History::open(&database).unwrap();
+    for (song, artist) in [("Intro", "Wintergatan"), ("Say \"Hi\", then\nleave", "Artist, with comma")] {
+        history.record(&songrec::RecognitionResult::new(song, artist, song)).unwrap();
+    }
+    let database = database.to_str().unwrap();
+
+    let stdout = run_cli(&["history", "export", "--history-db", database, "-f", "csv"]);
+    let mut lines = stdout.lines();
+    let header = csv_fields(lines.next().unwrap()).unwrap();
+    let records: Vec<_> = lines.map(|line| csv_fields(line).unwrap_or_else(|| panic!("Not a CSV record: {:?}", line))).collect();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|record| record.len() == header.len()), "{:?}", records);
+
+    let plays = json_lines(&run_cli(&["history", "export", "--history-db", database, "-f", "json"]), "song_name");
+    assert_eq!(plays.len(), 2);
+    assert_eq!(json_lines(&run_cli(&["history", "stats", "--history-db", database, "-f", "json"]), "day").len(), 1);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,175 @@
+//! Golden-file regression tests for signature generation
+//!
+//! Each fixture is fingerprinted and its peak table compared against the checked-in
+//! golden JSON file next to it. After an intentional change to the algorithm, regenerate
+//! the goldens with:
+//!
+//!     SONGREC_UPDATE_GOLDENS=1 cargo test --test golden_signatures
+
+use songrec::{DecodedSignature, SignatureGenerator};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Share of golden peaks that must be found again in the new signature
+const MIN_MATCHED_PEAKS: f64 = 0.97;
+
+/// A peak may move by this many FFT bins (and its magnitude by this much) and still match,
+/// so that harmless floating point differences between FFT implementations are tolerated
+const BIN_TOLERANCE: i64 = 1;
+const MAGNITUDE_TOLERANCE: i64 = 16;
+
+/// Peak table of a signature as stored in golden files: band -> [pass, magnitude, bin]
+fn peak_table(signature: &DecodedSignature) -> Value {
+    let mut bands: Vec<_> = signature.frequency_band_to_sound_peaks.iter().collect();
+    bands.sort_by_key(|(band, _)| **band);
+
+    let bands: serde_json::Map<String, Value> = bands
+        .into_iter()
+        .map(|(band, peaks)| {
+            let peaks = peaks
+                .iter()
+                .map(|peak| json!([peak.fft_pass_number, peak.peak_magnitude, peak.corrected_peak_frequency_bin]))
+                .collect();
+            ((*band as i32).to_string(), Value::Array(peaks))
+        })
+        .collect();
+
+    json!({
+        "sample_rate_hz": signature.sample_rate_hz,
+        "number_samples": signature.number_samples,
+        "bands": bands,
+    })
+}
+
+/// Pretty-print a peak table with one peak per line, to keep golden diffs readable
+fn format_golden(table: &Value) -> String {
+    let mut out = format!(
+        "{{\n  \"sample_rate_hz\": {},\n  \"number_samples\": {},\n  \"bands\": {{",
+        table["sample_rate_hz"], table["number_samples"]
+    );
+
+    let bands = table["bands"].as_object().unwrap();
+    for (index, (band, peaks)) in bands.iter().enumerate() {
+        let peaks: Vec<String> = peaks.as_array().unwrap().iter().map(|peak| format!("      {}", peak)).collect();
+        out += &format!("\n    \"{}\": [\n{}\n    ]", band, peaks.join(",\n"));
+        if index + 1 < bands.len() {
+            out += ",";
+        }
+    }
+
+    out + "\n  }\n}\n"
+}
+
+fn peaks_of(table: &Value, band: &str) -> Vec<(i64, i64, i64)> {
+    table["bands"][band]
+        .as_array()
+        .map(|peaks| {
+            peaks
+                .iter()
+                .map(|peak| (peak[0].as_i64().unwrap(), peak[1].as_i64().unwrap(), peak[2].as_i64().unwrap()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn check_golden(name: &str, audio_path: &str) {
+    if !Path::new(audio_path).exists() {
+        println!("Skipping golden test {} - {} not found", name, audio_path);
+        return;
+    }
+
+    let signature = SignatureGenerator::make_signature_from_file(audio_path).unwrap();
+
+    if std::env::var_os("SONGREC_UPDATE_GOLDENS").is_some() {
+        let golden_path = format!("tests/golden/{}.json", name);
+        std::fs::write(&golden_path, format_golden(&peak_table(&signature))).unwrap();
+        println!("Updated {}", golden_path);
+        return;
+    }
+
+    compare_to_golden(name, &signature);
+}
+
+/// Check a signature against the golden file `name`, within the tolerances above
+fn compare_to_golden(name: &str, signature: &DecodedSignature) {
+    let actual = peak_table(signature);
+    let golden_path = format!("tests/golden/{}.json", name);
+    let golden: Value = serde_json::from_str(&std::fs::read_to_string(&golden_path).unwrap()).unwrap();
+    assert_eq!(actual["sample_rate_hz"], golden["sample_rate_hz"], "{}: sample rate changed", name);
+    assert_eq!(actual["number_samples"], golden["number_samples"], "{}: sample count changed", name);
+
+    let mut golden_total = 0;
+    let mut matched = 0;
+    let mut actual_total = 0;
+
+    for band in ["0", "1", "2", "3"] {
+        let expected = peaks_of(&golden, band);
+        let found = peaks_of(&actual, band);
+        golden_total += expected.len();
+        actual_total += found.len();
+
+        matched += expected
+            .iter()
+            .filter(|(pass, magnitude, bin)| {
+                found.iter().any(|(found_pass, found_magnitude, found_bin)| {
+                    found_pass == pass
+                        && (found_bin - bin).abs() <= BIN_TOLERANCE
+                        && (found_magnitude - magnitude).abs() <= MAGNITUDE_TOLERANCE
+                })
+            })
+            .count();
+    }
+
+    let matched_ratio = if golden_total == 0 { 1.0 } else { matched as f64 / golden_total as f64 };
+    assert!(
+        matched_ratio >= MIN_MATCHED_PEAKS,
+        "{}: only {}/{} golden peaks found again",
+        name,
+        matched,
+        golden_total
+    );
+
+    let extra_ratio = (actual_total as f64 - golden_total as f64).abs() / (golden_total.max(1) as f64);
+    assert!(
+        extra_ratio <= 1.0 - MIN_MATCHED_PEAKS,
+        "{}: peak count changed from {} to {}",
+        name,
+        golden_total,
+        actual_total
+    );
+}
+
+/// Logarithmic sine sweep at 22.05 kHz, goes through the resampler
+#[test]
+fn golden_sine_sweep() {
+    check_golden("sine_sweep", "tests/golden/sine_sweep.wav");
+}
+
+/// Deterministic white noise at 16 kHz
+#[test]
+fn golden_white_noise() {
+    check_golden("white_noise", "tests/golden/white_noise.wav");
+}
+
+/// Music clip bundled for the recognition tests
+#[test]
+fn golden_music_clip() {
+    check_golden("music_clip", "tests/test_audio.wav");
+}
+
+/// The core with its own FFT finds the peaks of songrec-lib; the `std` feature is on
+/// here, core/tests covers the math of `no_std` builds
+#[test]
+fn golden_white_noise_core_fft() {
+    // 16-bit mono 16 kHz PCM after the 44-byte WAV header
+    let wav = std::fs::read("tests/golden/white_noise.wav").unwrap();
+    let samples: Vec<i16> = wav[44..].chunks_exact(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+
+    let mut generator = songrec_core::SignatureGenerator::new(songrec_core::RadixTwoFft::new());
+    generator.feed(&samples);
+    let signature = DecodedSignature::from(generator.finalize());
+
+    compare_to_golden("white_noise", &signature);
+    let reference = SignatureGenerator::make_signature_from_buffer(&samples);
+    assert!(signature.similarity(&reference) > 0.95);
+}
@@ -7,8 +7,8 @@ fn test_config_creation() {
     let config = Config::default();
     assert_eq!(config.sample_rate, 16000);
     assert_eq!(config.sensitivity, 0.5);
-    assert_eq!(config.quiet_mode, true); // Should default to quiet mode
-    assert_eq!(config.deduplicate_requests, true);
+    assert!(config.quiet_mode); // Should default to quiet mode
+    assert!(config.deduplicate_requests);
     
     // Test custom configuration
     let custom_config = Config::new()
@@ -20,7 +20,7 @@ fn test_config_creation() {
     assert_eq!(custom_config.sensitivity, 0.8);
     assert_eq!(custom_config.sample_rate, 44100);
     assert_eq!(custom_config.network_timeout, 30);
-    assert_eq!(custom_config.quiet_mode, false);
+    assert!(!custom_config.quiet_mode);
 }
 
 /// Test SongRec instance creation
@@ -42,16 +42,21 @@ fn test_config_builders() {
         .with_continuous_recognition(true)
         .with_recognition_interval(3.0)
         .with_deduplication(false)
-        .with_deduplication_cache_duration(600);
+        .with_deduplication_cache_duration(600)
+        .with_boundary_refinement(false);
     
     assert_eq!(config.sensitivity, 0.7);
     assert_eq!(config.min_audio_duration, 2.0);
     assert_eq!(config.max_audio_duration, 15.0);
     assert_eq!(config.buffer_size, 8192);
-    assert_eq!(config.continuous_recognition, true);
+    assert!(config.continuous_recognition);
     assert_eq!(config.recognition_interval, 3.0);
-    assert_eq!(config.deduplicate_requests, false);
+    assert!(!config.deduplicate_requests);
     assert_eq!(config.deduplication_cache_duration, 600);
+    assert!(!config.refine_tracklist_boundaries);
+    assert!(Config::default().refine_tracklist_boundaries);
+    assert_eq!(Config::default().retry_durations, vec![18.0, 24.0]);
+    assert_eq!(Config::default().with_retry_durations(vec![24.0, 10.0, 18.0, 24.0]).retry_durations, vec![18.0, 24.0]);
 }
 
 /// Test sensitivity clamping
@@ -68,6 +73,7 @@ fn test_sensitivity_clamping() {
 }
 
 /// Test audio device listing functionality
+#[cfg(feature = "audio-devices")]
 #[test]
 fn test_audio_device_listing() {
     match songrec::audio::AudioRecorder::list_input_devices() {
@@ -85,26 +91,67 @@ fn test_audio_device_listing() {
     }
 }
 
+/// Test probing the default input device, which may not exist in CI
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_device_probe() {
+    let recorder = songrec::audio::AudioRecorder::new(Config::default().with_quiet_mode(true));
+    match recorder.probe(None) {
+        Ok(report) => {
+            assert!(!report.recommended_config.is_empty());
+            assert_eq!(report.noise_floor_dbfs.is_some(), report.captured_ms >= 100);
+            assert!(!report.produces_audio || report.peak_dbfs > -80.0);
+        }
+        Err(e) => println!("Could not probe the default device (this may be normal in CI): {}", e),
+    }
+}
+
+/// Test finding the monitor source of the default output, which needs PulseAudio or PipeWire
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_system_audio_source() {
+    use songrec::audio::monitor::{default_monitor_source, is_monitor_source};
+
+    assert!(is_monitor_source("alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"));
+    assert!(!is_monitor_source("USB Microphone"));
+    assert!(!is_monitor_source(".monitor"));
+
+    match default_monitor_source() {
+        Ok(source) => assert!(is_monitor_source(&source)),
+        Err(e) => println!("No PulseAudio or PipeWire output (this may be normal in CI): {}", e),
+    }
+}
+
+/// Test recording from JACK, which needs the jack feature and a running server
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_jack_client() {
+    let config = Config::default().with_quiet_mode(true).with_jack_client("songrec_test");
+    assert_eq!(config.jack_client.as_deref(), Some("songrec_test"));
+
+    let recorder = songrec::audio::AudioRecorder::new(config);
+    match recorder.probe(None) {
+        Ok(report) => assert!(report.name.starts_with("songrec_test")),
+        Err(e) if cfg!(feature = "jack") => println!("No JACK server (this may be normal in CI): {}", e),
+        Err(e) => assert!(e.to_string().contains("jack feature"), "{}", e),
+    }
+}
+
 /// Test output format functionality with mock data
 #[test]
 fn test_output_formats() {
     // Create a mock recognition result
-    let mock_result = songrec::RecognitionResult {
-        song_name: "Proof of Concept".to_string(),
-        artist_name: "Wintergatan".to_string(),
-        album_name: Some("Test Album".to_string()),
-        track_key: "test_key_123".to_string(),
-        release_year: Some("2023".to_string()),
-        genre: Some("Electronic".to_string()),
-        recognition_timestamp: chrono::Utc::now(),
-        raw_response: serde_json::json!({
-            "track": {
-                "title": "Proof of Concept",
-                "subtitle": "Wintergatan",
-                "key": "test_key_123"
-            }
-        }),
-    };
+    let mut mock_result = songrec::RecognitionResult::new("Proof of Concept", "Wintergatan", "test_key_123");
+    mock_result.album_name = Some("Test Album".to_string());
+    mock_result.release_year = Some("2023".to_string());
+    mock_result.genre = Some("Electronic".to_string());
+    mock_result.raw_response = serde_json::json!({
+        "track": {
+            "title": "Proof of Concept",
+            "subtitle": "Wintergatan",
+            "key": "test_key_123"
+        }
+    });
     
     // Test Simple format
     let simple_output = RecognitionOutput::format_result(&mock_result, OutputFormat::Simple);
@@ -121,11 +168,25 @@ fn test_output_formats() {
         .expect("JSON output should be valid JSON");
     assert_eq!(parsed["song_name"], "Proof of Concept");
     assert_eq!(parsed["artist_name"], "Wintergatan");
+    assert!(parsed["timings"]["total_ms"].is_u64());
+
+    // Results saved before timings were added still deserialize
+    let mut legacy = parsed.clone();
+    legacy.as_object_mut().unwrap().remove("timings");
+    let legacy: songrec::RecognitionResult = serde_json::from_value(legacy).unwrap();
+    assert_eq!(legacy.timings, songrec::RecognitionTimings::default());
     
     // Test CSV format
     let csv_output = RecognitionOutput::format_result(&mock_result, OutputFormat::Csv);
     assert!(csv_output.content.contains("Wintergatan"));
     assert!(csv_output.content.contains("Proof of Concept"));
+
+    // Quotes are escaped and line breaks removed, so every record stays on one line
+    let mut quoted = mock_result.clone();
+    quoted.song_name = "The \"Live\"\nVersion".to_string();
+    let csv_output = RecognitionOutput::format_result(&quoted, OutputFormat::Csv);
+    assert!(csv_output.content.starts_with("\"The \"\"Live\"\" Version\",\"Wintergatan\""));
+    assert_eq!(csv_output.content.lines().count(), 1);
     
     // Test CSV header
     let csv_header = RecognitionOutput::csv_header();
@@ -208,6 +269,40 @@ fn test_mp3_file_recognition() {
     }
 }
 
+/// Test progress reporting of file recognition
+#[test]
+fn test_file_recognition_progress() {
+    use songrec::{RecognitionPhase, RecognitionProgress};
+
+    let test_audio_path = "tests/test_audio.wav";
+
+    if !Path::new(test_audio_path).exists() {
+        println!("Skipping progress test - test audio file not found");
+        return;
+    }
+
+    let songrec = SongRec::new(Config::default().with_quiet_mode(true).with_network_timeout(10));
+
+    let mut updates: Vec<RecognitionProgress> = Vec::new();
+    let result = songrec.recognize_from_file_with_progress(test_audio_path, |progress| updates.push(progress));
+
+    // Decoding and fingerprinting happen offline and must both complete
+    for phase in [RecognitionPhase::Decode, RecognitionPhase::Fingerprint] {
+        let percents: Vec<f32> = updates.iter().filter(|u| u.phase == phase).map(|u| u.percent).collect();
+        assert_eq!(percents.first(), Some(&0.0), "{:?} should start at 0%", phase);
+        assert_eq!(percents.last(), Some(&100.0), "{:?} should end at 100%", phase);
+        assert!(percents.windows(2).all(|w| w[0] <= w[1]), "{:?} progress went backwards", phase);
+    }
+
+    // Phases are reported in order
+    let phases: Vec<RecognitionPhase> = updates.iter().map(|u| u.phase).collect();
+    assert!(phases.windows(2).all(|w| w[0] as u8 <= w[1] as u8));
+
+    if result.is_ok() {
+        assert_eq!(updates.last().map(|u| (u.phase, u.percent)), Some((RecognitionPhase::Lookup, 100.0)));
+    }
+}
+
 /// Test error handling with invalid file
 #[test]
 fn test_invalid_file_handling() {
@@ -244,7 +339,7 @@ fn test_config_serialization() {
                 Ok(loaded_config) => {
                     assert_eq!(loaded_config.sensitivity, 0.7);
                     assert_eq!(loaded_config.network_timeout, 25);
-                    assert_eq!(loaded_config.quiet_mode, false);
+                    assert!(!loaded_config.quiet_mode);
                 }
                 Err(e) => println!("Could not load config (TOML support may not be available): {}", e),
             }
@@ -257,6 +352,7 @@ fn test_config_serialization() {
 }
 
 /// Test audio recorder creation with config
+#[cfg(feature = "audio-devices")]
 #[test]
 fn test_audio_recorder_creation() {
     let config = Config::default();
@@ -264,6 +360,63 @@ fn test_audio_recorder_creation() {
     // Should create successfully
 }
 
+/// Test the choice of capture settings among the ones a device supports
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_input_config_negotiation() {
+    use cpal::{SampleFormat, SampleRate, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange};
+    use songrec::audio::recorder::choose_input_config;
+
+    let range = |channels, min, max, format| SupportedStreamConfigRange::new(channels, SampleRate(min), SampleRate(max), SupportedBufferSize::Unknown, format);
+
+    // 48 kHz is picked over the exotic default
+    let default = SupportedStreamConfig::new(8, SampleRate(192000), SupportedBufferSize::Unknown, SampleFormat::F32);
+    let config = choose_input_config(Some(default.clone()), &[range(8, 192000, 192000, SampleFormat::F32), range(2, 8000, 192000, SampleFormat::I16)]).unwrap();
+    assert_eq!((config.channels(), config.sample_rate().0, config.sample_format()), (2, 48000, SampleFormat::I16));
+
+    // The default is kept when no preferred rate is available
+    let config = choose_input_config(Some(default), &[range(8, 192000, 192000, SampleFormat::F32)]).unwrap();
+    assert_eq!(config.sample_rate().0, 192000);
+
+    // Fewer channels, then the lowest rate of at least 16 kHz
+    let config = choose_input_config(None, &[range(6, 22050, 22050, SampleFormat::F32), range(1, 8000, 32000, SampleFormat::U16)]).unwrap();
+    assert_eq!((config.channels(), config.sample_rate().0), (1, 16000));
+
+    let error = choose_input_config(None, &[range(1, 8000, 11025, SampleFormat::I16), range(2, 48000, 48000, SampleFormat::I32)]).unwrap_err();
+    assert!(error.to_string().contains("1 channel(s) at 8000-11025 Hz (I16)"), "{}", error);
+}
+
+/// Test that captured audio is filtered before decimation, so tones above 8 kHz do not fold back
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_capture_decimation() {
+    use songrec::audio::recorder::Decimator;
+
+    // RMS of the 16 kHz output of a tone after the filter settled
+    let rms = |sample_rate: u32, frequency: f64| {
+        let mut decimator = Decimator::new(sample_rate);
+        let output: Vec<f64> = (0..sample_rate)
+            .map(|n| 10000.0 * (2.0 * std::f64::consts::PI * frequency * n as f64 / sample_rate as f64).sin())
+            .filter_map(|sample| decimator.push(sample as i16))
+            .skip(1600)
+            .map(f64::from)
+            .collect();
+        assert!((output.len() as i64 - 14400).abs() <= 1, "{} samples at {} Hz", output.len(), sample_rate);
+        (output.iter().map(|sample| sample * sample).sum::<f64>() / output.len() as f64).sqrt()
+    };
+
+    for sample_rate in [44100, 48000, 192000] {
+        let kept = rms(sample_rate, 1000.0);
+        assert!((kept - 10000.0 / 2f64.sqrt()).abs() < 200.0, "1 kHz at {} Hz: {}", sample_rate, kept);
+        // Would fold back to 4 kHz and 3 kHz
+        for frequency in [12000.0, 13000.0] {
+            let aliased = rms(sample_rate, frequency);
+            assert!(aliased < 70.0, "{} Hz at {} Hz: {}", frequency, sample_rate, aliased);
+        }
+    }
+    assert_eq!(rms(16000, 1000.0).round(), (10000.0 / 2f64.sqrt()).round());
+}
+
 /// Integration test for the complete recognition pipeline
 #[test]
 fn test_recognition_pipeline_integration() {
@@ -282,3 +435,2423 @@ fn test_recognition_pipeline_integration() {
         // Should create successfully with all configurations
     }
 }
+
+/// Test parsing of the related tracks response
+#[test]
+fn test_related_tracks_parsing() {
+    let response = serde_json::json!({
+        "tracks": [
+            {
+                "key": "12345",
+                "title": "Starmachine2000",
+                "subtitle": "Wintergatan",
+                "images": { "coverart": "https://example.com/cover.jpg" },
+                "url": "https://www.shazam.com/track/12345"
+            },
+            {
+                "title": "Missing key is skipped"
+            }
+        ]
+    });
+
+    let tracks = songrec::TrackSummary::list_from_related_response(&response);
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].track_key, "12345");
+    assert_eq!(tracks[0].song_name, "Starmachine2000");
+    assert_eq!(tracks[0].artist_name, "Wintergatan");
+    assert_eq!(tracks[0].cover_art_url.as_deref(), Some("https://example.com/cover.jpg"));
+
+    // Unexpected shapes yield an empty list rather than an error
+    assert!(songrec::TrackSummary::list_from_related_response(&serde_json::json!({})).is_empty());
+}
+
+/// Test parsing of the artist endpoint response
+#[test]
+fn test_artist_info_parsing() {
+    let response = serde_json::json!({
+        "data": [{
+            "id": "1234",
+            "attributes": {
+                "name": "Wintergatan",
+                "genreNames": ["Electronic", "Pop"],
+                "artistBio": "Swedish folktronica band.",
+                "origin": "Gothenburg, Sweden"
+            },
+            "views": {
+                "top-songs": {
+                    "data": [
+                        { "id": "1", "attributes": { "name": "Marble Machine", "artistName": "Wintergatan", "isrc": "SE1234567890" } }
+                    ]
+                }
+            }
+        }]
+    });
+
+    let artist = songrec::ArtistInfo::from_artist_response(&response).expect("artist should parse");
+    assert_eq!(artist.artist_id, "1234");
+    assert_eq!(artist.name, "Wintergatan");
+    assert_eq!(artist.genres, vec!["Electronic", "Pop"]);
+    assert_eq!(artist.top_songs.len(), 1);
+    assert_eq!(artist.top_songs[0].song_name, "Marble Machine");
+
+    assert!(songrec::ArtistInfo::from_artist_response(&serde_json::json!({ "data": [] })).is_none());
+}
+
+/// Test extraction of the preview clip URL from hub actions
+#[test]
+fn test_preview_url_extraction() {
+    let mut result = songrec::RecognitionResult::new("Proof of Concept", "Wintergatan", "test_key_123");
+    result.raw_response = serde_json::json!({
+        "track": {
+            "hub": {
+                "actions": [
+                    { "name": "apple", "type": "applemusicplay", "id": "1" },
+                    { "name": "apple", "type": "uri", "uri": "https://audio-ssl.itunes.apple.com/preview.m4a" }
+                ]
+            }
+        }
+    });
+
+    assert_eq!(result.preview_url(), Some("https://audio-ssl.itunes.apple.com/preview.m4a"));
+
+    result.raw_response = serde_json::json!({ "track": {} });
+    assert_eq!(result.preview_url(), None);
+}
+
+/// Test that an explicit install ID overrides the persisted identity
+#[test]
+fn test_install_id_override() {
+    let config = Config::default().with_install_id("8c3a7a4e-0000-4000-8000-000000000001");
+
+    let (tag_1, install_1) = songrec::identity::Identity::next_tag(&config);
+    let (tag_2, install_2) = songrec::identity::Identity::next_tag(&config);
+
+    assert_eq!(install_1, "8c3a7a4e-0000-4000-8000-000000000001");
+    assert_eq!(install_1, install_2);
+    assert_ne!(tag_1, tag_2, "Each request should get a fresh tag UUID");
+}
+
+/// Test the response inspection helper
+#[test]
+fn test_describe_response() {
+    let response = serde_json::json!({
+        "matches": [{ "id": "1", "offset": 12.5 }],
+        "track": { "key": "12345", "title": "Starmachine2000", "subtitle": "Wintergatan" },
+        "surprise": "field"
+    });
+
+    let description = songrec::debug::describe_response(&response);
+    assert!(description.contains("MATCHES FOUND: 1"));
+    assert!(description.contains("Starmachine2000"));
+    assert!(description.contains("UNKNOWN TOP-LEVEL FIELD surprise"));
+}
+
+/// Test recognition from an encoded file held in memory
+#[test]
+fn test_bytes_recognition() {
+    let test_audio_path = "tests/test_audio.mp3";
+
+    if !Path::new(test_audio_path).exists() {
+        println!("Skipping in-memory recognition test - test audio file not found");
+        return;
+    }
+
+    // In-memory decoding must produce the same signature as decoding the file
+    let data = std::fs::read(test_audio_path).unwrap();
+    let from_file = songrec::SignatureGenerator::make_signature_from_file(test_audio_path).unwrap();
+    let from_bytes = songrec::SignatureGenerator::make_signature_from_bytes(&data).unwrap();
+    assert_eq!(from_file.encode_to_uri().unwrap(), from_bytes.encode_to_uri().unwrap());
+
+    let songrec = SongRec::new(Config::default().with_network_timeout(10));
+    assert!(songrec.recognize_from_bytes(b"This is not an audio file").is_err());
+
+    match songrec.recognize_from_bytes(&data) {
+        Ok(result) => assert!(!result.song_name.is_empty()),
+        Err(e) => println!("In-memory recognition failed (this may be normal): {}", e),
+    }
+}
+
+/// Test PCM descriptors: validation and downmix/resample to mono 16 kHz
+#[test]
+fn test_pcm_buffer_conversion() {
+    use songrec::{PcmBuffer, PcmFormat, SongRecError};
+
+    // One second of stereo 44.1 kHz audio, left and right channels cancel out
+    let data: Vec<u8> = (0..44100)
+        .flat_map(|_| [1000i16.to_le_bytes(), (-1000i16).to_le_bytes()])
+        .flatten()
+        .collect();
+    let pcm = PcmBuffer::new(&data, 44100, 2, PcmFormat::S16Le);
+    assert!((pcm.duration() - 1.0).abs() < 1e-3);
+
+    let mono = pcm.to_mono_16khz().unwrap();
+    assert!((mono.len() as i64 - 16000).abs() <= 1, "got {} samples", mono.len());
+    assert!(mono.iter().all(|&s| s == 0));
+
+    // Impossible descriptions are rejected
+    let songrec = SongRec::new(Config::default());
+    for pcm in [
+        PcmBuffer::new(&data, 0, 2, PcmFormat::S16Le),
+        PcmBuffer::new(&data, 44100, 0, PcmFormat::S16Le),
+        PcmBuffer::new(&data[..3], 44100, 1, PcmFormat::S16Le),
+    ] {
+        assert!(matches!(songrec.recognize_from_pcm(&pcm), Err(SongRecError::InvalidInput(_))));
+    }
+}
+
+/// Test that sample slices of any length can be fed to the signature generator
+#[test]
+fn test_odd_length_sample_slices() {
+    use songrec::SignatureGenerator;
+
+    // Five seconds of a deterministic, non-silent signal
+    let samples: Vec<i16> = (0..5 * 16000)
+        .map(|i| ((i as f32 * 0.05).sin() * 8000.0 + (i as f32 * 0.31).sin() * 4000.0) as i16)
+        .collect();
+
+    let expected = SignatureGenerator::make_signature_from_buffer(&samples);
+
+    for piece_len in [1, 7, 127, 129, 1000, 4097] {
+        let mut generator = SignatureGenerator::new();
+        for piece in samples.chunks(piece_len) {
+            generator.feed(piece);
+        }
+
+        let signature = generator.finalize();
+        assert_eq!(signature.number_samples, samples.len() as u32);
+        assert_eq!(
+            signature.encode_to_uri().unwrap(),
+            expected.encode_to_uri().unwrap(),
+            "signature differs when fed in slices of {} samples",
+            piece_len
+        );
+    }
+
+    // A slice shorter than one step must not panic
+    let mut generator = SignatureGenerator::new();
+    generator.feed(&samples[..50]);
+    assert_eq!(generator.finalize().number_samples, 50);
+}
+
+/// Test the streaming contract of the signature generator
+#[test]
+fn test_signature_generator_streaming() {
+    use songrec::SignatureGenerator;
+
+    let samples: Vec<i16> = (0..4 * 16000)
+        .map(|i| ((i as f32 * 0.07).sin() * 6000.0 + (i as f32 * 0.23).sin() * 3000.0) as i16)
+        .collect();
+
+    let mut generator = SignatureGenerator::new();
+    generator.feed(&samples[..2 * 16000]);
+    assert!(!generator.ready(), "two seconds should not be enough");
+    generator.feed(&samples[2 * 16000..]);
+    assert!(generator.ready());
+
+    let first = generator.finalize();
+    assert!(!generator.ready(), "finalize should start over");
+    assert_eq!(first.number_samples, samples.len() as u32);
+
+    // After finalize (or reset) the generator behaves like a new one
+    generator.feed(&samples[..1000]);
+    generator.reset();
+    generator.feed(&samples);
+    let second = generator.finalize();
+    assert_eq!(first.encode_to_uri().unwrap(), second.encode_to_uri().unwrap());
+    assert_eq!(
+        first.encode_to_uri().unwrap(),
+        SignatureGenerator::make_signature_from_buffer(&samples).encode_to_uri().unwrap()
+    );
+}
+
+/// Test that the processed ledger survives a reload and notices replaced files
+#[test]
+fn test_processed_ledger() {
+    use songrec::ledger::ProcessedLedger;
+
+    let dir = std::env::temp_dir().join(format!("songrec_ledger_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let audio = dir.join("track.wav");
+    let ledger_path = dir.join("ledger.jsonl");
+    std::fs::write(&audio, b"first version").unwrap();
+
+    let mut ledger = ProcessedLedger::open(&ledger_path).unwrap();
+    assert!(ledger.is_empty());
+    assert!(!ledger.contains(&audio));
+    ledger.record(&audio, None).unwrap();
+    assert!(ledger.contains(&audio));
+
+    // A truncated last line is ignored on reload
+    std::fs::OpenOptions::new().append(true).open(&ledger_path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, b"{\"path\":"))
+        .unwrap();
+    let ledger = ProcessedLedger::open(&ledger_path).unwrap();
+    assert_eq!(ledger.len(), 1);
+    assert!(ledger.contains(&audio));
+
+    // A file replaced by a different one is processed again
+    std::fs::write(&audio, b"second, longer version").unwrap();
+    assert!(!ledger.contains(&audio));
+
+    // Files recorded in a dry run are only known to that run
+    let other = dir.join("other.wav");
+    std::fs::write(&other, b"other").unwrap();
+    let mut dry_run = ProcessedLedger::open(&ledger_path).unwrap().with_dry_run(true);
+    dry_run.record(&other, None).unwrap();
+    assert!(dry_run.contains(&other));
+    assert!(!ProcessedLedger::open(&ledger_path).unwrap().contains(&other));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that content identity recognizes renamed and duplicate files
+#[test]
+fn test_processed_ledger_content_identity() {
+    use songrec::ledger::{FileIdentity, ProcessedLedger};
+
+    let dir = std::env::temp_dir().join(format!("songrec_ledger_content_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let original = dir.join("original.mp3");
+    let duplicate = dir.join("subdir_copy.mp3");
+    let ledger_path = dir.join("ledger.jsonl");
+    std::fs::write(&original, b"same audio content").unwrap();
+    std::fs::write(&duplicate, b"same audio content").unwrap();
+
+    let mut ledger = ProcessedLedger::open(&ledger_path).unwrap().with_identity(FileIdentity::Content);
+    ledger.record(&original, None).unwrap();
+    assert!(ledger.contains(&duplicate));
+    assert!(ledger.get(&original).unwrap().content_hash.is_some());
+
+    // Hashes are persisted, but only used when matching by content
+    let ledger = ProcessedLedger::open(&ledger_path).unwrap();
+    assert!(!ledger.contains(&duplicate));
+    let ledger = ledger.with_identity(FileIdentity::Content);
+    assert!(ledger.contains(&duplicate));
+
+    std::fs::write(&duplicate, b"other audio content").unwrap();
+    assert!(!ledger.contains(&duplicate));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that cached results are reused for unchanged files only, without a lookup
+#[test]
+fn test_result_cache() {
+    use songrec::batch::{BatchEvent, BatchJob, BatchState};
+    use songrec::cache::ResultCache;
+
+    let dir = std::env::temp_dir().join(format!("songrec_cache_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let audio = dir.join("track.mp3");
+    let cache_path = dir.join("results.jsonl");
+    std::fs::write(&audio, b"cached audio content").unwrap();
+
+    let result = songrec::RecognitionResult::new("Cached Song", "Cached Artist", "42");
+
+    let mut cache = ResultCache::open(&cache_path).unwrap();
+    assert!(cache.get(&audio).is_none());
+    cache.store(&audio, &result).unwrap();
+
+    // A cached file is answered from the cache, without recognizing it
+    let songrec = SongRec::new(Config::default().with_quiet_mode(true));
+    let mut track_keys = Vec::new();
+    let summary = BatchJob::new(&songrec, BatchState::in_memory())
+        .with_cache(ResultCache::open(&cache_path).unwrap())
+        .run(std::slice::from_ref(&audio), |event| {
+            if let BatchEvent::Finished { outcome: Ok(result), .. } = event {
+                track_keys.push(result.track_key.clone());
+            }
+        })
+        .unwrap();
+    assert_eq!(track_keys, vec!["42".to_string()]);
+    assert_eq!((summary.recognized, summary.cached, summary.processed, summary.api_calls), (1, 1, 0, 0));
+
+    // The file is the same whatever the path it is reached by
+    let cache = ResultCache::open(&cache_path).unwrap();
+    assert!(cache.get(&dir.join(".").join("track.mp3")).is_some());
+
+    // Results recognized with other settings are not used
+    let french = Config::default().with_metadata_language("fr-FR");
+    assert!(ResultCache::open(&cache_path).unwrap().with_config(&french).get(&audio).is_none());
+    assert!(ResultCache::open(&cache_path).unwrap().with_config(&Config::default()).get(&audio).is_some());
+
+    // A changed file is recognized again
+    std::fs::write(&audio, b"different audio content").unwrap();
+    assert!(ResultCache::open(&cache_path).unwrap().get(&audio).is_none());
+
+    // Entries of caches older than the settings hash are not used either
+    std::fs::write(&audio, b"cached audio content").unwrap();
+    assert!(ResultCache::open(&cache_path).unwrap().get(&audio).is_some());
+    let entry = std::fs::read_to_string(&cache_path).unwrap();
+    let legacy = serde_json::to_string(&{
+        let mut entry: serde_json::Value = serde_json::from_str(entry.lines().next().unwrap()).unwrap();
+        entry.as_object_mut().unwrap().remove("config_hash");
+        entry
+    })
+    .unwrap();
+    std::fs::write(&cache_path, legacy + "\n").unwrap();
+    assert!(ResultCache::open(&cache_path).unwrap().get(&audio).is_none());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test library layout and organizing recognized files
+#[test]
+fn test_library_organizer() {
+    use songrec::library::{LibraryOrganizer, OrganizeMode};
+
+    let dir = std::env::temp_dir().join(format!("songrec_library_test_{}", std::process::id()));
+    let inbox = dir.join("inbox");
+    let library = dir.join("library");
+    std::fs::create_dir_all(&inbox).unwrap();
+
+    let mut result = songrec::RecognitionResult::new("Marble Machine", "AC/DC: Tribute?", "1");
+    result.album_name = Some("Live".to_string());
+
+    let source = inbox.join("download.mp3");
+    let organizer = LibraryOrganizer::new(&library, OrganizeMode::Move);
+    assert_eq!(
+        organizer.destination(&source, &result),
+        library.join("AC_DC_ Tribute_").join("Live").join("AC_DC_ Tribute_ - Marble Machine.mp3")
+    );
+
+    // Track numbers only start file names when asked for and known
+    let numbered = LibraryOrganizer::new(&library, OrganizeMode::Move).with_track_numbers(true);
+    assert_eq!(numbered.destination(&source, &result), organizer.destination(&source, &result));
+    let mut on_second_disc = result.clone();
+    on_second_disc.track_number = Some(3);
+    on_second_disc.disc_number = Some(2);
+    assert_eq!(numbered.destination(&source, &on_second_disc).file_name().unwrap(), "2-03 - AC_DC_ Tribute_ - Marble Machine.mp3");
+
+    // Existing files are never overwritten
+    std::fs::write(&source, b"one").unwrap();
+    let first = organizer.organize(&source, &result).unwrap();
+    assert!(first.exists() && !source.exists());
+    std::fs::write(&source, b"two").unwrap();
+    let second = organizer.organize(&source, &result).unwrap();
+    assert_eq!(second.file_name().unwrap(), "AC_DC_ Tribute_ - Marble Machine (2).mp3");
+
+    // Renaming keeps the file in its directory, copying leaves the original
+    std::fs::write(&source, b"three").unwrap();
+    let renamed = LibraryOrganizer::new(&library, OrganizeMode::Rename).organize(&source, &result).unwrap();
+    assert_eq!(renamed.parent(), Some(inbox.as_path()));
+    let copied = LibraryOrganizer::new(&library, OrganizeMode::Copy).organize(&renamed, &result).unwrap();
+    assert!(renamed.exists() && copied.starts_with(&library));
+
+    // A dry run gives the destination without touching anything
+    let planned = LibraryOrganizer::new(&library, OrganizeMode::Move)
+        .with_dry_run(true)
+        .organize(&renamed, &result)
+        .unwrap();
+    assert_eq!(planned.file_name().unwrap(), "AC_DC_ Tribute_ - Marble Machine (4).mp3");
+    assert!(renamed.exists() && !planned.exists());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that batch job state is resumed and summarized
+#[test]
+fn test_batch_state_resume() {
+    use songrec::batch::{BatchJob, BatchState, FileStatus};
+    use std::path::PathBuf;
+
+    let state_path = std::env::temp_dir().join(format!("songrec_batch_state_{}.jsonl", std::process::id()));
+    std::fs::remove_file(&state_path).ok();
+
+    let files: Vec<PathBuf> = ["a.wav", "b.wav", "c.wav", "tests/nonexistent.wav"].iter().map(PathBuf::from).collect();
+    let recognized = songrec::RecognitionResult::new("Marble Machine", "Wintergatan", "1");
+
+    let mut state = BatchState::open(&state_path).unwrap();
+    state.record(&files[0], &Ok(recognized)).unwrap();
+    state.record(&files[1], &Err(songrec::SongRecError::NoMatch(None))).unwrap();
+    state.record(&files[2], &Err(songrec::SongRecError::NetworkError("timeout".to_string()))).unwrap();
+
+    let summary = state.summary(&files);
+    assert_eq!((summary.recognized, summary.no_match, summary.errors, summary.pending), (1, 1, 1, 1));
+    assert_eq!(summary.errors_by_kind.get("network"), Some(&1));
+    assert!(summary.to_string().starts_with("4 files: 1 matched, 1 no match, 1 errors (network: 1), 1 pending;"));
+
+    // After a restart, recognized and unknown files are skipped, errors are retried
+    let state = BatchState::open(&state_path).unwrap();
+    assert!(state.is_done(&files[0]) && state.is_done(&files[1]));
+    assert!(!state.is_done(&files[2]));
+    assert_eq!(state.get(&files[0]).unwrap().song.as_deref(), Some("Wintergatan - Marble Machine"));
+
+    // The missing file cannot be recorded in the ledger, which does not stop the job
+    let ledger = songrec::ledger::ProcessedLedger::open(std::env::temp_dir().join(format!("songrec_batch_ledger_{}.jsonl", std::process::id()))).unwrap();
+    let songrec = SongRec::new(Config::default());
+    let mut job = BatchJob::new(&songrec, state).with_ledger(ledger);
+    let (mut skipped, mut record_failures) = (0, 0);
+    let summary = job.run(&files[3..], |event| match event {
+        songrec::batch::BatchEvent::Skipped { .. } => skipped += 1,
+        songrec::batch::BatchEvent::RecordFailed { .. } => record_failures += 1,
+        _ => {}
+    }).unwrap();
+    assert_eq!((skipped, record_failures), (0, 1));
+    assert_eq!(summary.errors, 1);
+    assert_eq!(summary.failures[0].status, FileStatus::Error);
+    assert!(job.state().get(&files[3]).unwrap().error.is_some());
+    // A file that cannot be decoded is never looked up
+    assert_eq!((summary.processed, summary.api_calls), (1, 0));
+    assert_eq!(summary.errors_by_kind.get("fingerprinting"), Some(&1));
+
+    let mut watched = songrec::batch::BatchSummary::default();
+    watched.add(&files[0], None);
+    watched.add(&files[1], Some(&songrec::SongRecError::NoMatch(None)));
+    assert_eq!((watched.total, watched.processed, watched.recognized, watched.no_match), (2, 2, 1, 1));
+
+    std::fs::remove_file(&state_path).ok();
+}
+
+/// Test concurrent batch processing and politeness settings
+#[test]
+fn test_batch_concurrency() {
+    use songrec::batch::{BatchEvent, BatchJob, BatchState};
+    use std::path::PathBuf;
+
+    use songrec::RecognitionPhase;
+
+    assert_eq!(Config::default().max_concurrent_lookups, None);
+    let config = Config::default().with_max_concurrent_lookups(0).with_lookup_delay(-1.0);
+    assert_eq!(config.max_concurrent_lookups, Some(1));
+    assert_eq!(config.lookup_delay, 0.0);
+
+    let config = Config::default().with_max_concurrent_lookups(2).with_network_timeout(5);
+    let songrec = SongRec::new(config);
+
+    let files: Vec<PathBuf> = (0..5).map(|_| PathBuf::from("tests/test_audio.wav")).collect();
+    let mut looking_up = std::collections::BTreeSet::new();
+    let mut most_looking_up = 0;
+    let mut finished = Vec::new();
+    let summary = BatchJob::new(&songrec, BatchState::in_memory())
+        .run(&files, |event| match event {
+            BatchEvent::Progress { index, progress, .. } if progress.phase == RecognitionPhase::Lookup && progress.percent == 0.0 => {
+                looking_up.insert(index);
+                most_looking_up = most_looking_up.max(looking_up.len());
+            }
+            BatchEvent::Finished { index, path, .. } => {
+                assert_eq!(path, files[index].as_path());
+                looking_up.remove(&index);
+                finished.push(index);
+            }
+            _ => {}
+        })
+        .unwrap();
+
+    // Every file is reported exactly once, whatever the completion order
+    finished.sort();
+    assert_eq!(finished, (0..5).collect::<Vec<_>>());
+    assert_eq!(summary.pending, 0);
+    assert!((1..=2).contains(&most_looking_up), "{} lookups at once", most_looking_up);
+}
+
+/// Test parsing raw responses captured outside of a recognition
+#[test]
+fn test_raw_response_parsing() {
+    use songrec::RecognitionResult;
+    use std::convert::TryFrom;
+
+    let response = serde_json::json!({
+        "matches": [{ "id": "11111" }],
+        "track": {
+            "key": "11111",
+            "title": "Marble Machine",
+            "subtitle": "Wintergatan",
+            "genres": { "primary": "Electronic" },
+            "sections": [{ "metadata": [{ "title": "Album", "text": "Single" }, { "title": "Released", "text": "2016" }] }]
+        }
+    });
+
+    let result = RecognitionResult::try_from(response.clone()).unwrap();
+    assert_eq!(result.song_name, "Marble Machine");
+    assert_eq!(result.artist_name, "Wintergatan");
+    assert_eq!(result.album_name.as_deref(), Some("Single"));
+    assert_eq!(result.release_year.as_deref(), Some("2016"));
+    assert_eq!(result.genre.as_deref(), Some("Electronic"));
+    assert_eq!(result.raw_response, response);
+
+    // Re-parsing keeps when and how fast the song was recognized
+    let mut saved = RecognitionResult::from_raw_response(response).unwrap();
+    saved.recognition_timestamp = chrono::DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap().into();
+    saved.timings.total_ms = 1234;
+    saved.song_name = "Outdated".to_string();
+    let reparsed = saved.reparse().unwrap();
+    assert_eq!(reparsed.song_name, "Marble Machine");
+    assert_eq!(reparsed.recognition_timestamp, saved.recognition_timestamp);
+    assert_eq!(reparsed.timings.total_ms, 1234);
+
+    assert!(matches!(
+        RecognitionResult::try_from(serde_json::json!({ "matches": [] })),
+        Err(songrec::SongRecError::NoMatch(None))
+    ));
+}
+
+/// Test the locale used for metadata requests
+#[test]
+fn test_metadata_locale() {
+    use songrec::fingerprinting::communication::MetadataLocale;
+
+    assert_eq!(Config::default().metadata_language, "en-US");
+    let config = Config::default().with_metadata_language("de-DE");
+    let locale = MetadataLocale::from_config(&config);
+    assert_eq!((locale.language.as_str(), locale.region.as_str()), ("de", "DE"));
+
+    let locale = MetadataLocale::parse("pt_br");
+    assert_eq!((locale.language.as_str(), locale.region.as_str()), ("pt", "BR"));
+
+    // A language alone gets its primary region, garbage falls back to en-US
+    let locale = MetadataLocale::parse("fr");
+    assert_eq!((locale.language.as_str(), locale.region.as_str()), ("fr", "FR"));
+    let locale = MetadataLocale::parse("en");
+    assert_eq!((locale.language.as_str(), locale.region.as_str()), ("en", "US"));
+    let locale = MetadataLocale::parse("../etc");
+    assert_eq!((locale.language.as_str(), locale.region.as_str()), ("en", "US"));
+}
+
+/// Test the continuous recognition event stream
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_pipeline_events() {
+    use songrec::PipelineEvent;
+
+    let event = serde_json::to_value(PipelineEvent::Buffering { percent: 37.5 }).unwrap();
+    assert_eq!(event, serde_json::json!({ "event": "buffering", "percent": 37.5 }));
+    let event = serde_json::to_value(PipelineEvent::RequestSent).unwrap();
+    assert_eq!(event, serde_json::json!({ "event": "request-sent" }));
+    let event = serde_json::to_value(PipelineEvent::DeviceLost { message: "unplugged".to_string() }).unwrap();
+    assert_eq!(event["event"], "device-lost");
+    let event = serde_json::to_value(PipelineEvent::Resumed { gap_ms: 3_600_000 }).unwrap();
+    assert_eq!(event, serde_json::json!({ "event": "resumed", "gap_ms": 3_600_000 }));
+    let result = songrec::RecognitionResult::from_raw_response(serde_json::json!({ "track": { "key": "1", "title": "Song", "subtitle": "Artist" } })).unwrap();
+    let event = serde_json::to_value(PipelineEvent::TrackEnded { result: Box::new(result), played_ms: 180_000 }).unwrap();
+    assert_eq!((&event["event"], &event["played_ms"], &event["result"]["song_name"]), (&"track-ended".into(), &180_000.into(), &"Song".into()));
+
+    // Capture either starts or fails, both are reported as the first event
+    let songrec = SongRec::new(Config::default());
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    let stream = songrec.start_continuous_recognition_with_events(None, event_tx).unwrap();
+    match event_rx.recv_timeout(std::time::Duration::from_secs(10)) {
+        Ok(PipelineEvent::Listening { device }) => assert_eq!(device, None),
+        Ok(PipelineEvent::Error { .. }) => assert!(stream.next_timeout(std::time::Duration::from_secs(1)).is_some_and(|result| result.is_err())),
+        other => panic!("Unexpected first event: {:?}", other),
+    }
+}
+
+/// Test reloading the configuration of a running stream
+#[test]
+fn test_stream_reload() {
+    use songrec::PipelineEvent;
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    // A radio sending 5 seconds of silence per connection, its bitrate making that one chunk
+    let mut silence = Vec::new();
+    silence.extend_from_slice(b"RIFF");
+    silence.extend_from_slice(&(36 + 160000u32).to_le_bytes());
+    silence.extend_from_slice(b"WAVEfmt ");
+    silence.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+    silence.extend_from_slice(&16000u32.to_le_bytes());
+    silence.extend_from_slice(&32000u32.to_le_bytes());
+    silence.extend_from_slice(&[2, 0, 16, 0]);
+    silence.extend_from_slice(b"data");
+    silence.extend_from_slice(&160000u32.to_le_bytes());
+    silence.resize(silence.len() + 160000, 0);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/noise.wav", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for connection in listener.incoming() {
+            let Ok(mut connection) = connection else { break };
+            let _ = connection.read(&mut [0; 4096]);
+            let _ = connection.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\nicy-br: 91\r\nConnection: close\r\n\r\n");
+            let _ = connection.write_all(&silence);
+        }
+    });
+
+    let songrec = SongRec::new(Config::default().with_network_timeout(2));
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    let stream = songrec.start_stream_recognition_with_events(&url, event_tx).unwrap();
+    let next_window = || loop {
+        let event = event_rx.recv_timeout(Duration::from_secs(20)).unwrap();
+        if matches!(event, PipelineEvent::RequestSent | PipelineEvent::Gated { .. }) {
+            break event;
+        }
+    };
+
+    // Silence is looked up until the music gate is turned on by a reload
+    assert!(matches!(next_window(), PipelineEvent::RequestSent));
+    stream.reload(Config::default().with_network_timeout(2).with_music_only(true));
+    loop {
+        match next_window() {
+            PipelineEvent::Gated { .. } => break,
+            // Retries of the window looked up before the reload
+            PipelineEvent::RequestSent => {}
+            _ => unreachable!(),
+        }
+    }
+    assert!(stream.stats().gated_windows >= 1);
+}
+
+/// Test station profiles and recognition from a network stream
+#[test]
+fn test_stations() {
+    use songrec::{DiscordConfig, StationConfig};
+
+    let discord = DiscordConfig { webhook_url: "https://discord.example/hook".to_string(), stale_after: 600 };
+    let config = Config::default().with_station(
+        "bbc6",
+        StationConfig {
+            stream_url: Some("http://127.0.0.1:9/bbc6.mp3".to_string()),
+            metadata_language: Some("en-GB".to_string()),
+            discord: Some(discord.clone()),
+            ..Default::default()
+        },
+    );
+
+    let station = config.station_config("bbc6").unwrap();
+    assert_eq!(station.metadata_language, "en-GB");
+    assert_eq!(station.discord, Some(discord));
+    assert!(config.station_config("fip").is_none());
+
+    let path = std::env::temp_dir().join(format!("songrec-stations-{}.toml", std::process::id()));
+    config.to_file(path.to_str().unwrap()).unwrap();
+    let loaded = Config::from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.stations, config.stations);
+
+    // Nothing listens on the discard port, the failure comes through the stream
+    let songrec = SongRec::new(Config::default().with_network_timeout(2));
+    let stream = songrec.start_stream_recognition("http://127.0.0.1:9/bbc6.mp3").unwrap();
+    match stream.next_timeout(std::time::Duration::from_secs(10)) {
+        Some(Err(songrec::SongRecError::NetworkError(message))) => assert!(message.contains("bbc6.mp3")),
+        other => panic!("Unexpected outcome: {:?}", other),
+    }
+
+    // The stream keeps retrying but never received audio
+    let health = stream.health();
+    assert!(health.alive);
+    assert_eq!(health.last_audio_at, None);
+    assert_eq!(health.last_lookup_at, None);
+    assert!(health.errors_by_kind["network"] >= 1);
+    assert!(!health.is_healthy(std::time::Duration::from_secs(60)));
+}
+
+/// Test the state machine wrapper around continuous recognition
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_recognizer_handle() {
+    use songrec::{RecognizerHandle, RecognizerState};
+    use std::time::Duration;
+
+    let handle = RecognizerHandle::new(SongRec::new(Config::default()));
+    let states = handle.subscribe();
+    assert!(matches!(states.recv().unwrap(), RecognizerState::Idle));
+
+    // Capture either starts or fails, depending on the audio devices available
+    handle.start(None).unwrap();
+    match states.recv_timeout(Duration::from_secs(10)) {
+        Ok(RecognizerState::Listening) | Ok(RecognizerState::Error(_)) => {}
+        other => panic!("Unexpected state: {:?}", other),
+    }
+
+    handle.stop();
+    assert!(matches!(handle.state(), RecognizerState::Idle));
+    assert!(states.try_iter().last().is_some_and(|state| matches!(state, RecognizerState::Idle)));
+}
+
+/// Test the generated systemd unit and launchd agent
+#[test]
+fn test_service_definition() {
+    use songrec::service::ServiceDefinition;
+    use std::path::Path;
+
+    let service = ServiceDefinition::new(
+        "/opt/song rec/songrec-lib-cli",
+        vec!["--device".to_string(), "USB \"Pro\" 100%".to_string(), "-f".to_string(), "json".to_string()],
+    );
+
+    let unit = service.systemd_unit();
+    assert!(unit.contains(r#"ExecStart="/opt/song rec/songrec-lib-cli" listen --device "USB \"Pro\" 100%%" -f json"#));
+    assert!(unit.contains("WantedBy=default.target"));
+
+    let plist = service.launchd_plist(Path::new("/tmp/songrec & co.log"));
+    assert!(plist.contains("<string>listen</string>"));
+    assert!(plist.contains("<string>USB &quot;Pro&quot; 100%</string>"));
+    assert!(plist.contains("<string>/tmp/songrec &amp; co.log</string>"));
+
+    // launchd does not expand ~, the log path is spelled out
+    if std::env::var_os("HOME").is_some() {
+        let log_path = songrec::service::launchd_log_path().unwrap();
+        assert!(!log_path.starts_with("~") && log_path.ends_with("Library/Logs/songrec.log"));
+    }
+}
+
+/// Test recording and querying the recognition history
+#[cfg(feature = "history")]
+#[test]
+fn test_history() {
+    check_history(&songrec::history::History::open_in_memory().unwrap());
+}
+
+/// Test a history as a sink, recording matches only
+#[cfg(feature = "history")]
+#[test]
+fn test_history_sink() {
+    use songrec::{PipelineEvent, Sink};
+
+    let mut history = songrec::history::History::open_in_memory().unwrap();
+    let result = songrec::RecognitionResult::new("Never Gonna Give You Up", "Rick Astley", "1");
+    history.emit(&PipelineEvent::NoMatch { diagnostics: None }).unwrap();
+    history.emit(&PipelineEvent::Match { result: Box::new(result) }).unwrap();
+    assert_eq!(history.len().unwrap(), 1);
+}
+
+/// Test opening a history written before detections were merged into plays
+#[cfg(feature = "history")]
+#[test]
+fn test_history_migration() {
+    use songrec::history::{History, HistoryFilter};
+
+    let database = std::env::temp_dir().join(format!("songrec_history_migration_{}.sqlite3", std::process::id()));
+    std::fs::remove_file(&database).ok();
+    rusqlite::Connection::open(&database)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE recognitions (
+                id INTEGER PRIMARY KEY, recognized_at INTEGER NOT NULL, song_name TEXT NOT NULL, artist_name TEXT NOT NULL,
+                album_name TEXT, track_key TEXT NOT NULL, device TEXT, source TEXT NOT NULL
+            );
+            INSERT INTO recognitions VALUES (1, 1714564800, 'Intro', 'Wintergatan', NULL, '1', 'default', 'device:default');
+            INSERT INTO recognitions VALUES (2, 1714565100, 'Marble Machine', 'Wintergatan', 'Live', '2', 'default', 'device:default');",
+        )
+        .unwrap();
+
+    // Every recognition becomes a play of one detection, once
+    for _ in 0..2 {
+        let history = History::open(&database).unwrap();
+        let plays = history.list(&HistoryFilter::default()).unwrap();
+        assert_eq!(plays.len(), 2);
+        assert_eq!((plays[0].song_name.as_str(), plays[0].detections, plays[0].first_seen.timestamp()), ("Intro", 1, 1714564800));
+        assert_eq!((plays[1].song_name.as_str(), plays[1].album_name.as_deref()), ("Marble Machine", Some("Live")));
+    }
+    std::fs::remove_file(&database).ok();
+}
+
+/// Test the PostgreSQL history, in the scratch database of `SONGREC_TEST_POSTGRES_URL`
+/// whose history tables are dropped first
+#[cfg(feature = "postgres")]
+#[test]
+fn test_postgres_history() {
+    let Ok(url) = std::env::var("SONGREC_TEST_POSTGRES_URL") else {
+        println!("Skipping PostgreSQL history test - SONGREC_TEST_POSTGRES_URL not set");
+        return;
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        use sqlx::Connection;
+        let mut connection = sqlx::PgConnection::connect(&url).await.unwrap();
+        sqlx::raw_sql("DROP TABLE IF EXISTS plays, snippets, metadata").execute(&mut connection).await.unwrap();
+    });
+
+    check_history(&songrec::history::History::open_location(&url).unwrap());
+    let history = songrec::history::History::connect_postgres(&url).unwrap();
+    assert_eq!(history.len().unwrap(), 6);
+    let verification = history.verify(&songrec::history::SigningKey::generate().verifying_key()).unwrap();
+    assert_eq!((verification.unsigned.len(), verification.is_intact()), (6, false));
+
+    // Signed plays are checked as in SQLite, and ids skipped by the sequence are no deleted plays
+    let key = songrec::history::SigningKey::generate();
+    let public_key = key.verifying_key();
+    let history = history.with_signing_key(key);
+    let execute = |sql: &str| {
+        runtime.block_on(async {
+            use sqlx::Connection;
+            let mut connection = sqlx::PgConnection::connect(&url).await.unwrap();
+            sqlx::raw_sql(sql).execute(&mut connection).await.unwrap();
+        })
+    };
+    history.record(&songrec::RecognitionResult::new("Encore", "Wintergatan", "encore")).unwrap();
+    execute("SELECT nextval(pg_get_serial_sequence('plays', 'id'))");
+    history.record(&songrec::RecognitionResult::new("Bonus", "Wintergatan", "bonus")).unwrap();
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.verified, verification.unsigned.len()), (2, 6));
+    assert!(verification.invalid.is_empty() && verification.missing.is_empty());
+
+    execute("UPDATE plays SET signature = NULL WHERE id = 9");
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.invalid.as_slice(), verification.is_intact()), ([9].as_slice(), false));
+    assert!(songrec::history::History::connect_postgres("postgres://127.0.0.1:1/songrec").is_err());
+}
+
+/// Record plays in an empty history and query them
+#[cfg(feature = "history")]
+fn check_history(history: &songrec::history::History) {
+    use chrono::TimeZone;
+    use songrec::history::HistoryFilter;
+    use songrec::RecognitionSource;
+
+    let detections = [
+        ("Rick Astley", "Never Gonna Give You Up", (1, 0), Some("USB Audio")),
+        ("Rick Astley", "Together Forever", (1, 0), None),
+        ("Rick Astley", "Never Gonna Give You Up", (1, 2), Some("USB Audio")),
+        ("Rick Astley", "Never Gonna Give You Up", (1, 4), Some("USB Audio")),
+        ("Daft Punk", "One More Time", (2, 0), None),
+        ("Rick Astley", "Never Gonna Give You Up", (2, 0), Some("USB Audio")),
+        ("100% Pure", "Wildcard", (2, 3), Some("USB Audio")),
+        ("Rick Astley", "Never Gonna Give You Up", (2, 6), Some("USB Audio")),
+    ];
+    for (artist, song, (day, minute), device) in detections {
+        let mut result = songrec::RecognitionResult::new(song, artist, song);
+        result.recognition_timestamp = chrono::Utc.with_ymd_and_hms(2024, 5, day, 12, minute, 0).unwrap();
+        history.record(&result.with_source(RecognitionSource::Device { name: device.map(String::from) })).unwrap();
+    }
+
+    // Consecutive detections of a song are one play, another song in between starts a new one
+    assert_eq!(history.len().unwrap(), 6);
+    let all = history.list(&HistoryFilter::default()).unwrap();
+    assert_eq!((all[0].detections, all[0].last_seen - all[0].first_seen), (3, chrono::Duration::minutes(4)));
+
+    let since = chrono::Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 0).unwrap();
+    assert_eq!(history.list(&HistoryFilter::default().with_since(since)).unwrap().len(), 4);
+    assert_eq!(history.list(&HistoryFilter::default().with_artist("rick")).unwrap().len(), 4);
+    assert_eq!(history.list(&HistoryFilter::default().with_artist("100%")).unwrap().len(), 1);
+    assert_eq!(history.list(&HistoryFilter::default().with_device("default")).unwrap().len(), 2);
+
+    let latest = history.list(&HistoryFilter::default().with_device("usb").with_limit(2)).unwrap();
+    assert_eq!(latest.iter().map(|entry| entry.song_name.as_str()).collect::<Vec<_>>(), ["Wildcard", "Never Gonna Give You Up"]);
+    assert_eq!(latest[0].source, RecognitionSource::Device { name: Some("USB Audio".to_string()) });
+    assert!(latest[0].to_csv().ends_with(",\"device:USB Audio\""));
+
+    let stats = history.daily_stats(&HistoryFilter::default(), 1).unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!((stats[0].plays, stats[0].top_artists[0].name.as_str(), stats[0].top_artists[0].plays), (2, "Rick Astley", 2));
+    assert_eq!(stats[1].plays, 4);
+    assert_eq!(stats[1].top_tracks[0].name, "Rick Astley - Never Gonna Give You Up");
+}
+
+/// Test that audio snippets keep the fingerprinted window and survive FLAC encoding unchanged
+#[test]
+fn test_audio_snippets() {
+    use songrec::audio::{AudioProcessor, AudioSnippet};
+    use songrec::SignatureGenerator;
+
+    // Tone with a pseudo-random hiss, 40 seconds so the window is taken from the middle
+    let mut noise: u32 = 1;
+    let samples: Vec<i16> = (0..40 * 16000 + 1000)
+        .map(|i| {
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let tone = (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin() * 8000.0;
+            tone as i16 + (noise >> 20) as i16 - 2048
+        })
+        .collect();
+
+    let flac = AudioSnippet::new(&samples, 16000).to_flac();
+    assert!(flac.starts_with(b"fLaC"));
+    assert!(flac.len() < samples.len() * 2);
+
+    // Decoding the FLAC file gives back the exact samples, so the same signature
+    let middle = samples.len() / 2;
+    let window = &samples[middle - 6 * 16000..middle + 6 * 16000];
+    let from_flac = SignatureGenerator::make_signature_from_bytes(&flac).unwrap();
+    let from_samples = SignatureGenerator::make_signature_from_buffer(window);
+    assert_eq!(from_flac.encode_to_binary().unwrap(), from_samples.encode_to_binary().unwrap());
+
+    // Live capture keeps the samples of each signature only when asked to
+    let mut processor = AudioProcessor::with_config(Config::default().with_audio_snippets(true));
+    assert!(processor.process_samples(&samples[..13 * 16000]).unwrap().is_some());
+    let snippet = processor.take_snippet().unwrap();
+    assert_eq!((&snippet.samples[..], snippet.duration()), (&samples[..12 * 16000], std::time::Duration::from_secs(12)));
+
+    let mut processor = AudioProcessor::with_config(Config::default());
+    assert!(processor.process_samples(&samples[..13 * 16000]).unwrap().is_some());
+    assert!(processor.take_snippet().is_none());
+}
+
+/// Test that continuous mode fingerprints windows of the configured length, skipping the audio in between
+#[test]
+fn test_recognition_window_and_interval() {
+    use songrec::audio::AudioProcessor;
+
+    let samples: Vec<i16> = (0..30 * 16000).map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16).collect();
+    let config = Config::default()
+        .with_window_duration(4.0)
+        .with_recognition_interval(10.0)
+        .with_audio_snippets(true);
+
+    let mut processor = AudioProcessor::with_config(config);
+    let mut windows = Vec::new();
+    for second in samples.chunks(16000) {
+        if processor.process_samples(second).unwrap().is_some() {
+            windows.push(processor.take_snippet().unwrap().samples);
+        }
+    }
+    assert_eq!(windows.len(), 3);
+    assert_eq!(&windows[1][..], &samples[10 * 16000..14 * 16000]);
+    // The cap on analyzed audio is a setting of its own
+    assert_eq!(Config::default().with_max_audio_duration(4.0).window_duration, 12.0);
+
+    let low_resource = Config::default().with_low_resource_mode();
+    assert_eq!((low_resource.recognition_interval, low_resource.recognition_workers), (30.0, 1));
+    assert!(low_resource.retry_durations.is_empty());
+}
+
+/// Test that history snippets are saved with their play and pruned by the retention policy
+#[cfg(feature = "history")]
+#[test]
+fn test_history_snippets() {
+    use songrec::audio::AudioSnippet;
+    use songrec::history::{History, SnippetRetention};
+
+    let dir = std::env::temp_dir().join(format!("songrec_snippets_test_{}", std::process::id()));
+    let history = History::open_in_memory()
+        .unwrap()
+        .with_snippet_dir(&dir)
+        .with_snippet_retention(SnippetRetention::default().with_max_count(2));
+
+    let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+    for (minute, song) in [(0, "Intro"), (1, "Intro"), (2, "Outro")] {
+        let mut result = songrec::RecognitionResult::new(song, "Wintergatan", song);
+        result.recognition_timestamp = start + chrono::Duration::minutes(minute);
+        result.audio_snippet = Some(AudioSnippet::new(&[minute as i16; 16000], 16000));
+        history.record(&result).unwrap();
+    }
+
+    let plays = history.list(&Default::default()).unwrap();
+    let intro = history.snippets(plays[0].id).unwrap();
+    let outro = history.snippets(plays[1].id).unwrap();
+    assert_eq!((intro.len(), outro.len()), (1, 1));
+    assert!(std::fs::read(&outro[0].path).unwrap().starts_with(b"fLaC"));
+
+    // Only the two most recent snippets are kept, files included
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    assert!(intro[0].path.exists());
+
+    let history = history.with_snippet_retention(SnippetRetention::default().with_max_age(std::time::Duration::from_secs(8 * 60 + 30)));
+    assert_eq!(history.prune_snippets().unwrap(), 1);
+    assert!(!intro[0].path.exists());
+    assert!(history.snippets(plays[0].id).unwrap().is_empty());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that signed history plays are verified and changes to them detected, removing
+/// their signature included
+#[cfg(feature = "history")]
+#[test]
+fn test_history_signing() {
+    use songrec::history::{History, SigningKey, VerifyingKey};
+
+    let dir = std::env::temp_dir().join(format!("songrec_signing_test_{}", std::process::id()));
+    let key_path = dir.join("signing.key");
+    let database = dir.join("history.sqlite3");
+
+    let key = SigningKey::load_or_create(&key_path).unwrap();
+    let public_key = key.verifying_key();
+    assert_eq!(SigningKey::load(&key_path).unwrap().verifying_key(), public_key);
+    assert_eq!(VerifyingKey::from_base64(&public_key.to_base64()).unwrap(), public_key);
+    assert!(VerifyingKey::from_base64("not a key").is_err());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&key_path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+    let result = |song: &str, minute: i64| {
+        let mut result = songrec::RecognitionResult::new(song, "Wintergatan", song);
+        result.recognition_timestamp = start + chrono::Duration::minutes(minute);
+        result
+    };
+
+    let history = History::open(&database).unwrap().with_signing_key(key);
+    for (song, minute) in [("Intro", 0), ("Intro", 1), ("Marble Machine", 2), ("Outro", 5)] {
+        history.record(&result(song, minute)).unwrap();
+    }
+
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.verified, verification.is_intact()), (3, true));
+    assert!(!history.verify(&SigningKey::generate().verifying_key()).unwrap().is_intact());
+
+    // Plays recorded without the key once signing began are invalid
+    History::open(&database).unwrap().record(&result("Encore", 8)).unwrap();
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.unsigned.len(), verification.invalid.as_slice()), (0, [4].as_slice()));
+    assert!(!verification.is_intact());
+
+    // Deleting a play shows from the play signed after it, removing a signature hides no change
+    let connection = rusqlite::Connection::open(&database).unwrap();
+    connection
+        .execute_batch(
+            "DELETE FROM plays WHERE id = 4;
+             UPDATE plays SET detections = 5 WHERE id = 1;
+             UPDATE plays SET song_name = 'Outro (edit)', signature = NULL WHERE id = 3;
+             DELETE FROM plays WHERE id = 2",
+        )
+        .unwrap();
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.invalid.as_slice(), verification.missing.as_slice()), ([1, 3].as_slice(), [2].as_slice()));
+    assert_eq!((verification.verified, verification.unsigned.len()), (0, 0));
+    assert!(!verification.is_intact());
+
+    // Nor does deleting the record of when signing began
+    connection.execute_batch("DELETE FROM metadata").unwrap();
+    let verification = history.verify(&public_key).unwrap();
+    assert_eq!((verification.invalid.as_slice(), verification.unsigned.as_slice()), ([1].as_slice(), [3].as_slice()));
+    assert!(!verification.is_intact());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that agents report to a hub, keeping their results in the outbox while it is down
+#[cfg(feature = "history")]
+#[test]
+fn test_hub() {
+    use songrec::history::{History, HistoryFilter};
+    use songrec::hub::{AgentReport, HubReporter, HubServer, MAX_REPORT_SIZE};
+    use songrec::{PipelineEvent, Sink};
+
+    let dir = std::env::temp_dir().join(format!("songrec_hub_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let outbox = dir.join("outbox.jsonl");
+    let database = dir.join("hub.sqlite3");
+
+    // A history created before agents were recorded
+    rusqlite::Connection::open(&database)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE plays (id INTEGER PRIMARY KEY, first_seen INTEGER NOT NULL, last_seen INTEGER NOT NULL,
+             detections INTEGER NOT NULL, song_name TEXT NOT NULL, artist_name TEXT NOT NULL, album_name TEXT,
+             track_key TEXT NOT NULL, device TEXT, source TEXT NOT NULL)",
+        )
+        .unwrap();
+
+    let result = |song: &str, minute: i64| {
+        let mut result = songrec::RecognitionResult::new(song, "Wintergatan", song);
+        result.recognition_timestamp = chrono::Utc::now() - chrono::Duration::minutes(10 - minute);
+        result
+    };
+
+    // The hub is down: results wait in the outbox, across restarts of the agent
+    let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let url = format!("http://{}", address);
+    let mut reporter = HubReporter::new(&url, "studio-1").unwrap().with_token("secret").with_outbox(&outbox).unwrap();
+    assert!(reporter.report(&result("Intro", 0)).is_err());
+    assert!(reporter.report(&result("Marble Machine", 1)).is_err());
+    assert_eq!(std::fs::read_to_string(&outbox).unwrap().lines().count(), 2);
+
+    let hub = HubServer::bind(&address.to_string(), History::open(&database).unwrap()).unwrap().with_token("secret");
+    std::thread::spawn(move || hub.serve());
+
+    let mut reporter = HubReporter::new(&url, "studio-1").unwrap().with_token("secret").with_outbox(&outbox).unwrap();
+    assert_eq!(reporter.pending_len(), 2);
+    reporter.report(&result("Outro", 2)).unwrap();
+    assert_eq!(reporter.pending_len(), 0);
+    assert_eq!(std::fs::read_to_string(&outbox).unwrap(), "");
+
+    // Agents without the token are turned away, their results kept
+    let mut intruder = HubReporter::new(&url, "intruder").unwrap();
+    assert!(intruder.report(&result("Intro", 3)).is_err());
+    assert_eq!(intruder.pending_len(), 1);
+    let mut guesser = HubReporter::new(&url, "intruder").unwrap().with_token("secreT");
+    assert!(guesser.report(&result("Intro", 3)).is_err());
+
+    // A client holding a connection without sending anything does not hold back the others
+    let _idle = std::net::TcpStream::connect(address).unwrap();
+    let started = std::time::Instant::now();
+    let mut other = HubReporter::new(&url, "studio-2").unwrap().with_token("secret");
+    other.report(&result("Outro", 3)).unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "{:?}", started.elapsed());
+
+    // As a sink, reports are sent from a thread of its own, and are all sent once it is dropped
+    let mut sink = HubReporter::new(&url, "studio-3").unwrap().with_token("secret").into_sink();
+    sink.emit(&PipelineEvent::Match { result: Box::new(result("Intro", 4)) }).unwrap();
+    sink.emit(&PipelineEvent::RequestSent).unwrap();
+    drop(sink);
+
+    // Reports up to the size limit are recorded, larger ones answered as such
+    let post = |body: &[u8]| {
+        use std::io::{Read, Write};
+        let mut connection = std::net::TcpStream::connect(address).unwrap();
+        let headers = format!("POST /results HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\n\r\n", body.len());
+        connection.write_all(headers.as_bytes()).unwrap();
+        connection.write_all(body).unwrap();
+        let mut response = String::new();
+        connection.read_to_string(&mut response).unwrap();
+        response
+    };
+    let mut report = serde_json::to_vec(&AgentReport { agent: "studio-4".to_string(), result: result("Bonus", 5) }).unwrap();
+    report.resize(MAX_REPORT_SIZE as usize, b' ');
+    assert!(post(&report).starts_with("HTTP/1.1 200"));
+    report.push(b' ');
+    assert!(post(&report).starts_with("HTTP/1.1 413"));
+
+    let history = History::open(&database).unwrap();
+    let plays = history.list(&HistoryFilter::default()).unwrap();
+    let songs: Vec<_> = plays.iter().map(|play| (play.song_name.as_str(), play.agent.as_deref())).collect();
+    assert_eq!(
+        songs,
+        [
+            ("Intro", Some("studio-1")),
+            ("Marble Machine", Some("studio-1")),
+            ("Outro", Some("studio-1")),
+            ("Outro", Some("studio-2")),
+            ("Intro", Some("studio-3")),
+            ("Bonus", Some("studio-4"))
+        ]
+    );
+    assert_eq!(history.list(&HistoryFilter::default().with_agent("studio-2")).unwrap().len(), 1);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that spectral weighting is off by default and de-emphasizes bass when enabled
+#[test]
+fn test_spectral_weighting() {
+    use songrec::fingerprinting::signature_format::FrequencyBand;
+    use songrec::{SignatureGenerator, SpectralWeighting};
+
+    // Loud bass line under a quiet melody, as heard from a phone speaker near a subwoofer
+    let samples: Vec<i16> = (0..12 * 16000)
+        .map(|i| {
+            let t = i as f32 / 16000.0;
+            let bass = (t * std::f32::consts::TAU * (300.0 + 40.0 * (t * 2.0).floor())).sin() * 12000.0;
+            let melody = (t * std::f32::consts::TAU * (2000.0 + 150.0 * (t * 4.0).floor())).sin() * 600.0;
+            (bass + melody) as i16
+        })
+        .collect();
+
+    let plain = SignatureGenerator::make_signature_from_buffer(&samples);
+    let unweighted = SignatureGenerator::make_signature_from_buffer_with_weighting(&samples, SpectralWeighting::None);
+    assert_eq!(plain.encode_to_binary().unwrap(), unweighted.encode_to_binary().unwrap());
+
+    let bass_peaks = |weighting| {
+        let signature = SignatureGenerator::make_signature_from_buffer_with_weighting(&samples, weighting);
+        signature.frequency_band_to_sound_peaks.get(&FrequencyBand::_250_520).map_or(0, Vec::len)
+    };
+    assert!(bass_peaks(SpectralWeighting::None) > 0);
+    assert!(bass_peaks(SpectralWeighting::AWeighting) < bass_peaks(SpectralWeighting::None));
+    assert!(bass_peaks(SpectralWeighting::PreEmphasis) < bass_peaks(SpectralWeighting::None));
+
+    assert_eq!(Config::default().spectral_weighting, SpectralWeighting::None);
+    let config: Config = toml::from_str("spectral_weighting = \"pre-emphasis\"").unwrap();
+    assert_eq!(config.spectral_weighting, SpectralWeighting::PreEmphasis);
+}
+
+/// Test that the channel mode picks which part of stereo audio is fingerprinted
+#[test]
+fn test_channel_mode() {
+    use songrec::{ChannelMode, PcmBuffer, PcmFormat};
+
+    // Left at 3000, right at 1000
+    let data: Vec<u8> = (0..16000)
+        .flat_map(|_| [3000i16.to_le_bytes(), 1000i16.to_le_bytes()])
+        .flatten()
+        .collect();
+    let pcm = PcmBuffer::new(&data, 16000, 2, PcmFormat::S16Le);
+    let level = |mode| {
+        let mono = pcm.to_mono_16khz_with(mode).unwrap();
+        mono[mono.len() / 2]
+    };
+    assert!((level(ChannelMode::Mid) - 2000).abs() <= 1);
+    assert!((level(ChannelMode::Left) - 3000).abs() <= 1);
+    assert!((level(ChannelMode::Right) - 1000).abs() <= 1);
+    assert!((level(ChannelMode::Side) - 1000).abs() <= 1);
+    assert_eq!(pcm.to_mono_16khz().unwrap(), pcm.to_mono_16khz_with(ChannelMode::Mid).unwrap());
+
+    // Mono audio is the same whatever the mode
+    let mono_pcm = PcmBuffer::new(&data, 16000, 1, PcmFormat::S16Le);
+    assert_eq!(mono_pcm.to_mono_16khz_with(ChannelMode::Side).unwrap(), mono_pcm.to_mono_16khz().unwrap());
+
+    assert_eq!(Config::default().channel_mode, ChannelMode::Mid);
+    assert_eq!(Config::default().with_channel_mode(ChannelMode::Left).channel_mode, ChannelMode::Left);
+    let config: Config = toml::from_str("channel_mode = \"side\"").unwrap();
+    assert_eq!(config.channel_mode, ChannelMode::Side);
+}
+
+/// Test fingerprinting audio at other speeds to match pitched or sped up recordings
+#[test]
+fn test_pitch_shifts() {
+    use songrec::SignatureGenerator;
+    use songrec::SpectralWeighting;
+
+    // A melody of changing tones, then the same melody played 8% faster
+    let melody = |speed: f32| -> Vec<i16> {
+        (0..(12.0 * 16000.0 / speed) as usize)
+            .map(|i| {
+                let t = i as f32 * speed / 16000.0;
+                let frequency = 400.0 + 90.0 * ((t * 3.0).floor() % 11.0) + 35.0 * ((t * 7.0).floor() % 5.0);
+                ((t * std::f32::consts::TAU * frequency).sin() * 8000.0) as i16
+            })
+            .collect()
+    };
+    let original = SignatureGenerator::make_signature_from_buffer(&melody(1.0));
+    let sped_up = melody(1.08);
+
+    assert_eq!(SignatureGenerator::make_pitch_shifted_signatures(&sped_up, &[], SpectralWeighting::None).count(), 0);
+    let shifted: Vec<_> = SignatureGenerator::make_pitch_shifted_signatures(&sped_up, &[4.0, 8.0], SpectralWeighting::None).collect();
+    assert_eq!(shifted.iter().map(|(percent, _)| *percent).collect::<Vec<_>>(), vec![4.0, -4.0, 8.0, -8.0]);
+    let restored = &shifted[2].1;
+    assert!(original.similarity(restored) > original.similarity(&SignatureGenerator::make_signature_from_buffer(&sped_up)));
+    assert!(original.similarity(restored) > original.similarity(&shifted[3].1));
+
+    assert!(Config::default().pitch_shifts.is_empty());
+    let config = Config::default().with_pitch_shifts(vec![8.0, 4.0, 0.0, -3.0, 4.0, 75.0]);
+    assert_eq!(config.pitch_shifts, vec![4.0, 8.0]);
+}
+
+/// Test reading the audio track of a video file without ffmpeg
+#[test]
+fn test_video_file_audio() {
+    use songrec::SignatureGenerator;
+
+    // Ten seconds of changing tones, stored as PCM next to an empty video track in an MP4 file
+    let samples: Vec<i16> = (0..10 * 16000)
+        .map(|i| {
+            let t = i as f32 / 16000.0;
+            ((t * std::f32::consts::TAU * (500.0 + 120.0 * (t * 3.0).floor())).sin() * 8000.0) as i16
+        })
+        .collect();
+
+    let atom = |kind: &[u8; 4], body: &[u8]| [&(body.len() as u32 + 8).to_be_bytes()[..], kind, body].concat();
+    let full_atom = |kind: &[u8; 4], body: &[u8]| atom(kind, &[&[0u8; 4][..], body].concat());
+    let be = |values: &[u32]| values.iter().flat_map(|value| value.to_be_bytes()).collect::<Vec<u8>>();
+    let track = |handler: &[u8; 4], sample_entry: Vec<u8>, sample_count: u32, offset: u32| {
+        let sample_table = [
+            full_atom(b"stsd", &[be(&[1]), sample_entry].concat()),
+            full_atom(b"stts", &be(&[1, sample_count, 1])),
+            full_atom(b"stsc", &if sample_count > 0 { be(&[1, 1, sample_count, 1]) } else { be(&[0]) }),
+            full_atom(b"stsz", &be(&[2, sample_count])),
+            full_atom(b"stco", &if sample_count > 0 { be(&[1, offset]) } else { be(&[0]) }),
+        ]
+        .concat();
+        let media = [
+            full_atom(b"mdhd", &[be(&[0, 0, 16000, sample_count]), vec![0; 4]].concat()),
+            full_atom(b"hdlr", &[&[0u8; 4][..], handler, &[0; 13]].concat()),
+            atom(b"minf", &atom(b"stbl", &sample_table)),
+        ]
+        .concat();
+        atom(b"trak", &[full_atom(b"tkhd", &[be(&[0, 0, 1, 0, 0]), vec![0; 60]].concat()), atom(b"mdia", &media)].concat())
+    };
+    let file = |offset: u32| {
+        let video_entry = atom(b"avc1", &[0; 78]);
+        let audio_entry = atom(b"sowt", &[&[0u8, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0][..], &be(&[16000 << 16])].concat());
+        let movie = [
+            full_atom(b"mvhd", &[be(&[0, 0, 16000, samples.len() as u32, 0x10000]), vec![0; 76]].concat()),
+            track(b"vide", video_entry, 0, offset),
+            track(b"soun", audio_entry, samples.len() as u32, offset),
+        ]
+        .concat();
+        [atom(b"ftyp", b"isom\0\0\0\0isom"), atom(b"moov", &movie)].concat()
+    };
+    let header_length = file(0).len() as u32 + 8;
+    let pcm: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let video = [file(header_length), atom(b"mdat", &pcm)].concat();
+
+    let path = std::env::temp_dir().join(format!("songrec_video_test_{}.mp4", std::process::id()));
+    std::fs::write(&path, &video).unwrap();
+    let from_file = SignatureGenerator::make_signature_from_file(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let expected = SignatureGenerator::make_signature_from_buffer(&samples).encode_to_binary().unwrap();
+    assert_eq!(from_file.unwrap().encode_to_binary().unwrap(), expected);
+    assert_eq!(SignatureGenerator::make_signature_from_bytes(&video).unwrap().encode_to_binary().unwrap(), expected);
+}
+
+/// Test that Ogg files rodio cannot read fail with an explanation of what is missing
+#[test]
+fn test_ogg_codec_errors() {
+    use songrec::SignatureGenerator;
+
+    // First page of an Ogg stream holding only the identification header of a codec
+    let ogg_file = |header: &[u8]| {
+        let mut page = b"OggS\0\x02".to_vec();
+        page.extend_from_slice(&[0; 20]);
+        page.push(1);
+        page.push(header.len() as u8);
+        page.extend_from_slice(header);
+        page
+    };
+
+    let speex = SignatureGenerator::make_signature_from_bytes(&ogg_file(b"Speex   1.2.0")).unwrap_err();
+    assert!(speex.to_string().contains("Speex audio is not supported"), "{}", speex);
+
+    #[cfg(not(feature = "opus"))]
+    {
+        let opus = SignatureGenerator::make_signature_from_bytes(&ogg_file(b"OpusHead\x01\x01\x38\x01\x80\x3e\0\0\0\0\0")).unwrap_err();
+        assert!(opus.to_string().contains("--features opus"), "{}", opus);
+    }
+}
+
+/// Test that silent and DRM-protected files fail with UnsupportedMedia before any lookup
+#[test]
+fn test_unsupported_media() {
+    use songrec::{AudioSnippet, SongRecError};
+
+    let dir = std::env::temp_dir().join(format!("songrec_unsupported_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let songrec = SongRec::new(Config::default());
+    let recognize = |name: &str, data: &[u8]| {
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        songrec.recognize_from_file(path.to_str().unwrap()).unwrap_err()
+    };
+
+    // Five seconds of digital silence, with a little dither
+    let silence: Vec<i16> = (0..5 * 16000).map(|i| (i % 3) as i16 - 1).collect();
+    let silent_flac = AudioSnippet::new(&silence, 16000).to_flac();
+    let error = recognize("silent.flac", &silent_flac);
+    assert!(matches!(&error, SongRecError::UnsupportedMedia(reason) if reason.contains("silent")), "{}", error);
+    assert_eq!(error.kind(), "unsupported-media");
+    assert!(matches!(songrec.recognize_from_bytes(&silent_flac), Err(SongRecError::UnsupportedMedia(_))));
+
+    // Headers of an iTunes purchase and of an MP4 whose audio sample entry is FairPlay-encrypted
+    let m4p = b"\0\0\0\x18ftypM4P \0\0\0\0M4P mp42\0\0\0\x08free";
+    let error = recognize("song.m4p", m4p);
+    assert!(matches!(&error, SongRecError::UnsupportedMedia(reason) if reason.contains("Apple FairPlay")), "{}", error);
+    let atom = |kind: &[u8], body: &[u8]| [&(8 + body.len() as u32).to_be_bytes()[..], kind, body].concat();
+    let mp4 = |entry: &[u8], extra: &[u8]| {
+        let stsd = atom(b"stsd", &[&[0, 0, 0, 0, 0, 0, 0, 1][..], &atom(entry, &[0; 28])].concat());
+        let trak = atom(b"trak", &atom(b"mdia", &atom(b"minf", &atom(b"stbl", &stsd))));
+        [atom(b"ftyp", b"M4A \0\0\0\0mp42"), atom(b"moov", &[&trak[..], extra].concat())].concat()
+    };
+    let drms = mp4(b"drms", b"");
+    let error = recognize("song.m4a", &drms);
+    assert!(matches!(&error, SongRecError::UnsupportedMedia(reason) if reason.contains("Apple FairPlay")), "{}", error);
+    assert!(matches!(songrec.recognize_from_bytes(&drms), Err(SongRecError::UnsupportedMedia(_))));
+    let enca = mp4(b"enca", b"");
+    assert!(matches!(recognize("song.mp4", &enca), SongRecError::UnsupportedMedia(reason) if reason.contains("Common Encryption")));
+
+    // The same types anywhere but in a sample entry, e.g. in metadata, are not DRM
+    let tagged = mp4(b"mp4a", &atom(b"udta", &atom(b"enca", b"drms aavd")));
+    let error = recognize("tagged.m4a", &tagged);
+    assert!(!matches!(&error, SongRecError::UnsupportedMedia(reason) if reason.contains("DRM")), "{}", error);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test reading the format and duration of files from their headers, and rejecting short files in batches
+#[test]
+fn test_media_probe() {
+    use songrec::audio::probe;
+    use songrec::batch::{BatchJob, BatchState, FileStatus};
+    use songrec::{AudioSnippet, SongRecError};
+    use std::time::Duration;
+
+    let info = probe("tests/test_audio.wav").unwrap();
+    assert_eq!((info.sample_rate, info.channels, info.codec.as_str()), (16000, 1, "pcm_s16le"));
+    let wav_duration = info.duration.unwrap();
+    assert!(wav_duration > Duration::from_secs(60), "{:?}", wav_duration);
+
+    let info = probe("tests/test_audio.mp3").unwrap();
+    assert_eq!(info.codec, "mp3");
+    assert!(info.sample_rate > 0 && info.channels > 0);
+    assert!(info.duration.is_some());
+
+    let dir = std::env::temp_dir().join(format!("songrec_probe_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let samples: Vec<i16> = (0..16000 * 2).map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16).collect();
+    let short = dir.join("ringtone.flac");
+    std::fs::write(&short, AudioSnippet::new(&samples, 16000).to_flac()).unwrap();
+
+    let info = probe(&short).unwrap();
+    assert_eq!((info.sample_rate, info.channels, info.codec.as_str()), (16000, 1, "flac"));
+    assert_eq!(info.duration, Some(Duration::from_secs(2)));
+
+    assert!(matches!(probe(dir.join("missing.wav")), Err(SongRecError::InvalidInput(_))));
+    let text = dir.join("notes.mp3");
+    std::fs::write(&text, "This is not an audio file").unwrap();
+    assert!(matches!(probe(&text), Err(SongRecError::AudioError(_))));
+
+    // Too short to fingerprint: rejected before decoding, without any lookup
+    let songrec = SongRec::new(Config::default());
+    let mut job = BatchJob::new(&songrec, BatchState::in_memory());
+    let summary = job.run(std::slice::from_ref(&short), |_| {}).unwrap();
+    assert_eq!(summary.api_calls, 0);
+    let record = job.state().get(&short).unwrap();
+    assert_eq!(record.status, FileStatus::Error);
+    assert_eq!(record.error_kind.as_deref(), Some("fingerprinting"));
+    assert!(record.error.as_deref().unwrap().contains("too short"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test skipping files outside the duration limits in batch mode, without decoding them
+#[test]
+fn test_file_duration_limits() {
+    use songrec::batch::{BatchEvent, BatchJob, BatchState, FileStatus};
+    use songrec::{AudioSnippet, LongFileAction};
+
+    let dir = std::env::temp_dir().join(format!("songrec_duration_limits_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tone = |seconds: usize| -> Vec<i16> { (0..16000 * seconds).map(|i| ((i as f32 * 0.05).sin() * 8000.0) as i16).collect() };
+    let ringtone = dir.join("ringtone.flac");
+    std::fs::write(&ringtone, AudioSnippet::new(&tone(5), 16000).to_flac()).unwrap();
+    let ambience = dir.join("ambience.flac");
+    std::fs::write(&ambience, AudioSnippet::new(&tone(40), 16000).to_flac()).unwrap();
+
+    let config = Config::default().with_file_duration_limits(Some(10.0), Some(30.0)).with_long_files(LongFileAction::Skip);
+    assert_eq!((config.min_file_duration, config.max_file_duration), (Some(10.0), Some(30.0)));
+    assert_eq!(Config::default().with_file_duration_limits(Some(-1.0), None).min_file_duration, None);
+
+    let songrec = SongRec::new(config);
+    let files = vec![ringtone.clone(), ambience.clone()];
+    let mut skipped = Vec::new();
+    let mut job = BatchJob::new(&songrec, BatchState::in_memory());
+    let summary = job
+        .run(&files, |event| {
+            if let BatchEvent::OutOfLimits { index, duration, .. } = event {
+                skipped.push((index, duration.as_secs()));
+            }
+        })
+        .unwrap();
+
+    skipped.sort();
+    assert_eq!(skipped, vec![(0, 5), (1, 40)]);
+    assert_eq!((summary.skipped, summary.errors, summary.api_calls), (2, 0, 0));
+    assert!(summary.failures.is_empty());
+    assert!(summary.to_string().contains("2 skipped"), "{}", summary);
+
+    // Skipped files are checked again by the next run, the limits may have changed
+    let record = job.state().get(&ringtone).unwrap();
+    assert_eq!(record.status, FileStatus::Skipped);
+    assert!(!job.state().is_done(&ringtone));
+
+    let toml = "max_file_duration = 3600.0\nlong_files = \"tracklist\"\n";
+    let config: Config = toml::from_str(toml).unwrap();
+    assert_eq!(config.long_files, LongFileAction::Tracklist);
+    assert_eq!(config.min_file_duration, None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Test reading an evaluation manifest and computing accuracy and latency metrics
+#[test]
+fn test_eval_report() {
+    use songrec::eval::{self, EvalCase, EvalOutcome, EvalReport};
+
+    let dir = std::env::temp_dir().join(format!("songrec_eval_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let manifest = dir.join("manifest.csv");
+    std::fs::write(
+        &manifest,
+        "# Bar recordings\nfile,expected\nbar/1.mp3,Daft Punk - One More Time\n\"bar/2, live.mp3\",12345\n\nsilence.wav,\n",
+    )
+    .unwrap();
+
+    let cases = eval::read_manifest(&manifest).unwrap();
+    assert_eq!(cases.len(), 3);
+    assert_eq!(cases[1].path, dir.join("bar/2, live.mp3"));
+    assert_eq!(cases[1].expected.as_deref(), Some("12345"));
+    assert_eq!(cases[2].expected, None);
+
+    // Missing files are errors, not misses
+    let songrec = SongRec::new(Config::default());
+    let report = eval::evaluate(&songrec, "default", &cases, |_| {});
+    assert_eq!((report.cases, report.errors, report.recall), (3, 3, 1.0));
+
+    let outcome = |expected: Option<&str>, recognized: Option<&str>, correct, latency_ms| EvalOutcome {
+        case: EvalCase { path: "clip.wav".into(), expected: expected.map(String::from) },
+        recognized: recognized.map(String::from),
+        track_key: None,
+        correct,
+        error: None,
+        latency_ms,
+    };
+    let mut outcomes: Vec<EvalOutcome> = (1..=7).map(|i| outcome(Some("A - B"), Some("A - B"), true, i * 100)).collect();
+    outcomes.push(outcome(Some("A - B"), Some("C - D"), false, 800));
+    outcomes.push(outcome(Some("A - B"), None, false, 900));
+    outcomes.push(outcome(None, Some("E - F"), false, 1000));
+
+    let report = EvalReport::from_outcomes("weighted", outcomes);
+    assert_eq!((report.expected, report.matched, report.correct), (9, 9, 7));
+    assert_eq!((report.precision, report.recall), (7.0 / 9.0, 7.0 / 9.0));
+    assert_eq!((report.latency_p50_ms, report.latency_p90_ms, report.latency_p99_ms), (500, 900, 1000));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that signature similarity finds the same audio at any offset and rejects other audio
+#[test]
+fn test_signature_similarity() {
+    use songrec::SignatureGenerator;
+
+    // Random melody of short notes with harmonics over a little hiss
+    let melody = |seed: u32, seconds: usize| -> Vec<i16> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let notes: Vec<f32> = (0..seconds * 8).map(|_| 200.0 * 2f32.powf((next() % 36) as f32 / 12.0)).collect();
+        (0..seconds * 16000)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                let note = notes[i / 2000];
+                let tone: f32 = (1..=3).map(|harmonic| (t * std::f32::consts::TAU * note * harmonic as f32).sin() / harmonic as f32).sum();
+                (tone * 6000.0) as i16 + (next() % 400) as i16 - 200
+            })
+            .collect()
+    };
+
+    let song = melody(1, 30);
+    let clip = SignatureGenerator::make_signature_from_buffer(&song[2 * 16000..12 * 16000]);
+    let later_clip = SignatureGenerator::make_signature_from_buffer(&song[7 * 16000 + 500..17 * 16000 + 500]);
+    let quieter: Vec<i16> = song[2 * 16000..12 * 16000].iter().map(|sample| sample / 2).collect();
+    let other_song = SignatureGenerator::make_signature_from_buffer(&melody(2, 10));
+
+    assert_eq!(clip.similarity(&clip), 1.0);
+    assert!(clip.similarity(&SignatureGenerator::make_signature_from_buffer(&quieter)) > 0.8);
+    let overlapping = clip.similarity(&later_clip);
+    assert!(overlapping > 0.3, "{}", overlapping);
+    assert!((overlapping - later_clip.similarity(&clip)).abs() < 0.05);
+    let unrelated = clip.similarity(&other_song);
+    assert!(unrelated < 0.1, "{}", unrelated);
+}
+
+/// Test that dedupe groups encodes of the same recording and prefers the best one as keeper
+#[test]
+fn test_find_duplicates() {
+    use songrec::dedupe::{audio_files, find_duplicates, FileFingerprint};
+
+    let melody = |seed: u32, seconds: usize| -> Vec<i16> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let notes: Vec<f32> = (0..seconds * 8).map(|_| 200.0 * 2f32.powf((next() % 36) as f32 / 12.0)).collect();
+        (0..seconds * 16000)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                (t * std::f32::consts::TAU * notes[i / 2000]).sin() * 8000.0 + (next() % 400) as f32 - 200.0
+            })
+            .map(|sample| sample as i16)
+            .collect()
+    };
+    let wav = |samples: &[i16]| -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples.len() as u32 * 2).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&32000u32.to_le_bytes());
+        data.extend_from_slice(&[2, 0, 16, 0]);
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+        samples.iter().for_each(|sample| data.extend_from_slice(&sample.to_le_bytes()));
+        data
+    };
+
+    let dir = std::env::temp_dir().join(format!("songrec_dedupe_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("old")).unwrap();
+    let song = melody(1, 30);
+    let mut late_start = vec![0i16; 24000];
+    late_start.extend(song.iter().map(|sample| sample / 2));
+    std::fs::write(dir.join("song.wav"), wav(&song)).unwrap();
+    std::fs::write(dir.join("old/song.flac"), songrec::AudioSnippet::new(&late_start, 16000).to_flac()).unwrap();
+    std::fs::write(dir.join("other.wav"), wav(&melody(2, 30))).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not audio").unwrap();
+
+    let files = audio_files(&dir);
+    assert_eq!(files.len(), 3);
+    let fingerprints: Vec<FileFingerprint> = files.iter().map(|path| FileFingerprint::from_file(path).unwrap()).collect();
+
+    let groups = find_duplicates(&fingerprints, 0.5);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].keeper, dir.join("song.wav"));
+    let redundant: Vec<_> = groups[0].redundant().collect();
+    assert_eq!(redundant.len(), 1);
+    assert_eq!(redundant[0].path, dir.join("old/song.flac"));
+    assert!(redundant[0].similarity >= 0.5, "{}", redundant[0].similarity);
+    assert!(find_duplicates(&fingerprints, 1.01).is_empty());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test key names and their Camelot notation
+#[test]
+fn test_musical_key_notation() {
+    use songrec::{KeyMode, MusicalKey};
+
+    let key = |tonic, mode| MusicalKey { tonic, mode };
+    assert_eq!(key(9, KeyMode::Minor).name(), "A minor");
+    assert_eq!(key(9, KeyMode::Minor).camelot(), "8A");
+    assert_eq!(key(0, KeyMode::Major).camelot(), "8B");
+    assert_eq!(key(7, KeyMode::Major).camelot(), "9B");
+    assert_eq!(key(4, KeyMode::Minor).camelot(), "9A");
+    assert_eq!(key(6, KeyMode::Major).to_string(), "F# major");
+    assert_eq!(key(6, KeyMode::Major).camelot(), "2B");
+}
+
+/// Test that the tempo and key of a synthetic track are found
+#[cfg(feature = "analysis")]
+#[test]
+fn test_track_analysis() {
+    use songrec::{KeyMode, MusicalKey};
+
+    // Kick drum at 128 BPM over an Am, Dm, E, Am progression
+    let chords: [[f32; 3]; 4] = [[220.0, 261.63, 329.63], [293.66, 349.23, 440.0], [329.63, 415.30, 493.88], [220.0, 261.63, 329.63]];
+    let beat = 60.0 / 128.0;
+    let track: Vec<i16> = (0..16000 * 12)
+        .map(|i| {
+            let t = i as f32 / 16000.0;
+            let since_beat = t % beat;
+            let kick = (std::f32::consts::TAU * 55.0 * since_beat).sin() * (-since_beat * 30.0).exp();
+            let chord = chords[(t / (beat * 4.0)) as usize % 4];
+            let tones: f32 = chord
+                .iter()
+                .flat_map(|note| (1..=3).map(move |harmonic| (t * std::f32::consts::TAU * note * harmonic as f32).sin() / harmonic as f32))
+                .sum();
+            (kick * 12000.0 + tones * 2000.0) as i16
+        })
+        .collect();
+
+    let analysis = songrec::analysis::analyze(&track, 16000);
+    let bpm = analysis.bpm.expect("no tempo found");
+    assert!((bpm - 128.0).abs() < 2.0, "{}", bpm);
+    assert_eq!(analysis.key, Some(MusicalKey { tonic: 9, mode: KeyMode::Minor }));
+
+    let mut state = 1u32;
+    let noise: Vec<i16> = (0..16000 * 12)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as i16
+        })
+        .collect();
+    let analysis = songrec::analysis::analyze(&noise, 16000);
+    assert_eq!(analysis.bpm, None);
+    assert_eq!(analysis.key, None);
+    assert_eq!(songrec::analysis::analyze(&vec![0; 16000 * 12], 16000), songrec::TrackAnalysis::default());
+}
+
+/// Test loudness measurement against the BS.1770 reference levels
+#[test]
+fn test_audio_stats() {
+    use songrec::audio::LoudnessMeter;
+    use songrec::AudioStats;
+
+    // A 997 Hz sine peaking at -20 dBFS measures -23 LUFS
+    let sine: Vec<i16> = (0..16000 * 5)
+        .map(|i| ((i as f32 / 16000.0 * std::f32::consts::TAU * 997.0).sin() * 3277.0) as i16)
+        .collect();
+    let stats = AudioStats::measure(&sine, 16000);
+    let lufs = stats.integrated_lufs.unwrap();
+    assert!((lufs + 23.0).abs() < 0.3, "{}", lufs);
+    assert!((stats.rms_dbfs + 23.0).abs() < 0.1, "{}", stats.rms_dbfs);
+    assert!((stats.peak_dbfs + 20.0).abs() < 0.1, "{}", stats.peak_dbfs);
+
+    // Quiet passages are gated out of the integrated loudness but not of the RMS level
+    let mut with_pause = sine.clone();
+    with_pause.extend(sine.iter().map(|sample| sample / 100));
+    let gated = AudioStats::measure(&with_pause, 16000);
+    assert!((gated.integrated_lufs.unwrap() - lufs).abs() < 0.2);
+    assert!(gated.rms_dbfs < stats.rms_dbfs - 2.5);
+
+    // Feeding in pieces measures the same, and the meter starts over after each result
+    let mut meter = LoudnessMeter::new(16000);
+    sine.chunks(1000).for_each(|chunk| meter.feed(chunk));
+    assert_eq!(meter.finish(), stats);
+    meter.feed(&vec![0; 16000]);
+    let silence = meter.finish();
+    assert_eq!(silence.integrated_lufs, None);
+    assert_eq!((silence.rms_dbfs, silence.peak_dbfs), (songrec::audio::loudness::SILENCE_DBFS, songrec::audio::loudness::SILENCE_DBFS));
+}
+
+/// Test that speech, music and silence windows are told apart
+#[test]
+fn test_audio_classification() {
+    use songrec::audio::classifier::{classify, AudioFeatures};
+    use songrec::AudioClass;
+
+    let mut state = 7u32;
+    let mut noise = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    // Speech: voiced syllables on a glottal pulse train, some starting with a fricative,
+    // separated by short pauses
+    let mut speech = Vec::new();
+    let mut syllable = 0;
+    while speech.len() < 16000 * 12 {
+        let pitch = 110.0 + (syllable % 5) as f32 * 8.0;
+        if syllable % 3 == 0 {
+            speech.extend((0..1200).map(|_| (noise() * 3000.0) as i16));
+        }
+        let length = 2400 + (syllable % 4) * 400;
+        let formant = 500.0 + (syllable % 7) as f32 * 150.0;
+        speech.extend((0..length).map(|i| {
+            let t = i as f32 / 16000.0;
+            let envelope = (std::f32::consts::PI * i as f32 / length as f32).sin();
+            let voice: f32 = (1..=20)
+                .map(|harmonic| {
+                    let frequency = pitch * harmonic as f32;
+                    let gain = 1.0 / (1.0 + ((frequency - formant) / 200.0).powi(2));
+                    (t * std::f32::consts::TAU * frequency).sin() * gain
+                })
+                .sum();
+            (voice * envelope * 6000.0) as i16
+        }));
+        speech.extend((0..800 + (syllable % 3) * 600).map(|_| (noise() * 30.0) as i16));
+        syllable += 1;
+    }
+
+    // Music: sustained chords over a kick drum
+    let beat = 60.0 / 124.0;
+    let music: Vec<i16> = (0..16000 * 12)
+        .map(|i| {
+            let t = i as f32 / 16000.0;
+            let since_beat = t % beat;
+            let kick = (std::f32::consts::TAU * 55.0 * since_beat).sin() * (-since_beat * 30.0).exp();
+            let root = [220.0, 174.61, 261.63, 196.0][(t / (beat * 4.0)) as usize % 4];
+            let chord: f32 = [1.0, 1.25, 1.5, 2.0].iter().map(|ratio| (t * std::f32::consts::TAU * root * ratio).sin()).sum();
+            (kick * 8000.0 + chord * 2500.0 + noise() * 300.0) as i16
+        })
+        .collect();
+
+    assert_eq!(classify(&speech, 16000), AudioClass::Speech);
+    assert_eq!(classify(&music, 16000), AudioClass::Music);
+
+    // A real, very percussive track has an uneven level like speech
+    let wav = std::fs::read("tests/test_audio.wav").unwrap();
+    let data = wav.windows(4).position(|chunk| chunk == b"data").unwrap() + 8;
+    let track = songrec::PcmBuffer::new(&wav[data..], 16000, 1, songrec::PcmFormat::S16Le).to_mono_16khz().unwrap();
+    for start in [20, 40, 60, 80, 100] {
+        let features = AudioFeatures::measure(&track[16000 * start..16000 * (start + 12)], 16000);
+        assert_eq!(features.class(), AudioClass::Music, "{:?}", features);
+    }
+    assert_eq!(classify(&vec![3; 16000 * 12], 16000), AudioClass::Silence);
+    let quiet: Vec<i16> = music.iter().map(|sample| sample / 1000).collect();
+    assert_eq!(classify(&quiet, 16000), AudioClass::Silence);
+
+    let event = serde_json::to_value(songrec::PipelineEvent::Gated { class: AudioClass::Speech }).unwrap();
+    assert_eq!(event, serde_json::json!({ "event": "gated", "class": "speech" }));
+}
+
+/// Test that sinks get the pipeline events and that the file sink appends matches
+#[cfg(feature = "audio-devices")]
+#[test]
+fn test_sinks() {
+    use songrec::{FileSink, PipelineEvent, RecognitionResult, Sink};
+    use std::sync::{Arc, Mutex};
+
+    struct Collector(Arc<Mutex<Vec<PipelineEvent>>>);
+    impl Sink for Collector {
+        fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    // Capture either starts or fails, both reach the sink
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let songrec = SongRec::new(Config::default().with_quiet_mode(true));
+    let _stream = songrec.start_continuous_recognition_with_sinks(None, vec![Box::new(Collector(Arc::clone(&events)))]).unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while events.lock().unwrap().is_empty() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert!(matches!(events.lock().unwrap().first(), Some(PipelineEvent::Listening { .. } | PipelineEvent::Error { .. })));
+
+    let result = RecognitionResult::from_raw_response(serde_json::json!({
+        "matches": [{}],
+        "track": { "key": "42", "title": "Song", "subtitle": "Artist" }
+    }))
+    .unwrap();
+    let event = PipelineEvent::Match { result: Box::new(result) };
+
+    let dir = std::env::temp_dir().join(format!("songrec-sinks-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("matches.csv");
+    for _ in 0..2 {
+        let mut sink = FileSink::open(&path, OutputFormat::Csv).unwrap();
+        sink.emit(&event).unwrap();
+        sink.emit(&PipelineEvent::NoMatch { diagnostics: None }).unwrap();
+    }
+    let content = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], RecognitionOutput::csv_header());
+    assert!(lines[1].contains("Song") && lines[1] == lines[2]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Test that tracklist windows are merged into tracks and exported with their positions
+#[test]
+fn test_tracklist() {
+    use songrec::{RecognitionResult, Tracklist};
+
+    let track = |key: &str, title: &str| {
+        RecognitionResult::from_raw_response(serde_json::json!({
+            "track": { "key": key, "title": title, "subtitle": "Artist \"DJ\"" }
+        }))
+        .ok()
+    };
+
+    let mut tracklist = Tracklist::new("/recordings/mix.mp3", 3_600_000);
+    tracklist.add_window(0, 12_000, None);
+    tracklist.add_window(30_000, 42_000, track("1", "First"));
+    tracklist.add_window(60_000, 72_000, None);
+    tracklist.add_window(90_000, 102_000, track("1", "First"));
+    tracklist.add_window(120_000, 132_000, track("2", "Second"));
+    tracklist.add_window(2_232_500, 2_244_500, track("3", "Third"));
+
+    let spans: Vec<(u64, u64, &str)> = tracklist.entries.iter().map(|entry| (entry.start_ms, entry.end_ms, entry.result.song_name.as_str())).collect();
+    assert_eq!(spans, vec![(30_000, 102_000, "First"), (120_000, 132_000, "Second"), (2_232_500, 2_244_500, "Third")]);
+    assert_eq!(tracklist.entries[2].position(), "00:37:12");
+    assert_eq!(tracklist.to_text().lines().nth(2), Some("00:37:12  Artist \"DJ\" – Third"));
+
+    let started_at = chrono::DateTime::parse_from_rfc3339("2024-05-01T20:00:00Z").unwrap().with_timezone(&chrono::Utc);
+    let tracklist = tracklist.with_started_at(started_at);
+    assert_eq!(tracklist.entries[2].played_at(started_at).to_rfc3339(), "2024-05-01T20:37:12.500+00:00");
+    assert_eq!(tracklist.to_text().lines().count(), 3);
+
+    let cue = tracklist.to_cue();
+    assert!(cue.starts_with("FILE \"mix.mp3\" MP3\n  TRACK 01 AUDIO\n    TITLE \"First\"\n    PERFORMER \"Artist 'DJ'\"\n    INDEX 01 00:30:00\n"));
+    assert!(cue.ends_with("  TRACK 03 AUDIO\n    TITLE \"Third\"\n    PERFORMER \"Artist 'DJ'\"\n    INDEX 01 37:12:37\n"));
+}
+
+/// Test the chapter exports of a tracklist
+#[test]
+fn test_tracklist_chapters() {
+    use songrec::{ChapterFormat, RecognitionResult, Tracklist};
+
+    let mut tracklist = Tracklist::new("mix.flac", 600_000);
+    for (start, key, title) in [(0, "1", "One & Two"), (250_500, "2", "Three=Four")] {
+        let result = RecognitionResult::from_raw_response(serde_json::json!({ "track": { "key": key, "title": title, "subtitle": "Artist" } }));
+        tracklist.add_window(start, start + 12_000, result.ok());
+    }
+
+    let xml = tracklist.export(ChapterFormat::MatroskaXml);
+    assert!(xml.contains("<ChapterTimeStart>00:04:10.500000000</ChapterTimeStart>\n      <ChapterTimeEnd>00:10:00.000000000</ChapterTimeEnd>"));
+    assert!(xml.contains("<ChapterString>Artist – One &amp; Two</ChapterString>"));
+    assert!(xml.trim_end().ends_with("</Chapters>"));
+
+    // Chapters run until the next track
+    let metadata = tracklist.export(ChapterFormat::FfMetadata);
+    assert_eq!(
+        metadata,
+        ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=250500\ntitle=Artist – One & Two\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=250500\nEND=600000\ntitle=Artist – Three\\=Four\n"
+    );
+    assert!(tracklist.export(ChapterFormat::Cue).starts_with("FILE \"mix.flac\" WAVE\n"));
+
+    let path = std::env::temp_dir().join(format!("songrec-chapters-{}.xml", std::process::id()));
+    tracklist.write_chapters(&path, ChapterFormat::MatroskaXml).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), xml);
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Test the names of the files a tracklist is split into
+#[test]
+fn test_tracklist_split_names() {
+    use songrec::tracklist::DEFAULT_SPLIT_TEMPLATE;
+    use songrec::{RecognitionResult, Tracklist};
+
+    let mut tracklist = Tracklist::new("/airchecks/show.flac", 3_600_000);
+    for (start, key, title) in [(0, "1", "Intro"), (180_000, "2", "What/Ever?")] {
+        let result = RecognitionResult::from_raw_response(serde_json::json!({ "track": { "key": key, "title": title, "subtitle": "Artist" } }));
+        tracklist.add_window(start, start + 12_000, result.ok());
+    }
+
+    assert_eq!(tracklist.piece_name(0, DEFAULT_SPLIT_TEMPLATE), "01 - Artist - Intro.flac");
+    assert_eq!(tracklist.piece_name(1, DEFAULT_SPLIT_TEMPLATE), "02 - Artist - What_Ever_.flac");
+    assert_eq!(tracklist.piece_name(1, "{artist}/{number}"), "Artist_02.flac");
+}
+
+/// Test the scrobble eligibility rules and the filter applying them
+#[test]
+fn test_scrobble_policy() {
+    use songrec::scrobble::{ScrobbleFilter, ScrobblePolicy};
+    use songrec::{PipelineEvent, RecognitionResult, Sink};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let track = |duration_ms: Option<u64>| {
+        let mut response = serde_json::json!({ "track": { "key": "1", "title": "Song", "subtitle": "Artist" } });
+        if let Some(duration_ms) = duration_ms {
+            response["resources"] = serde_json::json!({ "songs": { "1": { "attributes": { "durationInMillis": duration_ms } } } });
+        }
+        RecognitionResult::from_raw_response(response).unwrap()
+    };
+
+    let policy = ScrobblePolicy::default();
+    let song = track(Some(200_000));
+    assert!(!policy.is_eligible(&song, Duration::from_secs(99)));
+    assert!(policy.is_eligible(&song, Duration::from_secs(100)));
+    // Half of a long track is more than 4 minutes, which are enough
+    assert!(policy.is_eligible(&track(Some(600_000)), Duration::from_secs(240)));
+    assert!(!policy.is_eligible(&track(Some(25_000)), Duration::from_secs(25)));
+    assert!(!policy.is_eligible(&track(None), Duration::from_secs(59)));
+    assert!(policy.is_eligible(&track(None), Duration::from_secs(60)));
+    assert!(!policy.with_min_fraction(0.8).is_eligible(&song, Duration::from_secs(150)));
+
+    struct Collector(Arc<Mutex<Vec<PipelineEvent>>>);
+    impl Sink for Collector {
+        fn emit(&mut self, event: &PipelineEvent) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut filter = ScrobbleFilter::new(Box::new(Collector(Arc::clone(&events))));
+    filter.emit(&PipelineEvent::TrackStarted { result: Box::new(song.clone()) }).unwrap();
+    filter.emit(&PipelineEvent::TrackEnded { result: Box::new(song.clone()), played_ms: 30_000 }).unwrap();
+    filter.emit(&PipelineEvent::TrackEnded { result: Box::new(song), played_ms: 150_000 }).unwrap();
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[1], PipelineEvent::TrackEnded { played_ms: 150_000, .. }));
+}
+
+/// Test that the state directory set in the config holds the identity, ledger and history
+#[test]
+fn test_state_dir() {
+    use songrec::identity::Identity;
+    use songrec::ledger::ProcessedLedger;
+    use songrec::StateDir;
+
+    let dir = std::env::temp_dir().join(format!("songrec_state_test_{}", std::process::id()));
+    let config = Config::default().with_state_dir(&dir);
+    let state = StateDir::for_config(&config).unwrap();
+    assert_eq!(state.path(), dir.as_path());
+    assert_eq!(state.file("identity.json"), dir.join("identity.json"));
+
+    let (_, install_id) = Identity::next_tag(&config);
+    assert!(dir.join("identity.json").exists());
+    assert_eq!(Identity::next_tag(&config).1, install_id);
+
+    let audio = dir.join("track.wav");
+    std::fs::write(&audio, b"audio").unwrap();
+    let mut ledger = ProcessedLedger::open_in(&state).unwrap();
+    ledger.record(&audio, None).unwrap();
+    assert!(ProcessedLedger::open_in(&state).unwrap().contains(&audio));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that stored secrets fill the integration settings left empty
+#[test]
+fn test_secret_store() {
+    use songrec::{DiscordConfig, ListenBrainzConfig, SecretLocation, SecretStore, StateDir};
+    use songrec::secrets::{DISCORD_WEBHOOK, LISTENBRAINZ_TOKEN};
+
+    let dir = std::env::temp_dir().join(format!("songrec_secrets_test_{}", std::process::id()));
+    let store = SecretStore::in_dir(&StateDir::new(&dir));
+    assert_eq!(store.get(DISCORD_WEBHOOK).unwrap(), None);
+
+    let location = store.set(DISCORD_WEBHOOK, "https://discord.com/api/webhooks/1/secret").unwrap();
+    #[cfg(unix)]
+    if let SecretLocation::File(path) = &location {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    let _ = location;
+
+    let config = Config::default().with_discord(DiscordConfig {
+        webhook_url: String::new(),
+        stale_after: 600,
+    });
+    let config = store.apply(config).unwrap();
+    assert_eq!(config.discord.unwrap().webhook_url, "https://discord.com/api/webhooks/1/secret");
+
+    store.set(LISTENBRAINZ_TOKEN, "user-token").unwrap();
+    let config = Config::default().with_listenbrainz(ListenBrainzConfig { token: String::new(), url: None });
+    assert_eq!(store.apply(config).unwrap().listenbrainz.unwrap().token, "user-token");
+    store.remove(LISTENBRAINZ_TOKEN).unwrap();
+
+    store.remove(DISCORD_WEBHOOK).unwrap();
+    assert_eq!(store.get(DISCORD_WEBHOOK).unwrap(), None);
+    let config = Config::default().with_discord(DiscordConfig { webhook_url: String::new(), stale_after: 600 });
+    assert!(store.apply(config).is_err());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Test that profiles of a configuration file are merged over its other settings
+#[test]
+fn test_config_profiles() {
+    let path = std::env::temp_dir().join(format!("songrec-profiles-{}.toml", std::process::id()));
+    std::fs::write(&path, r#"
+sensitivity = 0.7
+metadata_language = "fr-FR"
+
+[discord]
+webhook_url = "https://discord.com/api/webhooks/1/secret"
+stale_after = 600
+
+[profile.quiet-mic]
+device = "USB Microphone"
+music_only = true
+
+[profile.radio]
+device = "Monitor of Built-in Audio"
+metadata_language = "en-GB"
+
+[profile.radio.discord]
+stale_after = 120
+
+[profile.laptop]
+device = ""
+discord = {}
+"#).unwrap();
+    let config = Config::from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(config.profiles.len(), 3);
+
+    let mic = config.profile_config("quiet-mic").unwrap();
+    assert_eq!(mic.device.as_deref(), Some("USB Microphone"));
+    assert!(mic.music_only);
+    assert_eq!(mic.sensitivity, 0.7);
+    assert_eq!(mic.metadata_language, "fr-FR");
+
+    let radio = config.profile_config("radio").unwrap();
+    assert_eq!(radio.metadata_language, "en-GB");
+    let discord = radio.discord.unwrap();
+    assert_eq!(discord.stale_after, 120);
+    assert_eq!(discord.webhook_url, "https://discord.com/api/webhooks/1/secret");
+    assert!(config.profile_config("loopback").is_err());
+
+    // Empty values unset optional settings, the log callback is kept
+    let laptop = config
+        .clone()
+        .with_device("USB Microphone")
+        .with_log_callback(|_, _| {})
+        .profile_config("laptop")
+        .unwrap();
+    assert_eq!((laptop.device, laptop.discord), (None, None));
+    assert!(laptop.log_callback.is_some());
+
+    // Profiles survive saving the configuration
+    config.to_file(path.to_str().unwrap()).unwrap();
+    let loaded = Config::from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.profiles, config.profiles);
+}
+
+/// Test that SONGREC_* environment variables override settings, nested ones included
+#[test]
+fn test_config_env_overrides() {
+    let vars = [
+        ("SONGREC_NETWORK_TIMEOUT", "42"),
+        ("SONGREC_RETRY_DURATIONS", "[18.0, 24.0]"),
+        ("SONGREC_METADATA_LANGUAGE", "de-DE"),
+        ("SONGREC_DEVICE", "2"),
+        ("SONGREC_STATIONS__RADIO__STREAM_URL", "http://radio.example/stream"),
+        ("SONGREC_NOT_A_SETTING", "ignored"),
+    ];
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+
+    let config = Config::from_env();
+    std::env::set_var("SONGREC_NETWORK_TIMEOUT", "soon");
+    let invalid = Config::default().with_env_overrides();
+    for (name, _) in vars {
+        std::env::remove_var(name);
+    }
+
+    let config = config.unwrap();
+    assert_eq!(config.network_timeout, 42);
+    assert_eq!(config.retry_durations, vec![18.0, 24.0]);
+    assert_eq!(config.metadata_language, "de-DE");
+    // Read as a string, like the setting, although it looks like a number
+    assert_eq!(config.device.as_deref(), Some("2"));
+    let station = config.station_config("radio").unwrap();
+    assert_eq!(station.stations["radio"].stream_url.as_deref(), Some("http://radio.example/stream"));
+
+    assert!(invalid.is_err());
+}
+
+/// Test that library diagnostics go to the log callback instead of stderr
+#[test]
+fn test_log_callback() {
+    use songrec::identity::Identity;
+    use songrec::LogLevel;
+    use std::sync::{Arc, Mutex};
+
+    let dir = std::env::temp_dir().join(format!("songrec_log_test_{}", std::process::id()));
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let received = messages.clone();
+    let config = Config::default()
+        .with_state_dir(&dir)
+        .with_quiet_mode(true)
+        .with_log_callback(move |level, message| received.lock().unwrap().push((level, message.to_string())));
+
+    Identity::next_tag(&config);
+    std::fs::remove_dir_all(&dir).ok();
+
+    let messages = messages.lock().unwrap();
+    assert!(messages.contains(&(LogLevel::Info, "Generating a new installation identity".to_string())));
+}
+
+/// Test that a panic in a pipeline thread is reported instead of silently ending it
+#[test]
+fn test_pipeline_thread_panic() {
+    use songrec::{LogLevel, PipelineEvent, Sink};
+    use std::sync::{mpsc, Mutex};
+
+    struct PanickingSink;
+
+    impl Sink for PanickingSink {
+        fn emit(&mut self, _event: &PipelineEvent) -> Result<(), Box<dyn std::error::Error>> {
+            panic!("sink failure");
+        }
+    }
+
+    let (log_tx, log_rx) = mpsc::channel();
+    let log_tx = Mutex::new(log_tx);
+    let config = Config::default()
+        .with_network_timeout(2)
+        .with_log_callback(move |level, message| {
+            let _ = log_tx.lock().unwrap().send((level, message.to_string()));
+        });
+
+    // The connection error is the first event, the sink thread panics on it
+    let songrec = SongRec::new(config);
+    let _stream = songrec.start_stream_recognition_with_sinks("http://127.0.0.1:9/radio.mp3", vec![Box::new(PanickingSink)]).unwrap();
+    let message = loop {
+        let (level, message) = log_rx.recv_timeout(std::time::Duration::from_secs(10)).unwrap();
+        if level == LogLevel::Error {
+            break message;
+        }
+    };
+    assert!(message.contains("songrec-sinks") && message.contains("sink failure"), "{}", message);
+}
+
+/// Test that genres are normalized by the built-in rules and the user mapping
+#[test]
+fn test_genre_normalization() {
+    use songrec::genre::{normalize, GENRES};
+    use songrec::library::{LibraryOrganizer, OrganizeMode};
+    use std::collections::BTreeMap;
+
+    let rules = BTreeMap::new();
+    assert_eq!(normalize("Hip-Hop/Rap", &rules).as_deref(), Some("Hip-Hop"));
+    assert_eq!(normalize("Dancehall", &rules).as_deref(), Some("Reggae"));
+    assert_eq!(normalize("Latin Pop", &rules).as_deref(), Some("Latin"));
+    assert_eq!(normalize("Heavy Metal", &rules).as_deref(), Some("Metal"));
+    assert_eq!(normalize("Klassik", &rules).as_deref(), Some("Classical"));
+    assert_eq!(normalize("K-Pop", &rules).as_deref(), Some("Pop"));
+    assert_eq!(normalize("Spoken Word", &rules), None);
+    for genre in GENRES {
+        assert_eq!(normalize(genre, &rules).as_deref(), Some(genre));
+    }
+
+    // The user mapping comes first, from a [genre_mapping] table
+    let path = std::env::temp_dir().join(format!("songrec_genre_test_{}.toml", std::process::id()));
+    std::fs::write(&path, "normalize_genres = true\n\n[genre_mapping]\n\"K-Pop\" = \"K-Pop\"\n\"Spoken Word\" = \"Audiobooks\"\n").unwrap();
+    let config = Config::from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(config.normalize_genres);
+    assert_eq!(normalize("k-pop", &config.genre_mapping).as_deref(), Some("K-Pop"));
+    assert_eq!(normalize("Spoken Word", &config.genre_mapping).as_deref(), Some("Audiobooks"));
+    assert_eq!(normalize("Hip-Hop/Rap", &config.genre_mapping).as_deref(), Some("Hip-Hop"));
+
+    // Genre folders fall back to the built-in rules for results that were not normalized
+    let mut result = songrec::RecognitionResult::from_raw_response(serde_json::json!({
+        "track": {"title": "Song", "subtitle": "Artist", "key": "1", "genres": {"primary": "Hip-Hop/Rap"}}
+    }))
+    .unwrap();
+    assert_eq!(result.genre_normalized, None);
+    let organizer = LibraryOrganizer::new("library", OrganizeMode::Copy).with_genre_folders(true);
+    let source = std::path::Path::new("song.mp3");
+    assert_eq!(
+        organizer.destination(source, &result),
+        std::path::Path::new("library/Hip-Hop/Artist/Unknown Album/Artist - Song.mp3")
+    );
+    result.genre_normalized = Some("Rap".to_string());
+    assert!(organizer.destination(source, &result).starts_with("library/Rap"));
+    result.genre_normalized = None;
+    result.genre = None;
+    assert!(organizer.destination(source, &result).starts_with("library/Unknown Genre"));
+}
+
+/// Test that file names are made valid on each platform
+#[test]
+fn test_sanitize_filename() {
+    use songrec::filename::{sanitize_filename, sanitize_filename_with, Platform, Replacement, MAX_FILENAME_LENGTH};
+
+    assert_eq!(sanitize_filename("AC/DC: Live?.mp3", Platform::Windows), "AC_DC_ Live_.mp3");
+    assert_eq!(sanitize_filename("AC/DC: Live?.mp3", Platform::MacOs), "AC_DC_ Live?.mp3");
+    assert_eq!(sanitize_filename("AC/DC: Live?.mp3", Platform::Unix), "AC_DC: Live?.mp3");
+    assert_eq!(sanitize_filename_with("AC/DC: Live", Platform::Portable, Replacement::Remove), "ACDC Live");
+    assert_eq!(sanitize_filename_with("AC/DC: Live", Platform::Portable, Replacement::LookAlike), "AC∕DC꞉ Live");
+    assert_eq!(sanitize_filename_with("AC/DC: Live", Platform::Portable, Replacement::Char('-')), "AC-DC- Live");
+    // A replacement the platform does not allow either is not put back
+    assert_eq!(sanitize_filename_with("AC/DC", Platform::Unix, Replacement::Char('/')), "AC_DC");
+    assert_eq!(sanitize_filename_with("AC/DC: Live", Platform::Windows, Replacement::Char(':')), "AC_DC_ Live");
+    assert_eq!(sanitize_filename_with("AC/DC", Platform::Unix, Replacement::Char('\u{202E}')), "AC_DC");
+
+    // Device names, trailing dots, direction overrides and empty names
+    assert_eq!(sanitize_filename("con.mp3", Platform::Portable), "con_.mp3");
+    assert_eq!(sanitize_filename("con.mp3", Platform::Unix), "con.mp3");
+    assert_eq!(sanitize_filename(" Song . . ", Platform::Windows), "Song");
+    assert_eq!(sanitize_filename("song\u{202E}3pm.exe", Platform::Unix), "song3pm.exe");
+    assert_eq!(sanitize_filename("שלום\tעולם", Platform::Unix), "שלוםעולם");
+    assert_eq!(sanitize_filename("///", Platform::Unix), "___");
+    assert_eq!(sanitize_filename_with("///", Platform::Unix, Replacement::Remove), "Unknown");
+
+    // Long names keep their extension and whole emoji
+    let family = "👨\u{200D}👩\u{200D}👧";
+    let long = format!("{}.flac", family.repeat(30));
+    for platform in [Platform::Windows, Platform::Unix] {
+        let name = sanitize_filename(&long, platform);
+        assert!(name.ends_with(".flac"), "{}", name);
+        let stem = name.trim_end_matches(".flac");
+        assert!(!stem.is_empty() && stem.replace(family, "").is_empty(), "{:?} split an emoji", stem);
+        let length = if platform == Platform::Windows { name.encode_utf16().count() } else { name.len() };
+        assert!(length <= MAX_FILENAME_LENGTH);
+    }
+    assert_eq!(sanitize_filename(&"e\u{301}".repeat(200), Platform::Unix).chars().last(), Some('\u{301}'));
+}
+
+/// Test that no-match diagnostics tell quiet, short and usable audio apart
+#[test]
+fn test_no_match_diagnostics() {
+    use songrec::{NoMatchDiagnostics, PipelineEvent, SignatureGenerator};
+
+    let tone = |seconds: usize, amplitude: f32| -> Vec<i16> {
+        (0..seconds * 16000)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                let chord: f32 = [330.0f32, 880.0, 2200.0, 4400.0].iter().map(|f| (t * f * std::f32::consts::TAU).sin()).sum();
+                // Notes change every quarter second, like music
+                let envelope = if (i / 4000) % 2 == 0 { 1.0 } else { 0.6 };
+                (chord / 4.0 * envelope * amplitude * 32767.0) as i16
+            })
+            .collect()
+    };
+    let diagnose = |samples: &[i16]| NoMatchDiagnostics::measure(&SignatureGenerator::make_signature_from_buffer(samples), samples);
+
+    let quiet = diagnose(&tone(12, 0.003));
+    assert!(quiet.rms_dbfs < -40.0, "{:?}", quiet);
+    assert!(quiet.hint.contains("quiet") || quiet.hint.contains("silent"), "{:?}", quiet);
+
+    let short = diagnose(&tone(3, 0.5));
+    assert!((short.window_secs - 3.0).abs() < 0.1 && short.hint.contains("too short"), "{:?}", short);
+
+    let clip = diagnose(&tone(12, 0.5));
+    assert_eq!(clip.peak_count, clip.band_peaks.iter().sum::<usize>());
+    assert!(clip.peak_count > 0 && !clip.hint.contains("quiet") && !clip.hint.contains("short"), "{:?}", clip);
+    assert!(!clip.is_unusable() && clip.attempts == 1);
+
+    // Silent windows are recorded again by one-shot device recognition, up to the configured attempts
+    assert!(diagnose(&vec![0; 12 * 16000]).is_unusable());
+    assert_eq!(Config::default().record_attempts, 3);
+    assert_eq!(Config::default().with_record_attempts(0).record_attempts, 1);
+
+    // The hint is part of the error message, events without diagnostics serialize as before
+    let error = songrec::SongRecError::NoMatch(Some(Box::new(quiet.clone())));
+    assert_eq!(error.to_string(), format!("No match found: {}", quiet.hint));
+    assert_eq!(error.kind(), "no-match");
+    assert_eq!(serde_json::to_string(&PipelineEvent::NoMatch { diagnostics: None }).unwrap(), r#"{"event":"no-match"}"#);
+    let event = serde_json::to_value(PipelineEvent::NoMatch { diagnostics: Some(Box::new(quiet)) }).unwrap();
+    assert!(event["diagnostics"]["hint"].is_string());
+}
+
+/// Test the MessagePack and bincode encodings of results and signatures
+#[test]
+fn test_binary_encodings() {
+    use songrec::{DecodedSignature, RecognitionResult, RecognitionSource, SignatureGenerator};
+
+    let response = serde_json::json!({
+        "track": { "key": "42", "title": "Song (feat. Guest)", "subtitle": "Artist", "genres": { "primary": "Pop" } }
+    });
+    let mut result = RecognitionResult::from_raw_response(response).unwrap()
+        .with_source(RecognitionSource::Device { name: Some("Line In".to_string()) });
+    result.record_attempts = Some(2);
+
+    let encoded = result.to_msgpack().unwrap();
+    assert!(encoded.len() < serde_json::to_vec(&result).unwrap().len());
+    let decoded = RecognitionResult::from_msgpack(&encoded).unwrap();
+    assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&result).unwrap());
+    assert!(RecognitionResult::from_msgpack(&encoded[..encoded.len() / 2]).is_err());
+
+    // Signatures are held as their binary encoding, and as the data URI in JSON
+    let samples: Vec<i16> = (0..5 * 16000).map(|i| ((i as f32 * 0.07).sin() * (i as f32 * 0.0013).cos() * 12000.0) as i16).collect();
+    let signature = SignatureGenerator::make_signature_from_buffer(&samples);
+    let binary = signature.encode_to_binary().unwrap();
+    let encoded = signature.to_msgpack().unwrap();
+    assert!(encoded.len() <= binary.len() + 5);
+    assert_eq!(DecodedSignature::from_msgpack(&encoded).unwrap().encode_to_binary().unwrap(), binary);
+    assert_eq!(serde_json::to_value(&signature).unwrap(), signature.encode_to_uri().unwrap());
+
+    #[cfg(feature = "bincode")]
+    {
+        let decoded = RecognitionResult::from_bincode(&result.to_bincode().unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&result).unwrap());
+        let decoded = DecodedSignature::from_bincode(&signature.to_bincode().unwrap()).unwrap();
+        assert_eq!(decoded.encode_to_binary().unwrap(), binary);
+    }
+}
+
+/// Test publishing events to a NATS subject, against a minimal server
+#[cfg(feature = "nats")]
+#[test]
+fn test_nats_sink() {
+    use songrec::codec::EventEncoding;
+    use songrec::publish::NatsSink;
+    use songrec::{PipelineEvent, RecognitionResult, Sink};
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut connection, _) = listener.accept().unwrap();
+        connection.write_all(b"INFO {\"server_id\":\"test\"}\r\n").unwrap();
+        let mut reader = BufReader::new(connection.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("CONNECT ") && line.contains("\"auth_token\":\"secret\""), "{}", line);
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "PING\r\n");
+        connection.write_all(b"PONG\r\nPING\r\n").unwrap();
+
+        // The PING is answered along with a publication
+        let (mut messages, mut pongs) = (Vec::new(), 0);
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                return (messages, pongs);
+            }
+            if line == "PONG\r\n" {
+                pongs += 1;
+                continue;
+            }
+            let size: usize = line.trim_end().rsplit(' ').next().unwrap().parse().unwrap();
+            assert!(line.starts_with("PUB songrec.kitchen "), "{}", line);
+            let mut payload = vec![0; size + 2];
+            reader.read_exact(&mut payload).unwrap();
+            payload.truncate(size);
+            messages.push(payload);
+        }
+    });
+
+    let mut sink = NatsSink::connect(&format!("nats://secret@127.0.0.1:{}", port), "songrec.kitchen").unwrap();
+    let result = RecognitionResult::from_raw_response(serde_json::json!({ "track": { "key": "7", "title": "Song", "subtitle": "Artist" } })).unwrap();
+    sink.emit(&PipelineEvent::Buffering { percent: 50.0 }).unwrap();
+    sink.emit(&PipelineEvent::Match { result: Box::new(result.clone()) }).unwrap();
+    let mut sink = sink.with_encoding(EventEncoding::MessagePack);
+    sink.emit(&PipelineEvent::TrackEnded { result: Box::new(result), played_ms: 1000 }).unwrap();
+    drop(sink);
+
+    let (messages, pongs) = server.join().unwrap();
+    assert_eq!((messages.len(), pongs), (2, 1), "No buffering progress");
+    let event: serde_json::Value = serde_json::from_slice(&messages[0]).unwrap();
+    assert_eq!((&event["event"], &event["result"]["track_key"]), (&"match".into(), &"7".into()));
+    match PipelineEvent::from_msgpack(&messages[1]).unwrap() {
+        PipelineEvent::TrackEnded { result, played_ms } => assert_eq!((result.track_key.as_str(), played_ms), ("7", 1000)),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+
+    assert!(NatsSink::connect(&format!("127.0.0.1:{}", port), "songrec.kitchen").is_err());
+    assert!(NatsSink::connect("127.0.0.1:1", "two words").is_err());
+}
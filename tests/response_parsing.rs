@@ -0,0 +1,192 @@
+//! Parsing of the response shapes returned by the recognition API
+//!
+//! Fixtures live in `tests/responses`, see the README there.
+
+use serde_json::Value;
+use songrec::{RecognitionResult, RecognitionSource, SongRecError};
+use std::path::Path;
+
+fn fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/responses").join(name);
+    let content = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path.display(), e));
+    serde_json::from_str(&content).unwrap()
+}
+
+fn assert_rick_astley(result: &RecognitionResult) {
+    assert_eq!(result.song_name, "Never Gonna Give You Up");
+    assert_eq!(result.artist_name, "Rick Astley");
+    assert_eq!(result.album_name.as_deref(), Some("Whenever You Need Somebody"));
+    assert_eq!(result.track_key, "20066955");
+    assert_eq!(result.release_year.as_deref(), Some("1987"));
+    assert_eq!(result.genre.as_deref(), Some("Pop"));
+    assert_eq!(result.artist_id(), Some("669771"));
+    assert_eq!(result.isrc(), Some("GBARL9300135"));
+    assert_eq!(result.track_url(), Some("https://www.shazam.com/track/20066955/never-gonna-give-you-up"));
+    assert_eq!(result.preview_url(), Some("https://audio-ssl.itunes.apple.com/preview.m4a"));
+}
+
+#[test]
+fn test_matches_with_top_level_track() {
+    let result = RecognitionResult::from_raw_response(fixture("matches_with_track.json")).unwrap();
+    assert_rick_astley(&result);
+}
+
+#[test]
+fn test_top_level_track_without_matches() {
+    let result = RecognitionResult::from_raw_response(fixture("top_level_track.json")).unwrap();
+    assert_rick_astley(&result);
+}
+
+#[test]
+fn test_track_inside_match() {
+    let result = RecognitionResult::from_raw_response(fixture("track_in_match.json")).unwrap();
+    assert_rick_astley(&result);
+}
+
+#[test]
+fn test_no_match() {
+    let responses = [
+        fixture("no_match.json"),
+        serde_json::json!({}),
+        serde_json::json!({ "matches": [{ "id": "20066955" }] }),
+        serde_json::json!({ "track": null }),
+        serde_json::json!({ "track": null, "matches": [{ "id": "20066955", "track": { "key": "20066955", "title": "Never Gonna Give You Up" } }] }),
+    ];
+
+    for response in responses {
+        assert!(
+            matches!(RecognitionResult::from_raw_response(response.clone()), Err(SongRecError::NoMatch(_))),
+            "expected no match for {}",
+            response
+        );
+    }
+}
+
+#[test]
+fn test_invalid_response() {
+    for response in [Value::Null, serde_json::json!([]), serde_json::json!("error")] {
+        assert!(matches!(RecognitionResult::from_raw_response(response), Err(SongRecError::NetworkError(_))));
+    }
+}
+
+#[test]
+fn test_source() {
+    let result = RecognitionResult::from_raw_response(fixture("matches_with_track.json")).unwrap();
+    assert_eq!(result.source, RecognitionSource::Unknown);
+
+    let source = RecognitionSource::Stream { url: "https://radio.example/live".to_string() };
+    let result = result.with_source(source.clone());
+    assert_eq!(result.reparse().unwrap().source, source);
+
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["source"], serde_json::json!({ "type": "stream", "url": "https://radio.example/live" }));
+    let device = serde_json::to_value(RecognitionSource::Device { name: None }).unwrap();
+    assert_eq!(device, serde_json::json!({ "type": "device", "name": null }));
+
+    // Results saved before sources were recorded still load
+    let mut value = value;
+    value.as_object_mut().unwrap().remove("source");
+    let result: RecognitionResult = serde_json::from_value(value).unwrap();
+    assert_eq!(result.source, RecognitionSource::Unknown);
+}
+
+#[test]
+fn test_song_position() {
+    let result = RecognitionResult::from_raw_response(fixture("matches_with_track.json")).unwrap();
+    assert_eq!(result.song_position(), Some(std::time::Duration::from_millis(12_480)));
+    assert_eq!(result.song_duration(), Some(std::time::Duration::from_millis(213_573)));
+    let remaining = result.remaining_time().unwrap();
+    assert!(remaining <= std::time::Duration::from_millis(201_093) && remaining > std::time::Duration::from_secs(190));
+
+    let output = songrec::RecognitionOutput::format_result(&result, songrec::OutputFormat::Custom("at {position} of {duration}"));
+    assert_eq!(output.content, "at 0:12 of 3:33");
+
+    // The duration is only known from the Apple Music metadata
+    let result = RecognitionResult::from_raw_response(fixture("track_in_match.json")).unwrap();
+    assert_eq!(result.song_duration(), None);
+    assert_eq!(result.remaining_time(), None);
+}
+
+#[test]
+fn test_localized_sections() {
+    let response = fixture("localized_sections.json");
+
+    let result = RecognitionResult::from_localized_response(response.clone(), "de").unwrap();
+    assert_eq!(result.album_name.as_deref(), Some("Whenever You Need Somebody (Deutsche Ausgabe)"));
+    assert_eq!(result.release_year.as_deref(), Some("1988"));
+
+    // Other languages fall back to the English section, as raw responses do
+    for result in [
+        RecognitionResult::from_localized_response(response.clone(), "fr-FR").unwrap(),
+        RecognitionResult::from_raw_response(response).unwrap(),
+    ] {
+        assert_eq!(result.album_name.as_deref(), Some("Whenever You Need Somebody"));
+        assert_eq!(result.release_year.as_deref(), Some("1987"));
+    }
+
+    // A response in one language, whose field titles are translated
+    let response = serde_json::json!({
+        "track": {
+            "key": "1",
+            "title": "Titel",
+            "subtitle": "Künstler",
+            "sections": [{ "type": "SONG", "metadata": [{ "title": "Label", "text": "Label" }, { "title": "Sortie", "text": "2001" }] }]
+        }
+    });
+    let result = RecognitionResult::from_raw_response(response).unwrap();
+    assert_eq!(result.release_year.as_deref(), Some("2001"));
+}
+
+#[test]
+fn test_track_and_disc_numbers() {
+    let result = RecognitionResult::from_raw_response(fixture("matches_with_track.json")).unwrap();
+    assert_eq!((result.track_number, result.disc_number), (Some(1), Some(1)));
+    let result = RecognitionResult::from_raw_response(fixture("top_level_track.json")).unwrap();
+    assert_eq!((result.track_number, result.disc_number), (None, None));
+
+    // Without Apple Music metadata, the numbers and album come from the song section or the attributes
+    let response = serde_json::json!({
+        "track": {
+            "key": "1",
+            "title": "Title",
+            "subtitle": "Artist",
+            "sections": [{ "type": "SONG", "metadata": [{ "title": "Track", "text": "7/12" }, { "title": "Disc", "text": "2 of 2" }] }]
+        },
+        "resources": { "songs": { "1": { "attributes": { "albumName": "Album", "trackNumber": 0 } } } }
+    });
+    let result = RecognitionResult::from_raw_response(response).unwrap();
+    assert_eq!((result.track_number, result.disc_number), (Some(7), Some(2)));
+    assert_eq!(result.album_name.as_deref(), Some("Album"));
+
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["track_number"], 7);
+    assert_eq!(serde_json::from_value::<RecognitionResult>(value).unwrap().disc_number, Some(2));
+}
+
+#[test]
+fn test_featured_artists() {
+    use songrec::{ArtistCredit, ArtistRole};
+
+    let parse = |subtitle: &str, title: &str| {
+        RecognitionResult::from_raw_response(serde_json::json!({ "track": { "key": "1", "title": title, "subtitle": subtitle } })).unwrap()
+    };
+    let credit = |name: &str, role| ArtistCredit { name: name.to_string(), role };
+
+    let result = parse("Calvin Harris feat. Rihanna", "This Is What You Came For");
+    assert_eq!(result.artist_name, "Calvin Harris feat. Rihanna");
+    assert_eq!(result.primary_artist(), "Calvin Harris");
+    assert_eq!(result.artists, vec![credit("Calvin Harris", ArtistRole::Main), credit("Rihanna", ArtistRole::Featured)]);
+
+    // Main credits are kept whole, featured lists are split, also from the title
+    let result = parse("Simon & Garfunkel (ft. A, B & C)", "Song [Feat. D]");
+    assert_eq!(result.primary_artist(), "Simon & Garfunkel");
+    let featured: Vec<_> = result.artists.iter().filter(|credit| credit.role == ArtistRole::Featured).map(|credit| credit.name.as_str()).collect();
+    assert_eq!(featured, ["A", "B", "C", "D"]);
+
+    // Words merely containing "ft" are not credits
+    let result = parse("Daft Punk", "Get Lucky (feat. Pharrell Williams)");
+    assert_eq!(result.artists, vec![credit("Daft Punk", ArtistRole::Main), credit("Pharrell Williams", ArtistRole::Featured)]);
+
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["artists"][1]["role"], "featured");
+}
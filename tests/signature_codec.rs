@@ -0,0 +1,196 @@
+//! Property tests for the signature binary and URI codec
+//!
+//! Signatures can come from untrusted sources (e.g. URIs pasted by users), so besides
+//! round-tripping, decoding arbitrary or corrupted input must fail cleanly and never panic.
+
+use proptest::prelude::*;
+use songrec::fingerprinting::signature_format::{FrequencyBand, FrequencyPeak, MAX_SIGNATURE_SIZE};
+use songrec::DecodedSignature;
+use std::collections::HashMap;
+
+const URI_PREFIX: &str = "data:audio/vnd.shazam.sig;base64,";
+
+const BANDS: [FrequencyBand; 4] = [
+    FrequencyBand::_250_520,
+    FrequencyBand::_520_1450,
+    FrequencyBand::_1450_3500,
+    FrequencyBand::_3500_5500,
+];
+
+type PeakTable = Vec<(FrequencyBand, Vec<(u32, u16, u16)>)>;
+
+/// Comparable view of a signature, bands without peaks are ignored
+fn peak_table(signature: &DecodedSignature) -> PeakTable {
+    let mut table: PeakTable = signature
+        .frequency_band_to_sound_peaks
+        .iter()
+        .filter(|(_, peaks)| !peaks.is_empty())
+        .map(|(band, peaks)| {
+            let peaks = peaks
+                .iter()
+                .map(|peak| (peak.fft_pass_number, peak.peak_magnitude, peak.corrected_peak_frequency_bin))
+                .collect();
+            (*band, peaks)
+        })
+        .collect();
+    table.sort_by_key(|(band, _)| *band);
+    table
+}
+
+/// Peaks of one band, sorted by FFT pass number with gaps large enough to need the long pass encoding
+fn arb_peaks() -> impl Strategy<Value = Vec<FrequencyPeak>> {
+    prop::collection::vec((0u32..600, any::<u16>(), any::<u16>()), 0..150).prop_map(|deltas| {
+        let mut fft_pass_number = 0;
+        deltas
+            .into_iter()
+            .map(|(delta, peak_magnitude, corrected_peak_frequency_bin)| {
+                fft_pass_number += delta;
+                FrequencyPeak {
+                    fft_pass_number,
+                    peak_magnitude,
+                    corrected_peak_frequency_bin,
+                }
+            })
+            .collect()
+    })
+}
+
+fn arb_signature() -> impl Strategy<Value = DecodedSignature> {
+    (
+        prop::sample::select(vec![8000u32, 11025, 16000, 32000, 44100, 48000]),
+        0u32..100_000_000,
+        prop::collection::vec(arb_peaks(), 4),
+    )
+        .prop_map(|(sample_rate_hz, number_samples, peaks)| {
+            let frequency_band_to_sound_peaks: HashMap<_, _> = BANDS.iter().copied().zip(peaks).collect();
+            DecodedSignature {
+                sample_rate_hz,
+                number_samples,
+                frequency_band_to_sound_peaks,
+            }
+        })
+}
+
+/// Recompute the CRC of a corrupted signature so the corruption reaches the body parser
+fn fix_crc(data: &mut [u8]) {
+    if data.len() >= 8 {
+        let crc = crc32fast::hash(&data[8..]);
+        data[4..8].copy_from_slice(&crc.to_le_bytes());
+    }
+}
+
+proptest! {
+    #[test]
+    fn binary_round_trip(signature in arb_signature()) {
+        let encoded = signature.encode_to_binary().unwrap();
+        let decoded = DecodedSignature::decode_from_binary(&encoded).unwrap();
+
+        prop_assert_eq!(decoded.sample_rate_hz, signature.sample_rate_hz);
+        prop_assert_eq!(decoded.number_samples, signature.number_samples);
+        prop_assert_eq!(peak_table(&decoded), peak_table(&signature));
+    }
+
+    #[test]
+    fn uri_round_trip(signature in arb_signature()) {
+        let uri = signature.encode_to_uri().unwrap();
+        prop_assert!(uri.starts_with(URI_PREFIX));
+
+        let decoded = DecodedSignature::decode_from_uri(&uri).unwrap();
+        prop_assert_eq!(peak_table(&decoded), peak_table(&signature));
+    }
+
+    #[test]
+    fn arbitrary_binary_never_panics(data in prop::collection::vec(any::<u8>(), 0..2048)) {
+        let _ = DecodedSignature::decode_from_binary(&data);
+    }
+
+    #[test]
+    fn corrupted_signature_never_panics(
+        signature in arb_signature(),
+        corruptions in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+        truncate in any::<prop::sample::Index>(),
+    ) {
+        let mut data = signature.encode_to_binary().unwrap();
+        for (index, value) in corruptions {
+            let index = index.index(data.len());
+            data[index] = value;
+        }
+        fix_crc(&mut data);
+        let _ = DecodedSignature::decode_from_binary(&data);
+
+        let truncated = truncate.index(data.len());
+        data.truncate(truncated);
+        fix_crc(&mut data);
+        let _ = DecodedSignature::decode_from_binary(&data);
+    }
+
+    #[test]
+    fn arbitrary_uri_never_panics(suffix in ".{0,512}", with_prefix in any::<bool>()) {
+        let uri = if with_prefix { format!("{}{}", URI_PREFIX, suffix) } else { suffix };
+        prop_assert!(DecodedSignature::decode_from_uri(&uri).is_err() || with_prefix);
+    }
+}
+
+#[test]
+fn empty_signature_round_trips() {
+    let signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 0,
+        frequency_band_to_sound_peaks: HashMap::new(),
+    };
+
+    let decoded = DecodedSignature::decode_from_binary(&signature.encode_to_binary().unwrap()).unwrap();
+    assert!(decoded.frequency_band_to_sound_peaks.is_empty());
+}
+
+#[test]
+fn length_limits_are_enforced() {
+    let oversized = vec![0u8; MAX_SIGNATURE_SIZE + 1];
+    assert!(DecodedSignature::decode_from_binary(&oversized).is_err());
+
+    let uri = format!("{}{}", URI_PREFIX, "A".repeat(MAX_SIGNATURE_SIZE * 2));
+    assert!(DecodedSignature::decode_from_uri(&uri).is_err());
+
+    // Signatures too large to be decoded are not produced either
+    let mut peaks = Vec::new();
+    for fft_pass_number in 0..(MAX_SIGNATURE_SIZE as u32 / 4) {
+        peaks.push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 1,
+            corrected_peak_frequency_bin: 1,
+        });
+    }
+    let signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 0,
+        frequency_band_to_sound_peaks: [(FrequencyBand::_250_520, peaks)].into_iter().collect(),
+    };
+    assert!(signature.encode_to_binary().is_err());
+}
+
+#[test]
+fn invalid_values_are_errors() {
+    let signature = DecodedSignature {
+        sample_rate_hz: 12345,
+        number_samples: 0,
+        frequency_band_to_sound_peaks: HashMap::new(),
+    };
+    assert!(signature.encode_to_binary().is_err());
+
+    let unsorted = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 0,
+        frequency_band_to_sound_peaks: [(
+            FrequencyBand::_250_520,
+            vec![
+                FrequencyPeak { fft_pass_number: 10, peak_magnitude: 1, corrected_peak_frequency_bin: 1 },
+                FrequencyPeak { fft_pass_number: 5, peak_magnitude: 1, corrected_peak_frequency_bin: 1 },
+            ],
+        )]
+        .into_iter()
+        .collect(),
+    };
+    assert!(unsorted.encode_to_binary().is_err());
+
+    assert!(DecodedSignature::decode_from_uri("not a signature").is_err());
+}